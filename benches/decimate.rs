@@ -0,0 +1,66 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Decimation and RMS of a full sample history, as done for every painted frame.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[path = "../src/decimate.rs"]
+mod decimate;
+
+/// Ten seconds at 48kHz
+const HISTORY_LEN: usize = 480_000;
+/// Pixel columns of a wide waveform pane
+const WIDTH: usize = 1920;
+
+fn history() -> Vec<f32> {
+    (0..HISTORY_LEN)
+        .map(|index| (index as f32 * 0.01).sin() * 0.5)
+        .collect()
+}
+
+fn bench_decimate(c: &mut Criterion) {
+    let samples = history();
+    let mut buckets = Vec::with_capacity(WIDTH);
+    c.bench_function("decimate_into", |b| {
+        b.iter(|| decimate::decimate_into(black_box(&samples), WIDTH, &mut buckets))
+    });
+    c.bench_function("decimate_rms_into", |b| {
+        b.iter(|| decimate::decimate_rms_into(black_box(&samples), WIDTH, &mut buckets))
+    });
+    c.bench_function("min_max", |b| {
+        b.iter(|| decimate::min_max(black_box(&samples)))
+    });
+}
+
+fn bench_summary(c: &mut Criterion) {
+    let samples = history();
+    c.bench_function("summary", |b| {
+        b.iter(|| {
+            let summary = decimate::summary(black_box(&samples));
+            (summary.rms(), summary.mean())
+        })
+    });
+}
+
+criterion_group!(benches, bench_decimate, bench_summary);
+criterion_main!(benches);
@@ -0,0 +1,261 @@
+# Deutsche Übersetzung, als Beispiel für eine zweite Sprache
+
+antialiasing = Kantenglättung
+attack = Attack
+auto-battery-saver = Energiesparen im Akkubetrieb
+autocorrelation = Autokorrelation
+balance = Balance
+balance-window = Fenster
+ballistics-fast = Schnell
+ballistics-peak = Spitze
+ballistics-ppm-1 = PPM I
+ballistics-ppm-2 = PPM II
+ballistics-slow = Langsam
+ballistics-vu = VU
+broadband = Breitband
+calibrate = Kalibrieren
+calibrator-spl = Kalibrator dB SPL
+capture-noise-print = Rauschprofil aufnehmen
+cepstrum = Cepstrum
+channel-overlay = L/R überlagern
+check-levels = Pegel prüfen
+clear = Leeren
+clear-markers = Marker und Schleife löschen
+clear-target-curve = Zielkurve entfernen
+clip-events = Übersteuerungen
+clip-kind-clip = Clipping
+clip-kind-dropout = Aussetzer
+clip-kind-held-samples = Gehaltene Samples
+clip-kind-true-peak-over = True-Peak-Überschreitung
+color-blue = Blau
+color-green = Grün
+color-red = Rot
+color-white = Weiß
+compare-difference = Differenz: { $difference }
+compare-title = Vergleich
+copy = Kopieren
+copy-csv = Als CSV kopieren
+copy-stats = Statistik kopieren
+db-waveform = dB-Wellenform
+default-device = Standard
+delete = Löschen
+denoise-preview = Entrauschen (Vorschau)
+detector-peak = Spitze
+detector-rms = RMS
+display-scaling = Skalierung
+envelope = Hüllkurve
+envelope-follower = Hüllkurvenfolger
+export = Exportieren...
+export-clip-log = Übersteuerungsprotokoll exportieren...
+export-image = Bild exportieren...
+export-log = Protokoll exportieren...
+export-report = Bericht exportieren...
+feedback-detection = Rückkopplungserkennung
+floor = Untergrenze
+format-buffer-warning = Puffer mit { $requested } Frames angefordert, das Gerät nimmt { $min } bis { $max }
+format-channels = Kanäle: { $channels }
+format-channels-warning = { $requested } Kanäle angefordert, das Gerät bietet { $offered }
+format-delivered-warning = Callbacks liefern { $delivered } Frames, angefordert waren { $requested }
+format-formats = Formate: { $formats }
+format-opened = Geöffnet mit { $rate } Hz, { $channels } Kan., { $buffer }
+format-rate-warning = { $rate } Hz wird nicht unterstützt, der Treiber resampelt eventuell oder läuft mit einer anderen Rate
+format-rates = Raten: { $rates } Hz
+frame-count = { $frames } Frames
+freeze = Einfrieren
+freeze-all = Alles einfrieren
+freeze-waveform = Wellenform einfrieren
+full-rate = Voll
+gain = Verstärkung
+gain-reduction-scale = Talkback-Limiter GR, 0 bis -{ $range } dB
+headroom = Aussteuerungsreserve dB
+host-buffer = Host-Puffer
+hum-detected = { $mains }-Hz-Brummen, { $harmonics } von { $total } Harmonischen
+import = Importieren...
+input-device-note = Eingabegerät, gilt nach Neustart
+input-name = Eingang
+interpolation-cubic = Kubisch
+interpolation-linear = Linear
+lag = Latenz
+lag-no-peak = { $title }: kein Maximum
+lag-peak = { $title }: Maximum { $peak } ms ({ $frequency } Hz)
+lane-name = Spur
+lane-names = Spurnamen
+line-width = Linienstärke
+listen-to-input = Eingang abhören
+load-script = Skript laden...
+level = Pegel
+level-history = Pegelverlauf
+load-target-curve = Zielkurve laden…
+loudness = Lautheit
+loudness-summary = Integriert: { $integrated } LUFS    Bereich: { $range } LU
+measure-latency = Latenz messen
+menu-audio = Audio
+menu-edit = Bearbeiten
+menu-file = Ablage
+menu-view = Darstellung
+midi-freeze = Einfrieren
+midi-gain = Verstärkung
+midi-input = MIDI-Eingang
+midi-learn = MIDI-Learn
+midi-view = Ansicht
+midi-zoom = Zoom
+monitor = Abhören
+no-hum = Kein Brummen erkannt
+no-input-device = Kein Eingabegerät
+no-metrics = Noch keine Messwerte
+no-presets = Keine Presets gespeichert
+on-battery = Akkubetrieb
+overlay = Überlagern
+pane-rates = Langsamere Ansichten
+peaks = Spitzen
+phase = Phase
+phase-group-delay = Phase / Gruppenlaufzeit
+pink-reference = Rosa-Rauschen-Referenz
+preset-name = Name des Presets
+presets = Presets
+processed-difference = Bearbeitet − Eingang, ±{ $range } dB
+processed-smoothing = Abhörausgang
+quality-balanced = Ausgewogen
+quality-battery-saver = Energiesparen
+quality-quality = Qualität
+quit = Beenden
+recording-timecode = AUFN. { $time }
+redo-settings = Einstellung wiederherstellen
+release = Abklingzeit
+remove-hum = Brummen entfernen
+render-quality = Darstellungsqualität
+replay-readouts = Spitze { $peak } dBFS    True Peak { $true_peak } dBTP    RMS { $rms } dBFS    Momentan { $momentary } LUFS    Kurzzeit { $short_term } LUFS    Integriert { $integrated } LUFS    Bereich { $range } LU    SPL { $spl } dBFS    Tonhöhe { $pitch }    Neigung { $tilt }
+replay-title = Wiedergabe
+reset-integration = Integration zurücksetzen
+reset-zoom = Zoom zurücksetzen
+room-response-summary = { $duration } s angezeigt    RT60  { $bands }
+room-response-title = Raumantwort
+save = Sichern
+scaling-agc = AGC
+scaling-fit-peak = An Spitze anpassen
+scaling-manual = Manuell
+script-error = Skriptfehler: { $error }
+show-all-history = Gesamten Verlauf zeigen
+smoothing-off = Aus
+solo = Solo
+solo-hint = Im Spektrum oder Spektrogramm ziehen, um ein Band abzuhören
+spectrogram = Spektrogramm
+spectrogram-ceiling = Obergrenze
+spectrogram-floor = Untergrenze
+spectrogram-gamma = Gamma
+spectrogram-pause = Anhalten
+spectrogram-paused = Angehalten  -{ $behind } s
+spectrum = Spektrum
+spectrum-smoothing = Glättung
+spectrum-window = Fenster
+start-engine = Audio starten
+start-failed = Eingang konnte nicht geöffnet werden: { $error }
+stats-buffer-size = Puffergröße
+stats-clock-drift = Taktdrift
+stats-cpu = CPU
+stats-dc-offset = DC-Versatz
+stats-dropped = Verworfen
+stats-dsp-load = DSP-Last
+stats-max = Max
+stats-memory = Speicher
+stats-min = Min
+stats-noise-floor = Grundrauschen
+stats-peak = Spitze
+stats-rms = RMS
+stats-sample-rate = Abtastrate
+stats-snr = Rauschabstand
+stats-update-rate = Aktualisierungsrate
+stop-engine = Audio stoppen
+stop-solo = Beenden
+talkback = Halten zum Abhören
+target-broadcast = Rundfunk (-23 LUFS)
+target-podcast = Podcast (-16 LUFS)
+target-streaming = Streaming (-14 LUFS)
+transient-gate = Gate
+transient-threshold = Schwelle
+transients = Transienten
+tray-tooltip = Pegel
+undo-settings = Einstellung widerrufen
+unit-dbfs = dBFS
+unit-linear = Linear
+unit-percent = %
+units = Einheiten
+update-rate = Aktualisierungsrate
+wave-color = Farbe
+waveform = Wellenform
+window-title = External Event Demo
+workspace-name = Name des Arbeitsbereichs
+workspaces = Arbeitsbereiche
+zoom-in = Vergrößern
+zoom-out = Verkleinern
+zoomed-in = Vergrößert
+agc-range = { $active ->
+    [true] AGC { $gain } dB, { $floor } bis { $ceiling } dBFS
+   *[false] {""}
+}
+record-metrics = { $recording ->
+    [true] Aufnahme beenden
+   *[false] Messwerte aufnehmen...
+}
+record-video = { $recording ->
+    [true] Video beenden
+   *[false] Video aufnehmen...
+}
+spl-reading = { $state ->
+    [calibrating] Kalibrierung...
+    [calibrated] { $level } dB({ $weighting }) SPL, { $ballistics }
+   *[uncalibrated] { $level } ({ $weighting }-bewertet, { $ballistics }, unkalibriert)
+}
+clip-event = { $dropout ->
+    [true] { $time }  { $kind }  Kanal { $channel }  { $level } davor  { $duration } ms
+   *[false] { $time }  { $kind }  Kanal { $channel }  { $level } TP  { $duration } ms
+}
+gain-status = { $state ->
+    [measuring] Höre zu... { $progress } %
+    [no-signal] Kein Signal, prüfe, ob der Eingang angeschlossen und nicht stummgeschaltet ist  (Spitze { $peak } dBFS, RMS { $rms } dBFS)
+    [good] Pegel sind gut  (Spitze { $peak } dBFS, RMS { $rms } dBFS)
+    [raise] Eingang um { $adjustment } dB anheben  (Spitze { $peak } dBFS, RMS { $rms } dBFS)
+    [lower] Eingang um { $adjustment } dB absenken  (Spitze { $peak } dBFS, RMS { $rms } dBFS)
+   *[idle] Sprich oder spiele mit Auftrittslautstärke und prüfe dann die Pegel
+}
+midi-mapping = { $state ->
+    [learning] { $target }: Regler bewegen...
+    [mapped] { $target }: CC { $controller }
+   *[unmapped] { $target }: nicht zugewiesen
+}
+replay-play = { $playing ->
+    [true] Pause
+   *[false] Abspielen
+}
+tray-pause = { $paused ->
+    [true] Fortsetzen
+   *[false] Pause
+}
+noise-print = { $state ->
+    [capturing] Rauschprofil wird aufgenommen, bitte Ruhe...
+    [captured] Rauschprofil von { $seconds } s
+   *[none] Kein Rauschprofil
+}
+balance = { $side ->
+    [mono] Mono-Eingang
+    [center] Mitte
+    [left] L { $difference } dB
+    [right] R { $difference } dB
+   *[silent] -
+}
+latency-status = { $state ->
+    [measuring] …
+    [measured] { $round_trip } ms Umlaufzeit
+    [not-heard] Klick nicht gehört
+   *[unmeasured] -
+}
+format-buffers = { $reported ->
+    [true] Puffer: { $min } bis { $max } Frames
+   *[false] Puffer: nicht gemeldet
+}
+buffer-state = { $state ->
+    [buffering] Puffern
+    [playing] Wiedergabe, { $detail } s gepuffert
+    [reconnecting] Neu verbinden: { $detail }
+   *[connecting] Verbinden
+}
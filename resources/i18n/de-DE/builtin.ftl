@@ -0,0 +1,41 @@
+-app-name = Druid
+
+hello-counter = Der aktuelle Wert ist { $count }
+
+# The 'application' menu on macOS
+macos-menu-about-app = Über { -app-name }
+macos-menu-preferences = Einstellungen...
+macos-menu-hide-app = { -app-name } ausblenden
+macos-menu-hide-others = Andere ausblenden
+macos-menu-show-all = Alle einblenden
+macos-menu-services = Dienste
+macos-menu-application-menu = { -app-name }
+macos-menu-quit-app = { -app-name } beenden
+
+# common 'File' menu items
+common-menu-file-menu = Datei
+common-menu-file-new = Neu
+common-menu-file-new-window = Neues Fenster
+
+common-menu-file-open = Öffnen...
+common-menu-file-close = Schließen
+
+common-menu-file-save = Speichern
+# used for new files, if we need to show a dialog
+common-menu-file-save-ellipsis = Speichern...
+common-menu-file-save-as = Speichern als...
+
+common-menu-file-page-setup = Seiteneinstellungen...
+common-menu-file-print = Drucken...
+
+# windows 'File' menu items
+win-menu-file-exit = Beenden
+
+# common 'Edit' menu items.
+common-menu-edit-menu = Bearbeiten
+
+common-menu-cut = Ausschneiden
+common-menu-copy = Kopieren
+common-menu-paste = Einfügen
+common-menu-undo = Rückgängig
+common-menu-redo = Wiederherstellen
@@ -0,0 +1,261 @@
+# Strings for the live window. Keys are looked up with druid's `LocalizedString`.
+
+antialiasing = Anti-aliasing
+attack = Attack
+auto-battery-saver = Battery saver on battery
+autocorrelation = Autocorrelation
+balance = Balance
+balance-window = Window
+ballistics-fast = Fast
+ballistics-peak = Peak
+ballistics-ppm-1 = PPM I
+ballistics-ppm-2 = PPM II
+ballistics-slow = Slow
+ballistics-vu = VU
+broadband = Broadband
+calibrate = Calibrate
+calibrator-spl = Calibrator dB SPL
+capture-noise-print = Capture noise print
+cepstrum = Cepstrum
+channel-overlay = L/R overlay
+check-levels = Check levels
+clear = Clear
+clear-markers = Clear markers and loop
+clear-target-curve = Clear target
+clip-events = Clip events
+clip-kind-clip = Clip
+clip-kind-dropout = Dropout
+clip-kind-held-samples = Held samples
+clip-kind-true-peak-over = True-peak over
+color-blue = Blue
+color-green = Green
+color-red = Red
+color-white = White
+compare-difference = Difference: { $difference }
+compare-title = Compare
+copy = Copy
+copy-csv = Copy as CSV
+copy-stats = Copy stats
+db-waveform = dB waveform
+default-device = Default
+delete = Delete
+denoise-preview = Denoise preview
+detector-peak = Peak
+detector-rms = RMS
+display-scaling = Scaling
+envelope = Envelope
+envelope-follower = Envelope follower
+export = Export...
+export-clip-log = Export clip log...
+export-image = Export image...
+export-log = Export log...
+export-report = Export report...
+feedback-detection = Feedback detection
+floor = Floor
+format-buffer-warning = { $requested } frame buffers asked for, the device takes { $min } to { $max }
+format-channels = Channels: { $channels }
+format-channels-warning = { $requested } channels asked for, the device offers { $offered }
+format-delivered-warning = Callbacks bring { $delivered } frames, { $requested } were asked for
+format-formats = Formats: { $formats }
+format-opened = Opened at { $rate } Hz, { $channels } ch, { $buffer }
+format-rate-warning = { $rate } Hz isn't supported, the driver may resample or run at another rate
+format-rates = Rates: { $rates } Hz
+frame-count = { $frames } frames
+freeze = Freeze
+freeze-all = Freeze all
+freeze-waveform = Freeze waveform
+full-rate = Full
+gain = Gain
+gain-reduction-scale = Talkback limiter GR, 0 to -{ $range } dB
+headroom = Headroom dB
+host-buffer = host buffer
+hum-detected = { $mains } Hz hum, { $harmonics } of { $total } harmonics
+import = Import...
+input-device-note = Input device, takes effect on restart
+input-name = Input
+interpolation-cubic = Cubic
+interpolation-linear = Linear
+lag = Lag
+lag-no-peak = { $title }: no peak
+lag-peak = { $title }: peak { $peak } ms ({ $frequency } Hz)
+lane-name = Lane
+lane-names = Lane names
+line-width = Line width
+listen-to-input = Listen to input
+load-script = Load script...
+level = Level
+level-history = Level history
+load-target-curve = Load target curve…
+loudness = Loudness
+loudness-summary = Integrated: { $integrated } LUFS    Range: { $range } LU
+measure-latency = Measure latency
+menu-audio = Audio
+menu-edit = Edit
+menu-file = File
+menu-view = View
+midi-freeze = Freeze
+midi-gain = Gain
+midi-input = MIDI input
+midi-learn = MIDI learn
+midi-view = View
+midi-zoom = Zoom
+monitor = Monitor
+no-hum = No hum detected
+no-input-device = No input device
+no-metrics = No metrics yet
+no-presets = No presets saved
+on-battery = On battery
+overlay = Overlay
+pane-rates = Slower panes
+peaks = Peaks
+phase = Phase
+phase-group-delay = Phase / group delay
+pink-reference = Pink noise reference
+preset-name = Preset name
+presets = Presets
+processed-difference = Processed − input, ±{ $range } dB
+processed-smoothing = Monitor output
+quality-balanced = Balanced
+quality-battery-saver = Battery saver
+quality-quality = Quality
+quit = Quit
+recording-timecode = REC { $time }
+redo-settings = Redo settings change
+release = Release
+remove-hum = Remove hum
+render-quality = Render quality
+replay-readouts = Peak { $peak } dBFS    True peak { $true_peak } dBTP    RMS { $rms } dBFS    Momentary { $momentary } LUFS    Short-term { $short_term } LUFS    Integrated { $integrated } LUFS    Range { $range } LU    SPL { $spl } dBFS    Pitch { $pitch }    Tilt { $tilt }
+replay-title = Replay
+reset-integration = Reset integration
+reset-zoom = Reset zoom
+room-response-summary = { $duration } s shown    RT60  { $bands }
+room-response-title = Room response
+save = Save
+scaling-agc = AGC
+scaling-fit-peak = Fit to peak
+scaling-manual = Manual
+script-error = Script error: { $error }
+show-all-history = Show all history
+smoothing-off = Off
+solo = Solo
+solo-hint = Drag across the spectrum or spectrogram to listen to one band
+spectrogram = Spectrogram
+spectrogram-ceiling = Ceiling
+spectrogram-floor = Floor
+spectrogram-gamma = Gamma
+spectrogram-pause = Pause
+spectrogram-paused = Paused  -{ $behind } s
+spectrum = Spectrum
+spectrum-smoothing = Smoothing
+spectrum-window = Window
+start-engine = Start engine
+start-failed = Couldn't open the input: { $error }
+stats-buffer-size = Buffer size
+stats-clock-drift = Clock drift
+stats-cpu = CPU
+stats-dc-offset = DC offset
+stats-dropped = Dropped
+stats-dsp-load = DSP load
+stats-max = Max
+stats-memory = Memory
+stats-min = Min
+stats-noise-floor = Noise floor
+stats-peak = Peak
+stats-rms = RMS
+stats-sample-rate = Sample rate
+stats-snr = SNR
+stats-update-rate = Update rate
+stop-engine = Stop engine
+stop-solo = Stop
+talkback = Hold to listen
+target-broadcast = Broadcast (-23 LUFS)
+target-podcast = Podcast (-16 LUFS)
+target-streaming = Streaming (-14 LUFS)
+transient-gate = Gate
+transient-threshold = Threshold
+transients = Transients
+tray-tooltip = Audio level
+undo-settings = Undo settings change
+unit-dbfs = dBFS
+unit-linear = Linear
+unit-percent = %
+units = Units
+update-rate = Update rate
+wave-color = Color
+waveform = Waveform
+window-title = External Event Demo
+workspace-name = Workspace name
+workspaces = Workspaces
+zoom-in = Zoom in
+zoom-out = Zoom out
+zoomed-in = Zoomed in
+agc-range = { $active ->
+    [true] AGC { $gain } dB, { $floor } to { $ceiling } dBFS
+   *[false] {""}
+}
+record-metrics = { $recording ->
+    [true] Stop recording
+   *[false] Record metrics...
+}
+record-video = { $recording ->
+    [true] Stop video
+   *[false] Record video...
+}
+spl-reading = { $state ->
+    [calibrating] Calibrating...
+    [calibrated] { $level } dB({ $weighting }) SPL, { $ballistics }
+   *[uncalibrated] { $level } ({ $weighting }-weighted, { $ballistics }, uncalibrated)
+}
+clip-event = { $dropout ->
+    [true] { $time }  { $kind }  ch { $channel }  { $level } before  { $duration } ms
+   *[false] { $time }  { $kind }  ch { $channel }  { $level } TP  { $duration } ms
+}
+gain-status = { $state ->
+    [measuring] Listening... { $progress }%
+    [no-signal] No signal, check the input is connected and unmuted  (peak { $peak } dBFS, RMS { $rms } dBFS)
+    [good] Levels are good  (peak { $peak } dBFS, RMS { $rms } dBFS)
+    [raise] Raise input { $adjustment } dB  (peak { $peak } dBFS, RMS { $rms } dBFS)
+    [lower] Lower input { $adjustment } dB  (peak { $peak } dBFS, RMS { $rms } dBFS)
+   *[idle] Speak or play at performance level, then check levels
+}
+midi-mapping = { $state ->
+    [learning] { $target }: move a control...
+    [mapped] { $target }: CC { $controller }
+   *[unmapped] { $target }: unmapped
+}
+replay-play = { $playing ->
+    [true] Pause
+   *[false] Play
+}
+tray-pause = { $paused ->
+    [true] Resume
+   *[false] Pause
+}
+noise-print = { $state ->
+    [capturing] Capturing noise print, keep quiet...
+    [captured] Noise print of { $seconds } s
+   *[none] No noise print
+}
+balance = { $side ->
+    [mono] Mono input
+    [center] Center
+    [left] L { $difference } dB
+    [right] R { $difference } dB
+   *[silent] -
+}
+latency-status = { $state ->
+    [measuring] …
+    [measured] { $round_trip } ms round trip
+    [not-heard] Click not heard
+   *[unmeasured] -
+}
+format-buffers = { $reported ->
+    [true] Buffers: { $min } to { $max } frames
+   *[false] Buffers: not reported
+}
+buffer-state = { $state ->
+    [buffering] Buffering
+    [playing] Playing, { $detail } s buffered
+    [reconnecting] Reconnecting: { $detail }
+   *[connecting] Connecting
+}
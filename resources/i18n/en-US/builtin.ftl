@@ -0,0 +1,41 @@
+-app-name = Druid
+
+hello-counter = Current value is { $count }
+
+# The 'application' menu on macOS
+macos-menu-about-app = About { -app-name }
+macos-menu-preferences = Preferences...
+macos-menu-hide-app = Hide { -app-name }
+macos-menu-hide-others = Hide Others
+macos-menu-show-all = Show All
+macos-menu-services = Services
+macos-menu-application-menu = { -app-name }
+macos-menu-quit-app = Quit { -app-name }
+
+# common 'File' menu items
+common-menu-file-menu = File
+common-menu-file-new = New
+common-menu-file-new-window = New Window
+
+common-menu-file-open = Open...
+common-menu-file-close = Close
+
+common-menu-file-save = Save
+# used for new files, if we need to show a dialog
+common-menu-file-save-ellipsis = Save...
+common-menu-file-save-as = Save As...
+
+common-menu-file-page-setup = Page Setup...
+common-menu-file-print = Print...
+
+# windows 'File' menu items
+win-menu-file-exit = Exit
+
+# common 'Edit' menu items.
+common-menu-edit-menu = Edit
+
+common-menu-cut = Cut
+common-menu-copy = Copy
+common-menu-paste = Paste
+common-menu-undo = Undo
+common-menu-redo = Redo
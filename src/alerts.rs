@@ -0,0 +1,351 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Threshold alerts for unattended monitoring: rules such as `true-peak > -1` or `silence > 30`
+//! checked against every loudness block, firing a desktop notification and optionally a webhook.
+
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use notify_rust::Notification;
+
+use crate::metrics_log::MetricsRow;
+use crate::vad::SILENCE_DB;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric {
+    Peak,
+    TruePeak,
+    Rms,
+    Momentary,
+    ShortTerm,
+    Integrated,
+    Spl,
+}
+
+impl Metric {
+    pub const NAMES: [&'static str; 7] = [
+        "peak",
+        "true-peak",
+        "rms",
+        "momentary",
+        "short-term",
+        "integrated",
+        "spl",
+    ];
+
+    fn value(self, row: &MetricsRow) -> f32 {
+        match self {
+            Metric::Peak => row.peak_dbfs,
+            Metric::TruePeak => row.true_peak_dbtp,
+            Metric::Rms => row.rms_dbfs,
+            Metric::Momentary => row.momentary_lufs,
+            Metric::ShortTerm => row.short_term_lufs,
+            Metric::Integrated => row.integrated_lufs,
+            Metric::Spl => row.spl_dbfs,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "peak" => Ok(Metric::Peak),
+            "true-peak" => Ok(Metric::TruePeak),
+            "rms" => Ok(Metric::Rms),
+            "momentary" => Ok(Metric::Momentary),
+            "short-term" => Ok(Metric::ShortTerm),
+            "integrated" => Ok(Metric::Integrated),
+            "spl" => Ok(Metric::Spl),
+            _ => Err(format!(
+                "Unknown metric '{}', expected silence or one of {}",
+                s,
+                Metric::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Condition {
+    Above(Metric, f32),
+    Below(Metric, f32),
+    /// Peak under [`SILENCE_DB`] for longer than this many seconds
+    Silence(f64),
+}
+
+/// A condition and the text it was written as, which is what notifications show
+#[derive(Clone, PartialEq, Debug)]
+pub struct AlertRule {
+    pub text: String,
+    pub condition: Condition,
+}
+
+impl FromStr for AlertRule {
+    type Err = String;
+
+    /// `<metric> <'>' or '<'> <value> [dB or LU]`, or `silence > <seconds> [s]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let (name, operator, value, unit) = match tokens[..] {
+            [name, operator, value] => (name, operator, value, None),
+            [name, operator, value, unit] => (name, operator, value, Some(unit)),
+            _ => {
+                return Err(format!(
+                    "Can't parse alert '{}', expected e.g. \"true-peak > -1\" or \"silence > 30\"",
+                    s
+                ))
+            }
+        };
+        let value: f32 = value
+            .parse()
+            .map_err(|_| format!("Invalid threshold '{}' in alert '{}'", value, s))?;
+        let units: &[&str] = if name == "silence" {
+            &["s"]
+        } else {
+            &["dB", "LU"]
+        };
+        if let Some(unit) = unit.filter(|unit| !units.contains(unit)) {
+            return Err(format!(
+                "Unknown unit '{}' in alert '{}', expected {}",
+                unit,
+                s,
+                units.join(" or ")
+            ));
+        }
+        let condition = match (name, operator) {
+            ("silence", ">") => Condition::Silence(value as f64),
+            ("silence", _) => return Err("Silence alerts take the form \"silence > 30\"".into()),
+            (name, ">") => Condition::Above(name.parse()?, value),
+            (name, "<") => Condition::Below(name.parse()?, value),
+            (_, operator) => {
+                return Err(format!(
+                    "Unknown comparison '{}' in alert '{}', expected > or <",
+                    operator, s
+                ))
+            }
+        };
+        Ok(AlertRule {
+            text: s.to_string(),
+            condition,
+        })
+    }
+}
+
+struct RuleState {
+    rule: AlertRule,
+    /// Fires again only once the condition has cleared
+    active: bool,
+    silent_since: Option<f64>,
+}
+
+impl RuleState {
+    fn check(&mut self, row: &MetricsRow) -> bool {
+        let triggered = match self.rule.condition {
+            Condition::Above(metric, threshold) => metric.value(row) > threshold,
+            Condition::Below(metric, threshold) => metric.value(row) < threshold,
+            Condition::Silence(seconds) => {
+                if row.peak_dbfs < SILENCE_DB {
+                    let since = *self.silent_since.get_or_insert(row.time_s);
+                    row.time_s - since > seconds
+                } else {
+                    self.silent_since = None;
+                    false
+                }
+            }
+        };
+        let fired = triggered && !self.active;
+        self.active = triggered;
+        fired
+    }
+}
+
+pub struct Alert {
+    pub rule: String,
+    pub time_s: f64,
+}
+
+/// Checks the rules on the analysis thread and hands alerts to a delivery thread, so a slow
+/// webhook never holds up the meters
+pub struct AlertMonitor {
+    rules: Vec<RuleState>,
+    alerts: Sender<Alert>,
+}
+
+impl AlertMonitor {
+    pub fn new(rules: Vec<AlertRule>, webhook: Option<String>) -> Self {
+        let (alerts, receiver) = channel::<Alert>();
+        thread::spawn(move || {
+            for alert in receiver {
+                deliver(&alert, webhook.as_deref());
+            }
+        });
+        AlertMonitor {
+            rules: rules
+                .into_iter()
+                .map(|rule| RuleState {
+                    rule,
+                    active: false,
+                    silent_since: None,
+                })
+                .collect(),
+            alerts,
+        }
+    }
+
+    pub fn process(&mut self, row: &MetricsRow) {
+        for state in self.rules.iter_mut() {
+            if state.check(row) {
+                let _ = self.alerts.send(Alert {
+                    rule: state.rule.text.clone(),
+                    time_s: row.time_s,
+                });
+            }
+        }
+    }
+}
+
+fn deliver(alert: &Alert, webhook: Option<&str>) {
+    log::warn!("Alert at {:.1}s: {}", alert.time_s, alert.rule);
+    if let Err(err) = Notification::new()
+        .summary("Audio alert")
+        .body(&alert.rule)
+        .show()
+    {
+        log::error!("Failed to show alert notification: {}", err);
+    }
+    if let Some(url) = webhook {
+        let body = serde_json::json!({ "rule": alert.rule, "time_s": alert.time_s });
+        if let Err(err) = ureq::post(url)
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+        {
+            log::error!("Failed to post alert to {}: {}", url, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rule: &str) -> Result<Condition, String> {
+        rule.parse::<AlertRule>().map(|rule| rule.condition)
+    }
+
+    fn state(rule: &str) -> RuleState {
+        RuleState {
+            rule: rule.parse().unwrap(),
+            active: false,
+            silent_since: None,
+        }
+    }
+
+    /// A block with every level at `level_db`
+    fn row(time_s: f64, level_db: f32) -> MetricsRow {
+        MetricsRow {
+            time_s,
+            peak_dbfs: level_db,
+            true_peak_dbtp: level_db,
+            rms_dbfs: level_db,
+            momentary_lufs: level_db,
+            short_term_lufs: level_db,
+            integrated_lufs: level_db,
+            loudness_range_lu: 0.0,
+            spl_dbfs: level_db,
+            pitch_hz: None,
+            tilt_db_per_octave: None,
+        }
+    }
+
+    #[test]
+    fn parses_rules_with_and_without_units() {
+        assert_eq!(
+            parse("true-peak > -1"),
+            Ok(Condition::Above(Metric::TruePeak, -1.0))
+        );
+        assert_eq!(
+            parse("true-peak > -1 dB"),
+            Ok(Condition::Above(Metric::TruePeak, -1.0))
+        );
+        assert_eq!(
+            parse("short-term < -30 LU"),
+            Ok(Condition::Below(Metric::ShortTerm, -30.0))
+        );
+        assert_eq!(parse("silence > 30"), Ok(Condition::Silence(30.0)));
+        assert_eq!(parse("silence > 30 s"), Ok(Condition::Silence(30.0)));
+    }
+
+    #[test]
+    fn rejects_malformed_rules() {
+        for rule in [
+            "true-peak > -1 potato",
+            "silence > 30 dB",
+            "true-peak > -1 s",
+            "true-peak >= -1",
+            "true-peak = -1",
+            "silence < 30",
+            "true-peak > loud",
+            "true-peak > ",
+            "volume > -1",
+            "true-peak > -1 dB extra",
+            "",
+        ] {
+            assert!(parse(rule).is_err(), "'{}' parsed", rule);
+        }
+    }
+
+    #[test]
+    fn fires_once_until_the_condition_clears() {
+        let mut state = state("peak > -6");
+        let fired: Vec<bool> = [-10.0, -3.0, -2.0, -10.0, -3.0]
+            .iter()
+            .enumerate()
+            .map(|(index, level)| state.check(&row(index as f64 * 0.1, *level)))
+            .collect();
+        assert_eq!(fired, [false, true, false, false, true]);
+    }
+
+    #[test]
+    fn silence_fires_after_its_duration() {
+        let mut state = state("silence > 1");
+        let quiet = SILENCE_DB - 10.0;
+        // Sound resets the timer, so the silence counts from 1.5s
+        let blocks = [
+            (0.0, quiet),
+            (0.9, quiet),
+            (1.0, 0.0),
+            (1.5, quiet),
+            (2.5, quiet),
+            (2.6, quiet),
+            (3.0, quiet),
+        ];
+        let fired: Vec<bool> = blocks
+            .iter()
+            .map(|(time_s, level)| state.check(&row(*time_s, *level)))
+            .collect();
+        assert_eq!(fired, [false, false, false, false, false, true, false]);
+    }
+}
@@ -0,0 +1,659 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The analysis thread: drains the audio thread's queue, runs the analysers and submits their
+//! results to the UI.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use atomic_queue::Queue;
+use basedrop::Shared;
+use druid::{ExtEventSink, Target};
+
+use crate::alerts::AlertMonitor;
+use crate::analysis_frame::{
+    AnalysisFrame, EventFrame, MeterFrame, SpectrumFrame, VersionedFrame, ANALYSIS_FRAME,
+};
+use crate::audio_wave::WaveSnapshot;
+use crate::balance::BalanceMeter;
+use crate::ballistics::{BallisticMeter, Ballistics};
+use crate::buffer_analyser::{ProcessorCounters, SampleChunk, SampleHistory, HISTORY_LEN};
+use crate::clip_log::ClipDetector;
+use crate::clip_snapshots::ClipSnapshotter;
+use crate::clock::{ClockRate, DriftEstimator};
+use crate::denoise::SpectralDenoiser;
+use crate::display_agc::DisplayAgc;
+use crate::dropouts::DropoutDetector;
+use crate::envelope::{EnvelopeFollower, EnvelopeSettings};
+use crate::feedback::FeedbackDetector;
+use crate::gain_reduction::{GainReductionHistory, GainReductionReading};
+use crate::gain_staging::GainCheck;
+use crate::lag_views::{LagAnalyser, WINDOW_LEN};
+use crate::latency::LatencyStatus;
+use crate::level_history::LevelHistory;
+use crate::link::LinkSession;
+use crate::loudness::LoudnessMeter;
+use crate::loudness_graph::{LoudnessData, LOUDNESS_HISTORY_LEN};
+use crate::metrics_log::{BlockMetrics, MetricsRecorder};
+use crate::midi::{ControlPoint, MidiEvent, MidiHistory, MidiMessage, MIDI_CONTROL};
+use crate::noise_floor::NoiseFloorEstimator;
+use crate::phase_view::{self, PhaseAnalyser};
+use crate::profiling::profile_scope;
+use crate::report::{LoudnessReport, LoudnessTarget};
+use crate::resample::StreamResampler;
+use crate::resource_usage::ResourceMonitor;
+use crate::script::{self, ScriptHost, ScriptOutput};
+use crate::spectrogram_view::LiveSpectrogram;
+use crate::spectrum_view::{self, SpectrumAnalyser, SpectrumWindow};
+use crate::spl::{SplMeter, Weighting};
+use crate::stats::StatsData;
+use crate::throttle::{FrameTiming, Pane, PaneRates, PaneSchedule, UpdateThrottle};
+use crate::tilt;
+use crate::transients::{TransientDetector, TransientSettings};
+use crate::true_peak::TruePeakMeter;
+use crate::vad::{ActivityHistory, VoiceActivityDetector};
+
+/// How often the queues are drained and a new waveform snapshot published
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Requests from the UI to the analysis thread
+pub enum AnalysisCommand {
+    ResetLoudness,
+    ExportReport {
+        path: PathBuf,
+        target: LoudnessTarget,
+        /// Named after the input, "Live input" when empty
+        source: String,
+    },
+    ClearClipLog,
+    SetFeedbackDetection(bool),
+    ClearFeedback,
+    /// Run the spectral subtraction preview, dropping its noise print when switched off
+    SetDenoisePreview(bool),
+    CaptureNoisePrint,
+    ClearNoisePrint,
+    SetWeighting(Weighting),
+    SetSplBallistics(Ballistics),
+    /// Seconds the stereo balance is measured over
+    SetBalanceWindow(f64),
+    /// Ballistics of the bar meter
+    SetMeterBallistics(Ballistics),
+    /// Measure a calibrator tone producing this many dB SPL
+    CalibrateSpl(f32),
+    SetEnvelope(EnvelopeSettings),
+    SetSpectrumWindow(SpectrumWindow),
+    /// Mark onsets on the time-based panes, found a block at a time
+    SetTransients(TransientSettings),
+    /// Write the metrics to a CSV file until recording is stopped
+    StartRecording(PathBuf),
+    StopRecording,
+    /// Compile a script and run it on every update from now on, replacing the current one
+    LoadScript(PathBuf),
+    /// Check alert rules against every loudness block from now on
+    SetAlerts(AlertMonitor),
+    /// How many times a second meters and analysis views are submitted, before throttling
+    SetUpdateRate(f64),
+    /// Caps for panes too heavy to update at the full rate
+    SetPaneRates(PaneRates),
+    /// Listen for a while, then suggest a gain change leaving this much headroom
+    StartGainCheck(f32),
+    /// Save a PNG of the waveform around every clip event from now on, in a new folder in here
+    StartClipSnapshots(PathBuf),
+    /// Finish the metrics recording and return, sent once the window has closed
+    Shutdown,
+}
+
+pub fn generate_audio_updates(
+    event_sink: ExtEventSink,
+    queue_handle: Shared<Queue<SampleChunk>>,
+    counters: Shared<ProcessorCounters>,
+    device_rate: f32,
+    commands: Receiver<AnalysisCommand>,
+    frame_timing: Arc<FrameTiming>,
+    clock_rate: Arc<ClockRate>,
+) {
+    // Rebuilt with the meters below for as many channels as the stream has
+    let mut resampler = StreamResampler::new(device_rate);
+    let sample_rate = resampler.output_rate();
+    let mut history = SampleHistory::default();
+    // The monitor's output, resampled alike so its windows line up with the input's
+    let mut processed_resampler = StreamResampler::new(device_rate);
+    let mut processed_history = SampleHistory::default();
+    let mut loudness = LoudnessMeter::with_channels(sample_rate, resampler.channels());
+    let mut true_peak = TruePeakMeter::with_channels(resampler.channels());
+    let mut spl = SplMeter::new(sample_rate);
+    let mut level_meter = BallisticMeter::new(Ballistics::SamplePeak, sample_rate);
+    let mut display_agc = DisplayAgc::new(sample_rate);
+    let mut clip_detector = ClipDetector::new(sample_rate);
+    let mut dropout_detector = DropoutDetector::new(sample_rate);
+    let mut clip_events = Vec::new();
+    let mut clip_snapshots: Option<ClipSnapshotter> = None;
+    let mut envelope = EnvelopeFollower::new(sample_rate);
+    let mut transients = TransientDetector::new(sample_rate);
+    let mut noise_floor = NoiseFloorEstimator::new(sample_rate);
+    let mut level_history = LevelHistory::new(sample_rate);
+    let mut spectrogram = LiveSpectrogram::new(sample_rate);
+    let mut gain_check: Option<GainCheck> = None;
+    let mut midi = MidiHistory::new(HISTORY_LEN);
+    let mut gain_reduction = GainReductionHistory::new(HISTORY_LEN);
+    let mut script: Option<ScriptHost> = None;
+    let mut block_metrics = BlockMetrics::new(sample_rate);
+    let mut recorder: Option<MetricsRecorder> = None;
+    let mut alerts: Option<AlertMonitor> = None;
+    // Reported with the next update
+    let mut script_error: Option<String> = None;
+    let lag_analyser = LagAnalyser::new(sample_rate);
+    let phase_analyser = PhaseAnalyser::new(sample_rate);
+    let mut spectrum_analyser = SpectrumAnalyser::new(sample_rate);
+    // Only runs while feedback detection is switched on
+    let mut feedback: Option<FeedbackDetector> = None;
+    // Only runs while the denoise preview is shown
+    let mut denoiser: Option<SpectralDenoiser> = None;
+    // Samples measured since the last reset
+    let mut num_samples = 0;
+    let mut momentary = VecDeque::with_capacity(LOUDNESS_HISTORY_LEN);
+    let mut short_term = VecDeque::with_capacity(LOUDNESS_HISTORY_LEN);
+    let mut throttle = UpdateThrottle::new(frame_timing.clone());
+    let mut panes = PaneSchedule::default();
+    let mut drift = DriftEstimator::new(device_rate);
+    let mut resources = ResourceMonitor::new(device_rate);
+    let mut balance = BalanceMeter::new(device_rate);
+    // The newest calibration result not sent yet
+    let mut latency = None;
+    // Start of the newest clip or dropout event not sent yet
+    let mut trigger = None;
+    let mut next_update = Instant::now();
+    // As of the last update
+    let mut was_hidden = false;
+    let mut clip_log_changed = false;
+    let mut feedback_changed = false;
+    let mut level_history_changed = false;
+    let mut spectrogram_changed = false;
+    let mut denoise_changed = false;
+
+    'updates: loop {
+        for command in commands.try_iter() {
+            match command {
+                AnalysisCommand::Shutdown => break 'updates,
+                AnalysisCommand::ResetLoudness => {
+                    loudness.reset();
+                    true_peak.reset();
+                    num_samples = 0;
+                }
+                AnalysisCommand::ExportReport {
+                    path,
+                    target,
+                    source,
+                } => {
+                    let source = if source.is_empty() {
+                        "Live input".to_string()
+                    } else {
+                        source
+                    };
+                    let report = LoudnessReport::new(
+                        source,
+                        num_samples as f32 / sample_rate,
+                        &loudness,
+                        &true_peak,
+                        target,
+                    );
+                    if let Err(err) = report.write(&path) {
+                        log::error!("Failed to write report to {:?}: {}", path, err);
+                    }
+                }
+                AnalysisCommand::ClearClipLog => {
+                    clip_events.clear();
+                    clip_log_changed = true;
+                }
+                AnalysisCommand::SetFeedbackDetection(enabled) => {
+                    feedback = enabled.then(|| FeedbackDetector::new(sample_rate));
+                    feedback_changed = true;
+                }
+                AnalysisCommand::SetWeighting(weighting) => spl.set_weighting(weighting),
+                AnalysisCommand::SetSplBallistics(ballistics) => spl.set_ballistics(ballistics),
+                AnalysisCommand::SetBalanceWindow(seconds) => balance.set_window(seconds),
+                AnalysisCommand::SetMeterBallistics(ballistics) => {
+                    level_meter.set_ballistics(ballistics)
+                }
+                AnalysisCommand::CalibrateSpl(reference_db) => spl.calibrate(reference_db),
+                AnalysisCommand::SetEnvelope(settings) => envelope.set_settings(settings),
+                AnalysisCommand::SetSpectrumWindow(window) => spectrum_analyser.set_window(window),
+                AnalysisCommand::SetTransients(settings) => transients.set_settings(settings),
+                AnalysisCommand::StartRecording(path) => {
+                    finish_recording(recorder.take());
+                    recorder = MetricsRecorder::create(&path, block_metrics.time_s())
+                        .map_err(|err| {
+                            log::error!("Failed to create metrics log {:?}: {}", path, err)
+                        })
+                        .ok();
+                }
+                AnalysisCommand::StopRecording => finish_recording(recorder.take()),
+                AnalysisCommand::LoadScript(path) => match ScriptHost::load(&path) {
+                    Ok(host) => script = Some(host),
+                    Err(err) => {
+                        log::error!("Failed to load script {:?}: {}", path, err);
+                        script = None;
+                        script_error = Some(err);
+                    }
+                },
+                AnalysisCommand::SetAlerts(monitor) => alerts = Some(monitor),
+                AnalysisCommand::SetUpdateRate(rate_hz) => throttle.set_rate(rate_hz),
+                AnalysisCommand::SetPaneRates(rates) => panes.set_rates(rates),
+                AnalysisCommand::StartClipSnapshots(dir) => {
+                    clip_snapshots = ClipSnapshotter::create(&dir, sample_rate)
+                        .map_err(|err| {
+                            log::error!(
+                                "Failed to create clip snapshot folder in {:?}: {}",
+                                dir,
+                                err
+                            )
+                        })
+                        .ok();
+                }
+                AnalysisCommand::StartGainCheck(headroom_db) => {
+                    gain_check = Some(GainCheck::new(sample_rate, headroom_db))
+                }
+                AnalysisCommand::SetDenoisePreview(enabled) => {
+                    denoiser = enabled.then(|| SpectralDenoiser::new(sample_rate));
+                    denoise_changed = true;
+                }
+                AnalysisCommand::CaptureNoisePrint => {
+                    if let Some(denoiser) = &mut denoiser {
+                        denoiser.capture();
+                    }
+                    denoise_changed = true;
+                }
+                AnalysisCommand::ClearNoisePrint => {
+                    if let Some(denoiser) = &mut denoiser {
+                        denoiser.clear();
+                    }
+                    denoise_changed = true;
+                }
+                AnalysisCommand::ClearFeedback => {
+                    if let Some(feedback) = &mut feedback {
+                        feedback.clear();
+                    }
+                    feedback_changed = true;
+                }
+            }
+        }
+
+        {
+            profile_scope!("drain_queue");
+            while let Some(chunk) = queue_handle.pop() {
+                let channels = chunk.channels();
+                if channels != resampler.channels() {
+                    // The first callback, or a device with another channel count was started.
+                    // Loudness, true peak and clip detection cover every channel, the rest follow
+                    // the first.
+                    resampler = StreamResampler::with_channels(device_rate, channels);
+                    loudness = LoudnessMeter::with_channels(sample_rate, channels);
+                    true_peak = TruePeakMeter::with_channels(channels);
+                    num_samples = 0;
+                }
+                for frame in resampler.process(chunk.samples()).chunks(channels) {
+                    let sample = frame[0];
+                    history.push(sample);
+                    spl.process(sample);
+                    level_meter.process(sample);
+                    display_agc.process(sample);
+                    noise_floor.process(sample);
+                    level_history_changed |= level_history.process(sample);
+                    spectrogram_changed |= spectrogram.process(sample);
+                    envelope.process(sample);
+                    transients.process(sample);
+                    let sample_peak = true_peak.process_frame(frame);
+                    if let Some(check) = &mut gain_check {
+                        check.process(sample, sample_peak);
+                    }
+                    for event in clip_detector.process_frame(frame, true_peak.frame_peaks()) {
+                        if let Some(snapshots) = &mut clip_snapshots {
+                            snapshots.trigger(&event);
+                        }
+                        trigger = Some((event.time as f64 * sample_rate as f64).round() as u64);
+                        clip_events.push(event);
+                        clip_log_changed = true;
+                    }
+                    if let Some(event) = dropout_detector.process(sample) {
+                        if let Some(snapshots) = &mut clip_snapshots {
+                            snapshots.trigger(&event);
+                        }
+                        trigger = Some((event.time as f64 * sample_rate as f64).round() as u64);
+                        level_history.mark_dropout();
+                        clip_events.push(event);
+                        clip_log_changed = true;
+                    }
+                    if let Some(snapshots) = &mut clip_snapshots {
+                        snapshots.process(clip_detector.position(), &history);
+                    }
+                    if let Some(feedback) = &mut feedback {
+                        feedback_changed |= feedback.process(sample);
+                    }
+                    if let Some(denoiser) = &mut denoiser {
+                        denoise_changed |= denoiser.process(sample);
+                    }
+                    num_samples += 1;
+                    block_metrics.process(sample, sample_peak);
+                    if let Some(reading) = loudness.process_frame(frame) {
+                        // Only the recorder and alerts read the row, and its pitch and tilt cost
+                        // two FFTs
+                        if recorder.is_some() || alerts.is_some() {
+                            let pitch_hz = lag_analyser
+                                .analyse(&history.latest(WINDOW_LEN))
+                                .cepstrum
+                                .peak_ms
+                                .map(|peak_ms| 1000.0 / peak_ms);
+                            let tilt = tilt::fit(
+                                &spectrum_analyser
+                                    .analyse(&history.latest(spectrum_view::WINDOW_LEN)),
+                            );
+                            let row = block_metrics.row(
+                                reading,
+                                &loudness,
+                                spl.data().level_dbfs,
+                                pitch_hz,
+                                tilt.map(|tilt| tilt.slope_db_per_octave),
+                            );
+                            if let Some(alerts) = &mut alerts {
+                                alerts.process(&row);
+                            }
+                            let written = recorder.as_mut().map(|recorder| recorder.write_row(row));
+                            if let Some(Err(err)) = written {
+                                log::error!("Stopped recording metrics: {}", err);
+                                recorder = None;
+                            }
+                        } else {
+                            block_metrics.start_block();
+                        }
+                        if momentary.len() == LOUDNESS_HISTORY_LEN {
+                            momentary.pop_front();
+                            short_term.pop_front();
+                        }
+                        momentary.push_back(reading.momentary);
+                        short_term.push_back(reading.short_term);
+                    }
+                }
+            }
+            while let Some(chunk) = counters.processed.pop() {
+                for &sample in processed_resampler.process(chunk.samples()).iter() {
+                    processed_history.push(sample);
+                }
+            }
+        }
+
+        // Mapped parameters only need each controller's newest value, and they're sent every poll
+        // so knobs stay responsive when updates are throttled
+        let mut controls: Vec<ControlPoint> = Vec::new();
+        while let Some(event) = counters.midi_events.pop() {
+            // Stamped in device samples
+            let event = MidiEvent {
+                position: resampler.scale(event.position as f64) as u64,
+                ..event
+            };
+            if let MidiMessage::ControlChange { controller, value } = event.message {
+                controls.retain(|point| point.controller != controller);
+                controls.push(ControlPoint {
+                    controller,
+                    value,
+                    position: event.position,
+                });
+            }
+            midi.push(event);
+        }
+        while let Some(reading) = counters.gain_reduction.pop() {
+            // Stamped in device samples too
+            gain_reduction.push(GainReductionReading {
+                position: resampler.scale(reading.position as f64) as u64,
+                frames: resampler.scale(reading.frames as f64).ceil() as u64,
+                ..reading
+            });
+        }
+        if controls.into_iter().any(|control| {
+            event_sink
+                .submit_command(MIDI_CONTROL, control, Target::Auto)
+                .is_err()
+        }) {
+            break;
+        }
+        while let Some(stamp) = counters.stamps.pop() {
+            drift.push(stamp);
+        }
+        while let Some(energy) = counters.balance.pop() {
+            balance.push(energy);
+        }
+        while let Some(measurement) = counters.latency.pop() {
+            latency = Some(LatencyStatus::from_measurement(measurement, device_rate));
+        }
+        clock_rate.set(drift.effective_rate());
+
+        let now = Instant::now();
+        let hidden = frame_timing.hidden();
+        // Shown again, update at once rather than at the end of the hidden interval
+        let shown = was_hidden && !hidden;
+        if now < next_update && !shown {
+            thread::sleep(POLL_INTERVAL.min(next_update - now));
+            continue;
+        }
+        was_hidden = hidden;
+        let update_interval = throttle.next_interval();
+        next_update = now + update_interval;
+
+        {
+            profile_scope!("submit_commands");
+            let mut stats = StatsData {
+                sample_rate: device_rate,
+                buffer_size: counters.buffer_size.load(Ordering::Relaxed),
+                dropped_frames: counters.dropped_frames.load(Ordering::Relaxed),
+                update_rate_hz: 1.0 / update_interval.as_secs_f32(),
+                drift_ppm: drift.drift_ppm() as f32,
+                ..StatsData::default()
+            };
+            stats.measure(history.buffer());
+            let usage = resources.measure(
+                counters.callback_nanos.load(Ordering::Relaxed),
+                counters.position.load(Ordering::Relaxed),
+            );
+            stats.cpu_percent = usage.cpu_percent;
+            stats.memory_bytes = usage.memory_bytes;
+            stats.dsp_load_percent = usage.dsp_load_percent;
+            stats.noise_floor_db = noise_floor.floor_db();
+            stats.snr_db = noise_floor
+                .signal_db()
+                .zip(stats.noise_floor_db)
+                .map(|(signal, floor)| signal - floor);
+
+            let loudness_data = LoudnessData {
+                momentary: Arc::new(momentary.iter().copied().collect()),
+                short_term: Arc::new(short_term.iter().copied().collect()),
+                integrated: loudness.integrated(),
+                range: loudness.loudness_range(),
+            };
+            let spl_data = spl.data();
+            let script_output = match script
+                .as_mut()
+                .map(|host| host.run(script::frame_values(&stats, &loudness_data, &spl_data)))
+            {
+                Some(Ok(output)) => Some(output),
+                Some(Err(err)) => {
+                    log::error!("Stopped script: {}", err);
+                    script = None;
+                    script_error = Some(err);
+                    None
+                }
+                None => None,
+            }
+            .or_else(|| {
+                script_error.take().map(|error| ScriptOutput {
+                    error: Some(error),
+                    ..ScriptOutput::default()
+                })
+            });
+            let meters = MeterFrame {
+                loudness: loudness_data,
+                spl: spl_data,
+                level_db: level_meter.level_db(),
+                balance: balance.data(),
+                stats,
+                envelope: envelope.data(),
+                gain_status: gain_check.as_ref().map(GainCheck::status),
+                script: script_output,
+                display_gain_db: display_agc.gain_db(),
+            };
+            if gain_check.as_ref().is_some_and(GainCheck::is_done) {
+                gain_check = None;
+            }
+            // Capped panes skip their analysis too, not only the repaint, and while the window is
+            // hidden or minimized there's nothing to draw them into. Meters and events still go
+            // out for the tray, alerts and the logs.
+            let spectrum = (!hidden).then(|| SpectrumFrame {
+                spectrum: panes.due(Pane::Spectrum, now).then(|| {
+                    let mut spectrum =
+                        spectrum_analyser.analyse(&history.latest(spectrum_view::WINDOW_LEN));
+                    spectrum.processed_db =
+                        counters.monitoring.load(Ordering::Relaxed).then(|| {
+                            spectrum_analyser
+                                .analyse(&processed_history.latest(spectrum_view::WINDOW_LEN))
+                                .magnitudes_db
+                        });
+                    spectrum
+                }),
+                phase: panes
+                    .due(Pane::Phase, now)
+                    .then(|| phase_analyser.analyse(&history.latest(phase_view::WINDOW_LEN))),
+                lag_views: panes
+                    .due(Pane::LagViews, now)
+                    .then(|| lag_analyser.analyse(&history.latest(WINDOW_LEN))),
+                spectrogram: (spectrogram_changed && panes.due(Pane::Spectrogram, now)).then(
+                    || {
+                        spectrogram_changed = false;
+                        spectrogram.data()
+                    },
+                ),
+                denoise: denoiser
+                    .as_ref()
+                    .filter(|_| denoise_changed)
+                    .map(SpectralDenoiser::data),
+            });
+            let events = EventFrame {
+                clip_log: clip_log_changed.then(|| Arc::new(clip_events.clone())),
+                feedback: feedback_changed.then(|| {
+                    Arc::new(
+                        feedback
+                            .as_ref()
+                            .map(|feedback| feedback.candidates().to_vec())
+                            .unwrap_or_default(),
+                    )
+                }),
+                level_history: level_history_changed.then(|| level_history.data()),
+                // Same span as the live waveform, which ends at the newest sample too
+                midi: midi.data(history.position() as u64),
+                gain_reduction: gain_reduction.data(history.position() as u64),
+                latency: latency.take(),
+                trigger: trigger.take(),
+                transients: transients.take(),
+            };
+            let mut frames = std::iter::once(AnalysisFrame::Meters(meters))
+                .chain(spectrum.map(AnalysisFrame::Spectrum))
+                .chain(std::iter::once(AnalysisFrame::Events(events)));
+            if frames.any(|frame| {
+                event_sink
+                    .submit_command(ANALYSIS_FRAME, VersionedFrame::new(frame), Target::Auto)
+                    .is_err()
+            }) {
+                break;
+            }
+        }
+        clip_log_changed = false;
+        feedback_changed = false;
+        level_history_changed = false;
+        denoise_changed = false;
+        thread::sleep(POLL_INTERVAL);
+    }
+    finish_recording(recorder);
+}
+
+fn finish_recording(recorder: Option<MetricsRecorder>) {
+    if let Some(Err(err)) = recorder.map(MetricsRecorder::finish) {
+        log::error!("Failed to finish metrics log: {}", err);
+    }
+}
+
+/// The waveform's consumer of the analysis bus: keeps its own history, runs voice activity
+/// detection over it and publishes snapshots for the live pane until `stop` is set
+pub fn generate_waveform_snapshots(
+    queue_handle: Shared<Queue<SampleChunk>>,
+    stop: Arc<AtomicBool>,
+    snapshot: WaveSnapshot,
+    device_rate: f32,
+    frame_timing: Arc<FrameTiming>,
+    clock_rate: Arc<ClockRate>,
+) {
+    let mut resampler = StreamResampler::new(device_rate);
+    let sample_rate = resampler.output_rate();
+    let mut history = SampleHistory::default();
+    // Kept alongside the first channel's, silence while the stream is mono
+    let mut right_history = SampleHistory::default();
+    let mut vad = VoiceActivityDetector::new(sample_rate);
+    let mut voice_activity = ActivityHistory::default();
+    let mut link = LinkSession::new();
+    snapshot.set_nominal_rate(sample_rate);
+
+    while !stop.load(Ordering::Relaxed) {
+        while let Some(chunk) = queue_handle.pop() {
+            let channels = chunk.channels();
+            if channels != resampler.channels() {
+                resampler = StreamResampler::with_channels(device_rate, channels);
+            }
+            for frame in resampler.process(chunk.samples()).chunks(channels) {
+                history.push(frame[0]);
+                right_history.push(frame.get(1).copied().unwrap_or(0.0));
+                voice_activity.push(vad.process(frame[0]));
+            }
+        }
+        let right = (resampler.channels() > 1).then(|| right_history.latest(HISTORY_LEN));
+
+        // Nothing draws the waveform while the window is hidden, unless the video recorder is
+        // reading it. The position keeps up either way, recordings are stamped with it.
+        if !frame_timing.hidden() || snapshot.has_background_readers() {
+            profile_scope!("publish_snapshot");
+            let rate = resampler.scale(clock_rate.get(device_rate) as f64) as f32;
+            snapshot.publish(
+                history.latest(HISTORY_LEN),
+                right,
+                voice_activity.regions(),
+                history.position() as u64,
+                // The time axis scrolls at the rate the device really runs at
+                rate,
+                link.beat_grid(rate),
+            );
+        } else {
+            snapshot.set_position(history.position() as u64);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
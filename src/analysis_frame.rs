@@ -0,0 +1,210 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! One typed payload for everything the analysis thread sends the live window. It submits an
+//! [`AnalysisFrame`] per kind of product, and the window's delegate hands the parts on to the
+//! panes under their own draw commands.
+//!
+//! Frames carry [`FRAME_VERSION`]. Whoever changes a payload bumps it, and frames of another
+//! version are dropped rather than misread, so a window and analysis built apart can't mix them
+//! up.
+
+use std::sync::Arc;
+
+use druid::{Command, Selector};
+
+use crate::audio_wave::DRAW_AUDIO;
+use crate::balance::{BalanceData, DRAW_BALANCE};
+use crate::ballistics::DRAW_LEVEL;
+use crate::clip_log::{ClipEvent, DRAW_CLIP_LOG};
+use crate::denoise::{DenoiseData, DRAW_DENOISE};
+use crate::display_agc::DRAW_DISPLAY_GAIN;
+use crate::envelope::{EnvelopeData, DRAW_ENVELOPE};
+use crate::feedback::{FeedbackCandidate, DRAW_FEEDBACK};
+use crate::gain_reduction::{GainReductionData, DRAW_GAIN_REDUCTION};
+use crate::gain_staging::{GainStatus, DRAW_GAIN_STAGING};
+use crate::lag_views::{LagViews, DRAW_LAG_VIEWS};
+use crate::latency::{LatencyStatus, DRAW_LATENCY};
+use crate::level_history::{LevelHistoryData, DRAW_LEVEL_HISTORY};
+use crate::loudness_graph::{LoudnessData, DRAW_LOUDNESS};
+use crate::midi::{MidiRollData, DRAW_MIDI};
+use crate::phase_view::{PhaseData, DRAW_PHASE};
+use crate::script::{ScriptOutput, DRAW_SCRIPT};
+use crate::spectrogram_view::{SpectrogramData, DRAW_SPECTROGRAM};
+use crate::spectrum_view::{SpectrumData, DRAW_SPECTRUM};
+use crate::spl::{SplData, DRAW_SPL};
+use crate::stats::{StatsData, DRAW_STATS};
+use crate::timeline::DRAW_TRIGGER;
+use crate::transients::DRAW_TRANSIENTS;
+
+pub const ANALYSIS_FRAME: Selector<VersionedFrame> = Selector::new("event-example.analysis_frame");
+
+pub const FRAME_VERSION: u32 = 8;
+
+/// Level meters and readouts, sent every update
+#[derive(Clone)]
+pub struct MeterFrame {
+    pub loudness: LoudnessData,
+    pub spl: SplData,
+    /// Bar meter reading in dBFS
+    pub level_db: f32,
+    pub balance: BalanceData,
+    pub stats: StatsData,
+    pub envelope: EnvelopeData,
+    /// While a gain check is running
+    pub gain_status: Option<GainStatus>,
+    /// While a script is loaded, or once after it failed
+    pub script: Option<ScriptOutput>,
+    /// Display AGC's gain, whether or not it's shown
+    pub display_gain_db: f32,
+}
+
+/// Frequency and phase analyses of the latest window, each only when its pane is due
+#[derive(Clone)]
+pub struct SpectrumFrame {
+    pub spectrum: Option<SpectrumData>,
+    pub phase: Option<PhaseData>,
+    pub lag_views: Option<LagViews>,
+    /// When a column was added since it was last sent
+    pub spectrogram: Option<SpectrogramData>,
+    /// When the preview has new audio
+    pub denoise: Option<DenoiseData>,
+}
+
+/// Logs and histories, each only when it changed apart from the MIDI roll and gain reduction,
+/// which scroll
+#[derive(Clone)]
+pub struct EventFrame {
+    pub clip_log: Option<Arc<Vec<ClipEvent>>>,
+    pub feedback: Option<Arc<Vec<FeedbackCandidate>>>,
+    pub level_history: Option<LevelHistoryData>,
+    pub midi: MidiRollData,
+    pub gain_reduction: GainReductionData,
+    /// When a calibration click came back or timed out
+    pub latency: Option<LatencyStatus>,
+    /// Stream position of the newest clip or dropout event, when there's a new one
+    pub trigger: Option<u64>,
+    /// Onsets the transient detector has found since the last frame
+    pub transients: Vec<u64>,
+}
+
+#[derive(Clone)]
+pub enum AnalysisFrame {
+    /// Samples for a static pane, oldest first, as [`DRAW_AUDIO`] carries them. The live waveform
+    /// reads its snapshot instead, so nothing sends these yet.
+    #[allow(dead_code)]
+    Waveform(Vec<f32>),
+    Spectrum(SpectrumFrame),
+    Meters(MeterFrame),
+    Events(EventFrame),
+}
+
+impl AnalysisFrame {
+    /// The panes' draw commands carrying the frame's parts
+    pub fn commands(&self) -> Vec<Command> {
+        let mut commands = Vec::new();
+        match self {
+            AnalysisFrame::Waveform(samples) => commands.push(DRAW_AUDIO.with(samples.clone())),
+            AnalysisFrame::Spectrum(frame) => {
+                commands.extend(frame.spectrum.clone().map(|data| DRAW_SPECTRUM.with(data)));
+                commands.extend(frame.phase.clone().map(|data| DRAW_PHASE.with(data)));
+                commands.extend(
+                    frame
+                        .lag_views
+                        .clone()
+                        .map(|views| DRAW_LAG_VIEWS.with(views)),
+                );
+                commands.extend(
+                    frame
+                        .spectrogram
+                        .clone()
+                        .map(|data| DRAW_SPECTROGRAM.with(data)),
+                );
+                commands.extend(frame.denoise.clone().map(|data| DRAW_DENOISE.with(data)));
+            }
+            AnalysisFrame::Meters(frame) => {
+                commands.push(DRAW_LOUDNESS.with(frame.loudness.clone()));
+                commands.push(DRAW_SPL.with(frame.spl.clone()));
+                commands.push(DRAW_LEVEL.with(frame.level_db));
+                commands.push(DRAW_DISPLAY_GAIN.with(frame.display_gain_db));
+                commands.push(DRAW_BALANCE.with(frame.balance));
+                commands.push(DRAW_STATS.with(frame.stats.clone()));
+                commands.push(DRAW_ENVELOPE.with(frame.envelope.clone()));
+                commands.extend(
+                    frame
+                        .gain_status
+                        .map(|status| DRAW_GAIN_STAGING.with(status)),
+                );
+                commands.extend(frame.script.clone().map(|output| DRAW_SCRIPT.with(output)));
+            }
+            AnalysisFrame::Events(frame) => {
+                commands.extend(
+                    frame
+                        .clip_log
+                        .clone()
+                        .map(|events| DRAW_CLIP_LOG.with(events)),
+                );
+                commands.extend(
+                    frame
+                        .feedback
+                        .clone()
+                        .map(|candidates| DRAW_FEEDBACK.with(candidates)),
+                );
+                commands.extend(
+                    frame
+                        .level_history
+                        .clone()
+                        .map(|data| DRAW_LEVEL_HISTORY.with(data)),
+                );
+                commands.push(DRAW_MIDI.with(frame.midi.clone()));
+                commands.push(DRAW_GAIN_REDUCTION.with(frame.gain_reduction.clone()));
+                commands.extend(frame.latency.map(|status| DRAW_LATENCY.with(status)));
+                commands.extend(frame.trigger.map(|position| DRAW_TRIGGER.with(position)));
+                if !frame.transients.is_empty() {
+                    commands.push(DRAW_TRANSIENTS.with(frame.transients.clone()));
+                }
+            }
+        }
+        commands
+    }
+}
+
+/// What's submitted under [`ANALYSIS_FRAME`]
+#[derive(Clone)]
+pub struct VersionedFrame {
+    pub version: u32,
+    pub frame: AnalysisFrame,
+}
+
+impl VersionedFrame {
+    pub fn new(frame: AnalysisFrame) -> Self {
+        VersionedFrame {
+            version: FRAME_VERSION,
+            frame,
+        }
+    }
+
+    /// The frame, unless it was made by another version
+    pub fn frame(&self) -> Option<&AnalysisFrame> {
+        (self.version == FRAME_VERSION).then_some(&self.frame)
+    }
+}
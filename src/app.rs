@@ -0,0 +1,984 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The native app: command line, audio host, analysis threads and the window's state. The
+//! window's widgets, menus and command handling are in the submodules.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+use druid::{
+    commands, AppLauncher, Command, Data, FileDialogOptions, FileInfo, FileSpec, Lens,
+    LocalizedString, Selector, WindowDesc,
+};
+
+use audio_garbage_collector::GarbageCollector;
+use cpal::traits::{DeviceTrait, HostTrait};
+
+use crate::alerts::{AlertMonitor, AlertRule};
+use crate::analysis::{self, AnalysisCommand};
+use crate::audio_input::CpalBackend;
+use crate::audio_wave::{
+    Interpolation, WaveData, WaveSnapshot, MIN_VISIBLE_SAMPLES, STROKE_WIDTHS, WAVE_COLORS,
+};
+use crate::backend::{AudioBackend, AudioEngine, BufferStatus, FileBackend, NullBackend};
+use crate::balance::{BalanceData, DEFAULT_WINDOW_SECONDS};
+use crate::ballistics::Ballistics;
+use crate::buffer_analyser::{BufferAnalyserProcessor, HISTORY_LEN};
+use crate::clip_log::ClipEvent;
+use crate::clock::ClockRate;
+use crate::compare;
+use crate::config::Config;
+use crate::correlation_map;
+use crate::denoise::DenoiseData;
+use crate::device_formats::DeviceFormats;
+use crate::display_agc::DisplayScaling;
+use crate::envelope::{EnvelopeData, EnvelopeSettings};
+use crate::feedback::FeedbackCandidate;
+use crate::freeze::FrozenPanes;
+use crate::gain_reduction::GainReductionData;
+use crate::gain_staging::GainStatus;
+use crate::http_stream::HttpStreamBackend;
+use crate::hum::Hum;
+use crate::i18n;
+use crate::k_system::MeterScale;
+use crate::lag_views::LagViews;
+use crate::lanes::LaneInput;
+use crate::latency::LatencyStatus;
+use crate::level_history::LevelHistoryData;
+use crate::loudness_graph::LoudnessData;
+use crate::loudness_report;
+use crate::midi::{MidiMapping, MidiRollData, MidiTarget};
+use crate::monitor::{MonitorControl, SoloBand};
+use crate::network_input::{NetworkBackend, PacketFormat};
+use crate::phase_view::PhaseData;
+use crate::presets::{self, Preset};
+use crate::profiling;
+use crate::render_quality::{self, RenderProfile, RenderQuality};
+use crate::render_spectrogram;
+use crate::replay;
+use crate::report::LoudnessTarget;
+use crate::room_response;
+use crate::script::ScriptOutput;
+use crate::spectrogram::DisplayRange;
+use crate::spectrogram_view::SpectrogramData;
+use crate::spectrum_view::{Peak, SpectrumData, SpectrumWindow};
+use crate::spl::{SplData, Weighting};
+use crate::stats::StatsData;
+use crate::target_curve::{TargetCurve, TargetDeviation};
+use crate::throttle::{FrameTiming, PaneRates, UPDATE_RATES_HZ};
+use crate::tilt::Tilt;
+use crate::timeline::{Timeline, TimelineData};
+use crate::transients::TransientSettings;
+use crate::units::{AmplitudeUnit, DB_FLOORS};
+use crate::video::VideoRecorder;
+use crate::workspace::Workspace;
+
+use self::delegate::LiveDelegate;
+use self::menus::make_menu;
+use self::ui::make_ui;
+
+mod delegate;
+mod menus;
+mod ui;
+
+pub fn run() {
+    let matches = clap::App::new("example-druid-audio-viz")
+        .arg(clap::Arg::from_usage(
+            "--script=[SCRIPT] 'Rhai script computing custom meters and overlays, see src/script.rs'",
+        ))
+        .arg(
+            clap::Arg::from_usage(
+                "--alert=[RULE]... 'Notify when a rule such as \"true-peak > -1\" or \"silence > 30\" fires'",
+            )
+            .number_of_values(1),
+        )
+        .arg(
+            clap::Arg::from_usage(
+                "--lane=[DEVICE]... 'Also draw this input device in a lane under the waveform'",
+            )
+            .number_of_values(1),
+        )
+        .arg(clap::Arg::from_usage(
+            "--webhook=[URL] 'Also POST fired alerts to this URL as JSON'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--clip-snapshots=[DIR] 'Save a PNG of the waveform around every clip event to a new folder in DIR'",
+        ))
+        .arg(
+            clap::Arg::from_usage("--backend=[BACKEND] 'Where the live input comes from'")
+                .possible_values(&BACKENDS)
+                .default_value(BACKENDS[0]),
+        )
+        .arg(clap::Arg::from_usage(
+            "--input-file=[FILE] 'Audio file the file backend loops'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--stream-url=[URL] 'Icecast or other HTTP audio stream the http backend monitors'",
+        ))
+        .arg(
+            clap::Arg::from_usage(
+                "--listen=[ADDRESS] 'UDP address the network backend receives on'",
+            )
+            .default_value("0.0.0.0:5004"),
+        )
+        .arg(
+            clap::Arg::from_usage("--packet-format=[FORMAT] 'What the network backend receives'")
+                .possible_values(&PacketFormat::NAMES)
+                .default_value(PacketFormat::NAMES[0]),
+        )
+        .arg(
+            clap::Arg::from_usage(
+                "--network-rate=[HZ] 'Sample rate the network backend's sender uses'",
+            )
+            .default_value("44100"),
+        )
+        .arg(
+            clap::Arg::from_usage(
+                "--network-channels=[CHANNELS] 'Channels the network backend's sender interleaves'",
+            )
+            .default_value("2"),
+        )
+        .arg(clap::Arg::from_usage(
+            "--no-autostart 'Open with the audio engine stopped, until it is started from the window'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--stream-video=[URL] 'Stream the waveform as MPEG-TS for OBS or VJ software, e.g. udp://127.0.0.1:1234'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--ndi=[NAME] 'Publish the waveform as an NDI source with this name, needs the ndi feature'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--syphon=[NAME] 'Publish the waveform as a Syphon server with this name, needs macOS and the syphon feature'",
+        ))
+        .arg(clap::Arg::from_usage(
+            "--spout=[NAME] 'Publish the waveform as a Spout sender with this name, needs Windows and the spout feature'",
+        ))
+        .arg(
+            clap::Arg::from_usage(
+                "--profile-address=[ADDRESS] 'Where to serve puffin profiles, e.g. 0.0.0.0:8585 to allow other machines, needs the profiling feature'",
+            )
+            .default_value(profiling::DEFAULT_ADDRESS),
+        )
+        .subcommand(render_spectrogram::subcommand())
+        .subcommand(correlation_map::subcommand())
+        .subcommand(compare::subcommand())
+        .subcommand(loudness_report::subcommand())
+        .subcommand(room_response::subcommand())
+        .subcommand(replay::subcommand())
+        .get_matches();
+
+    match matches.subcommand() {
+        ("render-spectrogram", Some(matches)) => render_spectrogram::run(matches),
+        ("render-correlation", Some(matches)) => correlation_map::run(matches),
+        ("compare", Some(matches)) => compare::run(matches),
+        ("loudness-report", Some(matches)) => loudness_report::run(matches),
+        ("room-response", Some(matches)) => room_response::run(matches),
+        ("replay", Some(matches)) => replay::run(matches),
+        _ => run_gui(&matches),
+    }
+}
+
+/// Values of `--backend`, the first is the default
+const BACKENDS: [&str; 5] = ["cpal", "file", "http", "network", "null"];
+
+fn make_backend(
+    matches: &clap::ArgMatches,
+    handle: &basedrop::Handle,
+    input_device: Option<String>,
+) -> Box<dyn AudioBackend> {
+    match matches.value_of("backend") {
+        Some("file") => {
+            let path = matches.value_of("input-file").unwrap_or_else(|| {
+                eprintln!("--backend file needs an --input-file");
+                std::process::exit(1)
+            });
+            let backend = FileBackend::new(path).unwrap_or_else(|err| {
+                eprintln!("Failed to read {}: {}", path, err);
+                std::process::exit(1)
+            });
+            Box::new(backend)
+        }
+        Some("http") => {
+            let url = matches.value_of("stream-url").unwrap_or_else(|| {
+                eprintln!("--backend http needs a --stream-url");
+                std::process::exit(1)
+            });
+            let backend = HttpStreamBackend::new(url).unwrap_or_else(|err| {
+                eprintln!("Failed to open {}: {}", url, err);
+                std::process::exit(1)
+            });
+            Box::new(backend)
+        }
+        Some("network") => {
+            let number = |name: &str| {
+                let value = matches.value_of(name).unwrap();
+                value.parse::<u32>().unwrap_or_else(|_| {
+                    eprintln!("--{} must be a number, not {}", name, value);
+                    std::process::exit(1)
+                })
+            };
+            Box::new(NetworkBackend::new(
+                matches.value_of("listen").unwrap().to_string(),
+                matches.value_of("packet-format").unwrap().parse().unwrap(),
+                number("network-rate") as f32,
+                number("network-channels") as usize,
+            ))
+        }
+        Some("null") => Box::new(NullBackend::new(44100.0)),
+        _ => Box::new(CpalBackend::new(handle, input_device)),
+    }
+}
+
+fn run_gui(matches: &clap::ArgMatches) {
+    profiling::start(matches.value_of("profile-address").unwrap());
+    let (commands_tx, commands_rx) = channel();
+    if let Some(path) = matches.value_of("script") {
+        let _ = commands_tx.send(AnalysisCommand::LoadScript(PathBuf::from(path)));
+    }
+    if let Some(dir) = matches.value_of("clip-snapshots") {
+        let _ = commands_tx.send(AnalysisCommand::StartClipSnapshots(PathBuf::from(dir)));
+    }
+    let rules = matches
+        .values_of("alert")
+        .into_iter()
+        .flatten()
+        .map(str::parse)
+        .collect::<Result<Vec<AlertRule>, _>>()
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1)
+        });
+    if !rules.is_empty() {
+        let webhook = matches.value_of("webhook").map(str::to_string);
+        let _ = commands_tx.send(AnalysisCommand::SetAlerts(AlertMonitor::new(
+            rules, webhook,
+        )));
+    }
+    let snapshot = WaveSnapshot::default();
+    let monitor = Arc::new(MonitorControl::default());
+    let frame_timing = FrameTiming::new();
+    let lanes: Vec<LaneInput> = matches
+        .values_of("lane")
+        .into_iter()
+        .flatten()
+        .filter_map(|name| {
+            LaneInput::open(name, snapshot.clone(), frame_timing.clone())
+                .map_err(|err| log::error!("Failed to open lane {}: {}", name, err))
+                .ok()
+        })
+        .collect();
+    let config = Config::load();
+    let midi_mappings = Arc::new(config.midi_mappings.clone());
+    let input_device = config.input_device.clone();
+    let workspaces = if config.workspaces.is_empty() {
+        Workspace::defaults()
+    } else {
+        config.workspaces.clone()
+    };
+    let input_devices = input_device_names();
+    let mut garbage_collector = GarbageCollector::default();
+    let mut processor = BufferAnalyserProcessor::new(garbage_collector.handle(), monitor.clone());
+    let analysis_queue = processor.subscribe();
+    let waveform_queue = processor.subscribe();
+    let counters = processor.counters();
+    let backend = make_backend(matches, garbage_collector.handle(), input_device.clone());
+    let engine = Rc::new(RefCell::new(AudioEngine::new(processor, backend)));
+    let sample_rate = engine.borrow().sample_rate();
+    let start_error = if matches.is_present("no-autostart") {
+        None
+    } else {
+        engine
+            .borrow_mut()
+            .start(input_device.clone())
+            .err()
+            .map(|err| {
+                log::error!("Failed to start the input: {}", err);
+                err.to_string()
+            })
+    };
+    let delegate_commands = commands_tx.clone();
+    let shutdown_commands = commands_tx.clone();
+    let window = WindowDesc::new(make_ui(
+        commands_tx,
+        snapshot.clone(),
+        monitor,
+        frame_timing.clone(),
+        lanes.iter().map(LaneInput::snapshot).collect(),
+        config,
+    ))
+    .title(LocalizedString::new("window-title"))
+    .menu(move |_, _, _| make_menu(&input_devices));
+
+    let launcher = i18n::with_strings(AppLauncher::with_window(window)).delegate(
+        LiveDelegate::new(delegate_commands, snapshot.clone(), engine.clone()),
+    );
+    let event_sink = launcher.get_external_handle();
+    render_quality::watch_power_source(launcher.get_external_handle());
+
+    let clock_rate = Arc::new(ClockRate::default());
+    let analysis_thread = {
+        let frame_timing = frame_timing.clone();
+        let clock_rate = clock_rate.clone();
+        thread::spawn(move || {
+            analysis::generate_audio_updates(
+                event_sink,
+                analysis_queue,
+                counters,
+                sample_rate,
+                commands_rx,
+                frame_timing,
+                clock_rate,
+            )
+        })
+    };
+    let video_stream = matches.value_of("stream-video").and_then(|url| {
+        VideoRecorder::start(Path::new(url), snapshot.clone(), HISTORY_LEN)
+            .map_err(|err| log::error!("Failed to start ffmpeg for {}: {}", url, err))
+            .ok()
+    });
+    let ndi_stream = matches.value_of("ndi").and_then(|name| {
+        VideoRecorder::start_ndi(name, snapshot.clone(), HISTORY_LEN)
+            .map_err(|err| log::error!("Failed to start NDI source {}: {}", name, err))
+            .ok()
+    });
+    let syphon_stream = matches.value_of("syphon").and_then(|name| {
+        VideoRecorder::start_syphon(name, snapshot.clone(), HISTORY_LEN)
+            .map_err(|err| log::error!("Failed to start Syphon server {}: {}", name, err))
+            .ok()
+    });
+    let spout_stream = matches.value_of("spout").and_then(|name| {
+        VideoRecorder::start_spout(name, snapshot.clone(), HISTORY_LEN)
+            .map_err(|err| log::error!("Failed to start Spout sender {}: {}", name, err))
+            .ok()
+    });
+    let stop_snapshots = Arc::new(AtomicBool::new(false));
+    let snapshot_thread = {
+        let stop = stop_snapshots.clone();
+        thread::spawn(move || {
+            analysis::generate_waveform_snapshots(
+                waveform_queue,
+                stop,
+                snapshot,
+                sample_rate,
+                frame_timing,
+                clock_rate,
+            )
+        })
+    };
+
+    launcher
+        .launch(AppState {
+            audio: AudioSnapshot {
+                wave: WaveData::new(HISTORY_LEN),
+                lanes: Arc::new(vec![WaveData::new(HISTORY_LEN); lanes.len()]),
+                loudness: LoudnessData::default(),
+                clip_log: Arc::new(Vec::new()),
+                lag_views: LagViews::default(),
+                phase: PhaseData::default(),
+                spectrum: SpectrumData::default(),
+                spectrogram: SpectrogramData::default(),
+                denoise: DenoiseData::default(),
+                hum: None,
+                tilt: None,
+                target_deviation: None,
+                peak_table: Arc::new(Vec::new()),
+                midi: MidiRollData::default(),
+                script: ScriptOutput::default(),
+                envelope: EnvelopeData::default(),
+                level_history: LevelHistoryData::default(),
+                feedback: Arc::new(Vec::new()),
+                spl: SplData::default(),
+                level_db: f32::NEG_INFINITY,
+                balance: BalanceData::default(),
+                gain_status: GainStatus::default(),
+                latency: LatencyStatus::Unmeasured,
+                display_gain_db: 0.0,
+                gain_reduction: GainReductionData::default(),
+            },
+            settings: ViewSettings {
+                report_target: LoudnessTarget::Broadcast,
+                show_phase: false,
+                show_spectrum: false,
+                show_spectrogram: false,
+                spectrogram_range: DisplayRange::default(),
+                show_denoise: false,
+                show_midi: false,
+                midi_mappings,
+                presets: Arc::new(presets::load()),
+                preset_name: String::new(),
+                meters_ratio: 0.75,
+                wave_ratio: 0.4,
+                workspaces: Arc::new(workspaces),
+                workspace_name: String::new(),
+                envelope_settings: EnvelopeSettings::default(),
+                show_envelope: false,
+                show_level_history: false,
+                feedback_detection: false,
+                weighting: Weighting::A,
+                spl_ballistics: Ballistics::Fast,
+                balance_window: DEFAULT_WINDOW_SECONDS,
+                meter_ballistics: Ballistics::SamplePeak,
+                meter_scale: MeterScale::Dbfs,
+                calibration_reference: 94.0,
+                gain_headroom_db: 6.0,
+                amplitude_unit: AmplitudeUnit::Dbfs,
+                db_floor: DB_FLOORS[0],
+                db_waveform: false,
+                wave_gain_db: 0.0,
+                display_scaling: DisplayScaling::Manual,
+                wave_color: 0,
+                wave_stroke_width: STROKE_WIDTHS[2],
+                wave_antialias: true,
+                channel_overlay: false,
+                interpolation: Interpolation::Linear,
+                update_rate_hz: UPDATE_RATES_HZ[0],
+                pane_rates: PaneRates::default(),
+                spectrum_window: SpectrumWindow::default(),
+                pink_reference: false,
+                spectrum_smoothing: 0,
+                processed_smoothing: 0,
+                target_curve: None,
+                transients: TransientSettings::default(),
+                wave_resolution: 1.0,
+                spectrum_persistence: 0,
+                auto_battery_saver: true,
+                lane_names: Arc::new(
+                    std::iter::once(String::new())
+                        .chain(lanes.iter().map(|lane| lane.name().to_string()))
+                        .collect(),
+                ),
+            },
+            transport: Transport {
+                solo_band: None,
+                mains_hz: None,
+                monitor_input: false,
+                talkback: false,
+                remove_hum: false,
+                peaks_frozen: false,
+                frozen_panes: FrozenPanes::default(),
+                midi_learn: None,
+                recording: false,
+                recording_video: false,
+                engine_running: engine.borrow().is_running(),
+                timeline: Timeline::default(),
+                on_battery: false,
+            },
+            device: DeviceInfo {
+                input_device,
+                formats: engine.borrow().formats(),
+                buffer_status: None,
+                start_error,
+                stats: StatsData::default(),
+            },
+        })
+        .expect("launch failed");
+
+    // Torn down in dependency order: no more callbacks, then no more consumers, then collect what
+    // they shared
+    for stream in video_stream
+        .into_iter()
+        .chain(ndi_stream)
+        .chain(syphon_stream)
+        .chain(spout_stream)
+    {
+        stream.stop();
+    }
+    for lane in lanes {
+        lane.stop();
+    }
+    engine.borrow_mut().stop();
+    let _ = shutdown_commands.send(AnalysisCommand::Shutdown);
+    stop_snapshots.store(true, Ordering::Relaxed);
+    for thread in [analysis_thread, snapshot_thread] {
+        if thread.join().is_err() {
+            log::error!("Analysis thread panicked");
+        }
+    }
+    garbage_collector.blocking_collect();
+    if let Err(err) = garbage_collector.stop() {
+        log::error!("Failed to stop the garbage collector: {}", err);
+    }
+}
+
+/// Root of the live window's state, split so panels bind to the slice they show
+#[derive(Clone, Data, Lens)]
+struct AppState {
+    audio: AudioSnapshot,
+    settings: ViewSettings,
+    transport: Transport,
+    device: DeviceInfo,
+}
+
+impl TimelineData for AppState {
+    fn timeline(&self) -> &Timeline {
+        &self.transport.timeline
+    }
+
+    fn timeline_mut(&mut self) -> &mut Timeline {
+        &mut self.transport.timeline
+    }
+
+    /// The waveform's right edge, where the other panes line up with it
+    fn playhead(&self) -> Option<f64> {
+        Some(self.audio.wave.time_span().end).filter(|_| self.audio.wave.position > 0.0)
+    }
+}
+
+/// What the analysis thread last sent, replaced wholesale by its draw commands
+#[derive(Clone, Data, Lens)]
+struct AudioSnapshot {
+    wave: WaveData,
+    /// One per `--lane` device, following `wave`'s zoom
+    lanes: Arc<Vec<WaveData>>,
+    loudness: LoudnessData,
+    clip_log: Arc<Vec<ClipEvent>>,
+    lag_views: LagViews,
+    phase: PhaseData,
+    spectrum: SpectrumData,
+    spectrogram: SpectrogramData,
+    denoise: DenoiseData,
+    /// Detected in the latest spectrum
+    hum: Option<Hum>,
+    /// Fitted to the latest spectrum
+    tilt: Option<Tilt>,
+    /// The latest spectrum's, while a target curve is loaded
+    target_deviation: Option<TargetDeviation>,
+    /// The strongest peaks of the latest spectrum, kept while `transport.peaks_frozen`
+    peak_table: Arc<Vec<Peak>>,
+    midi: MidiRollData,
+    script: ScriptOutput,
+    envelope: EnvelopeData,
+    /// Peak and RMS a second over the last hour
+    level_history: LevelHistoryData,
+    feedback: Arc<Vec<FeedbackCandidate>>,
+    spl: SplData,
+    /// Bar meter reading in dBFS
+    level_db: f32,
+    balance: BalanceData,
+    gain_status: GainStatus,
+    latency: LatencyStatus,
+    /// Display AGC's gain, applied while `settings.display_scaling` is AGC
+    display_gain_db: f32,
+    /// The talkback limiter's, drawn under the waveform while there's any
+    gain_reduction: GainReductionData,
+}
+
+/// Which views are shown, display units and analysis settings, and the saved presets and
+/// workspaces
+#[derive(Clone, Data, Lens)]
+struct ViewSettings {
+    report_target: LoudnessTarget,
+    show_phase: bool,
+    show_spectrum: bool,
+    show_spectrogram: bool,
+    /// Levels the spectrogram's colors span
+    spectrogram_range: DisplayRange,
+    show_denoise: bool,
+    show_midi: bool,
+    midi_mappings: Arc<Vec<MidiMapping>>,
+    presets: Arc<Vec<Preset>>,
+    /// Name to save or export the current settings as, that of the last preset applied
+    preset_name: String,
+    /// Share of the width for the meters, beside the side panel
+    meters_ratio: f64,
+    /// Share of the meters' height for the waveform
+    wave_ratio: f64,
+    workspaces: Arc<Vec<Workspace>>,
+    /// Name to save the layout as, that of the last workspace restored
+    workspace_name: String,
+    envelope_settings: EnvelopeSettings,
+    show_envelope: bool,
+    show_level_history: bool,
+    feedback_detection: bool,
+    weighting: Weighting,
+    spl_ballistics: Ballistics,
+    /// Seconds the balance is measured over
+    balance_window: f64,
+    meter_ballistics: Ballistics,
+    /// dBFS or a K-System scale for the bar meter
+    meter_scale: MeterScale,
+    /// Level of the calibrator tone in dB SPL
+    calibration_reference: f64,
+    /// Peaks should land this far below full scale
+    gain_headroom_db: f64,
+    amplitude_unit: AmplitudeUnit,
+    /// Levels under this read as silence on meters and axes
+    db_floor: f64,
+    db_waveform: bool,
+    wave_gain_db: f64,
+    /// Whether `wave_gain_db` scales the waveform or it follows the signal
+    display_scaling: DisplayScaling,
+    /// Index into `WAVE_COLORS`
+    wave_color: usize,
+    /// Line width of the waveform, one of `STROKE_WIDTHS`
+    wave_stroke_width: f64,
+    /// Off draws the waveform snapped to whole pixels
+    wave_antialias: bool,
+    /// Draw the left and right channels over each other, blended additively
+    channel_overlay: bool,
+    /// How the waveform joins samples when zoomed in past a sample per pixel
+    interpolation: Interpolation,
+    /// Meter and analysis view updates a second, lowered automatically while hidden or slow
+    update_rate_hz: f64,
+    /// Lower rates for the heavier analysis panes
+    pane_rates: PaneRates,
+    spectrum_window: SpectrumWindow,
+    /// Draw a pink noise reference over the spectrum
+    pink_reference: bool,
+    /// Fraction of an octave the spectrum trace is smoothed over, 0 for none
+    spectrum_smoothing: u64,
+    /// The same for the monitor output's trace
+    processed_smoothing: u64,
+    /// Drawn over the spectrum at its level, with the deviation from it
+    target_curve: Option<TargetCurve>,
+    transients: TransientSettings,
+    /// Decimated columns per pixel of the zoomed out waveform
+    wave_resolution: f64,
+    /// Earlier spectra drawn fading behind the newest
+    spectrum_persistence: u64,
+    /// Switch to the battery saver while on battery, and back once plugged in
+    auto_battery_saver: bool,
+    /// Drawn over the waveform and each lane, the waveform's also naming the report's source
+    lane_names: Arc<Vec<String>>,
+}
+
+/// Recording, freezing and the monitor output
+#[derive(Clone, Data, Lens)]
+struct Transport {
+    /// Band played on the monitor output, dragged out on the spectrum or spectrogram
+    solo_band: Option<SoloBand>,
+    /// The last mains frequency hum was detected at, kept so the notches don't flicker with it
+    mains_hz: Option<f32>,
+    /// Play the input on the monitor output, not only while a band is soloed
+    monitor_input: bool,
+    /// Held talkback button, playing the input at a safe level
+    talkback: bool,
+    /// Notch out `mains_hz` and its harmonics on the monitor output
+    remove_hum: bool,
+    peaks_frozen: bool,
+    /// Panes holding their last frame, the waveform's flag is `audio.wave.frozen`
+    frozen_panes: FrozenPanes,
+    /// Waiting for a controller to map to this
+    midi_learn: Option<MidiTarget>,
+    /// Metrics are being written to a CSV file
+    recording: bool,
+    /// The waveform is being encoded to a video file
+    recording_video: bool,
+    /// The input device is open, in standby otherwise
+    engine_running: bool,
+    /// Markers, the loop and the cursor, shared by the waveform, spectrogram and level history
+    timeline: Timeline,
+    on_battery: bool,
+}
+
+/// The input device and how it is running
+#[derive(Clone, Data, Lens)]
+struct DeviceInfo {
+    /// Saved to the config and opened from the next start, the default device when `None`
+    input_device: Option<String>,
+    formats: DeviceFormats,
+    /// While a buffered source such as an HTTP stream runs
+    buffer_status: Option<BufferStatus>,
+    /// Why the input didn't open on the last start
+    start_error: Option<String>,
+    stats: StatsData,
+}
+
+/// Rows in the spectrum's peak table
+const PEAK_TABLE_LEN: usize = 10;
+
+/// Save dialog results, one per kind of export
+const EXPORT_REPORT: Selector<FileInfo> = Selector::new("event-example.export_report");
+const EXPORT_CLIP_LOG: Selector<FileInfo> = Selector::new("event-example.export_clip_log");
+const EXPORT_PEAKS: Selector<FileInfo> = Selector::new("event-example.export_peaks");
+const EXPORT_WAVE_IMAGE: Selector<FileInfo> = Selector::new("event-example.export_wave_image");
+/// Where to record the metrics to
+const RECORD_METRICS: Selector<FileInfo> = Selector::new("event-example.record_metrics");
+/// Where to encode the waveform video to
+const RECORD_VIDEO: Selector<FileInfo> = Selector::new("event-example.record_video");
+/// Open dialog result for a script to run
+const LOAD_SCRIPT: Selector<FileInfo> = Selector::new("event-example.load_script");
+const LOAD_TARGET_CURVE: Selector<FileInfo> = Selector::new("event-example.load_target_curve");
+/// Open dialog result for a preset to add
+const IMPORT_PRESET: Selector<FileInfo> = Selector::new("event-example.import_preset");
+/// Where to export the current settings to, as a preset
+const EXPORT_PRESET: Selector<FileInfo> = Selector::new("event-example.export_preset");
+/// Open the input device, or close it into standby
+const START_ENGINE: Selector = Selector::new("event-example.start_engine");
+const STOP_ENGINE: Selector = Selector::new("event-example.stop_engine");
+/// Stop recording, or ask where to record to when not recording
+const TOGGLE_RECORDING: Selector = Selector::new("event-example.toggle_recording");
+/// Likewise for the waveform video
+const TOGGLE_VIDEO: Selector = Selector::new("event-example.toggle_video");
+/// A render quality picked by hand, which being plugged back in then keeps
+const SET_RENDER_QUALITY: Selector<RenderQuality> =
+    Selector::new("event-example.set_render_quality");
+/// Switch to or back from the battery saver after auto battery saver is toggled
+const UPDATE_BATTERY_SAVER: Selector = Selector::new("event-example.update_battery_saver");
+
+fn export_report_dialog() -> Command {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![FileSpec::new("JSON", &["json"]), FileSpec::TEXT])
+        .default_name("loudness-report.json")
+        .accept_command(EXPORT_REPORT);
+    commands::SHOW_SAVE_PANEL.with(options)
+}
+
+fn export_clip_log_dialog() -> Command {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![FileSpec::new("CSV", &["csv"])])
+        .default_name("clip-log.csv")
+        .accept_command(EXPORT_CLIP_LOG);
+    commands::SHOW_SAVE_PANEL.with(options)
+}
+
+fn export_wave_image_dialog() -> Command {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![FileSpec::new("PNG", &["png"])])
+        .default_name("waveform.png")
+        .accept_command(EXPORT_WAVE_IMAGE);
+    commands::SHOW_SAVE_PANEL.with(options)
+}
+
+fn load_script_dialog() -> Command {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![FileSpec::new("Rhai", &["rhai"])])
+        .accept_command(LOAD_SCRIPT);
+    commands::SHOW_OPEN_PANEL.with(options)
+}
+
+fn load_target_curve_dialog() -> Command {
+    let options = FileDialogOptions::new()
+        .allowed_types(vec![FileSpec::new("Target curve", &["csv", "txt"])])
+        .accept_command(LOAD_TARGET_CURVE);
+    commands::SHOW_OPEN_PANEL.with(options)
+}
+
+/// Names of the default host's input devices, for the Audio menu
+fn input_device_names() -> Vec<String> {
+    match cpal::default_host().input_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(err) => {
+            log::error!("Failed to list input devices: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+fn capture_preset(data: &AppState) -> Preset {
+    Preset {
+        name: data.settings.preset_name.trim().to_string(),
+        input_device: data.device.input_device.clone(),
+        show_phase: data.settings.show_phase,
+        show_spectrum: data.settings.show_spectrum,
+        show_spectrogram: data.settings.show_spectrogram,
+        show_denoise: data.settings.show_denoise,
+        show_envelope: data.settings.show_envelope,
+        show_level_history: data.settings.show_level_history,
+        show_midi: data.settings.show_midi,
+        amplitude_unit: data.settings.amplitude_unit,
+        db_floor: data.settings.db_floor,
+        db_waveform: data.settings.db_waveform,
+        interpolation: data.settings.interpolation,
+        update_rate_hz: data.settings.update_rate_hz,
+        pane_rates: data.settings.pane_rates,
+        weighting: data.settings.weighting,
+        spl_ballistics: data.settings.spl_ballistics,
+        meter_ballistics: data.settings.meter_ballistics,
+        meter_scale: data.settings.meter_scale,
+        balance_window: data.settings.balance_window,
+        spectrogram_range: data.settings.spectrogram_range,
+        envelope: data.settings.envelope_settings,
+        spectrum_window: data.settings.spectrum_window,
+        transients: data.settings.transients,
+        pink_reference: data.settings.pink_reference,
+        spectrum_smoothing: data.settings.spectrum_smoothing,
+        processed_smoothing: data.settings.processed_smoothing,
+        wave_color: data.settings.wave_color,
+        wave_stroke_width: data.settings.wave_stroke_width,
+        wave_antialias: data.settings.wave_antialias,
+        lane_names: data.settings.lane_names.to_vec(),
+    }
+}
+
+fn apply_preset(preset: &Preset, data: &mut AppState) {
+    data.settings.preset_name = preset.name.clone();
+    data.device.input_device = preset.input_device.clone();
+    data.settings.show_phase = preset.show_phase;
+    data.settings.show_spectrum = preset.show_spectrum;
+    data.settings.show_spectrogram = preset.show_spectrogram;
+    data.settings.show_denoise = preset.show_denoise;
+    data.settings.show_envelope = preset.show_envelope;
+    data.settings.show_level_history = preset.show_level_history;
+    data.settings.show_midi = preset.show_midi;
+    data.settings.amplitude_unit = preset.amplitude_unit;
+    data.settings.db_floor = preset.db_floor;
+    data.settings.db_waveform = preset.db_waveform;
+    data.settings.interpolation = preset.interpolation;
+    data.settings.update_rate_hz = preset.update_rate_hz;
+    data.settings.pane_rates = preset.pane_rates;
+    data.settings.weighting = preset.weighting;
+    data.settings.spl_ballistics = preset.spl_ballistics;
+    data.settings.meter_ballistics = preset.meter_ballistics;
+    data.settings.meter_scale = preset.meter_scale;
+    data.settings.balance_window = preset.balance_window;
+    data.settings.spectrogram_range = preset.spectrogram_range;
+    data.settings.envelope_settings = preset.envelope;
+    data.settings.spectrum_window = preset.spectrum_window;
+    data.settings.transients = preset.transients;
+    data.settings.pink_reference = preset.pink_reference;
+    data.settings.spectrum_smoothing = preset.spectrum_smoothing;
+    data.settings.processed_smoothing = preset.processed_smoothing;
+    // Imported presets may come from a build with more colors
+    data.settings.wave_color = preset.wave_color.min(WAVE_COLORS.len() - 1);
+    data.settings.wave_stroke_width = preset.wave_stroke_width;
+    data.settings.wave_antialias = preset.wave_antialias;
+    // Lanes the preset has no name for, or that aren't open, keep theirs
+    let lane_names = Arc::make_mut(&mut data.settings.lane_names);
+    for (name, stored) in lane_names.iter_mut().zip(&preset.lane_names) {
+        name.clone_from(stored);
+    }
+}
+
+fn render_profile(settings: &ViewSettings) -> RenderProfile {
+    RenderProfile {
+        update_rate_hz: settings.update_rate_hz,
+        pane_rates: settings.pane_rates,
+        wave_antialias: settings.wave_antialias,
+        wave_resolution: settings.wave_resolution,
+        spectrum_persistence: settings.spectrum_persistence,
+    }
+}
+
+fn apply_render_profile(profile: RenderProfile, settings: &mut ViewSettings) {
+    settings.update_rate_hz = profile.update_rate_hz;
+    settings.pane_rates = profile.pane_rates;
+    settings.wave_antialias = profile.wave_antialias;
+    settings.wave_resolution = profile.wave_resolution;
+    settings.spectrum_persistence = profile.spectrum_persistence;
+}
+
+/// What undo and redo step through: the preset's settings, without its name, and the gain
+#[derive(Clone, PartialEq)]
+struct Settings {
+    preset: Preset,
+    wave_gain_db: f64,
+}
+
+fn capture_settings(data: &AppState) -> Settings {
+    Settings {
+        preset: Preset {
+            name: String::new(),
+            ..capture_preset(data)
+        },
+        wave_gain_db: data.settings.wave_gain_db,
+    }
+}
+
+fn apply_settings(settings: &Settings, data: &mut AppState) {
+    let name = std::mem::take(&mut data.settings.preset_name);
+    apply_preset(&settings.preset, data);
+    data.settings.preset_name = name;
+    data.settings.wave_gain_db = settings.wave_gain_db;
+}
+
+/// Add a preset, replacing any with the same name, and save the list
+fn store_preset(preset: Preset, data: &mut AppState) {
+    let mut presets: Vec<Preset> = data
+        .settings
+        .presets
+        .iter()
+        .filter(|stored| stored.name != preset.name)
+        .cloned()
+        .collect();
+    presets.push(preset);
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    presets::save(&presets);
+    data.settings.presets = Arc::new(presets);
+}
+
+fn capture_workspace(data: &AppState) -> Workspace {
+    Workspace {
+        name: data.settings.workspace_name.trim().to_string(),
+        show_phase: data.settings.show_phase,
+        show_spectrum: data.settings.show_spectrum,
+        show_spectrogram: data.settings.show_spectrogram,
+        show_denoise: data.settings.show_denoise,
+        show_envelope: data.settings.show_envelope,
+        show_level_history: data.settings.show_level_history,
+        show_midi: data.settings.show_midi,
+        meters_ratio: data.settings.meters_ratio,
+        wave_ratio: data.settings.wave_ratio,
+        visible_samples: data.audio.wave.visible_samples,
+        wave_gain_db: data.settings.wave_gain_db,
+        db_waveform: data.settings.db_waveform,
+    }
+}
+
+fn restore_workspace(workspace: &Workspace, data: &mut AppState) {
+    data.settings.workspace_name = workspace.name.clone();
+    data.settings.show_phase = workspace.show_phase;
+    data.settings.show_spectrum = workspace.show_spectrum;
+    data.settings.show_spectrogram = workspace.show_spectrogram;
+    data.settings.show_denoise = workspace.show_denoise;
+    data.settings.show_envelope = workspace.show_envelope;
+    data.settings.show_level_history = workspace.show_level_history;
+    data.settings.show_midi = workspace.show_midi;
+    data.settings.meters_ratio = workspace.meters_ratio;
+    data.settings.wave_ratio = workspace.wave_ratio;
+    data.audio.wave.visible_samples = workspace
+        .visible_samples
+        .clamp(MIN_VISIBLE_SAMPLES, HISTORY_LEN as f64);
+    data.settings.wave_gain_db = workspace.wave_gain_db;
+    data.settings.db_waveform = workspace.db_waveform;
+}
+
+/// Most gain a controller or the slider can put on the waveform
+const MAX_WAVE_GAIN_DB: f64 = 30.0;
+
+/// Set a mapped parameter from a controller's 0-127 value
+fn apply_midi_control(target: MidiTarget, value: u8, data: &mut AppState) {
+    let amount = value as f64 / 127.0;
+    match target {
+        MidiTarget::Gain => data.settings.wave_gain_db = amount * MAX_WAVE_GAIN_DB,
+        // Logarithmic, like the mouse wheel
+        MidiTarget::Zoom => {
+            data.audio.wave.visible_samples =
+                MIN_VISIBLE_SAMPLES * (HISTORY_LEN as f64 / MIN_VISIBLE_SAMPLES).powf(amount)
+        }
+        MidiTarget::Freeze => data.audio.wave.frozen = value >= 64,
+        MidiTarget::View => {
+            // The waveform alone, then each optional view in turn
+            let view = value as usize * 5 / 128;
+            data.settings.show_phase = view == 1;
+            data.settings.show_envelope = view == 2;
+            data.settings.show_midi = view == 3;
+            data.settings.show_spectrum = view == 4;
+        }
+    }
+}
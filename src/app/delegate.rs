@@ -0,0 +1,533 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! How the live window reacts to commands: the app delegate, which owns recordings, exports and
+//! the settings history, and the controller on the root widget that forwards settings to the
+//! analysis thread.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use druid::{
+    commands, AppDelegate, Command, Data, DelegateCtx, FileDialogOptions, FileSpec, Handled,
+    Target, WindowHandle, WindowId,
+};
+
+use crate::analysis::AnalysisCommand;
+use crate::analysis_frame::ANALYSIS_FRAME;
+use crate::audio_wave::{AudioData, WaveSnapshot, DRAW_AUDIO, ZOOM_AMPLITUDE};
+use crate::backend::AudioEngine;
+use crate::clip_log;
+use crate::clip_snapshots;
+use crate::config::Config;
+use crate::display_agc::{DisplayScaling, DRAW_DISPLAY_GAIN};
+use crate::gain_reduction::DRAW_GAIN_REDUCTION;
+use crate::hum;
+use crate::latency::DRAW_LATENCY;
+use crate::midi::{MidiMapping, MIDI_CONTROL};
+use crate::monitor::{MonitorControl, SOLO_BAND};
+use crate::presets;
+use crate::render_quality::{RenderProfile, RenderQuality, ON_BATTERY};
+use crate::script::DRAW_SCRIPT;
+use crate::spectrum_view::{self, DRAW_SPECTRUM};
+use crate::target_curve::TargetCurve;
+use crate::tilt;
+use crate::timeline::DRAW_TRIGGER;
+use crate::transients::DRAW_TRANSIENTS;
+use crate::tray::{Tray, TrayAction, TRAY_ACTION};
+use crate::undo::SettingsHistory;
+use crate::video::VideoRecorder;
+
+use super::{
+    apply_midi_control, apply_preset, apply_render_profile, apply_settings, capture_preset,
+    capture_settings, render_profile, store_preset, AppState, Settings, EXPORT_CLIP_LOG,
+    EXPORT_PEAKS, EXPORT_PRESET, EXPORT_REPORT, EXPORT_WAVE_IMAGE, IMPORT_PRESET, LOAD_SCRIPT,
+    LOAD_TARGET_CURVE, MAX_WAVE_GAIN_DB, PEAK_TABLE_LEN, RECORD_METRICS, RECORD_VIDEO,
+    SET_RENDER_QUALITY, START_ENGINE, STOP_ENGINE, TOGGLE_RECORDING, TOGGLE_VIDEO,
+    UPDATE_BATTERY_SAVER,
+};
+
+/// Handles the commands that change the app's state, recordings and exports in one place, so
+/// widgets only deal with their own draw commands and settings history sees every change
+pub(super) struct LiveDelegate {
+    history: SettingsHistory<AppState, Settings>,
+    commands: Sender<AnalysisCommand>,
+    /// Read by the video recorder
+    snapshot: WaveSnapshot,
+    video: Option<VideoRecorder>,
+    /// Also stopped after the window closes, once the app is done with it
+    engine: Rc<RefCell<AudioEngine>>,
+    /// Where save dialogs open, the live window once it's up
+    window: Option<WindowId>,
+    /// What the battery saver replaced, put back once plugged in. Cleared when a quality is
+    /// picked by hand, so the pick stays.
+    before_battery: Option<RenderProfile>,
+}
+
+impl LiveDelegate {
+    pub(super) fn new(
+        commands: Sender<AnalysisCommand>,
+        snapshot: WaveSnapshot,
+        engine: Rc<RefCell<AudioEngine>>,
+    ) -> Self {
+        LiveDelegate {
+            history: SettingsHistory::new(capture_settings, apply_settings),
+            commands,
+            snapshot,
+            video: None,
+            engine,
+            window: None,
+            before_battery: None,
+        }
+    }
+
+    fn show_save_panel(&self, ctx: &mut DelegateCtx, options: FileDialogOptions) {
+        let target = self.window.map_or(Target::Auto, Target::Window);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options).to(target));
+    }
+
+    /// Switch to the battery saver while on battery with auto battery saver on, and back to what
+    /// it replaced otherwise
+    fn update_battery_saver(&mut self, data: &mut AppState) {
+        if data.transport.on_battery && data.settings.auto_battery_saver {
+            if self.before_battery.is_none() {
+                self.before_battery = Some(render_profile(&data.settings));
+                apply_render_profile(RenderQuality::BatterySaver.profile(), &mut data.settings);
+            }
+        } else if let Some(profile) = self.before_battery.take() {
+            apply_render_profile(profile, &mut data.settings);
+        }
+    }
+}
+
+impl AppDelegate<AppState> for LiveDelegate {
+    fn event(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        window_id: WindowId,
+        event: Event,
+        data: &mut AppState,
+        env: &Env,
+    ) -> Option<Event> {
+        self.history.event(ctx, window_id, event, data, env)
+    }
+
+    fn window_added(
+        &mut self,
+        id: WindowId,
+        _handle: WindowHandle,
+        _data: &mut AppState,
+        _env: &Env,
+        _ctx: &mut DelegateCtx,
+    ) {
+        self.window.get_or_insert(id);
+    }
+
+    fn window_removed(
+        &mut self,
+        id: WindowId,
+        _data: &mut AppState,
+        _env: &Env,
+        ctx: &mut DelegateCtx,
+    ) {
+        if self.window != Some(id) {
+            return;
+        }
+        if let Some(video) = self.video.take() {
+            video.stop();
+        }
+        // macOS keeps running without windows, the rest of the teardown is after `launch`
+        ctx.submit_command(commands::QUIT_APP);
+    }
+
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        env: &Env,
+    ) -> Handled {
+        if self
+            .history
+            .command(ctx, target, cmd, data, env)
+            .is_handled()
+        {
+            return Handled::Yes;
+        }
+        if let Some(frame) = cmd.get(ANALYSIS_FRAME) {
+            // Polled along with the analysis, it only changes as fast
+            data.device.buffer_status = self.engine.borrow().buffer_status();
+            match frame.frame() {
+                Some(frame) => {
+                    for command in frame.commands() {
+                        ctx.submit_command(command);
+                    }
+                }
+                None => log::error!("Dropped an analysis frame of version {}", frame.version),
+            }
+        } else if let Some(samples) = cmd.get(DRAW_AUDIO) {
+            data.audio.wave.audio = AudioData::new(samples.clone());
+        } else if let Some(file_info) = cmd.get(EXPORT_REPORT) {
+            let _ = self.commands.send(AnalysisCommand::ExportReport {
+                path: file_info.path().to_path_buf(),
+                target: data.settings.report_target,
+                source: data.settings.lane_names[0].clone(),
+            });
+        } else if let Some(band) = cmd.get(SOLO_BAND) {
+            data.transport.solo_band = *band;
+        } else if let Some(output) = cmd.get(DRAW_SCRIPT) {
+            data.audio.script = output.clone();
+        } else if let Some(delta_db) = cmd.get(ZOOM_AMPLITUDE) {
+            // Zooming by hand takes over from auto-scaling
+            data.settings.display_scaling = DisplayScaling::Manual;
+            data.settings.wave_gain_db =
+                (data.settings.wave_gain_db + delta_db).clamp(0.0, MAX_WAVE_GAIN_DB);
+        } else if let Some(position) = cmd.get(DRAW_TRIGGER) {
+            data.transport.timeline.trigger = Some(*position);
+        } else if let Some(on_battery) = cmd.get(ON_BATTERY) {
+            data.transport.on_battery = *on_battery;
+            self.update_battery_saver(data);
+        } else if cmd.is(UPDATE_BATTERY_SAVER) {
+            self.update_battery_saver(data);
+        } else if let Some(quality) = cmd.get(SET_RENDER_QUALITY) {
+            apply_render_profile(quality.profile(), &mut data.settings);
+            self.before_battery = None;
+        } else if let Some(positions) = cmd.get(DRAW_TRANSIENTS) {
+            data.transport.timeline.add_transients(positions);
+        } else if let Some(gain_db) = cmd.get(DRAW_DISPLAY_GAIN) {
+            data.audio.display_gain_db = *gain_db;
+        } else if cmd.is(START_ENGINE) {
+            let mut engine = self.engine.borrow_mut();
+            // The device chosen in the menu since the last start
+            let result = engine.start(data.device.input_device.clone());
+            if let Err(err) = &result {
+                log::error!("Failed to start the input: {}", err);
+            }
+            data.device.start_error = result.err().map(|err| err.to_string());
+            data.transport.engine_running = engine.is_running();
+            data.device.formats = engine.formats();
+        } else if cmd.is(STOP_ENGINE) {
+            self.engine.borrow_mut().stop();
+            data.transport.engine_running = false;
+        } else if cmd.is(TOGGLE_RECORDING) {
+            if data.transport.recording {
+                let _ = self.commands.send(AnalysisCommand::StopRecording);
+                data.transport.recording = false;
+                data.audio.wave.record_start = None;
+            } else {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![FileSpec::new("CSV", &["csv"])])
+                    .default_name("metrics.csv")
+                    .accept_command(RECORD_METRICS);
+                self.show_save_panel(ctx, options);
+            }
+        } else if cmd.is(TOGGLE_VIDEO) {
+            if let Some(video) = self.video.take() {
+                video.stop();
+                data.transport.recording_video = false;
+            } else {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![
+                        FileSpec::new("MP4", &["mp4"]),
+                        FileSpec::new("GIF", &["gif"]),
+                    ])
+                    .default_name("waveform.mp4")
+                    .accept_command(RECORD_VIDEO);
+                self.show_save_panel(ctx, options);
+            }
+        } else if let Some(file_info) = cmd.get(RECORD_VIDEO) {
+            let path = file_info.path();
+            let visible_samples = data.audio.wave.visible_samples as usize;
+            match VideoRecorder::start(path, self.snapshot.clone(), visible_samples) {
+                Ok(video) => {
+                    self.video = Some(video);
+                    data.transport.recording_video = true;
+                }
+                Err(err) => log::error!("Failed to start ffmpeg for {:?}: {}", path, err),
+            }
+        } else if let Some(file_info) = cmd.get(IMPORT_PRESET) {
+            let path = file_info.path();
+            match presets::import(path) {
+                Ok(preset) => {
+                    apply_preset(&preset, data);
+                    store_preset(preset, data);
+                }
+                Err(err) => log::error!("Failed to import preset {:?}: {}", path, err),
+            }
+        } else if let Some(file_info) = cmd.get(EXPORT_PRESET) {
+            let path = file_info.path();
+            if let Err(err) = presets::export(path, &capture_preset(data)) {
+                log::error!("Failed to export preset to {:?}: {}", path, err);
+            }
+        } else if let Some(action) = cmd.get(TRAY_ACTION) {
+            match action {
+                TrayAction::Pause => data.audio.wave.frozen = !data.audio.wave.frozen,
+                TrayAction::Record => ctx.submit_command(TOGGLE_RECORDING),
+                TrayAction::Quit => ctx.submit_command(commands::QUIT_APP),
+            }
+        } else if let Some(file_info) = cmd.get(RECORD_METRICS) {
+            let path = file_info.path().to_path_buf();
+            let _ = self.commands.send(AnalysisCommand::StartRecording(path));
+            data.transport.recording = true;
+            data.audio.wave.record_start = Some(self.snapshot.position() as f64);
+        } else if let Some(file_info) = cmd.get(LOAD_TARGET_CURVE) {
+            match TargetCurve::load(file_info.path()) {
+                Ok(target) => {
+                    data.audio.target_deviation = target.deviation(&data.audio.spectrum);
+                    data.settings.target_curve = Some(target);
+                }
+                Err(err) => log::error!("Failed to load target curve: {}", err),
+            }
+        } else if let Some(file_info) = cmd.get(LOAD_SCRIPT) {
+            let path = file_info.path().to_path_buf();
+            let _ = self.commands.send(AnalysisCommand::LoadScript(path));
+        } else if let Some(file_info) = cmd.get(EXPORT_CLIP_LOG) {
+            let path = file_info.path();
+            if let Err(err) = std::fs::write(path, clip_log::to_csv(&data.audio.clip_log)) {
+                log::error!("Failed to write clip log to {:?}: {}", path, err);
+            }
+        } else if let Some(control) = cmd.get(MIDI_CONTROL) {
+            if let Some(target) = data.transport.midi_learn.take() {
+                // A controller drives one parameter, and a parameter follows one controller
+                let mut mappings: Vec<MidiMapping> = data
+                    .settings
+                    .midi_mappings
+                    .iter()
+                    .filter(|mapping| {
+                        mapping.controller != control.controller && mapping.target != target
+                    })
+                    .copied()
+                    .collect();
+                mappings.push(MidiMapping {
+                    controller: control.controller,
+                    target,
+                });
+                data.settings.midi_mappings = Arc::new(mappings);
+            }
+            for mapping in data.settings.midi_mappings.clone().iter() {
+                if mapping.controller == control.controller {
+                    apply_midi_control(mapping.target, control.value, data);
+                }
+            }
+        } else if let Some(spectrum) = cmd.get(DRAW_SPECTRUM) {
+            if !data.transport.peaks_frozen {
+                data.audio.peak_table = Arc::new(spectrum.peaks(PEAK_TABLE_LEN));
+            }
+            data.audio.hum = hum::detect(spectrum);
+            data.audio.tilt = tilt::fit(spectrum);
+            data.audio.target_deviation = data
+                .settings
+                .target_curve
+                .as_ref()
+                .and_then(|target| target.deviation(spectrum));
+            if let Some(hum) = &data.audio.hum {
+                data.transport.mains_hz = Some(hum.mains_hz);
+            }
+            // The spectrum pane draws it too
+            return Handled::No;
+        } else if let Some(gain_reduction) = cmd.get(DRAW_GAIN_REDUCTION) {
+            data.audio.gain_reduction = gain_reduction.clone();
+        } else if let Some(status) = cmd.get(DRAW_LATENCY) {
+            data.audio.latency = *status;
+            if let Some(seconds) = status.input_latency() {
+                data.audio.wave.input_latency = seconds;
+            }
+        } else if let Some(file_info) = cmd.get(EXPORT_PEAKS) {
+            let path = file_info.path();
+            if let Err(err) =
+                std::fs::write(path, spectrum_view::peaks_to_csv(&data.audio.peak_table))
+            {
+                log::error!("Failed to write peak list to {:?}: {}", path, err);
+            }
+        } else if let Some(file_info) = cmd.get(EXPORT_WAVE_IMAGE) {
+            let path = file_info.path();
+            if let Err(err) = clip_snapshots::write_waveform_png(path, data.audio.wave.visible()) {
+                log::error!("Failed to write waveform image to {:?}: {}", path, err);
+            }
+        } else {
+            return Handled::No;
+        }
+        Handled::Yes
+    }
+}
+
+pub(super) struct LiveController {
+    commands: Sender<AnalysisCommand>,
+    config: Config,
+    /// Created once the window is up, with the `tray` feature
+    tray: Option<Tray>,
+    monitor: Arc<MonitorControl>,
+}
+
+impl LiveController {
+    pub(super) fn new(
+        commands: Sender<AnalysisCommand>,
+        config: Config,
+        monitor: Arc<MonitorControl>,
+    ) -> Self {
+        LiveController {
+            commands,
+            config,
+            tray: None,
+            monitor,
+        }
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for LiveController {
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.tray = Tray::new(ctx.get_external_handle(), env);
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &AppState,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let Some(tray) = &mut self.tray {
+            // Momentary loudness between the display floor and 0
+            let level = data
+                .audio
+                .loudness
+                .momentary
+                .last()
+                .map_or(0.0, |lufs| 1.0 - *lufs as f64 / data.settings.db_floor);
+            tray.update(
+                level as f32,
+                data.audio.wave.frozen,
+                data.transport.recording,
+                env,
+            );
+        }
+        if old_data.settings.feedback_detection != data.settings.feedback_detection {
+            let _ = self.commands.send(AnalysisCommand::SetFeedbackDetection(
+                data.settings.feedback_detection,
+            ));
+        }
+        if old_data.settings.weighting != data.settings.weighting {
+            let _ = self
+                .commands
+                .send(AnalysisCommand::SetWeighting(data.settings.weighting));
+        }
+        if old_data.settings.spl_ballistics != data.settings.spl_ballistics {
+            let _ = self.commands.send(AnalysisCommand::SetSplBallistics(
+                data.settings.spl_ballistics,
+            ));
+        }
+        if old_data.settings.meter_ballistics != data.settings.meter_ballistics {
+            let _ = self.commands.send(AnalysisCommand::SetMeterBallistics(
+                data.settings.meter_ballistics,
+            ));
+        }
+        if old_data.settings.balance_window != data.settings.balance_window {
+            let _ = self.commands.send(AnalysisCommand::SetBalanceWindow(
+                data.settings.balance_window,
+            ));
+        }
+        if old_data.settings.auto_battery_saver != data.settings.auto_battery_saver {
+            ctx.submit_command(UPDATE_BATTERY_SAVER);
+        }
+        if old_data.settings.envelope_settings != data.settings.envelope_settings {
+            let _ = self.commands.send(AnalysisCommand::SetEnvelope(
+                data.settings.envelope_settings,
+            ));
+        }
+        if !old_data
+            .settings
+            .midi_mappings
+            .same(&data.settings.midi_mappings)
+        {
+            self.config.midi_mappings = data.settings.midi_mappings.to_vec();
+            self.config.save();
+        }
+        if !old_data.settings.workspaces.same(&data.settings.workspaces) {
+            self.config.workspaces = data.settings.workspaces.to_vec();
+            self.config.save();
+        }
+        if old_data.device.input_device != data.device.input_device {
+            self.config.input_device = data.device.input_device.clone();
+            self.config.save();
+        }
+        if old_data.settings.show_denoise != data.settings.show_denoise {
+            let _ = self.commands.send(AnalysisCommand::SetDenoisePreview(
+                data.settings.show_denoise,
+            ));
+        }
+        if old_data.transport.solo_band != data.transport.solo_band {
+            self.monitor.set_band(data.transport.solo_band);
+        }
+        if old_data.transport.monitor_input != data.transport.monitor_input {
+            self.monitor.set_listen(data.transport.monitor_input);
+        }
+        if old_data.transport.talkback != data.transport.talkback {
+            self.monitor.set_talkback(data.transport.talkback);
+        }
+        if old_data.transport.remove_hum != data.transport.remove_hum
+            || old_data.transport.mains_hz != data.transport.mains_hz
+        {
+            self.monitor.set_notch(
+                data.transport
+                    .mains_hz
+                    .filter(|_| data.transport.remove_hum),
+            );
+        }
+        if old_data.settings.update_rate_hz != data.settings.update_rate_hz {
+            let _ = self
+                .commands
+                .send(AnalysisCommand::SetUpdateRate(data.settings.update_rate_hz));
+        }
+        if old_data.settings.pane_rates != data.settings.pane_rates {
+            let _ = self
+                .commands
+                .send(AnalysisCommand::SetPaneRates(data.settings.pane_rates));
+        }
+        if old_data.settings.spectrum_window != data.settings.spectrum_window {
+            let _ = self.commands.send(AnalysisCommand::SetSpectrumWindow(
+                data.settings.spectrum_window,
+            ));
+        }
+        if old_data.settings.transients != data.settings.transients {
+            let _ = self
+                .commands
+                .send(AnalysisCommand::SetTransients(data.settings.transients));
+        }
+        child.update(ctx, old_data, data, env)
+    }
+}
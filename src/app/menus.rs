@@ -0,0 +1,328 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The window menu bar and the right-click menus of the panes.
+
+use druid::{Application, LocalizedString, Menu, MenuItem, SysMods};
+
+use crate::audio_wave::{self, MIN_VISIBLE_SAMPLES, STROKE_WIDTHS, WAVE_COLORS};
+use crate::buffer_analyser::HISTORY_LEN;
+use crate::presets::Preset;
+use crate::stats;
+use crate::undo::{REDO_SETTINGS, UNDO_SETTINGS};
+use crate::units::AmplitudeScale;
+
+use super::{
+    apply_preset, export_clip_log_dialog, export_report_dialog, export_wave_image_dialog,
+    load_script_dialog, AppState, TOGGLE_RECORDING, TOGGLE_VIDEO,
+};
+
+/// The presets as a drop-down menu, choosing one applies it
+pub(super) fn presets_menu(presets: &[Preset]) -> Menu<AppState> {
+    if presets.is_empty() {
+        return Menu::empty()
+            .entry(MenuItem::new(LocalizedString::new("no-presets")).enabled(false));
+    }
+    presets.iter().fold(Menu::empty(), |menu, preset| {
+        let preset = preset.clone();
+        let name = preset.name.clone();
+        menu.entry(
+            MenuItem::new(name.as_str())
+                .on_activate(move |_, data: &mut AppState, _| apply_preset(&preset, data))
+                .selected_if(move |data: &AppState, _| data.settings.preset_name == name),
+        )
+    })
+}
+
+pub(super) fn record_label() -> LocalizedString<AppState> {
+    LocalizedString::new("record-metrics").with_arg("recording", |data: &AppState, _| {
+        data.transport.recording.to_string().into()
+    })
+}
+
+fn video_label() -> LocalizedString<AppState> {
+    LocalizedString::new("record-video").with_arg("recording", |data: &AppState, _| {
+        data.transport.recording_video.to_string().into()
+    })
+}
+
+fn all_frozen(data: &AppState) -> bool {
+    data.audio.wave.frozen && data.transport.frozen_panes.all()
+}
+
+/// Halve or double the waveform's visible span
+fn zoom_wave(data: &mut AppState, factor: f64) {
+    data.audio.wave.visible_samples =
+        (data.audio.wave.visible_samples * factor).clamp(MIN_VISIBLE_SAMPLES, HISTORY_LEN as f64);
+}
+
+/// A checkable menu item flipping a flag
+fn toggle(
+    label: &'static str,
+    lens: fn(&mut AppState) -> &mut bool,
+    get: fn(&AppState) -> bool,
+) -> MenuItem<AppState> {
+    MenuItem::new(LocalizedString::new(label))
+        .on_activate(move |_, data: &mut AppState, _| {
+            let value = lens(data);
+            *value = !*value;
+        })
+        .selected_if(move |data, _| get(data))
+}
+
+pub(super) fn copy_csv_item(csv: fn(&AppState) -> String) -> MenuItem<AppState> {
+    MenuItem::new(LocalizedString::new("copy-csv")).on_activate(move |_, data: &mut AppState, _| {
+        Application::global().clipboard().put_string(csv(data));
+    })
+}
+
+fn copy_stats_item() -> MenuItem<AppState> {
+    MenuItem::new(LocalizedString::new("copy-stats")).on_activate(|_, data: &mut AppState, env| {
+        // The menu doesn't see the window's env, so the units come from the data
+        let scale = AmplitudeScale {
+            unit: data.settings.amplitude_unit,
+            floor_db: data.settings.db_floor,
+        };
+        Application::global().clipboard().put_string(stats::to_text(
+            &data.device.stats,
+            &scale,
+            env,
+        ));
+    })
+}
+
+/// Right-click menu of the waveform
+pub(super) fn wave_menu() -> Menu<AppState> {
+    let colors = WAVE_COLORS.iter().enumerate().fold(
+        Menu::new(LocalizedString::new("wave-color")),
+        |menu, (index, (label, _))| {
+            menu.entry(
+                MenuItem::new(LocalizedString::new(label))
+                    .on_activate(move |_, data: &mut AppState, _| data.settings.wave_color = index)
+                    .selected_if(move |data, _| data.settings.wave_color == index),
+            )
+        },
+    );
+    let widths = STROKE_WIDTHS.iter().fold(
+        Menu::new(LocalizedString::new("line-width")),
+        |menu, &width| {
+            menu.entry(
+                MenuItem::new(format!("{} px", width))
+                    .on_activate(move |_, data: &mut AppState, _| {
+                        data.settings.wave_stroke_width = width
+                    })
+                    .selected_if(move |data, _| data.settings.wave_stroke_width == width),
+            )
+        },
+    );
+    Menu::empty()
+        .entry(toggle(
+            "freeze",
+            |data| &mut data.audio.wave.frozen,
+            |data| data.audio.wave.frozen,
+        ))
+        .entry(
+            MenuItem::new(LocalizedString::new("export-image")).command(export_wave_image_dialog()),
+        )
+        .entry(copy_csv_item(|data| {
+            audio_wave::samples_to_csv(data.audio.wave.visible())
+        }))
+        .entry(copy_stats_item())
+        .entry(
+            MenuItem::new(LocalizedString::new("reset-zoom")).on_activate(
+                |_, data: &mut AppState, _| data.audio.wave.visible_samples = HISTORY_LEN as f64,
+            ),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("clear-markers"))
+                .on_activate(|_, data: &mut AppState, _| data.transport.timeline.clear()),
+        )
+        .entry(colors)
+        .entry(widths)
+        .entry(toggle(
+            "antialiasing",
+            |data| &mut data.settings.wave_antialias,
+            |data| data.settings.wave_antialias,
+        ))
+        .entry(toggle(
+            "channel-overlay",
+            |data| &mut data.settings.channel_overlay,
+            |data| data.settings.channel_overlay,
+        ))
+}
+
+/// Right-click menu of an analysis pane, freezing just that pane
+pub(super) fn pane_menu(
+    lens: fn(&mut AppState) -> &mut bool,
+    get: fn(&AppState) -> bool,
+) -> Menu<AppState> {
+    Menu::empty()
+        .entry(toggle("freeze", lens, get))
+        .entry(copy_stats_item())
+}
+
+/// Native menus for the main window, mirroring the in-window controls
+pub(super) fn make_menu(input_devices: &[String]) -> Menu<AppState> {
+    let file = Menu::new(LocalizedString::new("menu-file"))
+        .entry(
+            MenuItem::new(LocalizedString::new("load-script"))
+                .command(load_script_dialog())
+                .hotkey(SysMods::Cmd, "o"),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("export-report"))
+                .command(export_report_dialog())
+                .hotkey(SysMods::Cmd, "e"),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("export-clip-log"))
+                .command(export_clip_log_dialog()),
+        )
+        .separator()
+        .entry(
+            MenuItem::new(record_label())
+                .command(TOGGLE_RECORDING)
+                .hotkey(SysMods::Cmd, "r"),
+        )
+        .entry(MenuItem::new(video_label()).command(TOGGLE_VIDEO));
+
+    let edit = Menu::new(LocalizedString::new("menu-edit"))
+        .entry(
+            MenuItem::new(LocalizedString::new("undo-settings"))
+                .command(UNDO_SETTINGS)
+                .hotkey(SysMods::Cmd, "z"),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("redo-settings"))
+                .command(REDO_SETTINGS)
+                .hotkey(SysMods::CmdShift, "Z"),
+        );
+
+    let view = Menu::new(LocalizedString::new("menu-view"))
+        .entry(toggle(
+            "phase-group-delay",
+            |data| &mut data.settings.show_phase,
+            |data| data.settings.show_phase,
+        ))
+        .entry(toggle(
+            "spectrum",
+            |data| &mut data.settings.show_spectrum,
+            |data| data.settings.show_spectrum,
+        ))
+        .entry(toggle(
+            "spectrogram",
+            |data| &mut data.settings.show_spectrogram,
+            |data| data.settings.show_spectrogram,
+        ))
+        .entry(toggle(
+            "denoise-preview",
+            |data| &mut data.settings.show_denoise,
+            |data| data.settings.show_denoise,
+        ))
+        .entry(toggle(
+            "envelope-follower",
+            |data| &mut data.settings.show_envelope,
+            |data| data.settings.show_envelope,
+        ))
+        .entry(toggle(
+            "level-history",
+            |data| &mut data.settings.show_level_history,
+            |data| data.settings.show_level_history,
+        ))
+        .entry(toggle(
+            "midi-input",
+            |data| &mut data.settings.show_midi,
+            |data| data.settings.show_midi,
+        ))
+        .separator()
+        .entry(toggle(
+            "db-waveform",
+            |data| &mut data.settings.db_waveform,
+            |data| data.settings.db_waveform,
+        ))
+        .entry(toggle(
+            "freeze-waveform",
+            |data| &mut data.audio.wave.frozen,
+            |data| data.audio.wave.frozen,
+        ))
+        .entry(
+            MenuItem::new(LocalizedString::new("freeze-all"))
+                .on_activate(|_, data: &mut AppState, _| {
+                    let frozen = !all_frozen(data);
+                    data.audio.wave.frozen = frozen;
+                    data.transport.frozen_panes.set_all(frozen);
+                })
+                .selected_if(|data, _| all_frozen(data)),
+        )
+        .separator()
+        .entry(
+            MenuItem::new(LocalizedString::new("zoom-in"))
+                .on_activate(|_, data: &mut AppState, _| zoom_wave(data, 0.5))
+                .hotkey(SysMods::Cmd, "="),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("zoom-out"))
+                .on_activate(|_, data: &mut AppState, _| zoom_wave(data, 2.0))
+                .hotkey(SysMods::Cmd, "-"),
+        )
+        .entry(
+            MenuItem::new(LocalizedString::new("show-all-history"))
+                .on_activate(|_, data: &mut AppState, _| {
+                    data.audio.wave.visible_samples = HISTORY_LEN as f64
+                })
+                .hotkey(SysMods::Cmd, "0"),
+        );
+
+    // Opening another device means restarting the streams, so the choice applies from the next
+    // start
+    let audio = input_devices.iter().fold(
+        Menu::new(LocalizedString::new("menu-audio"))
+            .entry(MenuItem::new(LocalizedString::new("input-device-note")).enabled(false))
+            .separator()
+            .entry(
+                MenuItem::new(LocalizedString::new("default-device"))
+                    .on_activate(|_, data: &mut AppState, _| data.device.input_device = None)
+                    .selected_if(|data, _| data.device.input_device.is_none()),
+            ),
+        |menu, name| {
+            let selected = name.clone();
+            let chosen = name.clone();
+            menu.entry(
+                MenuItem::new(name.as_str())
+                    .on_activate(move |_, data: &mut AppState, _| {
+                        data.device.input_device = Some(chosen.clone())
+                    })
+                    .selected_if(move |data, _| {
+                        data.device.input_device.as_ref() == Some(&selected)
+                    }),
+            )
+        },
+    );
+
+    #[allow(unused_mut)]
+    let mut menu = Menu::empty();
+    #[cfg(target_os = "macos")]
+    {
+        menu = menu.entry(druid::platform_menus::mac::application::default());
+    }
+    menu.entry(file).entry(edit).entry(view).entry(audio)
+}
@@ -0,0 +1,1368 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The live window's widget tree.
+
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use druid::widget::prelude::*;
+use druid::widget::{
+    Button, Checkbox, CrossAxisAlignment, Either, EnvScope, Flex, Label, LabelText, Maybe, Painter,
+    ProgressBar, RadioGroup, Scroll, SizedBox, Slider, TextBox, ViewSwitcher,
+};
+use druid::{
+    commands, lens, text::ParseFormatter, FileDialogOptions, FileSpec, LensExt, LocalizedString,
+    Point, WidgetExt,
+};
+
+use crate::analysis::AnalysisCommand;
+use crate::audio_wave::{
+    AudioWave, Interpolation, WaveData, WaveSnapshot, DB_WAVEFORM, WAVE_ANTIALIAS, WAVE_AUTO_SCALE,
+    WAVE_CHANNEL_OVERLAY, WAVE_COLOR, WAVE_COLORS, WAVE_GAIN, WAVE_RESOLUTION, WAVE_STROKE_WIDTH,
+};
+use crate::backend::BufferStatus;
+use crate::balance::{BalanceBar, BalanceData, MAX_WINDOW_SECONDS, MIN_WINDOW_SECONDS};
+use crate::ballistics::{Ballistics, LevelBar};
+use crate::clip_log::clip_log_list;
+use crate::config::Config;
+use crate::context_menu::ContextMenu;
+use crate::denoise::DenoisePreview;
+use crate::device_formats::{device_formats_panel, WARNING_COLOR};
+use crate::display_agc::{DisplayScaling, METER_DISPLAY_GAIN};
+use crate::envelope::{
+    Detector, EnvelopeSettings, EnvelopeView, ATTACK_TIMES_MS, DRAW_ENVELOPE, RELEASE_TIMES_MS,
+};
+use crate::feedback::feedback_list;
+use crate::freeze::{FreezeController, FrozenPanes};
+use crate::gain_reduction::{GainReductionData, GainReductionStrip};
+use crate::gain_staging::{gain_status_label, GainStatus};
+use crate::i18n::localize;
+use crate::k_system::MeterScale;
+use crate::lag_views::{lag_views, DRAW_LAG_VIEWS};
+use crate::lanes;
+use crate::latency::LatencyStatus;
+use crate::level_history::LevelHistoryChart;
+use crate::loudness_graph::{format_db, LoudnessData, LoudnessGraph, DRAW_LOUDNESS};
+use crate::midi::{MidiRoll, MidiTarget};
+use crate::monitor::{MonitorControl, PushToListen, SoloBand, SOLO_BAND};
+use crate::phase_view::{PhasePlot, DRAW_PHASE};
+use crate::presets::{self, Preset};
+use crate::render_quality::RenderQuality;
+use crate::report::LoudnessTarget;
+use crate::script::{self, script_meters};
+use crate::spectrogram::DisplayRange;
+use crate::spectrogram_view::{self, SpectrogramData, SpectrogramView};
+use crate::spectrum_view::{
+    self, peak_list, SpectrumPlot, SpectrumWindow, DRAW_SPECTRUM, PROCESSED_SMOOTHING,
+    SMOOTHING_FRACTIONS, SPECTRUM_PERSISTENCE, SPECTRUM_PINK_REFERENCE, SPECTRUM_SMOOTHING,
+};
+use crate::spl::{spl_meter, Weighting};
+use crate::split::DataSplit;
+use crate::stats::stats_panel;
+use crate::target_curve::{self, TargetDeviation};
+use crate::throttle::{FrameTiming, PaneRates, PANE_RATES_HZ, UPDATE_RATES_HZ};
+use crate::tilt::Tilt;
+use crate::timeline::TimelineOverlay;
+use crate::transients::{TransientSettings, GATES_DB, THRESHOLD_FACTORS};
+use crate::units::{AmplitudeScale, AmplitudeUnit, DB_FLOORS};
+use crate::workspace::Workspace;
+
+use super::delegate::LiveController;
+use super::menus::{copy_csv_item, pane_menu, presets_menu, record_label, wave_menu};
+use super::{
+    capture_preset, capture_workspace, export_clip_log_dialog, export_report_dialog,
+    load_script_dialog, load_target_curve_dialog, restore_workspace, store_preset, AppState,
+    AudioSnapshot, DeviceInfo, Transport, ViewSettings, EXPORT_PEAKS, EXPORT_PRESET, IMPORT_PRESET,
+    MAX_WAVE_GAIN_DB, SET_RENDER_QUALITY, START_ENGINE, STOP_ENGINE, TOGGLE_RECORDING,
+};
+
+fn smoothing_label(fraction: u64) -> LabelText<u64> {
+    match fraction {
+        0 => LocalizedString::new("smoothing-off").into(),
+        fraction => format!("1/{}", fraction).into(),
+    }
+}
+
+/// One button per workspace, rebuilt when the list changes
+fn workspace_buttons() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _| data.settings.workspaces.clone(),
+        |workspaces, _, _| {
+            let row =
+                workspaces.iter().fold(Flex::row(), |row, workspace| {
+                    let workspace = workspace.clone();
+                    row.with_child(Button::new(workspace.name.as_str()).on_click(
+                        move |_, data: &mut AppState, _| restore_workspace(&workspace, data),
+                    ))
+                    .with_default_spacer()
+                });
+            Box::new(row)
+        },
+    )
+}
+
+/// The range the bar and the waveform show once the AGC's gain is taken off, empty without AGC
+fn agc_label() -> LocalizedString<AppState> {
+    let gain_db = |data: &AppState| data.audio.display_gain_db as f64;
+    LocalizedString::new("agc-range")
+        .with_arg("active", |data: &AppState, _| {
+            (data.settings.display_scaling == DisplayScaling::Agc)
+                .to_string()
+                .into()
+        })
+        .with_arg("gain", move |data: &AppState, _| {
+            format!("{:+.0}", gain_db(data)).into()
+        })
+        .with_arg("floor", move |data: &AppState, _| {
+            format!("{:.0}", data.settings.db_floor - gain_db(data)).into()
+        })
+        .with_arg("ceiling", move |data: &AppState, _| {
+            format!("{:.0}", -gain_db(data)).into()
+        })
+}
+
+/// A live waveform per lane device, scrolled and zoomed with the main one
+fn lane_panes(lanes: Vec<WaveSnapshot>, frame_timing: Arc<FrameTiming>) -> impl Widget<AppState> {
+    let mut column = Flex::column();
+    for (index, lane) in lanes.into_iter().enumerate() {
+        let wave = lens::Map::new(
+            move |audio: &AudioSnapshot| WaveData {
+                visible_samples: audio.wave.visible_samples,
+                frozen: audio.wave.frozen,
+                ..audio.lanes[index].clone()
+            },
+            move |audio: &mut AudioSnapshot, lane: WaveData| {
+                audio.wave.visible_samples = lane.visible_samples;
+                Arc::make_mut(&mut audio.lanes)[index] = lane;
+            },
+        );
+        column.add_child(
+            TimelineOverlay::new(
+                AudioWave::live(lane, frame_timing.clone()).lens(AppState::audio.then(wave)),
+                |data: &AppState| Some(data.audio.wave.time_span()),
+            )
+            .background(Painter::new(move |ctx, data: &AppState, _| {
+                lanes::paint_label(ctx, &data.settings.lane_names[index + 1])
+            }))
+            .fix_height(120.0)
+            .padding((10.0, 0.0, 10.0, 10.0)),
+        );
+    }
+    column
+}
+
+pub(super) fn make_ui(
+    commands: Sender<AnalysisCommand>,
+    snapshot: WaveSnapshot,
+    monitor: Arc<MonitorControl>,
+    frame_timing: Arc<FrameTiming>,
+    lanes: Vec<WaveSnapshot>,
+    config: Config,
+) -> impl Widget<AppState> {
+    let loudness_summary = Label::new(
+        LocalizedString::new("loudness-summary")
+            .with_arg("integrated", |data: &LoudnessData, _| {
+                format_db(data.integrated).into()
+            })
+            .with_arg("range", |data: &LoudnessData, _| {
+                format!("{:.1}", data.range).into()
+            }),
+    );
+    let reset_commands = commands.clone();
+    let reset = Button::new(LocalizedString::new("reset-integration")).on_click(move |_, _, _| {
+        let _ = reset_commands.send(AnalysisCommand::ResetLoudness);
+    });
+    let targets = RadioGroup::row(
+        [
+            LoudnessTarget::Streaming,
+            LoudnessTarget::Podcast,
+            LoudnessTarget::Broadcast,
+        ]
+        .iter()
+        .map(|target| (target.label(), *target))
+        .collect::<Vec<_>>(),
+    );
+    let export = Button::new(LocalizedString::new("export-report"))
+        .on_click(|ctx, _, _| ctx.submit_command(export_report_dialog()));
+
+    let export_clip_log = Button::new(LocalizedString::new("export-log"))
+        .on_click(|ctx, _, _| ctx.submit_command(export_clip_log_dialog()));
+    let record =
+        Button::new(record_label()).on_click(|ctx, _, _| ctx.submit_command(TOGGLE_RECORDING));
+    let load_script = Button::new(LocalizedString::new("load-script"))
+        .on_click(|ctx, _, _| ctx.submit_command(load_script_dialog()));
+    let clear_commands = commands.clone();
+    let clear_clip_log = Button::new(LocalizedString::new("clear")).on_click(move |_, _, _| {
+        let _ = clear_commands.send(AnalysisCommand::ClearClipLog);
+    });
+    let weightings = RadioGroup::row(
+        [Weighting::A, Weighting::C, Weighting::Z]
+            .iter()
+            .map(|weighting| (weighting.label(), *weighting))
+            .collect::<Vec<_>>(),
+    );
+    let ballistics = || {
+        RadioGroup::row(
+            Ballistics::ALL
+                .iter()
+                .map(|ballistics| (ballistics.label(), *ballistics))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let calibrate_commands = commands.clone();
+    let calibrate = Button::new(LocalizedString::new("calibrate")).on_click(
+        move |_, data: &mut AppState, _| {
+            let _ = calibrate_commands.send(AnalysisCommand::CalibrateSpl(
+                data.settings.calibration_reference as f32,
+            ));
+        },
+    );
+    let gain_check_commands = commands.clone();
+    let gain_check = Button::new(LocalizedString::new("check-levels")).on_click(
+        move |_, data: &mut AppState, _| {
+            let _ = gain_check_commands.send(AnalysisCommand::StartGainCheck(
+                data.settings.gain_headroom_db as f32,
+            ));
+            data.audio.gain_status = GainStatus::Measuring(0.0);
+        },
+    );
+    let latency_monitor = monitor.clone();
+    let measure_latency = Button::new(LocalizedString::new("measure-latency")).on_click(
+        move |_, data: &mut AppState, _| {
+            latency_monitor.request_click();
+            data.audio.latency = LatencyStatus::Measuring;
+        },
+    );
+    let gain_row = Flex::row()
+        .with_child(gain_check)
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("headroom")))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .fix_width(40.0)
+                .lens(AppState::settings.then(ViewSettings::gain_headroom_db)),
+        )
+        .with_default_spacer()
+        .with_child(gain_status_label().lens(AppState::audio.then(AudioSnapshot::gain_status)))
+        .with_spacer(20.0)
+        .with_child(measure_latency)
+        .with_default_spacer()
+        .with_child(
+            Label::dynamic(|status: &LatencyStatus, env| status.label(env))
+                .lens(AppState::audio.then(AudioSnapshot::latency)),
+        )
+        .padding(10.0);
+
+    let spl_row = Flex::row()
+        .with_child(spl_meter().lens(AppState::audio.then(AudioSnapshot::spl)))
+        .with_default_spacer()
+        .with_child(weightings.lens(AppState::settings.then(ViewSettings::weighting)))
+        .with_default_spacer()
+        .with_child(ballistics().lens(AppState::settings.then(ViewSettings::spl_ballistics)))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("calibrator-spl")))
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .fix_width(60.0)
+                .lens(AppState::settings.then(ViewSettings::calibration_reference)),
+        )
+        .with_default_spacer()
+        .with_child(calibrate)
+        .padding(10.0);
+    let meter_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("level")))
+        .with_default_spacer()
+        .with_child(LevelBar::default().lens(AppState::audio.then(AudioSnapshot::level_db)))
+        .with_default_spacer()
+        .with_child(
+            Label::dynamic(|data: &AppState, env| {
+                data.settings
+                    .meter_scale
+                    .format(data.audio.level_db)
+                    .unwrap_or_else(|| AmplitudeScale::from_env(env).format_db(data.audio.level_db))
+            })
+            .fix_width(80.0),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(agc_label()))
+        .with_default_spacer()
+        .with_child(ballistics().lens(AppState::settings.then(ViewSettings::meter_ballistics)))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                MeterScale::ALL
+                    .iter()
+                    .map(|scale| (scale.label(), *scale))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(AppState::settings.then(ViewSettings::meter_scale)),
+        )
+        .padding(10.0);
+    let balance_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("balance")))
+        .with_default_spacer()
+        .with_child(BalanceBar::default().lens(AppState::audio.then(AudioSnapshot::balance)))
+        .with_default_spacer()
+        .with_child(
+            Label::dynamic(|data: &BalanceData, env| data.label(env))
+                .fix_width(80.0)
+                .lens(AppState::audio.then(AudioSnapshot::balance)),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("balance-window")))
+        .with_default_spacer()
+        .with_child(
+            Slider::new()
+                .with_range(MIN_WINDOW_SECONDS, MAX_WINDOW_SECONDS)
+                .lens(AppState::settings.then(ViewSettings::balance_window)),
+        )
+        .with_default_spacer()
+        .with_child(Label::dynamic(|data: &AppState, _| {
+            format!("{:.1} s", data.settings.balance_window)
+        }))
+        .padding(10.0);
+
+    let units = RadioGroup::row(
+        [
+            AmplitudeUnit::Dbfs,
+            AmplitudeUnit::Linear,
+            AmplitudeUnit::Percent,
+        ]
+        .iter()
+        .map(|unit| (unit.label(), *unit))
+        .collect::<Vec<_>>(),
+    );
+    let floors = RadioGroup::row(
+        DB_FLOORS
+            .iter()
+            .map(|floor| (format!("{} dB", floor), *floor))
+            .collect::<Vec<_>>(),
+    );
+    let interpolations = RadioGroup::row(
+        [Interpolation::Linear, Interpolation::Cubic]
+            .iter()
+            .map(|interpolation| (interpolation.label(), *interpolation))
+            .collect::<Vec<_>>(),
+    );
+    let update_rates = RadioGroup::row(
+        UPDATE_RATES_HZ
+            .iter()
+            .map(|rate| (format!("{} Hz", rate), *rate))
+            .collect::<Vec<_>>(),
+    );
+    let pane_rates = || {
+        RadioGroup::row(
+            std::iter::once((LabelText::from(LocalizedString::new("full-rate")), None))
+                .chain(
+                    PANE_RATES_HZ
+                        .iter()
+                        .map(|rate| (format!("{} Hz", rate).into(), Some(*rate))),
+                )
+                .collect::<Vec<_>>(),
+        )
+    };
+    let detectors = RadioGroup::row(
+        [Detector::Peak, Detector::Rms]
+            .iter()
+            .map(|detector| (detector.label(), *detector))
+            .collect::<Vec<_>>(),
+    );
+    let time_options = |times: &[f64]| {
+        times
+            .iter()
+            .map(|time| (format!("{} ms", time), *time))
+            .collect::<Vec<_>>()
+    };
+    let envelope_controls = Flex::row()
+        .with_child(detectors.lens(EnvelopeSettings::detector))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("attack")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(time_options(&ATTACK_TIMES_MS)).lens(EnvelopeSettings::attack_ms),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("release")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(time_options(&RELEASE_TIMES_MS)).lens(EnvelopeSettings::release_ms),
+        );
+    let envelope_panel = Flex::column()
+        .with_child(
+            EnvelopeView::default()
+                .lens(AppState::audio.then(AudioSnapshot::envelope))
+                .controller(FreezeController::new(DRAW_ENVELOPE, |data: &AppState| {
+                    data.transport.frozen_panes.envelope
+                }))
+                .controller(ContextMenu::new(|| {
+                    pane_menu(
+                        |data| &mut data.transport.frozen_panes.envelope,
+                        |data| data.transport.frozen_panes.envelope,
+                    )
+                }))
+                .fix_height(150.0)
+                .padding(10.0),
+        )
+        .with_child(
+            envelope_controls
+                .lens(AppState::settings.then(ViewSettings::envelope_settings))
+                .padding((10.0, 0.0)),
+        );
+
+    let midi_learn = MidiTarget::ALL.iter().fold(
+        Flex::row().with_child(Label::new(LocalizedString::new("midi-learn"))),
+        |row, target| {
+            let target = *target;
+            let mapping = move |data: &AppState| {
+                data.settings
+                    .midi_mappings
+                    .iter()
+                    .find(|mapping| mapping.target == target)
+                    .map(|mapping| mapping.controller)
+            };
+            let label = LocalizedString::new("midi-mapping")
+                .with_arg("target", move |_, env| localize(target.label(), env).into())
+                .with_arg("state", move |data: &AppState, _| {
+                    if data.transport.midi_learn == Some(target) {
+                        "learning"
+                    } else if mapping(data).is_some() {
+                        "mapped"
+                    } else {
+                        "unmapped"
+                    }
+                    .into()
+                })
+                .with_arg("controller", move |data: &AppState, _| {
+                    mapping(data).unwrap_or_default().to_string().into()
+                });
+            let button = Button::new(label).on_click(move |_, data: &mut AppState, _| {
+                // Clicking again while waiting cancels
+                data.transport.midi_learn =
+                    (data.transport.midi_learn != Some(target)).then_some(target);
+            });
+            row.with_default_spacer().with_child(button)
+        },
+    );
+
+    let choose_preset =
+        Button::new(LocalizedString::new("presets")).on_click(|ctx, data: &mut AppState, _| {
+            let below = ctx.to_window(Point::new(0.0, ctx.size().height));
+            ctx.show_context_menu(presets_menu(&data.settings.presets), below);
+        });
+    let save_preset = Button::new(LocalizedString::new("save"))
+        .on_click(|_, data: &mut AppState, _| store_preset(capture_preset(data), data))
+        .disabled_if(|data: &AppState, _| data.settings.preset_name.trim().is_empty());
+    let delete_preset = Button::new(LocalizedString::new("delete"))
+        .on_click(|_, data: &mut AppState, _| {
+            let presets: Vec<Preset> = data
+                .settings
+                .presets
+                .iter()
+                .filter(|preset| preset.name != data.settings.preset_name)
+                .cloned()
+                .collect();
+            presets::save(&presets);
+            data.settings.presets = Arc::new(presets);
+        })
+        .disabled_if(|data: &AppState, _| {
+            !data
+                .settings
+                .presets
+                .iter()
+                .any(|preset| preset.name == data.settings.preset_name)
+        });
+    let json = || vec![FileSpec::new("JSON", &["json"])];
+    let import_preset = Button::new(LocalizedString::new("import")).on_click(move |ctx, _, _| {
+        let options = FileDialogOptions::new()
+            .allowed_types(json())
+            .accept_command(IMPORT_PRESET);
+        ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+    });
+    let export_preset = Button::new(LocalizedString::new("export"))
+        .on_click(move |ctx, data: &mut AppState, _| {
+            let options = FileDialogOptions::new()
+                .allowed_types(json())
+                .default_name(format!("{}.json", data.settings.preset_name.trim()))
+                .accept_command(EXPORT_PRESET);
+            ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+        })
+        .disabled_if(|data: &AppState, _| data.settings.preset_name.trim().is_empty());
+    let preset_row = Flex::row()
+        .with_child(choose_preset)
+        .with_default_spacer()
+        .with_child(
+            TextBox::new()
+                .with_placeholder(LocalizedString::new("preset-name"))
+                .fix_width(160.0)
+                .lens(AppState::settings.then(ViewSettings::preset_name)),
+        )
+        .with_default_spacer()
+        .with_child(save_preset)
+        .with_default_spacer()
+        .with_child(delete_preset)
+        .with_default_spacer()
+        .with_child(import_preset)
+        .with_default_spacer()
+        .with_child(export_preset)
+        .padding(10.0);
+
+    let save_workspace = Button::new(LocalizedString::new("save"))
+        .on_click(|_, data: &mut AppState, _| {
+            let workspace = capture_workspace(data);
+            let mut workspaces: Vec<Workspace> = data
+                .settings
+                .workspaces
+                .iter()
+                .filter(|stored| stored.name != workspace.name)
+                .cloned()
+                .collect();
+            workspaces.push(workspace);
+            data.settings.workspaces = Arc::new(workspaces);
+        })
+        .disabled_if(|data: &AppState, _| data.settings.workspace_name.trim().is_empty());
+    let delete_workspace = Button::new(LocalizedString::new("delete"))
+        .on_click(|_, data: &mut AppState, _| {
+            let workspaces: Vec<Workspace> = data
+                .settings
+                .workspaces
+                .iter()
+                .filter(|workspace| workspace.name != data.settings.workspace_name)
+                .cloned()
+                .collect();
+            data.settings.workspaces = Arc::new(workspaces);
+        })
+        .disabled_if(|data: &AppState, _| {
+            !data
+                .settings
+                .workspaces
+                .iter()
+                .any(|workspace| workspace.name == data.settings.workspace_name)
+        });
+    let workspace_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("workspaces")))
+        .with_default_spacer()
+        .with_child(workspace_buttons())
+        .with_child(
+            TextBox::new()
+                .with_placeholder(LocalizedString::new("workspace-name"))
+                .fix_width(160.0)
+                .lens(AppState::settings.then(ViewSettings::workspace_name)),
+        )
+        .with_default_spacer()
+        .with_child(save_workspace)
+        .with_default_spacer()
+        .with_child(delete_workspace)
+        .padding(10.0);
+
+    let solo_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("solo")))
+        .with_default_spacer()
+        .with_child(Either::new(
+            |data: &AppState, _| data.transport.solo_band.is_some(),
+            Label::dynamic(|data: &AppState, _| {
+                data.transport
+                    .solo_band
+                    .map(|band| band.label())
+                    .unwrap_or_default()
+            }),
+            Label::new(LocalizedString::new("solo-hint")),
+        ))
+        .with_default_spacer()
+        .with_child(
+            Button::new(LocalizedString::new("stop-solo"))
+                .on_click(|ctx, _, _| ctx.submit_command(SOLO_BAND.with(None))),
+        )
+        .padding(10.0);
+
+    let capture_commands = commands.clone();
+    let clear_print_commands = commands.clone();
+    let denoise_panel = Flex::column()
+        .with_flex_child(
+            DenoisePreview::default().lens(AppState::audio.then(AudioSnapshot::denoise)),
+            1.0,
+        )
+        .with_default_spacer()
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Button::new(LocalizedString::new("capture-noise-print")).on_click(
+                        move |_, _, _| {
+                            let _ = capture_commands.send(AnalysisCommand::CaptureNoisePrint);
+                        },
+                    ),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Button::new(LocalizedString::new("clear")).on_click(move |_, _, _| {
+                        let _ = clear_print_commands.send(AnalysisCommand::ClearNoisePrint);
+                    }),
+                )
+                .with_default_spacer()
+                .with_child(Label::dynamic(|data: &AppState, env| {
+                    data.audio.denoise.state.label(env)
+                })),
+        )
+        .fix_height(230.0)
+        .padding(10.0);
+
+    let lane_row = (0..=lanes.len()).fold(
+        Flex::row()
+            .with_child(Label::new(LocalizedString::new("lane-names")))
+            .with_default_spacer(),
+        |row, index| {
+            let name = lens::Map::new(
+                move |names: &Arc<Vec<String>>| names[index].clone(),
+                move |names: &mut Arc<Vec<String>>, name: String| {
+                    if names[index] != name {
+                        Arc::make_mut(names)[index] = name;
+                    }
+                },
+            );
+            row.with_child(
+                TextBox::new()
+                    .with_placeholder(LocalizedString::new(if index == 0 {
+                        "input-name"
+                    } else {
+                        "lane-name"
+                    }))
+                    .fix_width(120.0)
+                    .lens(AppState::settings.then(ViewSettings::lane_names).then(name)),
+            )
+            .with_default_spacer()
+        },
+    );
+    let lane_row = lane_row.padding(10.0);
+
+    let hum_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("monitor")))
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("listen-to-input"))
+                .lens(AppState::transport.then(Transport::monitor_input)),
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new(LocalizedString::new("talkback"))
+                .controller(PushToListen)
+                .lens(AppState::transport.then(Transport::talkback)),
+        )
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("remove-hum"))
+                .lens(AppState::transport.then(Transport::remove_hum)),
+        )
+        .with_default_spacer()
+        .with_child(Either::new(
+            |data: &AppState, _| data.audio.hum.is_some(),
+            Label::dynamic(|data: &AppState, env| {
+                data.audio
+                    .hum
+                    .as_ref()
+                    .map(|hum| hum.label(env))
+                    .unwrap_or_default()
+            }),
+            Label::new(LocalizedString::new("no-hum")),
+        ))
+        .padding(10.0);
+
+    let freeze_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("freeze")))
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("waveform"))
+                .lens(WaveData::frozen)
+                .lens(AppState::audio.then(AudioSnapshot::wave)),
+        )
+        .with_default_spacer()
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("loudness")).lens(FrozenPanes::loudness),
+                )
+                .with_default_spacer()
+                .with_child(Checkbox::new(LocalizedString::new("lag")).lens(FrozenPanes::lag_views))
+                .with_default_spacer()
+                .with_child(Checkbox::new(LocalizedString::new("phase")).lens(FrozenPanes::phase))
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("spectrum")).lens(FrozenPanes::spectrum),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("envelope")).lens(FrozenPanes::envelope),
+                )
+                .lens(AppState::transport.then(Transport::frozen_panes)),
+        )
+        .padding(10.0);
+
+    let range_slider = |label: &'static str, min: f64, max: f64, unit: &'static str| {
+        Flex::row()
+            .with_child(Label::new(LocalizedString::new(label)))
+            .with_default_spacer()
+            .with_child(Slider::new().with_range(min, max))
+            .with_default_spacer()
+            .with_child(Label::dynamic(move |value: &f64, _| {
+                format!("{:.1}{}", value, unit)
+            }))
+    };
+    let spectrogram_range = Flex::row()
+        .with_child(
+            range_slider(
+                "spectrogram-floor",
+                spectrogram_view::FLOOR_DB as f64,
+                -20.0,
+                " dB",
+            )
+            .lens(DisplayRange::floor_db),
+        )
+        .with_default_spacer()
+        .with_child(
+            range_slider("spectrogram-ceiling", -60.0, 0.0, " dB").lens(DisplayRange::ceiling_db),
+        )
+        .with_default_spacer()
+        .with_child(range_slider("spectrogram-gamma", 0.2, 3.0, "").lens(DisplayRange::gamma));
+
+    let display_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("units")))
+        .with_default_spacer()
+        .with_child(units.lens(ViewSettings::amplitude_unit))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("floor")))
+        .with_default_spacer()
+        .with_child(floors.lens(ViewSettings::db_floor))
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("db-waveform")).lens(ViewSettings::db_waveform),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("gain")))
+        .with_default_spacer()
+        .with_child(
+            Slider::new()
+                .with_range(0.0, MAX_WAVE_GAIN_DB)
+                .lens(ViewSettings::wave_gain_db)
+                .disabled_if(|data: &ViewSettings, _| {
+                    data.display_scaling != DisplayScaling::Manual
+                }),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("display-scaling")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                DisplayScaling::ALL
+                    .iter()
+                    .map(|scaling| (scaling.label(), *scaling))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(ViewSettings::display_scaling),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("zoomed-in")))
+        .with_default_spacer()
+        .with_child(interpolations.lens(ViewSettings::interpolation))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("update-rate")))
+        .with_default_spacer()
+        .with_child(update_rates.lens(ViewSettings::update_rate_hz))
+        .lens(AppState::settings)
+        .padding(10.0);
+    let pane_rate_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("pane-rates")))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("spectrum")))
+        .with_default_spacer()
+        .with_child(pane_rates().lens(PaneRates::spectrum))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("phase")))
+        .with_default_spacer()
+        .with_child(pane_rates().lens(PaneRates::phase))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("lag")))
+        .with_default_spacer()
+        .with_child(pane_rates().lens(PaneRates::lag_views))
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("spectrogram")))
+        .with_default_spacer()
+        .with_child(pane_rates().lens(PaneRates::spectrogram))
+        .lens(AppState::settings.then(ViewSettings::pane_rates))
+        .padding(10.0);
+    let quality_row =
+        RenderQuality::ALL.iter().fold(
+            Flex::row()
+                .with_child(Label::new(LocalizedString::new("render-quality")))
+                .with_default_spacer(),
+            |row, quality| {
+                let quality = *quality;
+                row.with_child(Button::new(quality.label()).on_click(move |ctx, _, _| {
+                    ctx.submit_command(SET_RENDER_QUALITY.with(quality))
+                }))
+                .with_default_spacer()
+            },
+        );
+    let quality_row = quality_row
+        .with_child(
+            Checkbox::new(LocalizedString::new("auto-battery-saver"))
+                .lens(AppState::settings.then(ViewSettings::auto_battery_saver)),
+        )
+        .with_default_spacer()
+        .with_child(Either::new(
+            |data: &AppState, _| data.transport.on_battery,
+            Label::new(LocalizedString::new("on-battery")),
+            Label::new(""),
+        ))
+        .padding(10.0);
+    let spectrum_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("spectrum-smoothing")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                SMOOTHING_FRACTIONS
+                    .iter()
+                    .map(|fraction| (smoothing_label(*fraction), *fraction))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(ViewSettings::spectrum_smoothing),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("processed-smoothing")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                SMOOTHING_FRACTIONS
+                    .iter()
+                    .map(|fraction| (smoothing_label(*fraction), *fraction))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(ViewSettings::processed_smoothing),
+        )
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("pink-reference"))
+                .lens(ViewSettings::pink_reference),
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new(LocalizedString::new("load-target-curve"))
+                .on_click(|ctx, _, _| ctx.submit_command(load_target_curve_dialog())),
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new(LocalizedString::new("clear-target-curve"))
+                .on_click(|_, settings: &mut ViewSettings, _| settings.target_curve = None)
+                .disabled_if(|settings: &ViewSettings, _| settings.target_curve.is_none()),
+        )
+        .lens(AppState::settings)
+        .padding(10.0);
+    let analysis_row = Flex::row()
+        .with_child(Label::new(LocalizedString::new("spectrum-window")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                SpectrumWindow::ALL
+                    .iter()
+                    .map(|window| (window.label(), *window))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(ViewSettings::spectrum_window),
+        )
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new(LocalizedString::new("transients"))
+                .lens(TransientSettings::enabled)
+                .lens(ViewSettings::transients),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("transient-threshold")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                THRESHOLD_FACTORS
+                    .iter()
+                    .map(|factor| (format!("{}×", factor), *factor))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(TransientSettings::threshold_factor)
+            .lens(ViewSettings::transients),
+        )
+        .with_default_spacer()
+        .with_child(Label::new(LocalizedString::new("transient-gate")))
+        .with_default_spacer()
+        .with_child(
+            RadioGroup::row(
+                GATES_DB
+                    .iter()
+                    .map(|gate_db| (format!("{} dB", gate_db), *gate_db))
+                    .collect::<Vec<_>>(),
+            )
+            .lens(TransientSettings::gate_db)
+            .lens(ViewSettings::transients),
+        )
+        .lens(AppState::settings)
+        .padding(10.0);
+
+    let start_engine = Button::new(LocalizedString::new("start-engine"))
+        .on_click(|ctx, _, _| ctx.submit_command(START_ENGINE))
+        .disabled_if(|data: &AppState, _| data.transport.engine_running);
+    let stop_engine = Button::new(LocalizedString::new("stop-engine"))
+        .on_click(|ctx, _, _| ctx.submit_command(STOP_ENGINE))
+        .disabled_if(|data: &AppState, _| !data.transport.engine_running);
+    let clear_feedback_commands = commands.clone();
+    let clear_feedback = Button::new(LocalizedString::new("clear")).on_click(move |_, _, _| {
+        let _ = clear_feedback_commands.send(AnalysisCommand::ClearFeedback);
+    });
+    let clip_panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_child(start_engine)
+                .with_default_spacer()
+                .with_child(stop_engine),
+        )
+        .with_child(
+            Maybe::or_empty(|| {
+                Label::new(
+                    LocalizedString::new("start-failed")
+                        .with_arg("error", |error: &String, _| error.clone().into()),
+                )
+                .with_text_color(WARNING_COLOR)
+            })
+            .lens(AppState::device.then(DeviceInfo::start_error)),
+        )
+        .with_default_spacer()
+        .with_child(device_formats_panel().lens(AppState::device.then(DeviceInfo::formats)))
+        .with_child(
+            Maybe::or_empty(|| {
+                Flex::row()
+                    .with_child(ProgressBar::new().lens(BufferStatus::fill))
+                    .with_default_spacer()
+                    .with_child(Label::dynamic(|status: &BufferStatus, env| {
+                        status.state.label(env)
+                    }))
+            })
+            .lens(AppState::device.then(DeviceInfo::buffer_status)),
+        )
+        .with_child(
+            Label::dynamic(|data: &DeviceInfo, env| {
+                data.formats
+                    .buffer_warning(data.stats.buffer_size)
+                    .map_or(String::new(), |warning| warning.message(env))
+            })
+            .with_text_color(WARNING_COLOR)
+            .lens(AppState::device),
+        )
+        .with_default_spacer()
+        .with_child(stats_panel().lens(AppState::device.then(DeviceInfo::stats)))
+        .with_spacer(20.0)
+        .with_child(load_script)
+        .with_default_spacer()
+        .with_child(script_meters().lens(AppState::audio.then(AudioSnapshot::script)))
+        .with_spacer(20.0)
+        .with_child(Label::new(LocalizedString::new("clip-events")))
+        .with_default_spacer()
+        .with_flex_child(
+            Scroll::new(clip_log_list())
+                .vertical()
+                .lens(AppState::audio.then(AudioSnapshot::clip_log)),
+            1.0,
+        )
+        .with_default_spacer()
+        .with_child(
+            Flex::row()
+                .with_child(export_clip_log)
+                .with_default_spacer()
+                .with_child(clear_clip_log),
+        )
+        .with_spacer(20.0)
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("feedback-detection"))
+                        .lens(AppState::settings.then(ViewSettings::feedback_detection)),
+                )
+                .with_default_spacer()
+                .with_child(clear_feedback),
+        )
+        .with_default_spacer()
+        .with_flex_child(
+            Scroll::new(feedback_list())
+                .vertical()
+                .lens(AppState::audio.then(AudioSnapshot::feedback)),
+            1.0,
+        )
+        .padding(10.0);
+
+    let export_peaks = Button::new(LocalizedString::new("export")).on_click(|ctx, _, _| {
+        let options = FileDialogOptions::new()
+            .allowed_types(vec![FileSpec::new("CSV", &["csv"])])
+            .default_name("spectrum-peaks.csv")
+            .accept_command(EXPORT_PEAKS);
+        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+    });
+    let peak_panel = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Flex::row()
+                .with_child(Label::new(LocalizedString::new("peaks")))
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("freeze"))
+                        .lens(AppState::transport.then(Transport::peaks_frozen)),
+                )
+                .with_default_spacer()
+                .with_child(export_peaks),
+        )
+        .with_default_spacer()
+        .with_child(Label::dynamic(|data: &AppState, _| {
+            data.audio
+                .tilt
+                .as_ref()
+                .map(Tilt::label)
+                .unwrap_or_default()
+        }))
+        .with_child(Label::dynamic(|data: &AppState, _| {
+            data.audio
+                .target_deviation
+                .as_ref()
+                .map(TargetDeviation::label)
+                .unwrap_or_default()
+        }))
+        .with_default_spacer()
+        .with_flex_child(
+            Scroll::new(peak_list())
+                .vertical()
+                .lens(AppState::audio.then(AudioSnapshot::peak_table)),
+            1.0,
+        )
+        .fix_width(260.0);
+
+    let wave_pane = Flex::column()
+        .with_flex_child(
+            TimelineOverlay::new(
+                AudioWave::live(snapshot.clone(), frame_timing.clone())
+                    .lens(AppState::audio.then(AudioSnapshot::wave)),
+                |data: &AppState| Some(data.audio.wave.time_span()),
+            )
+            .controller(ContextMenu::new(wave_menu))
+            .background(Painter::new(|ctx, data: &AppState, env| {
+                script::paint_overlay(ctx, &data.audio.script.overlay, env);
+                lanes::paint_label(ctx, &data.settings.lane_names[0]);
+            }))
+            .padding(10.0),
+            1.0,
+        )
+        .with_child(lane_panes(lanes, frame_timing))
+        .with_child(Either::new(
+            |data: &AppState, _| !data.audio.gain_reduction.readings.is_empty(),
+            TimelineOverlay::new(
+                GainReductionStrip.lens(AppState::audio.then(lens::Map::new(
+                    // On the waveform's span rather than the history's, to follow its zoom
+                    |audio: &AudioSnapshot| {
+                        let span = audio.wave.time_span();
+                        GainReductionData {
+                            start: span.start,
+                            end: span.end,
+                            ..audio.gain_reduction.clone()
+                        }
+                    },
+                    |_: &mut AudioSnapshot, _| {},
+                ))),
+                |data: &AppState| Some(data.audio.wave.time_span()),
+            )
+            .fix_height(50.0)
+            .padding((10.0, 0.0, 10.0, 10.0)),
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_midi,
+            MidiRoll::default()
+                .lens(AppState::audio.then(AudioSnapshot::midi))
+                .fix_height(80.0)
+                .padding((10.0, 0.0)),
+            SizedBox::empty(),
+        ));
+    let analysis_panes = Flex::column()
+        .with_flex_child(
+            LoudnessGraph::default()
+                .lens(AppState::audio.then(AudioSnapshot::loudness))
+                .controller(FreezeController::new(DRAW_LOUDNESS, |data: &AppState| {
+                    data.transport.frozen_panes.loudness
+                }))
+                .controller(ContextMenu::new(|| {
+                    pane_menu(
+                        |data| &mut data.transport.frozen_panes.loudness,
+                        |data| data.transport.frozen_panes.loudness,
+                    )
+                }))
+                .padding(10.0),
+            1.0,
+        )
+        .with_flex_child(
+            lag_views()
+                .lens(AppState::audio.then(AudioSnapshot::lag_views))
+                .controller(FreezeController::new(DRAW_LAG_VIEWS, |data: &AppState| {
+                    data.transport.frozen_panes.lag_views
+                }))
+                .controller(ContextMenu::new(|| {
+                    pane_menu(
+                        |data| &mut data.transport.frozen_panes.lag_views,
+                        |data| data.transport.frozen_panes.lag_views,
+                    )
+                }))
+                .padding(10.0),
+            1.0,
+        )
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_phase,
+            PhasePlot::default()
+                .lens(AppState::audio.then(AudioSnapshot::phase))
+                .controller(FreezeController::new(DRAW_PHASE, |data: &AppState| {
+                    data.transport.frozen_panes.phase
+                }))
+                .controller(ContextMenu::new(|| {
+                    pane_menu(
+                        |data| &mut data.transport.frozen_panes.phase,
+                        |data| data.transport.frozen_panes.phase,
+                    )
+                }))
+                .fix_height(150.0)
+                .padding(10.0),
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_spectrum,
+            Flex::row()
+                .with_flex_child(
+                    SpectrumPlot::default()
+                        .lens(AppState::audio.then(AudioSnapshot::spectrum))
+                        .controller(FreezeController::new(DRAW_SPECTRUM, |data: &AppState| {
+                            data.transport.frozen_panes.spectrum
+                        }))
+                        .background(Painter::new(|ctx, data: &AppState, _| {
+                            if let Some(target) = &data.settings.target_curve {
+                                target_curve::paint(
+                                    ctx,
+                                    target,
+                                    data.audio.target_deviation,
+                                    &data.audio.spectrum,
+                                );
+                            }
+                        }))
+                        .controller(ContextMenu::new(|| {
+                            pane_menu(
+                                |data| &mut data.transport.frozen_panes.spectrum,
+                                |data| data.transport.frozen_panes.spectrum,
+                            )
+                            .entry(copy_csv_item(|data| {
+                                spectrum_view::spectrum_to_csv(&data.audio.spectrum)
+                            }))
+                        })),
+                    1.0,
+                )
+                .with_default_spacer()
+                .with_child(peak_panel)
+                .fix_height(200.0)
+                .padding(10.0),
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_spectrogram,
+            Flex::column()
+                .with_flex_child(
+                    TimelineOverlay::new(
+                        SpectrogramView::default()
+                            .lens(AppState::audio.then(AudioSnapshot::spectrogram)),
+                        |data: &AppState| Some(data.audio.spectrogram.time_span()),
+                    ),
+                    1.0,
+                )
+                .with_default_spacer()
+                .with_child(
+                    Flex::row()
+                        .with_child(
+                            Checkbox::new(LocalizedString::new("spectrogram-pause")).lens(
+                                AppState::audio.then(AudioSnapshot::spectrogram).then(
+                                    lens::Map::new(
+                                        |data: &SpectrogramData| data.view_end.is_some(),
+                                        |data: &mut SpectrogramData, paused: bool| {
+                                            data.view_end = paused.then_some(data.end)
+                                        },
+                                    ),
+                                ),
+                            ),
+                        )
+                        .with_default_spacer()
+                        .with_child(
+                            spectrogram_range
+                                .lens(AppState::settings.then(ViewSettings::spectrogram_range)),
+                        ),
+                )
+                .fix_height(230.0)
+                .padding(10.0),
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_denoise,
+            denoise_panel,
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_envelope,
+            envelope_panel,
+            SizedBox::empty(),
+        ))
+        .with_child(Either::new(
+            |data: &AppState, _| data.settings.show_level_history,
+            TimelineOverlay::new(
+                LevelHistoryChart::default()
+                    .lens(AppState::audio.then(AudioSnapshot::level_history)),
+                |data: &AppState| Some(data.audio.level_history.time_span()),
+            )
+            .fix_height(100.0)
+            .padding(10.0),
+            SizedBox::empty(),
+        ))
+        .with_child(
+            Flex::row()
+                .with_child(loudness_summary.lens(AppState::audio.then(AudioSnapshot::loudness)))
+                .with_default_spacer()
+                .with_child(reset)
+                .with_default_spacer()
+                .with_child(targets.lens(AppState::settings.then(ViewSettings::report_target)))
+                .with_default_spacer()
+                .with_child(export)
+                .with_default_spacer()
+                .with_child(record)
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("phase-group-delay"))
+                        .lens(AppState::settings.then(ViewSettings::show_phase)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("spectrum"))
+                        .lens(AppState::settings.then(ViewSettings::show_spectrum)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("spectrogram"))
+                        .lens(AppState::settings.then(ViewSettings::show_spectrogram)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("denoise-preview"))
+                        .lens(AppState::settings.then(ViewSettings::show_denoise)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("envelope-follower"))
+                        .lens(AppState::settings.then(ViewSettings::show_envelope)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("level-history"))
+                        .lens(AppState::settings.then(ViewSettings::show_level_history)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("midi-input"))
+                        .lens(AppState::settings.then(ViewSettings::show_midi)),
+                )
+                .padding(10.0),
+        )
+        .with_child(spl_row)
+        .with_child(meter_row)
+        .with_child(balance_row)
+        .with_child(gain_row)
+        .with_child(display_row)
+        .with_child(pane_rate_row)
+        .with_child(quality_row)
+        .with_child(analysis_row)
+        .with_child(spectrum_row)
+        .with_child(solo_row)
+        .with_child(hum_row)
+        .with_child(freeze_row)
+        .with_child(midi_learn.padding(10.0))
+        .with_child(lane_row)
+        .with_child(preset_row)
+        .with_child(workspace_row);
+    let meters = DataSplit::rows(
+        wave_pane,
+        analysis_panes,
+        AppState::settings.then(ViewSettings::wave_ratio),
+    );
+
+    let root = DataSplit::columns(
+        meters,
+        clip_panel,
+        AppState::settings.then(ViewSettings::meters_ratio),
+    )
+    .controller(LiveController::new(commands, config, monitor));
+    EnvScope::new(
+        |env, data: &AppState| {
+            AmplitudeScale {
+                unit: data.settings.amplitude_unit,
+                floor_db: data.settings.db_floor,
+            }
+            .set_env(env);
+            env.set(DB_WAVEFORM, data.settings.db_waveform);
+            let agc = data.settings.display_scaling == DisplayScaling::Agc;
+            let display_gain_db = if agc {
+                data.audio.display_gain_db as f64
+            } else {
+                0.0
+            };
+            env.set(
+                WAVE_GAIN,
+                if agc {
+                    display_gain_db
+                } else {
+                    data.settings.wave_gain_db
+                },
+            );
+            env.set(METER_DISPLAY_GAIN, display_gain_db);
+            env.set(
+                WAVE_AUTO_SCALE,
+                data.settings.display_scaling == DisplayScaling::FitPeak,
+            );
+            env.set(WAVE_COLOR, WAVE_COLORS[data.settings.wave_color].1);
+            env.set(WAVE_STROKE_WIDTH, data.settings.wave_stroke_width);
+            env.set(WAVE_ANTIALIAS, data.settings.wave_antialias);
+            env.set(WAVE_CHANNEL_OVERLAY, data.settings.channel_overlay);
+            env.set(WAVE_RESOLUTION, data.settings.wave_resolution);
+            env.set(SPECTRUM_PERSISTENCE, data.settings.spectrum_persistence);
+            env.set(SPECTRUM_PINK_REFERENCE, data.settings.pink_reference);
+            env.set(SPECTRUM_SMOOTHING, data.settings.spectrum_smoothing);
+            env.set(PROCESSED_SMOOTHING, data.settings.processed_smoothing);
+            data.settings.interpolation.set_env(env);
+            data.settings.meter_scale.set_env(env);
+            spectrogram_view::set_display_range(env, &data.settings.spectrogram_range);
+            SoloBand::set_env(data.transport.solo_band, env);
+        },
+        root,
+    )
+}
@@ -0,0 +1,292 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The cpal backend, the live input from a sound card. Devices offering f32 go through the
+//! standalone host, which also forwards MIDI. Devices that only offer integer formats are opened
+//! directly at their native format, and the analyser converts the samples to f32 itself. Their
+//! input-only stream is paired with one on the default output device, which plays the monitor.
+
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+
+use atomic_queue::Queue;
+use audio_processor_standalone::standalone_cpal::StandaloneStartOptions;
+use audio_processor_standalone::standalone_processor::StandaloneOptions;
+use audio_processor_standalone::{standalone_start_with, StandaloneProcessorImpl};
+use basedrop::{Handle, Shared};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat, SampleRate, SupportedStreamConfig};
+
+use crate::backend::{AudioBackend, AudioStream};
+use crate::buffer_analyser::{BufferAnalyserProcessor, InputSample, SampleChunk};
+use crate::device_formats::{DeviceFormats, StreamRequest};
+
+/// The standalone host always asks its devices for this rate and buffer size
+const STANDALONE_SAMPLE_RATE: f32 = 44100.0;
+const STANDALONE_BUFFER_SIZE: u32 = 512;
+
+/// The named input device, falling back to the default one
+fn find_device(name: Option<&str>) -> Option<Device> {
+    let host = cpal::default_host();
+    name.and_then(|name| {
+        host.input_devices()
+            .ok()?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+    })
+    .or_else(|| host.default_input_device())
+}
+
+/// The device and its default configuration when that isn't f32
+fn integer_input(name: Option<&str>) -> Option<(Device, SupportedStreamConfig)> {
+    let device = find_device(name)?;
+    let config = device.default_input_config().ok()?;
+    (config.sample_format() != SampleFormat::F32).then_some((device, config))
+}
+
+fn build_stream<S: InputSample + cpal::Sample>(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    mut processor: BufferAnalyserProcessor,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let num_channels = config.channels() as usize;
+    device.build_input_stream(
+        &config.config(),
+        move |data: &[S], _: &cpal::InputCallbackInfo| processor.process_input(data, num_channels),
+        |err| log::error!("Input error: {:?}", err),
+    )
+}
+
+/// Plays a queue of mono samples on every channel of an output stream, silence while it's empty
+fn build_playback_stream<S: cpal::Sample>(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    playback: Shared<Queue<SampleChunk>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let num_channels = config.channels() as usize;
+    let mut chunk = SampleChunk::default();
+    let mut index = 0;
+    device.build_output_stream(
+        &config.config(),
+        move |data: &mut [S], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(num_channels) {
+                if index == chunk.samples().len() {
+                    chunk = playback.pop().unwrap_or_default();
+                    index = 0;
+                }
+                let sample = chunk.samples().get(index).copied().unwrap_or(0.0);
+                index += 1;
+                frame.fill(S::from(&sample));
+            }
+        },
+        |err| log::error!("Monitor output error: {:?}", err),
+    )
+}
+
+/// A stream on the default output device at `sample_rate` playing what the processor monitors.
+/// Without one the monitor, talkback and latency click have nowhere to play.
+fn start_playback(
+    processor: &mut BufferAnalyserProcessor,
+    sample_rate: SampleRate,
+) -> io::Result<cpal::Stream> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "There is no output device"))?;
+    let config = device
+        .supported_output_configs()
+        .map_err(io::Error::other)?
+        .find(|config| {
+            config.min_sample_rate() <= sample_rate && sample_rate <= config.max_sample_rate()
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("The output device doesn't run at {} Hz", sample_rate.0),
+            )
+        })?
+        .with_sample_rate(sample_rate);
+    let playback = processor.play_through();
+    let stream = match config.sample_format() {
+        SampleFormat::I16 => build_playback_stream::<i16>(&device, &config, playback),
+        SampleFormat::U16 => build_playback_stream::<u16>(&device, &config, playback),
+        SampleFormat::F32 => build_playback_stream::<f32>(&device, &config, playback),
+    }
+    .map_err(io::Error::other)?;
+    stream.play().map_err(io::Error::other)?;
+    Ok(stream)
+}
+
+/// The rate [`start`] opens the input device at, known before it's opened
+fn expected_sample_rate(input_device: Option<&str>) -> f32 {
+    integer_input(input_device).map_or(STANDALONE_SAMPLE_RATE, |(_, config)| {
+        config.sample_rate().0 as f32
+    })
+}
+
+/// What the named device supports and what [`start`] will ask of it
+fn device_formats(name: Option<&str>) -> DeviceFormats {
+    let device = match find_device(name) {
+        Some(device) => device,
+        None => return DeviceFormats::default(),
+    };
+    let request = match integer_input(name) {
+        Some((_, config)) => StreamRequest {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            buffer_size: None,
+        },
+        None => {
+            // Like the standalone host, stereo if any configuration has it
+            let stereo = device
+                .supported_input_configs()
+                .is_ok_and(|mut configs| configs.any(|config| config.channels() > 1));
+            StreamRequest {
+                sample_rate: STANDALONE_SAMPLE_RATE as u32,
+                channels: if stereo { 2 } else { 1 },
+                buffer_size: Some(STANDALONE_BUFFER_SIZE),
+            }
+        }
+    };
+    DeviceFormats::probe(&device, request)
+}
+
+/// Start the analyser on the input device, returning the stream and the sample rate
+fn start(
+    mut processor: BufferAnalyserProcessor,
+    handle: &Handle,
+    input_device: Option<String>,
+) -> io::Result<(AudioStream, f32)> {
+    if find_device(input_device.as_deref()).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "There is no input device",
+        ));
+    }
+    if let Some((device, config)) = integer_input(input_device.as_deref()) {
+        log::info!(
+            "Opening {} at its native {:?} format",
+            device.name().unwrap_or_default(),
+            config.sample_format()
+        );
+        processor.set_sample_rate(config.sample_rate().0 as f32);
+        let playback = start_playback(&mut processor, config.sample_rate())
+            .map_err(|err| log::warn!("Monitoring is silent, no output: {}", err))
+            .ok();
+        let stream = match config.sample_format() {
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, processor),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, processor),
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, processor),
+        }
+        .map_err(io::Error::other)?;
+        stream.play().map_err(io::Error::other)?;
+        // No MIDI input on this path
+        return Ok((
+            AudioStream::new((stream, playback)),
+            config.sample_rate().0 as f32,
+        ));
+    }
+
+    let options = StandaloneOptions {
+        input_device,
+        ..StandaloneOptions::default()
+    };
+    // The host panics when its audio thread fails to open the device, before handing back
+    // anything to report
+    let handles = panic::catch_unwind(AssertUnwindSafe(|| {
+        standalone_start_with::<_, cpal::Host>(
+            StandaloneProcessorImpl::new_with(processor, options),
+            StandaloneStartOptions {
+                handle: Some(handle.clone()),
+                ..StandaloneStartOptions::default()
+            },
+        )
+    }))
+    .map_err(|_| io::Error::other("The audio host failed to open the device"))?;
+    let sample_rate = handles
+        .configuration()
+        .output_configuration()
+        .sample_rate()
+        .0 as f32;
+    Ok((AudioStream::new(handles), sample_rate))
+}
+
+/// A sound card input through cpal's default host
+pub struct CpalBackend {
+    handle: Handle,
+    input_device: Option<String>,
+    sample_rate: f32,
+    formats: DeviceFormats,
+}
+
+impl CpalBackend {
+    /// The named input device, or the default one. It's probed here, without being opened.
+    pub fn new(handle: &Handle, input_device: Option<String>) -> Self {
+        CpalBackend {
+            handle: handle.clone(),
+            sample_rate: expected_sample_rate(input_device.as_deref()),
+            formats: device_formats(input_device.as_deref()),
+            input_device,
+        }
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn formats(&self) -> DeviceFormats {
+        self.formats.clone()
+    }
+
+    fn select_input_device(&mut self, name: Option<String>) {
+        if name != self.input_device {
+            self.formats = device_formats(name.as_deref());
+            self.input_device = name;
+        }
+    }
+
+    /// The analysis keeps the rate of the device probed at launch, so a device chosen since then
+    /// is only started if it runs at that rate too
+    fn open(&mut self, processor: BufferAnalyserProcessor) -> io::Result<AudioStream> {
+        let expected = expected_sample_rate(self.input_device.as_deref());
+        if expected != self.sample_rate {
+            return Err(rate_mismatch(expected, self.sample_rate));
+        }
+        let (stream, sample_rate) = start(processor, &self.handle, self.input_device.clone())?;
+        if sample_rate != self.sample_rate {
+            // Dropping the stream closes the device again
+            return Err(rate_mismatch(sample_rate, self.sample_rate));
+        }
+        Ok(stream)
+    }
+}
+
+fn rate_mismatch(device_rate: f32, analysis_rate: f32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "The device runs at {} Hz but the analysis was set up for {} Hz, restart the app \
+             to use it",
+            device_rate, analysis_rate
+        ),
+    )
+}
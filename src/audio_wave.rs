@@ -0,0 +1,1379 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use druid::kurbo::{BezPath, Circle, Line, Rect};
+use druid::piet::{ImageFormat, InterpolationMode, PietImage, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Data, FontFamily, Key, Lens, LocalizedString, Point, Selector};
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::clip_log::format_timestamp;
+use crate::decimate::{self, MinMax};
+use crate::i18n::localize_with;
+use crate::link::{BeatGrid, QUANTUM};
+use crate::profiling::{self, profile_scope};
+use crate::throttle::FrameTiming;
+use crate::timeline::TimeSpan;
+use crate::units::AmplitudeScale;
+use crate::wave_raster::{AdditiveSpans, RasterLayout, RasterRequest, WaveRaster};
+
+// If you want to submit commands to an event sink you have to give it some kind
+// of ID. The selector is that, it also assures the accompanying data-type is correct.
+// look at the docs for `Selector` for more detail. The live window's delegate handles it, setting
+// the data, which causes a call to `update` that requests a paint.
+pub const DRAW_AUDIO: Selector<Vec<f32>> = Selector::new("event-example.draw_audio");
+
+/// Draw the waveform on a dB scale, down to the [`crate::units::DB_FLOOR`]
+pub const DB_WAVEFORM: Key<bool> = Key::new("event-example.db_waveform");
+
+/// Gain in dB applied to the waveform, so quiet signals can fill the view
+pub const WAVE_GAIN: Key<f64> = Key::new("event-example.wave_gain");
+
+/// Fit the waveform's gain to the peak in view instead of using [`WAVE_GAIN`]
+pub const WAVE_AUTO_SCALE: Key<bool> = Key::new("event-example.wave_auto_scale");
+/// dB to change [`WAVE_GAIN`] by, submitted when the waveform is scrolled with Alt held
+pub const ZOOM_AMPLITUDE: Selector<f64> = Selector::new("event-example.zoom_amplitude");
+
+/// Stroke of the waveform, one of [`WAVE_COLORS`]
+pub const WAVE_COLOR: Key<Color> = Key::new("event-example.wave_color");
+/// Choices for [`WAVE_COLOR`] with their localization keys
+pub const WAVE_COLORS: [(&str, Color); 4] = [
+    ("color-red", Color::RED),
+    ("color-green", Color::rgb8(0, 200, 120)),
+    ("color-blue", Color::rgb8(60, 140, 255)),
+    ("color-white", Color::WHITE),
+];
+
+/// Width of the waveform's line, the built look's width when unset
+pub const WAVE_STROKE_WIDTH: Key<f64> = Key::new("event-example.wave_stroke_width");
+/// Choices for [`WAVE_STROKE_WIDTH`]
+pub const STROKE_WIDTHS: [f64; 4] = [1.0, 2.0, 3.0, 4.0];
+/// Off snaps the line to whole device pixels, since piet always antialiases. A 1px line is then a
+/// crisp hairline. On by default.
+pub const WAVE_ANTIALIAS: Key<bool> = Key::new("event-example.wave_antialias");
+/// Draws the second channel over the first with their colors added, where there is one
+pub const WAVE_CHANNEL_OVERLAY: Key<bool> = Key::new("event-example.wave_channel_overlay");
+/// Colors of the two channels in the overlay, adding up to nearly white where they coincide
+const LEFT_RGB: [u8; 3] = [255, 60, 0];
+const RIGHT_RGB: [u8; 3] = [0, 160, 255];
+/// Decimated columns per pixel once zoomed out past a sample per pixel, 1 when unset. Fewer are
+/// cheaper to decimate and rasterize, more resolve detail on high-DPI screens.
+pub const WAVE_RESOLUTION: Key<f64> = Key::new("event-example.wave_resolution");
+
+/// How samples are joined when zoomed in past a sample per pixel, see [`Interpolation`]
+pub const INTERPOLATION: Key<u64> = Key::new("event-example.interpolation");
+
+/// The fewest samples the waveform can be zoomed into
+pub const MIN_VISIBLE_SAMPLES: f64 = 64.0;
+/// How quickly the expected snapshot interval follows the measured one
+const INTERVAL_SMOOTHING: f64 = 0.1;
+/// Share of the scrolling delay's error corrected every frame
+const DELAY_CORRECTION: f64 = 0.05;
+/// Samples get a dot once they are at least this many pixels apart
+const MIN_DOT_SPACING: f64 = 6.0;
+const DOT_RADIUS: f64 = 2.5;
+/// Most gain auto-scaling applies, so silence isn't blown up to noise
+const MAX_AUTO_GAIN_DB: f64 = 30.0;
+/// Auto-scaling moves in steps this big, redrawing the whole pane each time
+const AUTO_SCALE_STEP_DB: f64 = 3.0;
+/// Room left above the peak in view when auto-scaled
+const AUTO_SCALE_HEADROOM: f64 = 1.1;
+/// A pan glides on once no pan events arrived for this long
+const MOMENTUM_DELAY: Duration = Duration::from_millis(50);
+/// Time constant of the glide's slowdown
+const MOMENTUM_DECAY_SECONDS: f64 = 0.3;
+
+/// Curve drawn between samples when there are fewer samples than pixels
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    Linear,
+    /// Catmull-Rom spline through the samples
+    Cubic,
+}
+
+impl Interpolation {
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            Interpolation::Linear => "interpolation-linear",
+            Interpolation::Cubic => "interpolation-cubic",
+        })
+    }
+
+    pub fn set_env(&self, env: &mut Env) {
+        env.set(INTERPOLATION, *self as u64);
+    }
+
+    fn from_env(env: &Env) -> Self {
+        match env.try_get(INTERPOLATION) {
+            Ok(1) => Interpolation::Cubic,
+            _ => Interpolation::Linear,
+        }
+    }
+}
+
+/// The line the waveform is drawn with, in widget coordinates
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Stroke {
+    width: f64,
+    /// Device pixels per widget unit, while points are snapped to the pixel grid
+    snap_scale: Option<f64>,
+}
+
+impl Stroke {
+    fn from_env(env: &Env, default_width: f64, device_scale: f64) -> Self {
+        let width = env.try_get(WAVE_STROKE_WIDTH).unwrap_or(default_width);
+        if env.try_get(WAVE_ANTIALIAS).unwrap_or(true) {
+            return Stroke {
+                width,
+                snap_scale: None,
+            };
+        }
+        let device_width = (width * device_scale).round().max(1.0);
+        Stroke {
+            width: device_width / device_scale,
+            snap_scale: Some(device_scale),
+        }
+    }
+
+    fn snap(&self, point: Point) -> Point {
+        let scale = match self.snap_scale {
+            Some(scale) => scale,
+            None => return point,
+        };
+        // Odd widths are centered on pixel centers and even ones on pixel edges, so the line
+        // covers whole pixels either way
+        let offset = if (self.width * scale).round() as u64 % 2 == 1 {
+            0.5
+        } else {
+            0.0
+        };
+        let snap = |coord: f64| ((coord * scale - offset).round() + offset) / scale;
+        Point::new(snap(point.x), snap(point.y))
+    }
+}
+
+#[derive(Clone, Data)]
+pub struct AudioData(pub Arc<Vec<f32>>);
+
+impl AudioData {
+    pub fn new(samples: Vec<f32>) -> Self {
+        AudioData(Arc::new(samples))
+    }
+}
+
+/// Latest waveform snapshot, written by the analysis thread and read by [`AudioWave::live`] on
+/// every animation frame
+#[derive(Clone, Default)]
+pub struct WaveSnapshot {
+    latest: Arc<Mutex<SnapshotFrame>>,
+    /// Readers other than the window, which need snapshots while it's hidden too
+    background_readers: Arc<AtomicUsize>,
+}
+
+/// Keeps snapshots coming while the window is hidden, until dropped
+pub struct BackgroundRead(Arc<AtomicUsize>);
+
+impl Drop for BackgroundRead {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Default)]
+struct SnapshotFrame {
+    revision: u64,
+    audio: Option<AudioData>,
+    right: Option<AudioData>,
+    voice_activity: Option<Arc<Vec<Range<usize>>>>,
+    /// Samples pushed to the stream up to the newest one in `audio`
+    position: u64,
+    sample_rate: f32,
+    /// What `position` counts at, for the timecode. `sample_rate` is the measured rate.
+    nominal_rate: f32,
+    beat_grid: Option<BeatGrid>,
+}
+
+impl WaveSnapshot {
+    /// Publish the latest samples, oldest first, with the second channel's if it's aligned with
+    /// them, and the speech-active ranges and beats among them
+    pub fn publish(
+        &self,
+        samples: Vec<f32>,
+        right: Option<Vec<f32>>,
+        voice_activity: Vec<Range<usize>>,
+        position: u64,
+        sample_rate: f32,
+        beat_grid: Option<BeatGrid>,
+    ) {
+        let mut latest = self
+            .latest
+            .lock()
+            .expect("Failed to lock waveform snapshot");
+        latest.revision += 1;
+        latest.audio = Some(AudioData::new(samples));
+        latest.right = right.map(AudioData::new);
+        latest.voice_activity = Some(Arc::new(voice_activity));
+        latest.position = position;
+        latest.sample_rate = sample_rate;
+        latest.beat_grid = beat_grid;
+    }
+
+    /// Set once by the analysis thread before publishing
+    pub fn set_nominal_rate(&self, rate: f32) {
+        self.latest
+            .lock()
+            .expect("Failed to lock waveform snapshot")
+            .nominal_rate = rate;
+    }
+
+    /// Move the position on without publishing samples, while nothing reads them
+    pub fn set_position(&self, position: u64) {
+        self.latest
+            .lock()
+            .expect("Failed to lock waveform snapshot")
+            .position = position;
+    }
+
+    pub fn read_in_background(&self) -> BackgroundRead {
+        self.background_readers.fetch_add(1, Ordering::Relaxed);
+        BackgroundRead(self.background_readers.clone())
+    }
+
+    pub fn has_background_readers(&self) -> bool {
+        self.background_readers.load(Ordering::Relaxed) > 0
+    }
+
+    /// The measured rate the waveform scrolls at, 0 before the first snapshot
+    pub fn sample_rate(&self) -> f32 {
+        self.latest
+            .lock()
+            .expect("Failed to lock waveform snapshot")
+            .sample_rate
+    }
+
+    /// Samples pushed to the stream so far
+    pub fn position(&self) -> u64 {
+        self.latest
+            .lock()
+            .expect("Failed to lock waveform snapshot")
+            .position
+    }
+
+    /// The newest samples published, oldest first
+    pub fn latest_audio(&self) -> Option<AudioData> {
+        self.latest
+            .lock()
+            .expect("Failed to lock waveform snapshot")
+            .audio
+            .clone()
+    }
+
+    /// The snapshot, if it's newer than `revision`
+    fn newer_than(&self, revision: u64) -> Option<SnapshotFrame> {
+        let latest = self
+            .latest
+            .lock()
+            .expect("Failed to lock waveform snapshot");
+        (latest.revision != revision).then(|| latest.clone())
+    }
+}
+
+/// Scrolls a live pane at the sample rate between snapshots. The right edge of the view trails
+/// the newest sample by about one snapshot interval, so there's always audio to scroll into.
+struct LiveWave {
+    snapshot: WaveSnapshot,
+    revision: u64,
+    /// Stream position of the newest sample received
+    newest: f64,
+    /// Stream position of the view's right edge
+    shown: f64,
+    /// Smoothed number of samples between snapshots
+    interval: f64,
+    sample_rate: f64,
+    nominal_rate: f64,
+    /// Tells the analysis thread when frames are drawn and how long painting takes
+    timing: Arc<FrameTiming>,
+    raster: WaveRaster,
+    /// Snapshot and layout last sent to the rasterizer
+    requested: Option<(AudioData, RasterLayout)>,
+    /// The raster uploaded for drawing, with its revision
+    image: Option<(u64, PietImage)>,
+}
+
+impl LiveWave {
+    fn new(snapshot: WaveSnapshot, timing: Arc<FrameTiming>) -> Self {
+        LiveWave {
+            snapshot,
+            revision: 0,
+            newest: 0.0,
+            shown: 0.0,
+            interval: 0.0,
+            sample_rate: 0.0,
+            nominal_rate: 0.0,
+            timing,
+            raster: WaveRaster::new(),
+            requested: None,
+            image: None,
+        }
+    }
+
+    /// Time at the view's right edge since the stream started, and since recording started, in
+    /// the top right corner. Counted in samples, so it keeps audio time rather than wall time, and
+    /// moved back by the calibrated input latency.
+    fn paint_timecode(&self, ctx: &mut PaintCtx, data: &WaveData, env: &Env) {
+        if self.nominal_rate <= 0.0 {
+            return;
+        }
+        let mut lines = vec![(
+            format_timestamp((self.shown / self.nominal_rate - data.input_latency).max(0.0) as f32),
+            Color::grey(0.8),
+        )];
+        if let Some(start) = data.record_start {
+            let elapsed = (self.shown - start).max(0.0) / self.nominal_rate;
+            lines.push((
+                localize_with(
+                    "recording-timecode",
+                    &[("time", format_timestamp(elapsed as f32))],
+                    env,
+                ),
+                Color::rgb8(255, 80, 80),
+            ));
+        }
+        let width = ctx.size().width;
+        for (index, (text, color)) in lines.into_iter().enumerate() {
+            let layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(FontFamily::MONOSPACE, 11.0)
+                .text_color(color)
+                .build();
+            if let Ok(layout) = layout {
+                let x_coord = width - layout.size().width - 4.0;
+                ctx.draw_text(&layout, (x_coord, 2.0 + index as f64 * 14.0));
+            }
+        }
+    }
+
+    /// Take a new snapshot if there is one and advance the view by `elapsed_nanos`
+    fn advance(&mut self, elapsed_nanos: u64, data: &mut WaveData) {
+        if let Some(frame) = self.snapshot.newer_than(self.revision) {
+            let position = frame.position as f64;
+            if self.revision == 0 {
+                self.shown = position;
+            } else {
+                self.interval += (position - self.newest - self.interval) * INTERVAL_SMOOTHING;
+            }
+            self.revision = frame.revision;
+            self.newest = position;
+            data.position = position;
+            self.sample_rate = frame.sample_rate as f64;
+            self.nominal_rate = frame.nominal_rate as f64;
+            if let Some(audio) = frame.audio {
+                data.audio = audio;
+                data.right = frame.right;
+            }
+            data.voice_activity = frame.voice_activity;
+            data.beat_grid = frame.beat_grid;
+        }
+
+        self.shown += elapsed_nanos as f64 / 1e9 * self.sample_rate;
+        // Ease towards the target delay rather than jumping when snapshots arrive early or late
+        let target = self.newest - self.interval;
+        self.shown += (target - self.shown) * DELAY_CORRECTION;
+        let max_delay = (data.audio.0.len() as f64 - data.visible_samples).max(0.0);
+        self.shown = self.shown.clamp(self.newest - max_delay, self.newest);
+        data.scroll = Some(self.newest - self.shown);
+    }
+
+    /// Blit the rasterized waveform. Returns false when there's no raster for this view yet, or
+    /// it's zoomed in past a sample per pixel, and the vector waveform has to be painted instead.
+    fn paint_raster(
+        &mut self,
+        ctx: &mut PaintCtx,
+        data: &WaveData,
+        scale: WaveScale,
+        stroke: &Stroke,
+        resolution: f64,
+    ) -> bool {
+        let size = ctx.size();
+        let device_height = ctx.scale().y();
+        let window_len = data.window_len();
+        if size.width < 1.0 || window_len < size.width {
+            return false;
+        }
+        let samples_per_column = ((window_len / (size.width * resolution)) as usize).max(1);
+        let view_columns = window_len / samples_per_column as f64;
+        let layout = RasterLayout {
+            samples_per_column,
+            num_columns: view_columns.ceil() as usize + 1,
+            height: (size.height * device_height).ceil() as usize,
+            scale,
+            stroke_width: stroke.width * device_height,
+            antialias: stroke.snap_scale.is_none(),
+        };
+        let first_sample = (self.newest as u64).saturating_sub(data.audio.0.len() as u64);
+        let requested = self
+            .requested
+            .as_ref()
+            .is_some_and(|(audio, requested_layout)| {
+                audio.same(&data.audio) && *requested_layout == layout
+            });
+        if !requested {
+            self.raster.request(RasterRequest {
+                audio: data.audio.clone(),
+                first_sample,
+                layout,
+            });
+            self.requested = Some((data.audio.clone(), layout));
+        }
+
+        let end_column = {
+            let raster = self.raster.image();
+            let raster = match &*raster {
+                Some(raster) if raster.layout == layout => raster,
+                _ => return false,
+            };
+            if self.image.as_ref().map(|(revision, _)| *revision) != Some(raster.revision) {
+                let image = ctx.make_image(
+                    layout.num_columns,
+                    layout.height,
+                    &raster.pixels,
+                    ImageFormat::RgbaPremul,
+                );
+                match image {
+                    Ok(image) => self.image = Some((raster.revision, image)),
+                    Err(err) => {
+                        log::error!("Failed to upload waveform raster: {}", err);
+                        return false;
+                    }
+                }
+            }
+            raster.end_column
+        };
+        let image = match &self.image {
+            Some((_, image)) => image,
+            None => return false,
+        };
+
+        // Stream column at the left edge, fractional while scrolling through it
+        let left = (first_sample as f64 + data.window_start()) / samples_per_column as f64;
+        let column_width = size.width / view_columns;
+        let first_slot = (left as u64 % layout.num_columns as u64) as f64;
+        let x0 = -left.fract() * column_width;
+        let x1 = x0 + (layout.num_columns as f64 - first_slot) * column_width;
+        let (rows, height) = (layout.height as f64, size.height);
+        // Slots past the newest drawn column still hold old audio
+        let drawn_width = (end_column as f64 - left) * column_width;
+        ctx.with_save(|ctx| {
+            ctx.clip(Rect::new(
+                0.0,
+                0.0,
+                drawn_width.min(size.width),
+                size.height,
+            ));
+            ctx.draw_image_area(
+                image,
+                Rect::new(first_slot, 0.0, layout.num_columns as f64, rows),
+                Rect::new(x0, 0.0, x1, height),
+                InterpolationMode::Bilinear,
+            );
+            ctx.draw_image_area(
+                image,
+                Rect::new(0.0, 0.0, first_slot, rows),
+                Rect::new(x1, 0.0, x1 + first_slot * column_width, height),
+                InterpolationMode::Bilinear,
+            );
+        });
+        true
+    }
+}
+
+/// What a waveform pane draws: the sample snapshot and how many samples fit in the view.
+#[derive(Clone, Data, Lens)]
+pub struct WaveData {
+    pub audio: AudioData,
+    /// The second channel, as long as `audio`, for [`WAVE_CHANNEL_OVERLAY`]
+    pub right: Option<AudioData>,
+    pub visible_samples: f64,
+    /// Speech-active ranges of `audio` to shade, if voice activity detection is running
+    pub voice_activity: Option<Arc<Vec<Range<usize>>>>,
+    /// Samples between the view's right edge and the end of `audio` while scrolling. Without it
+    /// the view starts at the first sample.
+    pub scroll: Option<f64>,
+    /// Live panes stop pulling new snapshots while set
+    pub frozen: bool,
+    /// Beat and bar lines to draw, relative to the end of `audio`
+    pub beat_grid: Option<BeatGrid>,
+    /// Stream position recording started at, shown in the live timecode
+    pub record_start: Option<f64>,
+    /// Seconds the input runs behind real time, taken off the live timecode
+    pub input_latency: f64,
+    /// Stream position of the end of `audio` in live panes, 0 in static ones
+    pub position: f64,
+}
+
+impl WaveData {
+    pub fn new(len: usize) -> Self {
+        WaveData {
+            audio: AudioData::new(Vec::new()),
+            right: None,
+            visible_samples: len as f64,
+            voice_activity: None,
+            scroll: None,
+            frozen: false,
+            beat_grid: None,
+            record_start: None,
+            input_latency: 0.0,
+            position: 0.0,
+        }
+    }
+
+    /// Stream positions in view, for the timeline
+    pub fn time_span(&self) -> TimeSpan {
+        let start = self.position - self.audio.0.len() as f64 + self.window_start();
+        TimeSpan {
+            start,
+            end: start + self.window_len(),
+        }
+    }
+
+    /// Index of the first sample in view, fractional while scrolling
+    pub fn window_start(&self) -> f64 {
+        match self.scroll {
+            None => 0.0,
+            Some(end_offset) => {
+                (self.audio.0.len() as f64 - end_offset - self.visible_samples).max(0.0)
+            }
+        }
+    }
+
+    /// Move the view `samples` later, or earlier when negative, keeping it within the snapshot
+    pub fn pan(&mut self, samples: f64) {
+        let max_offset = (self.audio.0.len() as f64 - self.visible_samples).max(0.0);
+        // No offset means the view starts at the first sample
+        let offset = self.scroll.unwrap_or(max_offset);
+        self.scroll = Some((offset - samples).clamp(0.0, max_offset));
+    }
+
+    /// How many samples span the width of the view
+    pub fn window_len(&self) -> f64 {
+        self.visible_samples.min(self.audio.0.len() as f64)
+    }
+
+    /// The second channel's samples in view, alongside [`WaveData::visible`]
+    pub fn visible_right(&self) -> Option<&[f32]> {
+        let right = self
+            .right
+            .as_ref()
+            .filter(|right| right.0.len() == self.audio.0.len())?;
+        let start = self.window_start();
+        Some(visible_window(
+            &right.0[start as usize..],
+            self.visible_samples + start.fract().ceil(),
+        ))
+    }
+
+    /// The part of the snapshot that is currently in view, including a partly visible sample at
+    /// the right edge while scrolling
+    pub fn visible(&self) -> &[f32] {
+        let start = self.window_start();
+        visible_window(
+            &self.audio.0[start as usize..],
+            self.visible_samples + start.fract().ceil(),
+        )
+    }
+}
+
+pub fn visible_window(samples: &[f32], visible_samples: f64) -> &[f32] {
+    &samples[..(visible_samples as usize).min(samples.len())]
+}
+
+/// The newest `visible_samples` of a snapshot that's in time order
+pub fn latest_window(samples: &[f32], visible_samples: f64) -> &[f32] {
+    &samples[samples.len() - (visible_samples as usize).min(samples.len())..]
+}
+
+/// Samples numbered from the first one given, for copying the visible window
+pub fn samples_to_csv(samples: &[f32]) -> String {
+    let mut csv = String::from("sample,value\n");
+    for (index, sample) in samples.iter().enumerate() {
+        csv += &format!("{},{}\n", index, sample);
+    }
+    csv
+}
+
+/// Apply a mouse-wheel delta to a visible window length
+pub fn zoom(visible_samples: f64, wheel_delta_y: f64, max_samples: usize) -> f64 {
+    let max_samples = (max_samples as f64).max(MIN_VISIBLE_SAMPLES);
+    (visible_samples * (wheel_delta_y / 200.0).exp()).clamp(MIN_VISIBLE_SAMPLES, max_samples)
+}
+
+/// Apply a pinch gesture's magnification to a visible window length, spreading zooms in
+pub fn pinch_zoom(visible_samples: f64, magnification: f64, max_samples: usize) -> f64 {
+    let max_samples = (max_samples as f64).max(MIN_VISIBLE_SAMPLES);
+    (visible_samples * (-magnification).exp()).clamp(MIN_VISIBLE_SAMPLES, max_samples)
+}
+
+/// Keeps a two-finger pan gliding after the fingers lift, slowing down like a scroll view
+#[derive(Default)]
+struct PanMomentum {
+    /// Samples per second, positive towards the end
+    velocity: f64,
+    last_pan: Option<Instant>,
+}
+
+impl PanMomentum {
+    fn push(&mut self, samples: f64) {
+        let now = Instant::now();
+        let since_last = self
+            .last_pan
+            .map_or(MOMENTUM_DELAY, |last| now.duration_since(last))
+            .clamp(Duration::from_millis(1), MOMENTUM_DELAY);
+        self.velocity = 0.5 * self.velocity + 0.5 * samples / since_last.as_secs_f64();
+        self.last_pan = Some(now);
+    }
+
+    /// Samples to pan by this frame, `None` while the gesture is going on or once it has stopped
+    fn step(&mut self, elapsed_nanos: u64) -> Option<f64> {
+        let last_pan = self.last_pan?;
+        if last_pan.elapsed() < MOMENTUM_DELAY {
+            return Some(0.0);
+        }
+        let seconds = elapsed_nanos as f64 / 1e9;
+        let samples = self.velocity * seconds;
+        self.velocity *= (-seconds / MOMENTUM_DECAY_SECONDS).exp();
+        if samples.abs() < 1.0 {
+            self.velocity = 0.0;
+            self.last_pan = None;
+            return None;
+        }
+        Some(samples)
+    }
+}
+
+/// The gain fitting `peak` into the view, in whole steps. It drops as soon as the peak would go
+/// off the edge but only rises once there's room for two steps, so it doesn't flicker.
+fn auto_gain_db(current_db: f64, peak: f32) -> f64 {
+    let fit_db = (-20.0 * (peak.max(f32::EPSILON) as f64 * AUTO_SCALE_HEADROOM).log10())
+        .clamp(0.0, MAX_AUTO_GAIN_DB);
+    if current_db > fit_db || fit_db >= current_db + 2.0 * AUTO_SCALE_STEP_DB {
+        (fit_db / AUTO_SCALE_STEP_DB).floor() * AUTO_SCALE_STEP_DB
+    } else {
+        current_db
+    }
+}
+
+/// Positions labelled on the Y axis, from the center line (0) to the top edge (1)
+const AXIS_POSITIONS: [f64; 3] = [1.0, 0.5, 0.0];
+
+/// Where a sample sits between the center line and the edge, linearly or through 20·log10 when a
+/// dB floor is given
+fn display_position(sample: f32, db_floor: Option<f64>) -> f64 {
+    match db_floor {
+        None => sample as f64,
+        Some(floor) => {
+            let db = 20.0 * (sample.abs() as f64).log10();
+            let position = if db > floor { 1.0 - db / floor } else { 0.0 };
+            position.min(1.0).copysign(sample as f64)
+        }
+    }
+}
+
+/// The amplitude drawn at a display position, the inverse of [`display_position`]
+fn position_amplitude(position: f64, db_floor: Option<f64>) -> f32 {
+    match db_floor {
+        None => position as f32,
+        Some(floor) => 10.0_f64.powf(floor * (1.0 - position) / 20.0) as f32,
+    }
+}
+
+/// How samples map to heights and what color they're drawn in, from the display settings in the
+/// env
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WaveScale {
+    pub db_floor: Option<f64>,
+    /// Applied to samples before they're drawn, 1 unless [`WAVE_GAIN`] is set
+    pub gain: f32,
+    pub color: Color,
+}
+
+impl WaveScale {
+    pub fn from_env(env: &Env) -> Self {
+        WaveScale {
+            db_floor: env
+                .try_get(DB_WAVEFORM)
+                .unwrap_or(false)
+                .then(|| AmplitudeScale::from_env(env).floor_db),
+            gain: 10.0_f32.powf(env.try_get(WAVE_GAIN).unwrap_or(0.0) as f32 / 20.0),
+            color: env.try_get(WAVE_COLOR).unwrap_or(Color::RED),
+        }
+    }
+
+    pub fn position(&self, sample: f32) -> f64 {
+        display_position(sample * self.gain, self.db_floor)
+    }
+
+    fn amplitude(&self, position: f64) -> f32 {
+        position_amplitude(position, self.db_floor) / self.gain
+    }
+}
+
+/// Margin around changed samples, for the stroke and the segment joining the next column
+const DIRTY_MARGIN: f64 = 4.0;
+
+/// The parts of the widget that changed between two snapshots, or `None` when the whole view
+/// has to be repainted
+fn dirty_rects(old_data: &WaveData, data: &WaveData, size: Size) -> Option<Vec<Rect>> {
+    let unchanged_view = old_data.visible_samples == data.visible_samples
+        && old_data.voice_activity.same(&data.voice_activity)
+        && old_data.beat_grid == data.beat_grid
+        && old_data.record_start == data.record_start
+        && old_data.input_latency == data.input_latency
+        && old_data.audio.0.len() == data.audio.0.len();
+    if !unchanged_view {
+        return None;
+    }
+    match (old_data.scroll, data.scroll) {
+        (None, None) => Some(changed_sample_rects(old_data, data, size)),
+        (Some(_), Some(_)) => new_sample_rects(old_data, data, size),
+        _ => None,
+    }
+}
+
+/// A ring buffer shown in storage order, usually the stretch behind its write position
+fn changed_sample_rects(old_data: &WaveData, data: &WaveData, size: Size) -> Vec<Rect> {
+    let (old_samples, samples) = (old_data.visible(), data.visible());
+    let to_x = |index: usize| index as f64 / samples.len() as f64 * size.width;
+    let mut rects: Vec<Rect> = Vec::new();
+    let mut index = 0;
+    while index < samples.len() {
+        if old_samples[index] == samples[index] {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < samples.len() && old_samples[index] != samples[index] {
+            index += 1;
+        }
+        let rect = Rect::new(
+            to_x(start) - DIRTY_MARGIN,
+            0.0,
+            to_x(index) + DIRTY_MARGIN,
+            size.height,
+        );
+        // Merge runs that are closer together than the margin
+        match rects.last_mut() {
+            Some(last) if last.x1 >= rect.x0 => last.x1 = rect.x1,
+            _ => rects.push(rect),
+        }
+    }
+    rects
+}
+
+/// A scrolling pane, where only the columns from the stream position the old snapshot ended at
+/// change as long as the view stays put. Once it moves every column shifts.
+fn new_sample_rects(old_data: &WaveData, data: &WaveData, size: Size) -> Option<Vec<Rect>> {
+    let span = data.time_span();
+    if old_data.time_span() != span {
+        return None;
+    }
+    let x_coord = (old_data.position - span.start) / (span.end - span.start) * size.width;
+    if x_coord >= size.width + DIRTY_MARGIN {
+        return Some(Vec::new());
+    }
+    // The column ending at the old position is decimated again once it's complete
+    let column_width = (size.width / (span.end - span.start)).max(1.0);
+    Some(vec![Rect::new(
+        (x_coord - column_width - DIRTY_MARGIN).max(0.0),
+        0.0,
+        size.width,
+        size.height,
+    )])
+}
+
+/// What the cached waveform was decimated from
+struct PointsKey {
+    audio: AudioData,
+    visible_samples: f64,
+    scroll: Option<f64>,
+    scale: WaveScale,
+    width: f64,
+    resolution: f64,
+}
+
+impl PointsKey {
+    fn same(&self, other: &PointsKey) -> bool {
+        self.audio.same(&other.audio)
+            && self.visible_samples == other.visible_samples
+            && self.scroll == other.scroll
+            && self.scale == other.scale
+            && self.width == other.width
+            && self.resolution == other.resolution
+    }
+}
+
+/// How each pixel column's samples are reduced when zoomed out past a sample per pixel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Decimation {
+    /// The column's lowest and highest samples, so no peak is hidden
+    MinMax,
+    /// Plus and minus the column's RMS, closer to how loud it sounds. Only for embedding, the
+    /// app's panes show peaks.
+    Rms,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WaveStyle {
+    /// A stroke through the samples
+    Line,
+    /// The area between the columns' ranges, or under the samples when zoomed in, filled in
+    Filled,
+}
+
+/// What an [`AudioWave`] looks like, fixed when it's built. Unset colors come from the env.
+#[derive(Clone, Debug)]
+struct WaveLook {
+    color: Option<Color>,
+    background: Option<Color>,
+    style: WaveStyle,
+    stroke_width: f64,
+    decimation: Decimation,
+    axis: bool,
+}
+
+impl Default for WaveLook {
+    fn default() -> Self {
+        WaveLook {
+            color: None,
+            background: None,
+            style: WaveStyle::Line,
+            stroke_width: 3.0,
+            decimation: Decimation::MinMax,
+            axis: true,
+        }
+    }
+}
+
+/// Configures an [`AudioWave`] for embedding, without going through the app's env keys:
+///
+/// ```no_run
+/// use druid::Color;
+/// use example_druid_audio_viz::audio_wave::{AudioWaveBuilder, Decimation, WaveStyle};
+///
+/// let wave = AudioWaveBuilder::new()
+///     .color(Color::rgb8(60, 140, 255))
+///     .style(WaveStyle::Filled)
+///     .decimation(Decimation::Rms)
+///     .axis(false)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct AudioWaveBuilder {
+    look: WaveLook,
+}
+
+impl AudioWaveBuilder {
+    pub fn new() -> Self {
+        AudioWaveBuilder::default()
+    }
+
+    /// Waveform color, [`WAVE_COLOR`] from the env when unset
+    pub fn color(mut self, color: Color) -> Self {
+        self.look.color = Some(color);
+        self
+    }
+
+    /// Filled behind the waveform, transparent when unset
+    pub fn background(mut self, color: Color) -> Self {
+        self.look.background = Some(color);
+        self
+    }
+
+    pub fn style(mut self, style: WaveStyle) -> Self {
+        self.look.style = style;
+        self
+    }
+
+    /// Width of [`WaveStyle::Line`] strokes, 3 by default. [`WAVE_STROKE_WIDTH`] overrides it.
+    pub fn stroke_width(mut self, width: f64) -> Self {
+        self.look.stroke_width = width;
+        self
+    }
+
+    pub fn decimation(mut self, decimation: Decimation) -> Self {
+        self.look.decimation = decimation;
+        self
+    }
+
+    /// Label amplitudes along the left edge, on by default
+    pub fn axis(mut self, axis: bool) -> Self {
+        self.look.axis = axis;
+        self
+    }
+
+    pub fn build(self) -> AudioWave {
+        AudioWave {
+            look: self.look,
+            ..AudioWave::default()
+        }
+    }
+
+    /// A waveform that follows `source`, see [`AudioWave::live`]
+    pub fn build_live(self, source: WaveSnapshot, timing: Arc<FrameTiming>) -> AudioWave {
+        AudioWave {
+            live: Some(LiveWave::new(source, timing)),
+            ..self.build()
+        }
+    }
+}
+
+/// A widget that displays a waveform, configured through [`AudioWaveBuilder`]
+#[derive(Default)]
+pub struct AudioWave {
+    look: WaveLook,
+    /// Min/max of each pixel column of the visible window
+    buckets: Vec<MinMax>,
+    /// Decimated waveform with x in 0..1 and y in -1..1, rebuilt when the snapshot or width changes.
+    /// Zoomed in, there is one point per sample instead.
+    points: Vec<Point>,
+    points_key: Option<PointsKey>,
+    zoomed_in: bool,
+    /// `points` scaled to the widget, rebuilt when the points, the size, the interpolation or the
+    /// stroke change
+    path: BezPath,
+    path_key: Option<(Size, Interpolation, Stroke)>,
+    /// Pulled from on every animation frame when live
+    live: Option<LiveWave>,
+    momentum: PanMomentum,
+    /// Gain fitted to the peak in view, while auto-scaling
+    auto_gain_db: Option<f64>,
+    channel_overlay: AdditiveSpans,
+}
+
+impl AudioWave {
+    /// A waveform that follows `source`, scrolling in step with the display
+    pub fn live(source: WaveSnapshot, timing: Arc<FrameTiming>) -> Self {
+        AudioWaveBuilder::new().build_live(source, timing)
+    }
+
+    /// The env's display settings with the built color in place of [`WAVE_COLOR`], and the
+    /// auto-scaled gain in place of [`WAVE_GAIN`] while it's on
+    fn scale(&self, env: &Env) -> WaveScale {
+        let scale = WaveScale::from_env(env);
+        WaveScale {
+            color: self.look.color.unwrap_or(scale.color),
+            gain: match self.auto_gain_db {
+                Some(gain_db) if env.try_get(WAVE_AUTO_SCALE).unwrap_or(false) => {
+                    10.0_f32.powf(gain_db as f32 / 20.0)
+                }
+                _ => scale.gain,
+            },
+            ..scale
+        }
+    }
+
+    /// Follow the peak in view, returning whether the gain changed
+    fn update_auto_gain(&mut self, data: &WaveData, env: &Env) -> bool {
+        if !env.try_get(WAVE_AUTO_SCALE).unwrap_or(false) {
+            return self.auto_gain_db.take().is_some();
+        }
+        let summary = decimate::summary(data.visible());
+        let peak = summary.max.max(-summary.min);
+        let gain_db = auto_gain_db(self.auto_gain_db.unwrap_or(0.0), peak);
+        self.auto_gain_db.replace(gain_db) != Some(gain_db)
+    }
+
+    /// Live panes follow the stream, so they only pan while frozen
+    fn pannable(&self, data: &WaveData) -> bool {
+        self.live.is_none() || data.frozen
+    }
+
+    fn paint_axis(ctx: &mut PaintCtx, env: &Env, scale: WaveScale) {
+        let size = ctx.size();
+        let units = AmplitudeScale::from_env(env);
+        for position in AXIS_POSITIONS {
+            let y_coord = size.height / 2.0 - position * size.height / 2.0;
+            let layout = ctx
+                .text()
+                .new_text_layout(units.format(scale.amplitude(position)))
+                .font(FontFamily::SYSTEM_UI, 10.0)
+                .text_color(Color::grey(0.6))
+                .build();
+            if let Ok(layout) = layout {
+                let y_coord = y_coord.clamp(0.0, size.height - 12.0);
+                ctx.draw_text(&layout, (2.0, y_coord));
+            }
+        }
+    }
+
+    /// Both channels' min/max spans with their colors added, at device resolution. Returns false
+    /// when there's no second channel and the waveform has to be painted instead.
+    fn paint_channel_overlay(
+        &mut self,
+        ctx: &mut PaintCtx,
+        data: &WaveData,
+        scale: WaveScale,
+    ) -> bool {
+        let right = match data.visible_right() {
+            Some(right) => right,
+            None => return false,
+        };
+        let size = ctx.size();
+        let device = ctx.scale();
+        let width = (size.width * device.x()).round() as usize;
+        let height = (size.height * device.y()).round() as usize;
+        if width == 0 || height == 0 {
+            return true;
+        }
+        // Same orientation as the vector waveform
+        let to_row = |sample: f32| (scale.position(sample) + 1.0) * 0.5 * (height - 1) as f64;
+        self.channel_overlay.clear(width, height);
+        self.channel_overlay.add(data.visible(), LEFT_RGB, to_row);
+        self.channel_overlay.add(right, RIGHT_RGB, to_row);
+        let image = ctx.make_image(
+            width,
+            height,
+            self.channel_overlay.pixels(),
+            ImageFormat::RgbaSeparate,
+        );
+        match image {
+            Ok(image) => ctx.draw_image(&image, size.to_rect(), InterpolationMode::Bilinear),
+            Err(err) => log::error!("Failed to draw channel overlay: {}", err),
+        }
+        true
+    }
+
+    /// A line on every beat, brighter on the first beat of a bar
+    fn paint_beat_grid(ctx: &mut PaintCtx, data: &WaveData, grid: BeatGrid) {
+        let size = ctx.size();
+        let len = data.audio.0.len() as f64;
+        let window_start = data.window_start();
+        let window_len = data.window_len();
+        // Beats since the last bar line at the window's edges
+        let beat_at = |index: f64| grid.phase_at_end - (len - index) / grid.samples_per_beat;
+        let first_beat = beat_at(window_start).ceil() as i64;
+        let last_beat = beat_at(window_start + window_len).floor() as i64;
+        for beat in first_beat..=last_beat {
+            let index = len - (grid.phase_at_end - beat as f64) * grid.samples_per_beat;
+            let x_coord = (index - window_start) / window_len * size.width;
+            let color = if beat.rem_euclid(QUANTUM as i64) == 0 {
+                Color::grey(0.6)
+            } else {
+                Color::grey(0.3)
+            };
+            ctx.stroke(
+                Line::new((x_coord, 0.0), (x_coord, size.height)),
+                &color,
+                1.0,
+            );
+        }
+    }
+
+    fn update_points(&mut self, data: &WaveData, scale: WaveScale, width: f64, resolution: f64) {
+        let key = PointsKey {
+            audio: data.audio.clone(),
+            visible_samples: data.visible_samples,
+            scroll: data.scroll,
+            scale,
+            width,
+            resolution,
+        };
+        if self
+            .points_key
+            .as_ref()
+            .is_some_and(|cached| cached.same(&key))
+        {
+            return;
+        }
+        self.points_key = Some(key);
+        self.path_key = None;
+        self.points.clear();
+
+        let samples = data.visible();
+        let window_len = data.window_len();
+        self.zoomed_in = window_len < width;
+        if self.zoomed_in {
+            // Each sample sits in the middle of its share of the width, shifted left by the part
+            // of the first sample that has scrolled out of view
+            let offset = 0.5 - data.window_start().fract();
+            self.points
+                .extend(samples.iter().enumerate().map(|(index, sample)| {
+                    Point::new(
+                        (index as f64 + offset) / window_len,
+                        scale.position(*sample),
+                    )
+                }));
+            return;
+        }
+
+        // One bucket per pixel at full resolution
+        let num_columns = ((width * resolution).ceil() as usize).max(1);
+        match self.look.decimation {
+            Decimation::MinMax => decimate::decimate_into(samples, num_columns, &mut self.buckets),
+            Decimation::Rms => decimate::decimate_rms_into(samples, num_columns, &mut self.buckets),
+        }
+        let num_buckets = self.buckets.len();
+        let mut prev = match self.buckets.first() {
+            Some(bucket) => scale.position(bucket.max),
+            None => return,
+        };
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            let x_coord = index as f64 / num_buckets as f64;
+            let x2_coord = (index + 1) as f64 / num_buckets as f64;
+            let max = scale.position(bucket.max);
+            let min = scale.position(bucket.min);
+            self.points.push(Point::new(x_coord, prev));
+            self.points.push(Point::new(x2_coord, max));
+            self.points.push(Point::new(x2_coord, min));
+            prev = min;
+        }
+    }
+
+    fn update_path(&mut self, size: Size, interpolation: Interpolation, stroke: Stroke) {
+        if self.path_key == Some((size, interpolation, stroke)) {
+            return;
+        }
+        self.path_key = Some((size, interpolation, stroke));
+        // Truncating keeps the path's allocation for the next rebuild
+        self.path.truncate(0);
+        let to_widget = |point: &Point| {
+            stroke.snap(Point::new(
+                point.x * size.width,
+                (point.y + 1.0) * size.height / 2.0,
+            ))
+        };
+        if !self.zoomed_in && self.look.style == WaveStyle::Filled {
+            // Along the maxima, then back along the minima
+            let segments = self.points.chunks_exact(3);
+            for (index, segment) in segments.clone().enumerate() {
+                if index == 0 {
+                    self.path.move_to(to_widget(&segment[0]));
+                }
+                self.path.line_to(to_widget(&segment[1]));
+            }
+            for segment in segments.rev() {
+                self.path.line_to(to_widget(&segment[2]));
+            }
+            self.path.close_path();
+            return;
+        }
+        if !self.zoomed_in {
+            for segment in self.points.chunks_exact(3) {
+                self.path.move_to(to_widget(&segment[0]));
+                self.path.line_to(to_widget(&segment[1]));
+                self.path.line_to(to_widget(&segment[2]));
+            }
+            return;
+        }
+
+        let points = &self.points;
+        let point = |index: usize| to_widget(&points[index.min(points.len() - 1)]);
+        self.path.move_to(point(0));
+        for index in 1..points.len() {
+            match interpolation {
+                Interpolation::Linear => self.path.line_to(point(index)),
+                Interpolation::Cubic => {
+                    // Catmull-Rom segment between the previous point and this one, as a Bézier
+                    let (p0, p1) = (point(index.saturating_sub(2)), point(index - 1));
+                    let (p2, p3) = (point(index), point(index + 1));
+                    self.path
+                        .curve_to(p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2);
+                }
+            }
+        }
+        if self.look.style == WaveStyle::Filled {
+            let center = size.height / 2.0;
+            self.path.line_to((point(points.len() - 1).x, center));
+            self.path.line_to((point(0).x, center));
+            self.path.close_path();
+        }
+    }
+
+    /// Mark each sample once they are far enough apart to tell apart
+    fn paint_sample_dots(&self, ctx: &mut PaintCtx) {
+        let size = ctx.size();
+        if !self.zoomed_in || size.width / (self.points.len() as f64) < MIN_DOT_SPACING {
+            return;
+        }
+        for point in &self.points {
+            let center = Point::new(point.x * size.width, (point.y + 1.0) * size.height / 2.0);
+            ctx.fill(Circle::new(center, DOT_RADIUS), &Color::WHITE);
+        }
+    }
+}
+
+impl Widget<WaveData> for AudioWave {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut WaveData, _env: &Env) {
+        match event {
+            // Scrolling sets the data, so `update` requests the paint
+            Event::AnimFrame(elapsed_nanos) => {
+                if let Some(live) = &mut self.live {
+                    profiling::new_frame();
+                    live.timing.record_frame();
+                    if !data.frozen {
+                        live.advance(*elapsed_nanos, data);
+                    }
+                    ctx.request_anim_frame();
+                }
+                if let Some(samples) = self.momentum.step(*elapsed_nanos) {
+                    if self.pannable(data) {
+                        data.pan(samples);
+                    }
+                    ctx.request_anim_frame();
+                }
+            }
+            // Trackpads scroll both ways at once, the dominant direction wins
+            Event::Wheel(mouse) if mouse.wheel_delta.x.abs() > mouse.wheel_delta.y.abs() => {
+                if self.pannable(data) {
+                    let samples =
+                        mouse.wheel_delta.x * data.window_len() / ctx.size().width.max(1.0);
+                    data.pan(samples);
+                    self.momentum.push(samples);
+                    ctx.request_anim_frame();
+                }
+                ctx.set_handled();
+            }
+            // Alt locks the zoom to the amplitude axis
+            Event::Wheel(mouse) if mouse.mods.alt() => {
+                ctx.submit_command(ZOOM_AMPLITUDE.with(-mouse.wheel_delta.y / 20.0));
+                ctx.set_handled();
+            }
+            Event::Wheel(mouse) => {
+                data.visible_samples = zoom(
+                    data.visible_samples,
+                    mouse.wheel_delta.y,
+                    data.audio.0.len(),
+                );
+                ctx.set_handled();
+            }
+            Event::Zoom(magnification) => {
+                data.visible_samples =
+                    pinch_zoom(data.visible_samples, *magnification, data.audio.0.len());
+                ctx.set_handled();
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, _data: &WaveData, _: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            if self.live.is_some() {
+                ctx.request_anim_frame();
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &WaveData, data: &WaveData, env: &Env) {
+        // A change of display settings or of the auto-scaled gain redraws everything
+        let rescaled = self.update_auto_gain(data, env);
+        let dirty = if ctx.env_changed() || rescaled {
+            None
+        } else {
+            dirty_rects(old_data, data, ctx.size())
+        };
+        match dirty {
+            Some(rects) => {
+                for rect in rects {
+                    ctx.request_paint_rect(rect);
+                }
+            }
+            _ => ctx.request_paint(),
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &WaveData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    // This is of course super slow due to using CoreGraphics
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &WaveData, env: &Env) {
+        // ctx.clear(Color::BLACK);
+        profile_scope!("AudioWave::paint");
+        let paint_start = Instant::now();
+        if data.visible().is_empty() {
+            return;
+        }
+
+        let size = ctx.size();
+        if let Some(background) = self.look.background {
+            ctx.fill(size.to_rect(), &background);
+        }
+        let scale = self.scale(env);
+        for region in data
+            .voice_activity
+            .iter()
+            .flat_map(|regions| regions.iter())
+        {
+            let to_x = |index: usize| {
+                ((index as f64 - data.window_start()) / data.window_len()).clamp(0.0, 1.0)
+                    * size.width
+            };
+            let (x0, x1) = (to_x(region.start), to_x(region.end));
+            if x1 <= x0 {
+                continue;
+            }
+            ctx.fill(
+                Rect::new(x0, 0.0, x1, size.height),
+                &Color::rgba8(0, 200, 120, 60),
+            );
+        }
+
+        if let Some(grid) = data.beat_grid {
+            Self::paint_beat_grid(ctx, data, grid);
+        }
+
+        let stroke = Stroke::from_env(env, self.look.stroke_width, ctx.scale().x());
+        let resolution = env.try_get(WAVE_RESOLUTION).unwrap_or(1.0);
+        let painted = env.try_get(WAVE_CHANNEL_OVERLAY).unwrap_or(false)
+            && self.paint_channel_overlay(ctx, data, scale)
+            // The raster only draws min/max columns
+            || match &mut self.live {
+                Some(live) if self.look.decimation == Decimation::MinMax => {
+                    live.paint_raster(ctx, data, scale, &stroke, resolution)
+                }
+                _ => false,
+            };
+        if !painted {
+            self.update_points(data, scale, size.width, resolution);
+            self.update_path(size, Interpolation::from_env(env), stroke);
+            match self.look.style {
+                WaveStyle::Line => ctx.stroke(&self.path, &scale.color, stroke.width),
+                WaveStyle::Filled => ctx.fill(&self.path, &scale.color),
+            }
+            self.paint_sample_dots(ctx);
+        }
+        if let Some(live) = &self.live {
+            live.paint_timecode(ctx, data, env);
+            live.timing.record_paint(paint_start.elapsed());
+        }
+        if self.look.axis {
+            Self::paint_axis(ctx, env, scale);
+        }
+    }
+}
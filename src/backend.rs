@@ -0,0 +1,326 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Where the live input comes from. An [`AudioBackend`] opens its source and drives the analyser
+//! from its own callback or thread until the stream it returns is dropped, so the engine and
+//! everything downstream of the analyser's queues run the same whichever one is picked with
+//! `--backend`.
+
+use std::any::Any;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use audio_processor_file::file_io::AudioFileError;
+use audio_processor_file::InMemoryAudioFile;
+use audio_processor_traits::{AudioBuffer, AudioProcessorSettings};
+use druid::{Data, Env, Lens};
+
+use crate::buffer_analyser::BufferAnalyserProcessor;
+use crate::device_formats::{DeviceFormats, StreamRequest};
+use crate::i18n::localize_with;
+
+/// Frames a thread-driven backend hands the analyser at a time
+const BLOCK_FRAMES: usize = 512;
+
+pub trait AudioBackend {
+    /// The rate the source delivers at, known before it's opened
+    fn sample_rate(&self) -> f32;
+
+    /// What the source supports and what it's opened with, for the device panel
+    fn formats(&self) -> DeviceFormats;
+
+    /// Start feeding `processor`, until the returned stream is dropped
+    fn open(&mut self, processor: BufferAnalyserProcessor) -> io::Result<AudioStream>;
+
+    /// Open the named input device from the next [`AudioBackend::open`], the default one when
+    /// unset. Sources without devices ignore it.
+    fn select_input_device(&mut self, _name: Option<String>) {}
+
+    /// How far ahead a buffered source is, polled while it runs
+    fn buffer_status(&self) -> Option<BufferStatus> {
+        None
+    }
+}
+
+/// A buffered source's state and how full it is
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+pub struct BufferStatus {
+    pub state: BufferState,
+    /// Share of what's buffered before playing, 0 to 1
+    pub fill: f64,
+}
+
+#[derive(Clone, Data, PartialEq, Debug)]
+pub enum BufferState {
+    Connecting,
+    Buffering,
+    Playing {
+        seconds: f64,
+    },
+    /// Lost the connection, trying again
+    Reconnecting(String),
+}
+
+impl BufferState {
+    pub fn label(&self, env: &Env) -> String {
+        let (state, detail) = match self {
+            BufferState::Connecting => ("connecting", String::new()),
+            BufferState::Buffering => ("buffering", String::new()),
+            BufferState::Playing { seconds } => ("playing", format!("{:.1}", seconds)),
+            BufferState::Reconnecting(error) => ("reconnecting", error.clone()),
+        };
+        localize_with(
+            "buffer-state",
+            &[("state", state.to_string()), ("detail", detail)],
+            env,
+        )
+    }
+}
+
+/// Keeps a backend's source running until dropped
+pub struct AudioStream {
+    _source: Box<dyn Any>,
+}
+
+impl AudioStream {
+    pub fn new(source: impl Any) -> Self {
+        AudioStream {
+            _source: Box::new(source),
+        }
+    }
+}
+
+/// A thread feeding the analyser, told to stop and joined on drop
+pub struct InputThread {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InputThread {
+    /// Run `input` on its own thread, which should return soon after the flag it's given is set
+    pub fn spawn(input: impl FnOnce(&AtomicBool) + Send + 'static) -> AudioStream {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || input(&stop))
+        };
+        AudioStream::new(InputThread {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for InputThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Input thread panicked");
+            }
+        }
+    }
+}
+
+/// Analyse blocks of interleaved frames filled by `fill`, at `sample_rate` frames a second
+pub fn spawn_paced(
+    mut processor: BufferAnalyserProcessor,
+    sample_rate: f32,
+    channels: usize,
+    mut fill: impl FnMut(&mut [f32]) + Send + 'static,
+) -> AudioStream {
+    processor.set_sample_rate(sample_rate);
+    InputThread::spawn(move |stop| {
+        let mut block = vec![0.0; BLOCK_FRAMES * channels];
+        let block_duration = Duration::from_secs_f64(BLOCK_FRAMES as f64 / sample_rate as f64);
+        let mut next_block = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            fill(&mut block);
+            processor.process_input(&block, channels);
+            next_block += block_duration;
+            if let Some(wait) = next_block.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+    })
+}
+
+/// The formats of a source that only runs one way
+pub fn fixed_formats(name: String, request: StreamRequest) -> DeviceFormats {
+    DeviceFormats {
+        name,
+        sample_rates: Arc::new(vec![request.sample_rate]),
+        channels: Arc::new(vec![request.channels]),
+        buffer_sizes: request.buffer_size.map(|size| (size, size)),
+        sample_formats: Arc::new(vec!["f32".to_string()]),
+        request: Some(request),
+        warnings: Arc::new(Vec::new()),
+    }
+}
+
+/// Silence at a fixed rate, for running without an input device
+pub struct NullBackend {
+    sample_rate: f32,
+}
+
+impl NullBackend {
+    pub fn new(sample_rate: f32) -> Self {
+        NullBackend { sample_rate }
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn formats(&self) -> DeviceFormats {
+        fixed_formats(
+            "Silence".to_string(),
+            StreamRequest {
+                sample_rate: self.sample_rate as u32,
+                channels: 1,
+                buffer_size: Some(BLOCK_FRAMES as u32),
+            },
+        )
+    }
+
+    fn open(&mut self, processor: BufferAnalyserProcessor) -> io::Result<AudioStream> {
+        Ok(spawn_paced(processor, self.sample_rate, 1, |block| {
+            block.fill(0.0)
+        }))
+    }
+}
+
+/// An audio file looped at its own speed, read into memory up front
+pub struct FileBackend {
+    name: String,
+    /// Interleaved
+    samples: Arc<Vec<f32>>,
+    channels: usize,
+    sample_rate: f32,
+}
+
+impl FileBackend {
+    pub fn new(path: &str) -> Result<Self, AudioFileError> {
+        let settings = AudioProcessorSettings::default();
+        let buffer = InMemoryAudioFile::from_path(path)?.read_into_vec_audio_buffer(&settings)?;
+        let channels = buffer.num_channels().max(1);
+        let samples = buffer.frames().flatten().copied().collect();
+        Ok(FileBackend {
+            name: Path::new(path)
+                .file_name()
+                .map_or(path.to_string(), |name| name.to_string_lossy().into_owned()),
+            samples: Arc::new(samples),
+            channels,
+            sample_rate: settings.sample_rate(),
+        })
+    }
+}
+
+impl AudioBackend for FileBackend {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn formats(&self) -> DeviceFormats {
+        fixed_formats(
+            self.name.clone(),
+            StreamRequest {
+                sample_rate: self.sample_rate as u32,
+                channels: self.channels as u16,
+                buffer_size: Some(BLOCK_FRAMES as u32),
+            },
+        )
+    }
+
+    fn open(&mut self, processor: BufferAnalyserProcessor) -> io::Result<AudioStream> {
+        let samples = self.samples.clone();
+        let mut position = 0;
+        Ok(spawn_paced(
+            processor,
+            self.sample_rate,
+            self.channels,
+            move |block| {
+                for sample in block.iter_mut() {
+                    *sample = samples.get(position).copied().unwrap_or(0.0);
+                    position = (position + 1) % samples.len().max(1);
+                }
+            },
+        ))
+    }
+}
+
+/// Starts and stops a backend at runtime. The analysis threads are set up once for the rate the
+/// backend delivers at and keep their queues, each start runs a new processor on them.
+pub struct AudioEngine {
+    /// Never started itself, every run is a copy of it
+    processor: BufferAnalyserProcessor,
+    backend: Box<dyn AudioBackend>,
+    stream: Option<AudioStream>,
+}
+
+impl AudioEngine {
+    /// Stopped until [`AudioEngine::start`]
+    pub fn new(processor: BufferAnalyserProcessor, backend: Box<dyn AudioBackend>) -> Self {
+        AudioEngine {
+            processor,
+            backend,
+            stream: None,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.backend.sample_rate()
+    }
+
+    pub fn formats(&self) -> DeviceFormats {
+        self.backend.formats()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn buffer_status(&self) -> Option<BufferStatus> {
+        self.backend.buffer_status().filter(|_| self.is_running())
+    }
+
+    /// Open the source, from `input_device` if it has devices. Stays stopped if that fails.
+    pub fn start(&mut self, input_device: Option<String>) -> io::Result<()> {
+        if self.is_running() {
+            return Ok(());
+        }
+        self.backend.select_input_device(input_device);
+        self.stream = Some(self.backend.open(self.processor.restarted())?);
+        Ok(())
+    }
+
+    /// Close the source, the analysis threads keep running on an empty queue
+    pub fn stop(&mut self) {
+        self.stream = None;
+    }
+}
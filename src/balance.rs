@@ -0,0 +1,225 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Stereo balance: the energy ratio of the first two input channels over a window, as a dB
+//! difference. The audio thread sums each callback's energy per channel, so the analysis thread
+//! doesn't have to.
+
+use std::collections::VecDeque;
+
+use druid::kurbo::{Line, Rect};
+use druid::widget::prelude::*;
+use druid::{Color, Data, Selector};
+
+use crate::i18n::localize_with;
+
+pub const DRAW_BALANCE: Selector<BalanceData> = Selector::new("event-example.draw_balance");
+
+/// Callback energies the queue holds, a few seconds' worth at small buffer sizes
+pub const BALANCE_QUEUE_LEN: usize = 1024;
+
+pub const MIN_WINDOW_SECONDS: f64 = 0.1;
+pub const MAX_WINDOW_SECONDS: f64 = 10.0;
+pub const DEFAULT_WINDOW_SECONDS: f64 = 1.0;
+
+/// Differences within this read as centered
+const DETENT_DB: f32 = 0.5;
+/// The bar's ends, full left and full right
+const RANGE_DB: f32 = 12.0;
+/// Windows quieter than this have no balance
+const SILENCE_POWER: f64 = 1e-10;
+
+/// Sums of squares of the first two channels over one callback
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ChannelEnergy {
+    pub frames: usize,
+    pub left: f64,
+    pub right: f64,
+    /// The device has a single channel, counted as both
+    pub mono: bool,
+}
+
+impl ChannelEnergy {
+    /// Sum a callback's frames, `right` being `None` on mono devices
+    pub fn measure(frames: impl Iterator<Item = (f32, Option<f32>)>) -> Self {
+        let mut energy = ChannelEnergy::default();
+        for (left, right) in frames {
+            let right = right.unwrap_or_else(|| {
+                energy.mono = true;
+                left
+            });
+            energy.frames += 1;
+            energy.left += (left * left) as f64;
+            energy.right += (right * right) as f64;
+        }
+        energy
+    }
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug, Default)]
+pub struct BalanceData {
+    /// Left level minus right level over the window, `None` while silent
+    pub difference_db: Option<f32>,
+    pub mono: bool,
+}
+
+impl BalanceData {
+    /// The difference with the louder side, or a note in its place
+    pub fn label(&self, env: &Env) -> String {
+        let (side, difference) = match self.difference_db {
+            _ if self.mono => ("mono", 0.0),
+            None => ("silent", 0.0),
+            Some(difference) if difference.abs() < DETENT_DB => ("center", 0.0),
+            Some(difference) if difference > 0.0 => ("left", difference),
+            Some(difference) => ("right", -difference),
+        };
+        localize_with(
+            "balance",
+            &[
+                ("side", side.to_string()),
+                ("difference", format!("{:.1}", difference)),
+            ],
+            env,
+        )
+    }
+}
+
+/// Energy of the callbacks covering the last window
+pub struct BalanceMeter {
+    sample_rate: f32,
+    window_frames: usize,
+    blocks: VecDeque<ChannelEnergy>,
+    total: ChannelEnergy,
+}
+
+impl BalanceMeter {
+    /// `sample_rate` is the device's, callbacks aren't resampled
+    pub fn new(sample_rate: f32) -> Self {
+        let mut meter = BalanceMeter {
+            sample_rate,
+            window_frames: 0,
+            blocks: VecDeque::new(),
+            total: ChannelEnergy::default(),
+        };
+        meter.set_window(DEFAULT_WINDOW_SECONDS);
+        meter
+    }
+
+    pub fn set_window(&mut self, seconds: f64) {
+        self.window_frames = (seconds * self.sample_rate as f64) as usize;
+        self.trim();
+    }
+
+    pub fn push(&mut self, energy: ChannelEnergy) {
+        self.total.frames += energy.frames;
+        self.total.left += energy.left;
+        self.total.right += energy.right;
+        self.total.mono = energy.mono;
+        self.blocks.push_back(energy);
+        self.trim();
+    }
+
+    /// Drop the oldest callbacks while the rest still cover the window
+    fn trim(&mut self) {
+        while let Some(oldest) = self.blocks.front() {
+            if self.total.frames - oldest.frames < self.window_frames {
+                break;
+            }
+            self.total.frames -= oldest.frames;
+            self.total.left -= oldest.left;
+            self.total.right -= oldest.right;
+            self.blocks.pop_front();
+        }
+    }
+
+    pub fn data(&self) -> BalanceData {
+        let frames = self.total.frames.max(1) as f64;
+        let (left, right) = (self.total.left / frames, self.total.right / frames);
+        BalanceData {
+            // Running sums can drift slightly negative
+            difference_db: (left.max(right) > SILENCE_POWER).then(|| {
+                (10.0 * (left.max(SILENCE_POWER) / right.max(SILENCE_POWER)).log10()) as f32
+            }),
+            mono: self.total.mono,
+        }
+    }
+}
+
+/// Horizontal bar from full left to full right with a detent at the center, the marker snapping
+/// to it within [`DETENT_DB`]
+#[derive(Default)]
+pub struct BalanceBar {}
+
+impl Widget<BalanceData> for BalanceBar {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut BalanceData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_BALANCE) {
+                *data = *cmd.get_unchecked(DRAW_BALANCE);
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &BalanceData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &BalanceData, data: &BalanceData, _: &Env) {
+        if !old_data.same(data) {
+            ctx.request_paint()
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &BalanceData, _: &Env) -> Size {
+        bc.constrain(Size::new(200.0, 12.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &BalanceData, _env: &Env) {
+        let size = ctx.size();
+        let center = size.width / 2.0;
+        ctx.fill(size.to_rect(), &Color::grey(0.15));
+        ctx.stroke(
+            Line::new((center, 0.0), (center, size.height)),
+            &Color::grey(0.5),
+            1.0,
+        );
+
+        let difference = match data.difference_db {
+            Some(difference) if !data.mono => difference,
+            _ => return,
+        };
+        let (x_coord, color) = if difference.abs() < DETENT_DB {
+            (center, Color::rgb8(0, 200, 80))
+        } else {
+            let amount = (difference / RANGE_DB).clamp(-1.0, 1.0) as f64;
+            (center - amount * center, Color::rgb8(255, 200, 0))
+        };
+        ctx.fill(
+            Rect::new(x_coord - 2.0, 0.0, x_coord + 2.0, size.height),
+            &color,
+        );
+    }
+}
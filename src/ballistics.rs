@@ -0,0 +1,215 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Standard meter ballistics shared by the level displays: digital sample peak, the two PPM
+//! types, VU, and the sound level meter's fast and slow time weightings.
+//!
+//! Each is approximated by a one-pole smoother with separate rise and fall times, applied to
+//! the rectified signal for the peak and VU meters and to its square for fast and slow.
+
+use druid::kurbo::{Line, Rect};
+use druid::widget::prelude::*;
+use druid::{Color, Data, LocalizedString, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::display_agc::METER_DISPLAY_GAIN;
+use crate::k_system::MeterScale;
+use crate::units::AmplitudeScale;
+
+/// Level of the bar meter in dBFS, with its ballistics applied
+pub const DRAW_LEVEL: Selector<f32> = Selector::new("event-example.draw_level");
+
+/// Makes a VU meter's rectified average read a sine's RMS
+const VU_SINE_SCALE: f32 = std::f32::consts::PI / (2.0 * std::f32::consts::SQRT_2);
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ballistics {
+    /// Instant rise, falls 20 dB in 1.7 s
+    SamplePeak,
+    /// DIN 45406: 10 ms integration, falls 20 dB in 1.5 s
+    PpmType1,
+    /// BBC: 10 ms integration to -2 dB, falls 24 dB in 2.8 s
+    PpmType2,
+    /// 300 ms to 99% either way
+    Vu,
+    /// IEC 61672 fast, 125 ms on the mean square
+    Fast,
+    /// IEC 61672 slow, 1 s on the mean square
+    Slow,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Detector {
+    Peak,
+    Average,
+    MeanSquare,
+}
+
+impl Ballistics {
+    pub const ALL: [Ballistics; 6] = [
+        Ballistics::SamplePeak,
+        Ballistics::PpmType1,
+        Ballistics::PpmType2,
+        Ballistics::Vu,
+        Ballistics::Fast,
+        Ballistics::Slow,
+    ];
+
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            Ballistics::SamplePeak => "ballistics-peak",
+            Ballistics::PpmType1 => "ballistics-ppm-1",
+            Ballistics::PpmType2 => "ballistics-ppm-2",
+            Ballistics::Vu => "ballistics-vu",
+            Ballistics::Fast => "ballistics-fast",
+            Ballistics::Slow => "ballistics-slow",
+        })
+    }
+
+    /// Detector and the rise and fall time constants in seconds, 0 rising instantly
+    fn params(&self) -> (Detector, f32, f32) {
+        // A fall of `db` in `seconds` on an exponential decay
+        let fall = |db: f32, seconds: f32| seconds / (db / 20.0 * std::f32::consts::LN_10);
+        match self {
+            Ballistics::SamplePeak => (Detector::Peak, 0.0, fall(20.0, 1.7)),
+            // A 10 ms burst reads within 1 dB
+            Ballistics::PpmType1 => (Detector::Peak, 0.0045, fall(20.0, 1.5)),
+            Ballistics::PpmType2 => (Detector::Peak, 0.0062, fall(24.0, 2.8)),
+            Ballistics::Vu => (Detector::Average, 0.065, 0.065),
+            Ballistics::Fast => (Detector::MeanSquare, 0.125, 0.125),
+            Ballistics::Slow => (Detector::MeanSquare, 1.0, 1.0),
+        }
+    }
+}
+
+fn coefficient(seconds: f32, sample_rate: f32) -> f32 {
+    if seconds > 0.0 {
+        (-1.0 / (seconds * sample_rate)).exp()
+    } else {
+        0.0
+    }
+}
+
+pub struct BallisticMeter {
+    sample_rate: f32,
+    detector: Detector,
+    rise: f32,
+    fall: f32,
+    state: f32,
+}
+
+impl BallisticMeter {
+    pub fn new(ballistics: Ballistics, sample_rate: f32) -> Self {
+        let mut meter = BallisticMeter {
+            sample_rate,
+            detector: Detector::Peak,
+            rise: 0.0,
+            fall: 0.0,
+            state: 0.0,
+        };
+        meter.set_ballistics(ballistics);
+        meter
+    }
+
+    /// Starts over from silence when the detector changes, since the state means something else
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        let (detector, rise, fall) = ballistics.params();
+        if detector != self.detector {
+            self.state = 0.0;
+        }
+        self.detector = detector;
+        self.rise = coefficient(rise, self.sample_rate);
+        self.fall = coefficient(fall, self.sample_rate);
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        let input = match self.detector {
+            Detector::Peak => sample.abs(),
+            Detector::Average => sample.abs() * VU_SINE_SCALE,
+            Detector::MeanSquare => sample * sample,
+        };
+        let coefficient = if input > self.state {
+            self.rise
+        } else {
+            self.fall
+        };
+        self.state = input + coefficient * (self.state - input);
+    }
+
+    pub fn level_db(&self) -> f32 {
+        match self.detector {
+            Detector::MeanSquare => 10.0 * self.state.log10(),
+            _ => 20.0 * self.state.log10(),
+        }
+    }
+}
+
+/// Horizontal bar from the display floor to full scale, colored by the [`MeterScale`] in the env
+/// with a tick at the scale's 0. The display AGC's gain shifts it up.
+#[derive(Default)]
+pub struct LevelBar {}
+
+impl Widget<f32> for LevelBar {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut f32, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_LEVEL) {
+                *data = *cmd.get_unchecked(DRAW_LEVEL);
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &f32, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &f32, data: &f32, _: &Env) {
+        if !old_data.same(data) || ctx.env_changed() {
+            ctx.request_paint()
+        }
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &f32, _: &Env) -> Size {
+        bc.constrain(Size::new(200.0, 12.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &f32, env: &Env) {
+        let size = ctx.size();
+        let floor_db = AmplitudeScale::from_env(env).floor_db;
+        let scale = MeterScale::from_env(env);
+        // Colors and the scale's 0 stay at their real levels, they just move along with the bar
+        let display_gain = env.try_get(METER_DISPLAY_GAIN).unwrap_or(0.0);
+        let x_coord =
+            |db: f32| (1.0 - (db as f64 + display_gain) / floor_db).clamp(0.0, 1.0) * size.width;
+        ctx.fill(size.to_rect(), &Color::grey(0.15));
+        ctx.fill(
+            Rect::new(0.0, 0.0, x_coord(*data), size.height),
+            &scale.color(*data),
+        );
+        if scale != MeterScale::Dbfs {
+            let zero = x_coord(scale.reference_db());
+            ctx.stroke(
+                Line::new((zero, 0.0), (zero, size.height)),
+                &Color::WHITE,
+                1.0,
+            );
+        }
+    }
+}
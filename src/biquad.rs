@@ -0,0 +1,113 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Direct form II transposed biquad, shared by the weighting, band, monitor and notch filters.
+
+#[derive(Clone, Copy, Default)]
+pub struct Biquad {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+    pub z1: f32,
+    pub z2: f32,
+}
+
+impl Biquad {
+    /// Band-pass with 0dB gain at the center frequency (RBJ cookbook)
+    pub fn band_pass(center_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * center_hz as f64 / sample_rate as f64;
+        let alpha = omega.sin() / (2.0 * q as f64);
+        let a0 = 1.0 + alpha;
+        Biquad {
+            b0: (alpha / a0) as f32,
+            b1: 0.0,
+            b2: (-alpha / a0) as f32,
+            a1: (-2.0 * omega.cos() / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+            ..Default::default()
+        }
+    }
+
+    /// Second-order low-pass, Butterworth at a Q of 1/sqrt(2) (RBJ cookbook)
+    pub fn low_pass(cutoff_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * cutoff_hz as f64 / sample_rate as f64;
+        let alpha = omega.sin() / (2.0 * q as f64);
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - omega.cos()) / a0;
+        Biquad {
+            b0: (b1 / 2.0) as f32,
+            b1: b1 as f32,
+            b2: (b1 / 2.0) as f32,
+            a1: (-2.0 * omega.cos() / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+            ..Default::default()
+        }
+    }
+
+    /// Second-order high-pass, Butterworth at a Q of 1/sqrt(2) (RBJ cookbook)
+    pub fn high_pass(cutoff_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * cutoff_hz as f64 / sample_rate as f64;
+        let alpha = omega.sin() / (2.0 * q as f64);
+        let a0 = 1.0 + alpha;
+        let b1 = -(1.0 + omega.cos()) / a0;
+        Biquad {
+            b0: (-b1 / 2.0) as f32,
+            b1: b1 as f32,
+            b2: (-b1 / 2.0) as f32,
+            a1: (-2.0 * omega.cos() / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+            ..Default::default()
+        }
+    }
+
+    /// Notch removing a narrow band around the center frequency (RBJ cookbook)
+    pub fn notch(center_hz: f32, q: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * center_hz as f64 / sample_rate as f64;
+        let alpha = omega.sin() / (2.0 * q as f64);
+        let a0 = 1.0 + alpha;
+        Biquad {
+            b0: (1.0 / a0) as f32,
+            b1: (-2.0 * omega.cos() / a0) as f32,
+            b2: (1.0 / a0) as f32,
+            a1: (-2.0 * omega.cos() / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+            ..Default::default()
+        }
+    }
+
+    /// Passes the input through unchanged
+    pub fn identity() -> Self {
+        Biquad {
+            b0: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = self.b1 * input - self.a1 * output + self.z2;
+        self.z2 = self.b2 * input - self.a2 * output;
+        output
+    }
+}
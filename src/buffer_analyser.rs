@@ -20,31 +20,448 @@
 // LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
 use atomic_queue::Queue;
-use audio_processor_traits::{AudioBuffer, AudioContext, AudioProcessor, AudioProcessorSettings};
+use audio_processor_traits::{
+    AudioBuffer, AudioContext, AudioProcessor, AudioProcessorSettings, InterleavedAudioBuffer,
+    MidiEventHandler, MidiMessageLike,
+};
 use basedrop::{Handle, Shared};
 
+use crate::balance::{ChannelEnergy, BALANCE_QUEUE_LEN};
+use crate::clock::{BatchStamp, STAMP_QUEUE_LEN};
+use crate::gain_reduction::{GainReductionReading, GAIN_REDUCTION_QUEUE_LEN};
+use crate::latency::{LatencyMeasurement, LatencyProbe, LATENCY_QUEUE_LEN};
+use crate::midi::{MidiEvent, MidiMessage, MIDI_QUEUE_LEN};
+use crate::monitor::{MonitorControl, MonitorFilter};
+use crate::profiling::profile_scope;
+
+/// Number of samples kept for display
+pub const HISTORY_LEN: usize = 5 * 4410;
+
+/// Most samples pushed onto an analyser queue at once
+pub const CHUNK_LEN: usize = 256;
+/// Analyser queue capacity in chunks. Callbacks push partly filled chunks, so this is sized for
+/// small buffers.
+pub const QUEUE_CHUNKS: usize = 1024;
+
+/// A run of consecutive frames, pushed as one queue element to keep the audio thread's atomic
+/// operations to one per chunk
+#[derive(Clone, Copy)]
+pub struct SampleChunk {
+    len: usize,
+    channels: usize,
+    samples: [f32; CHUNK_LEN],
+}
+
+impl Default for SampleChunk {
+    fn default() -> Self {
+        SampleChunk {
+            len: 0,
+            channels: 1,
+            samples: [0.0; CHUNK_LEN],
+        }
+    }
+}
+
+impl SampleChunk {
+    /// Interleaved, [`SampleChunk::channels`] samples a frame
+    pub fn samples(&self) -> &[f32] {
+        &self.samples[..self.len]
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.len / self.channels
+    }
+}
+
+/// Producer side of the analyser queues, collects samples into chunks
+#[derive(Default)]
+pub struct ChunkWriter {
+    chunk: SampleChunk,
+}
+
+impl ChunkWriter {
+    /// Add a sample of a single channel stream, returning the chunk once it's full
+    pub fn write(&mut self, sample: f32) -> Option<SampleChunk> {
+        self.write_frame(&[sample])
+    }
+
+    /// Add one sample per channel, returning the chunk once there's no room for another frame.
+    /// A chunk has as many channels as its first frame, later frames are cut or padded to it.
+    pub fn write_frame(&mut self, frame: &[f32]) -> Option<SampleChunk> {
+        if self.chunk.len == 0 {
+            self.chunk.channels = frame.len().clamp(1, CHUNK_LEN);
+        }
+        let channels = self.chunk.channels;
+        let start = self.chunk.len;
+        for (channel, sample) in self.chunk.samples[start..start + channels]
+            .iter_mut()
+            .enumerate()
+        {
+            *sample = frame.get(channel).copied().unwrap_or(0.0);
+        }
+        self.chunk.len += channels;
+        if self.chunk.len + channels > CHUNK_LEN {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Take whatever has been written so far, called at the end of every callback so chunks don't
+    /// add latency
+    pub fn flush(&mut self) -> Option<SampleChunk> {
+        if self.chunk.len == 0 {
+            return None;
+        }
+        let chunk = self.chunk;
+        self.chunk.len = 0;
+        Some(chunk)
+    }
+}
+
+/// Device sample formats the analyser accepts, scaled to -1..1
+pub trait InputSample: Copy {
+    fn to_f32(self) -> f32;
+}
+
+impl InputSample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl InputSample for i16 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 32768.0
+    }
+}
+
+impl InputSample for u16 {
+    /// Offset binary, silence is the midpoint
+    fn to_f32(self) -> f32 {
+        (self as f32 - 32768.0) / 32768.0
+    }
+}
+
+impl InputSample for f64 {
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+/// A packed 24-bit sample, sign-extended into an `i32`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct I24(i32);
+
+impl I24 {
+    pub fn from_be_bytes(bytes: [u8; 3]) -> Self {
+        I24(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 8)
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 3]) -> Self {
+        I24::from_be_bytes([bytes[2], bytes[1], bytes[0]])
+    }
+}
+
+impl InputSample for I24 {
+    fn to_f32(self) -> f32 {
+        self.0 as f32 / 8388608.0
+    }
+}
+
+/// Written by the audio thread, read by the stats panel
+pub struct ProcessorCounters {
+    /// Frames in the most recent callback
+    pub buffer_size: AtomicUsize,
+    /// Frames that didn't fit in a subscriber's queue because it fell behind, summed over them
+    pub dropped_frames: AtomicUsize,
+    /// Frames received so far, which a restarted engine carries on from
+    pub position: AtomicU64,
+    /// Nanoseconds spent in callbacks so far, for the DSP load
+    pub callback_nanos: AtomicU64,
+    /// One per callback, for measuring clock drift
+    pub stamps: Queue<BatchStamp>,
+    /// MIDI input, stamped with the position of the callback it arrived with
+    pub midi_events: Queue<MidiEvent>,
+    /// Energy of the first two channels, one per callback
+    pub balance: Queue<ChannelEnergy>,
+    /// Latency calibration results, one per click
+    pub latency: Queue<LatencyMeasurement>,
+    /// What the monitor played, a frame for every input frame, for comparing its spectrum with
+    /// the input's
+    pub processed: Queue<SampleChunk>,
+    /// Whether the monitor played anything in the most recent callback, when the processed
+    /// stream differs from silence
+    pub monitoring: AtomicBool,
+    /// The talkback limiter's, one per callback it ran in
+    pub gain_reduction: Queue<GainReductionReading>,
+}
+
+impl Default for ProcessorCounters {
+    fn default() -> Self {
+        ProcessorCounters {
+            buffer_size: AtomicUsize::new(0),
+            dropped_frames: AtomicUsize::new(0),
+            position: AtomicU64::new(0),
+            callback_nanos: AtomicU64::new(0),
+            stamps: Queue::new(STAMP_QUEUE_LEN),
+            midi_events: Queue::new(MIDI_QUEUE_LEN),
+            balance: Queue::new(BALANCE_QUEUE_LEN),
+            latency: Queue::new(LATENCY_QUEUE_LEN),
+            processed: Queue::new(QUEUE_CHUNKS),
+            monitoring: AtomicBool::new(false),
+            gain_reduction: Queue::new(GAIN_REDUCTION_QUEUE_LEN),
+        }
+    }
+}
+
+/// Captures the input's frames and fans them out to every subscribed consumer, playing the first
+/// channel back through the [`MonitorFilter`] while monitoring. What the monitor plays is
+/// published too, as the processed stream.
 pub struct BufferAnalyserProcessor {
-    buffer: Shared<Queue<f32>>,
+    handle: Handle,
+    /// Each consumer gets its own queue, so none of them takes samples from another
+    subscribers: Vec<Shared<Queue<SampleChunk>>>,
+    writer: ChunkWriter,
+    processed_writer: ChunkWriter,
+    counters: Shared<ProcessorCounters>,
+    /// Frames received so far
+    position: u64,
+    start: Instant,
+    monitor: MonitorFilter,
+    latency: LatencyProbe,
+    /// Input converted to f32 for [`BufferAnalyserProcessor::process_input`], then what it
+    /// plays, sized by the largest callback so far
+    scratch: Vec<f32>,
+    /// Where `process_input` sends what it plays, for streams opened without an output
+    playback: Option<Shared<Queue<SampleChunk>>>,
+    playback_writer: ChunkWriter,
 }
 
 impl BufferAnalyserProcessor {
-    pub fn new(handle: &Handle) -> Self {
+    pub fn new(handle: &Handle, monitor: Arc<MonitorControl>) -> Self {
         BufferAnalyserProcessor {
-            buffer: Shared::new(handle, Queue::new((5. * 4410.0) as usize)),
+            handle: handle.clone(),
+            subscribers: Vec::new(),
+            writer: ChunkWriter::default(),
+            processed_writer: ChunkWriter::default(),
+            counters: Shared::new(handle, ProcessorCounters::default()),
+            position: 0,
+            start: Instant::now(),
+            monitor: MonitorFilter::new(monitor),
+            latency: LatencyProbe::default(),
+            scratch: Vec::new(),
+            playback: None,
+            playback_writer: ChunkWriter::default(),
         }
     }
 
-    pub fn queue(&self) -> Shared<Queue<f32>> {
-        self.buffer.clone()
+    /// Add a consumer of the stream. The list is fixed once the processor is started, so the
+    /// audio thread never allocates.
+    pub fn subscribe(&mut self) -> Shared<Queue<SampleChunk>> {
+        let queue = Shared::new(&self.handle, Queue::new(QUEUE_CHUNKS));
+        self.subscribers.push(queue.clone());
+        queue
+    }
+
+    /// Push a chunk to every subscriber, returning how many frames didn't fit
+    fn publish(&self, chunk: SampleChunk) -> usize {
+        self.subscribers
+            .iter()
+            .filter(|queue| !queue.push(chunk))
+            .map(|_| chunk.num_frames())
+            .sum()
+    }
+
+    pub fn counters(&self) -> Shared<ProcessorCounters> {
+        self.counters.clone()
+    }
+
+    /// A processor for the next run of the engine, publishing to the same subscribers and
+    /// counters from the stream position the last run stopped at
+    pub fn restarted(&self) -> Self {
+        BufferAnalyserProcessor {
+            handle: self.handle.clone(),
+            subscribers: self.subscribers.clone(),
+            writer: ChunkWriter::default(),
+            processed_writer: ChunkWriter::default(),
+            counters: self.counters.clone(),
+            position: self.counters.position.load(Ordering::Relaxed),
+            start: self.start,
+            monitor: self.monitor.restarted(),
+            latency: LatencyProbe::default(),
+            scratch: Vec::new(),
+            playback: None,
+            playback_writer: ChunkWriter::default(),
+        }
+    }
+
+    /// The rate the monitor and latency probe run at, what the standalone host's `prepare`
+    /// would set for streams that don't go through it
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.monitor.set_sample_rate(sample_rate);
+        self.latency.set_sample_rate(sample_rate);
+    }
+
+    /// A queue of what [`BufferAnalyserProcessor::process_input`] plays, one sample a frame, for
+    /// an output stream to take
+    pub fn play_through(&mut self) -> Shared<Queue<SampleChunk>> {
+        let queue = Shared::new(&self.handle, Queue::new(QUEUE_CHUNKS));
+        self.playback = Some(queue.clone());
+        queue
+    }
+
+    /// Analyse an interleaved input callback in the device's own sample format, for streams that
+    /// don't go through the f32 standalone host. It's converted and processed as the standalone
+    /// host's callbacks are, with what it plays queued for [`BufferAnalyserProcessor::play_through`].
+    pub fn process_input<S: InputSample>(&mut self, data: &[S], num_channels: usize) {
+        let num_channels = num_channels.max(1);
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        // Only allocates when a callback is larger than any before it
+        scratch.extend(data.iter().map(|sample| sample.to_f32()));
+        self.process_frames(&mut InterleavedAudioBuffer::new(num_channels, &mut scratch));
+        if let Some(playback) = &self.playback {
+            for frame in scratch.chunks(num_channels) {
+                if let Some(chunk) = self.playback_writer.write(frame[0]) {
+                    // The output glitches rather than falling further behind
+                    let _ = playback.push(chunk);
+                }
+            }
+            if let Some(chunk) = self.playback_writer.flush() {
+                let _ = playback.push(chunk);
+            }
+        }
+        self.scratch = scratch;
+    }
+
+    /// Capture a callback's frames and replace them with what the monitor plays
+    fn process_frames<BufferType: AudioBuffer<SampleType = f32>>(&mut self, data: &mut BufferType) {
+        let started = Instant::now();
+        let num_frames = data.num_samples();
+        let start = self.position;
+        if let Some(measurement) = self
+            .latency
+            .listen(data.frames().map(|frame| frame[0]), start)
+        {
+            let _ = self.counters.latency.push(measurement);
+        }
+        self.measure_balance(ChannelEnergy::measure(
+            data.frames().map(|frame| (frame[0], frame.get(1).copied())),
+        ));
+        self.capture(data.frames(), num_frames);
+        // Silent unless the monitor is listening, a band is soloed or the calibration click plays
+        self.monitor.update();
+        if self.monitor.take_click() {
+            let input_peak = data
+                .frames()
+                .map(|frame| frame[0].abs())
+                .fold(0.0, f32::max);
+            self.latency.play(start, input_peak);
+        }
+        let mut monitoring = false;
+        for frame in data.frames_mut() {
+            let monitored = self.monitor.process(frame[0]);
+            monitoring |= monitored.is_some();
+            let monitored = monitored.unwrap_or(0.0);
+            if let Some(chunk) = self.processed_writer.write(monitored) {
+                let _ = self.counters.processed.push(chunk);
+            }
+            let output = monitored + self.latency.click_sample();
+            for sample in frame {
+                *sample = output;
+            }
+        }
+        if let Some(chunk) = self.processed_writer.flush() {
+            let _ = self.counters.processed.push(chunk);
+        }
+        self.counters
+            .monitoring
+            .store(monitoring, Ordering::Relaxed);
+        if let Some(reduction_db) = self.monitor.take_gain_reduction() {
+            // The strip just misses the callback when the analysis thread is behind
+            let _ = self.counters.gain_reduction.push(GainReductionReading {
+                position: start,
+                frames: num_frames as u64,
+                reduction_db,
+            });
+        }
+        self.record_callback_time(started);
+    }
+
+    fn record_callback_time(&self, started: Instant) {
+        self.counters
+            .callback_nanos
+            .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn measure_balance(&self, energy: ChannelEnergy) {
+        // The meter just misses the callback when the analysis thread is behind
+        let _ = self.counters.balance.push(energy);
+    }
+
+    /// Publish a callback's frames, every channel of them
+    fn capture<'a>(&mut self, frames: impl Iterator<Item = &'a [f32]>, num_frames: usize) {
+        profile_scope!("audio_callback");
+        self.counters
+            .buffer_size
+            .store(num_frames, Ordering::Relaxed);
+        let mut dropped = 0;
+        for frame in frames {
+            if let Some(chunk) = self.writer.write_frame(frame) {
+                dropped += self.publish(chunk);
+            }
+        }
+        if let Some(chunk) = self.writer.flush() {
+            dropped += self.publish(chunk);
+        }
+        if dropped > 0 {
+            self.counters
+                .dropped_frames
+                .fetch_add(dropped, Ordering::Relaxed);
+        }
+        self.position += num_frames as u64;
+        self.counters
+            .position
+            .store(self.position, Ordering::Relaxed);
+        // A full queue only loses precision, the fit works from whichever stamps arrive
+        let _ = self.counters.stamps.push(BatchStamp {
+            position: self.position,
+            time_micros: self.start.elapsed().as_micros() as u64,
+        });
+    }
+}
+
+impl MidiEventHandler for BufferAnalyserProcessor {
+    /// Called just before the callback the messages arrived during, so they're stamped with the
+    /// position of its first frame
+    fn process_midi_events<Message: MidiMessageLike>(&mut self, midi_messages: &[Message]) {
+        for message in midi_messages {
+            if let Some(message) = message.bytes().and_then(MidiMessage::parse) {
+                // The roll just misses the event when the analysis thread is behind
+                let _ = self.counters.midi_events.push(MidiEvent {
+                    position: self.position,
+                    message,
+                });
+            }
+        }
     }
 }
 
 impl AudioProcessor for BufferAnalyserProcessor {
     type SampleType = f32;
 
-    fn prepare(&mut self, _context: &mut AudioContext, _settings: AudioProcessorSettings) {
-        // assert_eq!(settings.sample_rate(), 44100.0);
+    fn prepare(&mut self, _context: &mut AudioContext, settings: AudioProcessorSettings) {
+        self.set_sample_rate(settings.sample_rate());
     }
 
     fn process<BufferType: AudioBuffer<SampleType = Self::SampleType>>(
@@ -52,11 +469,115 @@ impl AudioProcessor for BufferAnalyserProcessor {
         _context: &mut AudioContext,
         data: &mut BufferType,
     ) {
-        for frame in data.frames_mut() {
-            self.buffer.push(frame[0]);
-            for sample in frame {
-                *sample = 0.0;
+        self.process_frames(data);
+    }
+}
+
+/// Consumer side of an analyser queue, writes popped samples into a ring buffer of the last
+/// [`HISTORY_LEN`] samples.
+pub struct SampleHistory {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Default for SampleHistory {
+    fn default() -> Self {
+        SampleHistory {
+            buffer: vec![0.0; HISTORY_LEN],
+            position: 0,
+        }
+    }
+}
+
+impl SampleHistory {
+    pub fn drain(&mut self, queue: &Queue<SampleChunk>) {
+        while let Some(chunk) = queue.pop() {
+            for sample in chunk.samples() {
+                self.push(*sample);
             }
         }
     }
+
+    pub fn push(&mut self, sample: f32) {
+        let buffer_size = self.buffer.len();
+        self.buffer[self.position % buffer_size] = sample;
+        self.position += 1;
+    }
+
+    /// Samples pushed so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn buffer(&self) -> &[f32] {
+        &self.buffer
+    }
+
+    /// The last `len` samples pushed, oldest first
+    pub fn latest(&self, len: usize) -> Vec<f32> {
+        let buffer_size = self.buffer.len();
+        let len = len.min(buffer_size);
+        (self.position + buffer_size - len..self.position + buffer_size)
+            .map(|index| self.buffer[index % buffer_size])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use basedrop::Collector;
+
+    use super::*;
+
+    #[test]
+    fn scales_integer_and_f64_samples() {
+        assert_eq!(i16::MIN.to_f32(), -1.0);
+        assert_eq!(32768_u16.to_f32(), 0.0);
+        assert_eq!(0_u16.to_f32(), -1.0);
+        assert_eq!(I24::from_be_bytes([0x80, 0, 0]).to_f32(), -1.0);
+        assert_eq!(I24::from_be_bytes([0x40, 0, 0]).to_f32(), 0.5);
+        assert_eq!(I24::from_le_bytes([0xff, 0xff, 0xff]), I24(-1));
+        assert!((I24::from_be_bytes([0x7f, 0xff, 0xff]).to_f32() - 1.0).abs() < 1e-6);
+        assert_eq!((-0.25_f64).to_f32(), -0.25);
+    }
+
+    #[test]
+    fn chunks_whole_frames() {
+        let mut writer = ChunkWriter::default();
+        let frame = [0.5, -0.5, 0.25];
+        let chunk = (0..CHUNK_LEN)
+            .find_map(|_| writer.write_frame(&frame))
+            .unwrap();
+        assert_eq!(chunk.channels(), 3);
+        assert_eq!(chunk.num_frames(), CHUNK_LEN / 3);
+        assert!(chunk.samples().chunks(3).all(|written| written == frame));
+        // Padded to the chunk's channels
+        writer.write_frame(&[1.0]);
+        assert_eq!(writer.flush().unwrap().samples(), &[1.0, 0.0, 0.0]);
+    }
+
+    /// Integer input is monitored and published like the standalone host's f32 callbacks
+    #[test]
+    fn monitors_integer_input() {
+        let collector = Collector::new();
+        let control = Arc::new(MonitorControl::default());
+        let mut processor = BufferAnalyserProcessor::new(&collector.handle(), control.clone());
+        let input = processor.subscribe();
+        let playback = processor.play_through();
+        let counters = processor.counters();
+        control.set_listen(true);
+        // Stereo, the second channel is ignored by the monitor
+        let data: Vec<i16> = (0..64).flat_map(|index| [index * 256, i16::MIN]).collect();
+        processor.process_input(&data, 2);
+
+        let expected: Vec<f32> = (0..64).map(|index| index as f32 / 128.0).collect();
+        let published: Vec<f32> = expected.iter().flat_map(|&left| [left, -1.0]).collect();
+        let chunk = input.pop().unwrap();
+        assert_eq!(chunk.channels(), 2);
+        assert_eq!(chunk.samples(), &published[..]);
+        assert_eq!(counters.processed.pop().unwrap().samples(), &expected[..]);
+        assert_eq!(playback.pop().unwrap().samples(), &expected[..]);
+        assert!(counters.monitoring.load(Ordering::Relaxed));
+        assert_eq!(counters.position.load(Ordering::Relaxed), 64);
+    }
 }
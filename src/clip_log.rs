@@ -0,0 +1,248 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Clipping and true-peak-over detection, grouped into timestamped events so overs that happened
+//! while nobody was watching are still visible afterwards. Dropouts from
+//! [`crate::dropouts`] are logged alongside.
+
+use std::sync::Arc;
+
+use druid::widget::prelude::*;
+use druid::widget::{Controller, Label, List};
+use druid::{Data, LocalizedString, Selector, WidgetExt};
+
+use crate::i18n::{localize, localize_with};
+use crate::report::MAX_TRUE_PEAK_DBTP;
+use crate::units::AmplitudeScale;
+
+pub const DRAW_CLIP_LOG: Selector<Arc<Vec<ClipEvent>>> =
+    Selector::new("event-example.draw_clip_log");
+
+/// Samples at or above this amplitude are considered clipped
+const CLIP_LEVEL: f32 = 0.999;
+/// An event ends once the signal stays under the threshold for this long
+const HOLD_MS: f32 = 10.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub enum ClipKind {
+    /// At least one sample reached full scale
+    Clip,
+    /// Only the reconstructed signal went over the true-peak limit
+    TruePeakOver,
+    /// A gap of digital silence in the middle of the signal
+    Dropout,
+    /// The same sample value repeated, as from a stalled buffer
+    HeldSamples,
+}
+
+impl ClipKind {
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            ClipKind::Clip => "clip-kind-clip",
+            ClipKind::TruePeakOver => "clip-kind-true-peak-over",
+            ClipKind::Dropout => "clip-kind-dropout",
+            ClipKind::HeldSamples => "clip-kind-held-samples",
+        })
+    }
+
+    /// English name for the CSV export and snapshot file names
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClipKind::Clip => "Clip",
+            ClipKind::TruePeakOver => "True-peak over",
+            ClipKind::Dropout => "Dropout",
+            ClipKind::HeldSamples => "Held samples",
+        }
+    }
+
+    /// Reported by the dropout detector, whose level is the one going into the event
+    pub fn is_dropout(&self) -> bool {
+        matches!(self, ClipKind::Dropout | ClipKind::HeldSamples)
+    }
+}
+
+#[derive(Clone, Data, Debug)]
+pub struct ClipEvent {
+    pub kind: ClipKind,
+    /// Counted from 0. Dropouts are only looked for in the first channel.
+    pub channel: usize,
+    /// Seconds since the stream started
+    pub time: f32,
+    /// Highest true-peak level during the event, in dBTP, for dropouts the RMS level going into
+    /// it in dBFS
+    pub level_db: f32,
+    pub duration_ms: f32,
+}
+
+/// Format a number of seconds as `HH:MM:SS.mmm`
+pub fn format_timestamp(seconds: f32) -> String {
+    let milliseconds = (seconds.max(0.0) * 1000.0) as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+struct OpenEvent {
+    start: u64,
+    last_over: u64,
+    peak: f32,
+    clipped: bool,
+}
+
+/// Finds clips and true-peak overs in each channel separately
+pub struct ClipDetector {
+    sample_rate: f32,
+    threshold: f32,
+    hold_samples: u64,
+    position: u64,
+    /// One per channel of the frame pushed last
+    current: Vec<Option<OpenEvent>>,
+}
+
+impl ClipDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        ClipDetector {
+            sample_rate,
+            threshold: 10.0_f32.powf(MAX_TRUE_PEAK_DBTP / 20.0),
+            hold_samples: (sample_rate * HOLD_MS / 1000.0) as u64,
+            position: 0,
+            current: Vec::new(),
+        }
+    }
+
+    /// Frames pushed so far, what event times are counted from
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Push a frame and each channel's true-peak amplitude in it, returning the events that
+    /// finished with it, at most one per channel.
+    pub fn process_frame(&mut self, frame: &[f32], true_peaks: &[f32]) -> Vec<ClipEvent> {
+        let position = self.position;
+        self.position += 1;
+        // Events open in channels a new device doesn't have are dropped
+        self.current.resize_with(frame.len(), || None);
+
+        let mut finished = Vec::new();
+        for (channel, ((sample, true_peak), current)) in frame
+            .iter()
+            .zip(true_peaks)
+            .zip(&mut self.current)
+            .enumerate()
+        {
+            if *true_peak >= self.threshold {
+                let event = current.get_or_insert(OpenEvent {
+                    start: position,
+                    last_over: position,
+                    peak: 0.0,
+                    clipped: false,
+                });
+                event.last_over = position;
+                event.peak = event.peak.max(*true_peak);
+                event.clipped |= sample.abs() >= CLIP_LEVEL;
+                continue;
+            }
+
+            match current.as_ref() {
+                Some(event) if position - event.last_over > self.hold_samples => {
+                    let event = current.take().unwrap();
+                    finished.push(ClipEvent {
+                        kind: if event.clipped {
+                            ClipKind::Clip
+                        } else {
+                            ClipKind::TruePeakOver
+                        },
+                        channel,
+                        time: event.start as f32 / self.sample_rate,
+                        level_db: 20.0 * event.peak.log10(),
+                        duration_ms: (event.last_over - event.start + 1) as f32 / self.sample_rate
+                            * 1000.0,
+                    });
+                }
+                _ => {}
+            }
+        }
+        finished
+    }
+}
+
+pub fn to_csv(events: &[ClipEvent]) -> String {
+    let mut csv = String::from("time,kind,channel,level_db,duration_ms\n");
+    for event in events {
+        csv += &format!(
+            "{},{},{},{:.2},{:.2}\n",
+            format_timestamp(event.time),
+            event.kind.name(),
+            event.channel + 1,
+            event.level_db,
+            event.duration_ms
+        );
+    }
+    csv
+}
+
+/// Scrollable list of clip events, newest last
+pub fn clip_log_list() -> impl Widget<Arc<Vec<ClipEvent>>> {
+    List::new(|| {
+        Label::dynamic(|event: &ClipEvent, env| {
+            let level = AmplitudeScale::from_env(env).format_db(event.level_db);
+            localize_with(
+                "clip-event",
+                &[
+                    ("time", format_timestamp(event.time)),
+                    ("kind", localize(event.kind.label(), env)),
+                    ("channel", (event.channel + 1).to_string()),
+                    ("level", level),
+                    ("dropout", event.kind.is_dropout().to_string()),
+                    ("duration", format!("{:.1}", event.duration_ms)),
+                ],
+                env,
+            )
+        })
+        .align_left()
+    })
+    .controller(ClipLogController)
+}
+
+struct ClipLogController;
+
+impl<W: Widget<Arc<Vec<ClipEvent>>>> Controller<Arc<Vec<ClipEvent>>, W> for ClipLogController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut Arc<Vec<ClipEvent>>,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_CLIP_LOG) => {
+                *data = cmd.get_unchecked(DRAW_CLIP_LOG).clone();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
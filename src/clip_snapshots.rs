@@ -0,0 +1,161 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `--clip-snapshots`: a PNG of the waveform around every clip event, with context before and
+//! after it, written to a folder per session so overs during unattended recording can be looked
+//! at afterwards.
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::buffer_analyser::{SampleHistory, HISTORY_LEN};
+use crate::clip_log::{format_timestamp, ClipEvent};
+use crate::decimate;
+use crate::video;
+
+/// Shown either side of the event's start, limited by the history's length
+const CONTEXT_SECONDS: f32 = 0.2;
+const WIDTH: usize = 1200;
+const HEIGHT: usize = 300;
+/// Column where the event started
+const MARKER_RGBA: [u8; 4] = [255, 200, 0, 255];
+
+/// A snapshot to take once the post-event context has arrived
+struct Pending {
+    due: u64,
+    path: PathBuf,
+}
+
+/// Samples to draw and the index the event starts at
+struct Capture {
+    path: PathBuf,
+    samples: Vec<f32>,
+    marker: usize,
+}
+
+pub struct ClipSnapshotter {
+    sample_rate: f32,
+    context_len: usize,
+    session: PathBuf,
+    pending: VecDeque<Pending>,
+    captures: Sender<Capture>,
+}
+
+impl ClipSnapshotter {
+    /// Make a new session folder in `dir` and write snapshots there on a worker thread
+    pub fn create(dir: &Path, sample_rate: f32) -> io::Result<Self> {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let session = dir.join(format!("clips-{}", started));
+        fs::create_dir_all(&session)?;
+        let (captures, captures_rx) = channel();
+        thread::spawn(move || write_captures(captures_rx));
+        Ok(ClipSnapshotter {
+            sample_rate,
+            context_len: ((sample_rate * CONTEXT_SECONDS) as usize).min(HISTORY_LEN / 2),
+            session,
+            pending: VecDeque::new(),
+            captures,
+        })
+    }
+
+    /// Schedule a snapshot of a finished event
+    pub fn trigger(&mut self, event: &ClipEvent) {
+        let start = (event.time * self.sample_rate) as u64;
+        let slug = event.kind.name().to_lowercase().replace(' ', "-");
+        let file_name = format!(
+            "{}-{}-ch{}.png",
+            format_timestamp(event.time),
+            slug,
+            event.channel + 1
+        );
+        self.pending.push_back(Pending {
+            due: start + self.context_len as u64,
+            // Colons aren't allowed in Windows file names
+            path: self.session.join(file_name.replace(':', "-")),
+        });
+    }
+
+    /// Take the snapshots that are due, `position` being the number of samples in the stream so
+    /// far, the same count clip event times are measured in
+    pub fn process(&mut self, position: u64, history: &SampleHistory) {
+        while let Some(pending) = self.pending.front() {
+            if pending.due > position {
+                break;
+            }
+            // Events longer than the context are only reported after it has passed
+            let late = (position - pending.due) as usize;
+            let pending = self.pending.pop_front().unwrap();
+            let capture = Capture {
+                path: pending.path,
+                samples: history.latest(2 * self.context_len),
+                marker: self.context_len.saturating_sub(late),
+            };
+            if self.captures.send(capture).is_err() {
+                log::error!("Clip snapshot writer stopped");
+            }
+        }
+    }
+}
+
+fn write_captures(captures: Receiver<Capture>) {
+    let mut buckets = Vec::new();
+    let mut frame = vec![0; WIDTH * HEIGHT * 4];
+    for capture in captures {
+        decimate::decimate_into(&capture.samples, WIDTH, &mut buckets);
+        video::draw_frame(&buckets, WIDTH, HEIGHT, &mut frame);
+        let marker = capture.marker * WIDTH / capture.samples.len().max(1);
+        for y in 0..HEIGHT {
+            let offset = (y * WIDTH + marker.min(WIDTH - 1)) * 4;
+            frame[offset..offset + 4].copy_from_slice(&MARKER_RGBA);
+        }
+        if let Err(err) = write_png(&capture.path, &frame) {
+            log::error!("Failed to write clip snapshot {:?}: {}", capture.path, err);
+        }
+    }
+}
+
+/// Save the waveform of `samples` as a PNG the size of a clip snapshot
+pub fn write_waveform_png(path: &Path, samples: &[f32]) -> io::Result<()> {
+    let mut buckets = Vec::new();
+    let mut frame = vec![0; WIDTH * HEIGHT * 4];
+    decimate::decimate_into(samples, WIDTH, &mut buckets);
+    video::draw_frame(&buckets, WIDTH, HEIGHT, &mut frame);
+    write_png(path, &frame)
+}
+
+fn write_png(path: &Path, frame: &[u8]) -> io::Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(frame))
+        .map_err(io::Error::other)
+}
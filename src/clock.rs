@@ -0,0 +1,125 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Audio clock drift: the audio thread stamps every callback with its stream position and the
+//! wall-clock time, and a line fitted through recent stamps gives the rate the device really runs
+//! at. Devices are often off their nominal rate by tens of ppm, which adds up over a long session.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Callback stamps the queue holds, a few seconds' worth at small buffer sizes
+pub const STAMP_QUEUE_LEN: usize = 1024;
+
+/// Seconds of stamps the rate is fitted over
+const FIT_SECONDS: f64 = 30.0;
+/// Seconds of stamps needed before the fit is trusted
+const MIN_FIT_SECONDS: f64 = 2.0;
+/// Seconds between stamps taken as the engine having been stopped, the fit starts over after it
+const MAX_GAP_SECONDS: f64 = 1.0;
+
+/// Stream position at the end of an audio callback and when it ran
+#[derive(Clone, Copy, Debug)]
+pub struct BatchStamp {
+    /// Frames received so far, including any dropped from the sample queue
+    pub position: u64,
+    /// Microseconds since the first run of the processor was created
+    pub time_micros: u64,
+}
+
+/// The measured rate, shared by the analysis thread with the bus's other consumers
+#[derive(Default)]
+pub struct ClockRate(AtomicU64);
+
+impl ClockRate {
+    pub fn set(&self, rate: f64) {
+        self.0.store(rate.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The measured rate, or `nominal_rate` before there is one
+    pub fn get(&self, nominal_rate: f32) -> f32 {
+        match self.0.load(Ordering::Relaxed) {
+            0 => nominal_rate,
+            bits => f64::from_bits(bits) as f32,
+        }
+    }
+}
+
+pub struct DriftEstimator {
+    nominal_rate: f64,
+    stamps: VecDeque<BatchStamp>,
+}
+
+impl DriftEstimator {
+    pub fn new(nominal_rate: f32) -> Self {
+        DriftEstimator {
+            nominal_rate: nominal_rate as f64,
+            stamps: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, stamp: BatchStamp) {
+        if self.stamps.back().is_some_and(|last| {
+            (stamp.time_micros.saturating_sub(last.time_micros)) as f64 / 1e6 > MAX_GAP_SECONDS
+        }) {
+            self.stamps.clear();
+        }
+        self.stamps.push_back(stamp);
+        while let (Some(first), Some(last)) = (self.stamps.front(), self.stamps.back()) {
+            if (last.time_micros - first.time_micros) as f64 / 1e6 <= FIT_SECONDS {
+                break;
+            }
+            self.stamps.pop_front();
+        }
+    }
+
+    /// Frames per second the device is delivering, the nominal rate until there are enough stamps
+    pub fn effective_rate(&self) -> f64 {
+        let (first, last) = match (self.stamps.front(), self.stamps.back()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return self.nominal_rate,
+        };
+        if ((last.time_micros - first.time_micros) as f64 / 1e6) < MIN_FIT_SECONDS {
+            return self.nominal_rate;
+        }
+        // Least-squares slope, relative to the oldest stamp to keep the sums small
+        let points = self.stamps.iter().map(|stamp| {
+            (
+                (stamp.time_micros - first.time_micros) as f64 / 1e6,
+                (stamp.position - first.position) as f64,
+            )
+        });
+        let count = self.stamps.len() as f64;
+        let (sum_x, sum_y, sum_xy, sum_xx) = points.fold(
+            (0.0, 0.0, 0.0, 0.0),
+            |(sum_x, sum_y, sum_xy, sum_xx), (x, y)| {
+                (sum_x + x, sum_y + y, sum_xy + x * y, sum_xx + x * x)
+            },
+        );
+        (count * sum_xy - sum_x * sum_y) / (count * sum_xx - sum_x * sum_x)
+    }
+
+    /// How far the device runs from its nominal rate, in parts per million
+    pub fn drift_ppm(&self) -> f64 {
+        (self.effective_rate() / self.nominal_rate - 1.0) * 1e6
+    }
+}
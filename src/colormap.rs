@@ -0,0 +1,124 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Perceptual colormaps used to turn spectrogram magnitudes into pixels.
+//!
+//! The stops are sampled from the matplotlib maps of the same name and linearly interpolated.
+
+use std::str::FromStr;
+
+type Rgb = [u8; 3];
+
+const MAGMA: [Rgb; 9] = [
+    [0, 0, 4],
+    [28, 16, 68],
+    [79, 18, 123],
+    [129, 37, 129],
+    [181, 54, 122],
+    [229, 80, 100],
+    [251, 135, 97],
+    [254, 194, 135],
+    [252, 253, 191],
+];
+
+const INFERNO: [Rgb; 9] = [
+    [0, 0, 4],
+    [31, 12, 72],
+    [85, 15, 109],
+    [136, 34, 106],
+    [186, 54, 85],
+    [227, 89, 51],
+    [249, 140, 10],
+    [249, 201, 50],
+    [252, 255, 164],
+];
+
+const VIRIDIS: [Rgb; 9] = [
+    [68, 1, 84],
+    [71, 44, 122],
+    [59, 81, 139],
+    [44, 113, 142],
+    [33, 144, 141],
+    [39, 173, 129],
+    [92, 200, 99],
+    [170, 220, 50],
+    [253, 231, 37],
+];
+
+const GRAYSCALE: [Rgb; 2] = [[0, 0, 0], [255, 255, 255]];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Magma,
+    Inferno,
+    Viridis,
+    Grayscale,
+}
+
+impl Colormap {
+    pub const NAMES: [&'static str; 4] = ["magma", "inferno", "viridis", "grayscale"];
+
+    fn stops(&self) -> &'static [Rgb] {
+        match self {
+            Colormap::Magma => &MAGMA,
+            Colormap::Inferno => &INFERNO,
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Grayscale => &GRAYSCALE,
+        }
+    }
+
+    /// Map `value` in the `0.0..=1.0` range onto a color. Values outside are clamped.
+    pub fn color(&self, value: f32) -> Rgb {
+        let stops = self.stops();
+        let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let amount = position - index as f32;
+        let start = stops[index];
+        let end = stops[index + 1];
+
+        let mut result = [0; 3];
+        for (channel, output) in result.iter_mut().enumerate() {
+            let start = start[channel] as f32;
+            let end = end[channel] as f32;
+            *output = (start + (end - start) * amount).round() as u8;
+        }
+        result
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "magma" => Ok(Colormap::Magma),
+            "inferno" => Ok(Colormap::Inferno),
+            "viridis" => Ok(Colormap::Viridis),
+            "grayscale" | "gray" => Ok(Colormap::Grayscale),
+            _ => Err(format!(
+                "Unknown colormap '{}', expected one of {}",
+                s,
+                Colormap::NAMES.join(", ")
+            )),
+        }
+    }
+}
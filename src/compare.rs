@@ -0,0 +1,368 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `compare` sub-command: two stacked, time-aligned waveform panes fed from two files, or from a
+//! file and the live input, with a linked zoom and a meter showing how much they differ. The
+//! overlay mode draws both in one pane with additive blending instead, so where they line up
+//! reads white and any phase or timing difference shows as colored fringes.
+
+use std::thread;
+use std::time::Duration;
+
+use atomic_queue::Queue;
+use audio_garbage_collector::GarbageCollector;
+use audio_processor_file::file_io::AudioFileError;
+use audio_processor_file::AudioFileProcessor;
+use audio_processor_standalone::audio_processor_start;
+use audio_processor_traits::{AudioBuffer, AudioContext, AudioProcessor, AudioProcessorSettings};
+use basedrop::{Handle, Shared};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use druid::piet::{ImageFormat, InterpolationMode};
+use druid::widget::prelude::*;
+use druid::widget::{Checkbox, Controller, Either, Flex, Label};
+use druid::{
+    lens, AppLauncher, Color, Data, Lens, LocalizedString, Selector, Target, WidgetExt, WindowDesc,
+};
+
+use crate::audio_wave::{self, latest_window, AudioData, AudioWaveBuilder, WaveData};
+use crate::buffer_analyser::{ChunkWriter, SampleChunk, SampleHistory, HISTORY_LEN, QUEUE_CHUNKS};
+use crate::i18n::{self, localize_with};
+use crate::units::AmplitudeScale;
+use crate::wave_raster::AdditiveSpans;
+
+const DRAW_COMPARE: Selector<(Vec<f32>, Vec<f32>)> = Selector::new("compare.draw_compare");
+
+/// Colors of the two sources in the overlay, adding up to nearly white where they coincide
+const OVERLAY_A_RGB: [u8; 3] = [255, 40, 0];
+const OVERLAY_B_RGB: [u8; 3] = [0, 160, 255];
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("compare")
+        .about("Compare two files, or a file against the live input, side-by-side")
+        .arg(Arg::from_usage("<FILE_A> 'The reference audio file'"))
+        .arg(Arg::from_usage(
+            "[FILE_B] 'The audio file to compare against, defaults to the live input'",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let garbage_collector = GarbageCollector::default();
+    let handle = garbage_collector.handle();
+    let open = |path: &str| {
+        CompareSource::file(handle, path).unwrap_or_else(|err| {
+            eprintln!("Failed to open {}: {}", path, err);
+            std::process::exit(1);
+        })
+    };
+    let source_a = open(matches.value_of("FILE_A").unwrap());
+    let source_b = matches
+        .value_of("FILE_B")
+        .map(open)
+        .unwrap_or(CompareSource::Input);
+
+    let window = WindowDesc::new(make_ui()).title(LocalizedString::new("compare-title"));
+    let launcher = i18n::with_strings(AppLauncher::with_window(window));
+    let event_sink = launcher.get_external_handle();
+
+    let processor = CompareProcessor::new(handle, [source_a, source_b]);
+    let queues = processor.queues();
+    let _audio_streams = audio_processor_start(processor);
+    thread::spawn(move || generate_compare_updates(event_sink, queues));
+
+    launcher
+        .launch(CompareData {
+            a: AudioData::new(Vec::new()),
+            b: AudioData::new(Vec::new()),
+            visible_samples: HISTORY_LEN as f64,
+            overlay: false,
+        })
+        .expect("launch failed");
+}
+
+pub enum CompareSource {
+    File(Box<AudioFileProcessor>),
+    Input,
+}
+
+impl CompareSource {
+    fn file(handle: &Handle, path: &str) -> Result<Self, AudioFileError> {
+        let processor = AudioFileProcessor::from_path(handle, Default::default(), path)?;
+        Ok(CompareSource::File(Box::new(processor)))
+    }
+}
+
+/// Pulls one sample from each source per frame, so both queues stay sample-aligned.
+pub struct CompareProcessor {
+    sources: [CompareSource; 2],
+    queues: [Shared<Queue<SampleChunk>>; 2],
+    writers: [ChunkWriter; 2],
+}
+
+impl CompareProcessor {
+    pub fn new(handle: &Handle, sources: [CompareSource; 2]) -> Self {
+        CompareProcessor {
+            sources,
+            queues: [
+                Shared::new(handle, Queue::new(QUEUE_CHUNKS)),
+                Shared::new(handle, Queue::new(QUEUE_CHUNKS)),
+            ],
+            writers: Default::default(),
+        }
+    }
+
+    pub fn queues(&self) -> [Shared<Queue<SampleChunk>>; 2] {
+        self.queues.clone()
+    }
+}
+
+impl AudioProcessor for CompareProcessor {
+    type SampleType = f32;
+
+    fn prepare(&mut self, context: &mut AudioContext, settings: AudioProcessorSettings) {
+        for source in self.sources.iter_mut() {
+            if let CompareSource::File(file) = source {
+                file.prepare(context, settings);
+            }
+        }
+    }
+
+    fn process<BufferType: AudioBuffer<SampleType = Self::SampleType>>(
+        &mut self,
+        _context: &mut AudioContext,
+        data: &mut BufferType,
+    ) {
+        for frame in data.frames_mut() {
+            let input = frame[0];
+            let streams = self
+                .sources
+                .iter()
+                .zip(self.queues.iter())
+                .zip(self.writers.iter_mut());
+            for ((source, queue), writer) in streams {
+                let sample = match source {
+                    CompareSource::File(file) if file.num_samples() > 0 => {
+                        file.process_single().next().unwrap_or(0.0)
+                    }
+                    CompareSource::File(_) => 0.0,
+                    CompareSource::Input => input,
+                };
+                if let Some(chunk) = writer.write(sample) {
+                    queue.push(chunk);
+                }
+            }
+            for sample in frame {
+                *sample = 0.0;
+            }
+        }
+        for (queue, writer) in self.queues.iter().zip(self.writers.iter_mut()) {
+            if let Some(chunk) = writer.flush() {
+                queue.push(chunk);
+            }
+        }
+    }
+}
+
+fn generate_compare_updates(
+    event_sink: druid::ExtEventSink,
+    queues: [Shared<Queue<SampleChunk>>; 2],
+) {
+    let mut history_a = SampleHistory::default();
+    let mut history_b = SampleHistory::default();
+
+    loop {
+        history_a.drain(&queues[0]);
+        history_b.drain(&queues[1]);
+
+        let payload = (history_a.latest(HISTORY_LEN), history_b.latest(HISTORY_LEN));
+        if event_sink
+            .submit_command(DRAW_COMPARE, payload, Target::Auto)
+            .is_err()
+        {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[derive(Clone, Data, Lens)]
+struct CompareData {
+    a: AudioData,
+    b: AudioData,
+    /// Shared by both panes so zooming one zooms the other
+    visible_samples: f64,
+    /// One blended pane instead of two stacked ones
+    overlay: bool,
+}
+
+impl CompareData {
+    /// Level of the difference signal relative to the reference, over the visible window
+    fn difference_db(&self) -> f32 {
+        let a = latest_window(&self.a.0, self.visible_samples);
+        let b = latest_window(&self.b.0, self.visible_samples);
+        let mut signal = 0.0;
+        let mut difference = 0.0;
+        for (a, b) in a.iter().zip(b) {
+            signal += a * a;
+            difference += (a - b) * (a - b);
+        }
+        10.0 * (difference / signal).log10()
+    }
+}
+
+struct CompareController;
+
+impl<W: Widget<CompareData>> Controller<CompareData, W> for CompareController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut CompareData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_COMPARE) => {
+                let (a, b) = cmd.get_unchecked(DRAW_COMPARE).clone();
+                data.a = AudioData::new(a);
+                data.b = AudioData::new(b);
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
+
+/// One source's waveform, in its overlay color
+fn pane(source: fn(&CompareData) -> &AudioData, rgb: [u8; 3]) -> impl Widget<CompareData> {
+    let [red, green, blue] = rgb;
+    let wave = AudioWaveBuilder::new()
+        .color(Color::rgb8(red, green, blue))
+        .build();
+    wave.lens(lens::Map::new(
+        move |data: &CompareData| WaveData {
+            audio: source(data).clone(),
+            right: None,
+            visible_samples: data.visible_samples,
+            voice_activity: None,
+            // Anchored at the newest sample, like the live waveform
+            scroll: Some(0.0),
+            frozen: false,
+            beat_grid: None,
+            record_start: None,
+            input_latency: 0.0,
+            position: 0.0,
+        },
+        |data: &mut CompareData, pane: WaveData| data.visible_samples = pane.visible_samples,
+    ))
+}
+
+/// Both sources in one pane, each column's min/max span added onto the other's
+#[derive(Default)]
+struct OverlayWave {
+    spans: AdditiveSpans,
+}
+
+impl Widget<CompareData> for OverlayWave {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut CompareData, _env: &Env) {
+        if let Event::Wheel(mouse) = event {
+            let max_samples = data.a.0.len().max(data.b.0.len());
+            data.visible_samples =
+                audio_wave::zoom(data.visible_samples, mouse.wheel_delta.y, max_samples);
+            ctx.set_handled();
+        }
+        if let Event::Zoom(magnification) = event {
+            let max_samples = data.a.0.len().max(data.b.0.len());
+            data.visible_samples =
+                audio_wave::pinch_zoom(data.visible_samples, *magnification, max_samples);
+            ctx.set_handled();
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &CompareData, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _: &CompareData, _: &CompareData, _: &Env) {
+        ctx.request_paint()
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &CompareData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &CompareData, _env: &Env) {
+        let size = ctx.size();
+        let (width, height) = (size.width as usize, size.height as usize);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let to_row =
+            |sample: f32| (1.0 - sample.clamp(-1.0, 1.0) as f64) * 0.5 * (height - 1) as f64;
+        self.spans.clear(width, height);
+        let a = latest_window(&data.a.0, data.visible_samples);
+        let b = latest_window(&data.b.0, data.visible_samples);
+        self.spans.add(a, OVERLAY_A_RGB, to_row);
+        self.spans.add(b, OVERLAY_B_RGB, to_row);
+        ctx.fill(size.to_rect(), &Color::BLACK);
+        match ctx.make_image(
+            width,
+            height,
+            self.spans.pixels(),
+            ImageFormat::RgbaSeparate,
+        ) {
+            Ok(image) => ctx.draw_image(&image, size.to_rect(), InterpolationMode::Bilinear),
+            Err(err) => log::error!("Failed to draw overlay: {}", err),
+        }
+    }
+}
+
+fn make_ui() -> impl Widget<CompareData> {
+    let difference = Label::dynamic(|data: &CompareData, env| {
+        let difference = data.difference_db();
+        let difference = if difference.is_finite() {
+            AmplitudeScale::from_env(env).format_db(difference)
+        } else {
+            "--".to_string()
+        };
+        localize_with("compare-difference", &[("difference", difference)], env)
+    });
+
+    let stacked = Flex::column()
+        .with_flex_child(pane(|data| &data.a, OVERLAY_A_RGB).padding(10.0), 1.0)
+        .with_flex_child(pane(|data| &data.b, OVERLAY_B_RGB).padding(10.0), 1.0);
+    Flex::column()
+        .with_flex_child(
+            Either::new(
+                |data: &CompareData, _| data.overlay,
+                OverlayWave::default().padding(10.0),
+                stacked,
+            ),
+            1.0,
+        )
+        .with_child(
+            Flex::row()
+                .with_child(difference)
+                .with_default_spacer()
+                .with_child(
+                    Checkbox::new(LocalizedString::new("overlay")).lens(CompareData::overlay),
+                )
+                .padding(10.0),
+        )
+        .controller(CompareController)
+}
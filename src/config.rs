@@ -0,0 +1,93 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Settings kept between runs, as JSON in the user's config directory.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::midi::MidiMapping;
+use crate::workspace::Workspace;
+
+const FILE_NAME: &str = "example-druid-audio-viz.json";
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub midi_mappings: Vec<MidiMapping>,
+    /// Name of the input device to open, the default device when unset
+    #[serde(default)]
+    pub input_device: Option<String>,
+    #[serde(default)]
+    pub workspaces: Vec<Workspace>,
+}
+
+/// A file in `$XDG_CONFIG_HOME`, falling back to `~/.config`
+pub fn path(file_name: &str) -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|directory| directory.join(file_name))
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    let text = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Creates the directory when needed
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(value)?;
+    path.parent().map_or(Ok(()), fs::create_dir_all)?;
+    fs::write(path, text)
+}
+
+impl Config {
+    /// The saved config, or the defaults when there isn't one
+    pub fn load() -> Self {
+        let path = match path(FILE_NAME) {
+            Some(path) => path,
+            None => return Config::default(),
+        };
+        match read_json(&path) {
+            Ok(config) => config,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(err) => {
+                log::error!("Failed to parse config {:?}: {}", path, err);
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = path(FILE_NAME) {
+            if let Err(err) = write_json(&path, self) {
+                log::error!("Failed to write config to {:?}: {}", path, err);
+            }
+        }
+    }
+}
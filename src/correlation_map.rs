@@ -0,0 +1,251 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `render-correlation` sub-command: how correlated a stereo file's channels are in each
+//! third-octave band over time, rendered as a heatmap PNG. Green bands are in phase, black ones
+//! unrelated and red ones out of phase, so phase problems show up at the frequencies they
+//! affect. Each band's correlation over the whole file is printed too.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::Arc;
+
+use audio_processor_analysis::window_functions::make_hann_vec;
+use audio_processor_file::InMemoryAudioFile;
+use audio_processor_traits::{AudioBuffer, AudioProcessorSettings};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+const FFT_SIZE: usize = 4096;
+const HOP: usize = FFT_SIZE / 4;
+/// Centers of the third-octave bands, from 1 kHz times 2^(n/3)
+const LOWEST_BAND: i32 = -17;
+const HIGHEST_BAND: i32 = 13;
+/// Pixel rows per band
+const ROW_HEIGHT: usize = 8;
+/// Band power per column below which a channel counts as silent, about -90 dB under a full-scale
+/// sine
+const SILENCE_POWER: f32 = (FFT_SIZE * FFT_SIZE) as f32 * 1e-9;
+/// Time constant the per-column spectra are averaged over
+const SMOOTHING_SECONDS: f32 = 0.2;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("render-correlation")
+        .about("Render the correlation between a stereo file's channels per frequency band")
+        .arg(Arg::from_usage(
+            "<INPUT_PATH> 'The stereo audio file to analyse'",
+        ))
+        .arg(Arg::from_usage(
+            "-o, --output=<OUTPUT_PATH> 'Where to write the PNG image'",
+        ))
+}
+
+/// Bins summed into one band
+struct Band {
+    center_hz: f32,
+    bins: std::ops::Range<usize>,
+}
+
+fn bands(sample_rate: f32) -> Vec<Band> {
+    let bin_hz = sample_rate / FFT_SIZE as f32;
+    (LOWEST_BAND..=HIGHEST_BAND)
+        .map(|band| {
+            let center_hz = 1000.0 * 2.0_f32.powf(band as f32 / 3.0);
+            let edge = 2.0_f32.powf(1.0 / 6.0);
+            let low = ((center_hz / edge / bin_hz).round() as usize).max(1);
+            let high = ((center_hz * edge / bin_hz).round() as usize).clamp(low + 1, FFT_SIZE / 2);
+            Band {
+                center_hz,
+                bins: low..high,
+            }
+        })
+        .filter(|band| band.center_hz < sample_rate / 2.0)
+        .collect()
+}
+
+/// Cross and auto power of a band, accumulated over time
+#[derive(Clone, Copy, Default)]
+struct BandPower {
+    cross: f32,
+    left: f32,
+    right: f32,
+}
+
+impl BandPower {
+    /// Between -1 and 1, `None` while either channel's power is under `floor`
+    fn correlation(&self, floor: f32) -> Option<f32> {
+        if self.left.min(self.right) < floor {
+            return None;
+        }
+        let norm = (self.left * self.right).sqrt();
+        (norm > 0.0).then(|| (self.cross / norm).clamp(-1.0, 1.0))
+    }
+}
+
+struct CorrelationAnalyser {
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    bands: Vec<Band>,
+    smoothing: f32,
+    smoothed: Vec<BandPower>,
+    total: Vec<BandPower>,
+}
+
+impl CorrelationAnalyser {
+    fn new(sample_rate: f32) -> Self {
+        let bands = bands(sample_rate);
+        let hop_seconds = HOP as f32 / sample_rate;
+        CorrelationAnalyser {
+            window: make_hann_vec(FFT_SIZE),
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            smoothed: vec![BandPower::default(); bands.len()],
+            total: vec![BandPower::default(); bands.len()],
+            bands,
+            smoothing: (-hop_seconds / SMOOTHING_SECONDS).exp(),
+        }
+    }
+
+    fn spectrum(&self, samples: &[f32]) -> Vec<Complex<f32>> {
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, window)| Complex::new(sample * window, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        buffer
+    }
+
+    /// One column of correlations, lowest band first
+    fn process(&mut self, left: &[f32], right: &[f32]) -> Vec<Option<f32>> {
+        let left = self.spectrum(left);
+        let right = self.spectrum(right);
+        let smoothing = self.smoothing;
+        self.bands
+            .iter()
+            .zip(self.smoothed.iter_mut().zip(self.total.iter_mut()))
+            .map(|(band, (smoothed, total))| {
+                let mut power = BandPower::default();
+                for bin in band.bins.clone() {
+                    power.cross += (left[bin] * right[bin].conj()).re;
+                    power.left += left[bin].norm_sqr();
+                    power.right += right[bin].norm_sqr();
+                }
+                let blend = |old: f32, new: f32| new + smoothing * (old - new);
+                smoothed.cross = blend(smoothed.cross, power.cross);
+                smoothed.left = blend(smoothed.left, power.left);
+                smoothed.right = blend(smoothed.right, power.right);
+                total.cross += power.cross;
+                total.left += power.left;
+                total.right += power.right;
+                smoothed.correlation(SILENCE_POWER)
+            })
+            .collect()
+    }
+}
+
+/// Red at -1 through black at 0 to green at +1, dark grey where a channel is silent
+fn color(correlation: Option<f32>) -> [u8; 3] {
+    match correlation {
+        None => [40, 40, 40],
+        Some(value) if value >= 0.0 => [0, (value * 220.0) as u8, (value * 120.0) as u8],
+        Some(value) => [(-value * 255.0) as u8, 0, 0],
+    }
+}
+
+fn format_hz(hz: f32) -> String {
+    if hz >= 1000.0 {
+        format!("{:.1} kHz", hz / 1000.0)
+    } else {
+        format!("{:.0} Hz", hz)
+    }
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_path = matches.value_of("INPUT_PATH").unwrap();
+    let output_path = matches.value_of("output").unwrap();
+
+    let settings = AudioProcessorSettings::default();
+    let buffer = InMemoryAudioFile::from_path(input_path)
+        .and_then(|mut file| file.read_into_vec_audio_buffer(&settings))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", input_path, err);
+            std::process::exit(1);
+        });
+    if buffer.num_channels() < 2 {
+        eprintln!(
+            "{} has a single channel, there's nothing to correlate",
+            input_path
+        );
+        std::process::exit(1);
+    }
+    let (left, right): (Vec<f32>, Vec<f32>) =
+        buffer.frames().map(|frame| (frame[0], frame[1])).unzip();
+
+    let mut analyser = CorrelationAnalyser::new(settings.sample_rate());
+    let columns: Vec<Vec<Option<f32>>> = (0..left.len().saturating_sub(FFT_SIZE) / HOP)
+        .map(|column| {
+            let start = column * HOP;
+            analyser.process(
+                &left[start..start + FFT_SIZE],
+                &right[start..start + FFT_SIZE],
+            )
+        })
+        .collect();
+
+    let width = columns.len().max(1);
+    let num_bands = analyser.bands.len();
+    let height = num_bands * ROW_HEIGHT;
+    let mut pixels = vec![0; width * height * 3];
+    for (x, column) in columns.iter().enumerate() {
+        for (band, correlation) in column.iter().enumerate() {
+            // Lowest band at the bottom
+            let top = (num_bands - 1 - band) * ROW_HEIGHT;
+            for y in top..top + ROW_HEIGHT {
+                let offset = (y * width + x) * 3;
+                pixels[offset..offset + 3].copy_from_slice(&color(*correlation));
+            }
+        }
+    }
+
+    let file = File::create(output_path).unwrap_or_else(|err| {
+        eprintln!("Failed to create {}: {}", output_path, err);
+        std::process::exit(1);
+    });
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Err(err) = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+    {
+        eprintln!("Failed to write {}: {}", output_path, err);
+        std::process::exit(1);
+    }
+
+    for (band, total) in analyser.bands.iter().zip(&analyser.total) {
+        let correlation = total
+            .correlation(SILENCE_POWER * columns.len() as f32)
+            .map_or("--".to_string(), |value| format!("{:+.2}", value));
+        println!("{:<10} {}", format_hz(band.center_hz), correlation);
+    }
+}
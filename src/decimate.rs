@@ -0,0 +1,139 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Min/max decimation and sum/RMS of sample buffers, written over fixed-width lanes so the
+//! compiler turns the inner loops into SIMD instructions.
+//!
+//! Kept free of other crate modules so `benches/decimate.rs` can include it directly.
+
+/// Samples handled per loop iteration; 8 lanes fill an AVX register, or two NEON/SSE ones
+const LANES: usize = 8;
+
+/// Minimum and maximum of a run of samples
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinMax {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Amplitude sums of a buffer, enough to derive the mean, DC offset and RMS
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub min: f32,
+    pub max: f32,
+    pub sum: f32,
+    pub sum_squares: f32,
+    pub len: usize,
+}
+
+impl Summary {
+    pub fn mean(&self) -> f32 {
+        self.sum / self.len.max(1) as f32
+    }
+
+    pub fn rms(&self) -> f32 {
+        (self.sum_squares / self.len.max(1) as f32).sqrt()
+    }
+}
+
+/// Minimum and maximum of `samples`, both 0 when it's empty
+pub fn min_max(samples: &[f32]) -> MinMax {
+    if samples.is_empty() {
+        return MinMax { min: 0.0, max: 0.0 };
+    }
+    let mut min = [f32::INFINITY; LANES];
+    let mut max = [f32::NEG_INFINITY; LANES];
+    let chunks = samples.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        for lane in 0..LANES {
+            min[lane] = min[lane].min(chunk[lane]);
+            max[lane] = max[lane].max(chunk[lane]);
+        }
+    }
+    for (lane, sample) in remainder.iter().enumerate() {
+        min[lane] = min[lane].min(*sample);
+        max[lane] = max[lane].max(*sample);
+    }
+    MinMax {
+        min: min.iter().copied().fold(f32::INFINITY, f32::min),
+        max: max.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+    }
+}
+
+/// Split `samples` into `buckets` equal runs and write the min/max of each into `output`, reusing
+/// its allocation
+pub fn decimate_into(samples: &[f32], buckets: usize, output: &mut Vec<MinMax>) {
+    output.clear();
+    if samples.is_empty() || buckets == 0 {
+        return;
+    }
+    let buckets = buckets.min(samples.len());
+    output.extend((0..buckets).map(|bucket| {
+        let start = bucket * samples.len() / buckets;
+        let end = (bucket + 1) * samples.len() / buckets;
+        min_max(&samples[start..end])
+    }));
+}
+
+/// Like [`decimate_into`], but each bucket spans plus and minus its RMS rather than its peaks
+pub fn decimate_rms_into(samples: &[f32], buckets: usize, output: &mut Vec<MinMax>) {
+    output.clear();
+    if samples.is_empty() || buckets == 0 {
+        return;
+    }
+    let buckets = buckets.min(samples.len());
+    output.extend((0..buckets).map(|bucket| {
+        let start = bucket * samples.len() / buckets;
+        let end = (bucket + 1) * samples.len() / buckets;
+        let rms = summary(&samples[start..end]).rms();
+        MinMax {
+            min: -rms,
+            max: rms,
+        }
+    }));
+}
+
+pub fn summary(samples: &[f32]) -> Summary {
+    let mut sum = [0.0_f32; LANES];
+    let mut sum_squares = [0.0_f32; LANES];
+    let chunks = samples.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        for lane in 0..LANES {
+            sum[lane] += chunk[lane];
+            sum_squares[lane] += chunk[lane] * chunk[lane];
+        }
+    }
+    for (lane, sample) in remainder.iter().enumerate() {
+        sum[lane] += sample;
+        sum_squares[lane] += sample * sample;
+    }
+    let MinMax { min, max } = min_max(samples);
+    Summary {
+        min,
+        max,
+        sum: sum.iter().sum(),
+        sum_squares: sum_squares.iter().sum(),
+        len: samples.len(),
+    }
+}
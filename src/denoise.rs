@@ -0,0 +1,379 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Noise print capture and a spectral subtraction preview. While capturing, the power in each
+//! bin is averaged into a noise print. Once one exists, every frame has that much power taken off
+//! each bin, with a floor against musical noise, and is resynthesised by overlap-add. The preview
+//! shows the raw and denoised signal and spectrum side by side. Nothing reaches the outputs.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use audio_processor_analysis::window_functions::make_hann_vec;
+use druid::kurbo::{BezPath, Line, Rect};
+use druid::widget::prelude::*;
+use druid::{Color, Data, Point, Selector};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::i18n::localize_with;
+
+pub const DRAW_DENOISE: Selector<DenoiseData> = Selector::new("event-example.draw_denoise");
+
+const FFT_SIZE: usize = 2048;
+/// Hann windows at half overlap sum to one, so the frames add back up without a synthesis window
+const HOP: usize = FFT_SIZE / 2;
+/// How long a noise print is averaged over
+const CAPTURE_SECONDS: f32 = 2.0;
+/// Multiple of the noise print taken off, above 1 to catch the noise's own variation
+const OVER_SUBTRACTION: f32 = 2.0;
+/// Least power left in a bin, as a share of what was there
+const SPECTRAL_FLOOR: f32 = 0.02;
+/// Seconds of waveform in the preview
+const PREVIEW_SECONDS: f32 = 0.5;
+const MIN_HZ: f32 = 20.0;
+/// Bottom of the spectrum plot, the top is 0 dBFS
+const FLOOR_DB: f32 = -120.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub enum NoisePrintState {
+    None,
+    Capturing,
+    /// Seconds averaged
+    Captured(f32),
+}
+
+#[derive(Clone, Data)]
+pub struct DenoiseData {
+    pub state: NoisePrintState,
+    /// The latest frame before and after subtraction, and the noise print, in dBFS per bin
+    pub raw_db: Arc<Vec<f32>>,
+    pub denoised_db: Arc<Vec<f32>>,
+    pub noise_db: Arc<Vec<f32>>,
+    pub bin_hz: f32,
+    /// The last [`PREVIEW_SECONDS`], the raw signal delayed to line up with the denoised one
+    pub raw: Arc<Vec<f32>>,
+    pub denoised: Arc<Vec<f32>>,
+}
+
+impl Default for DenoiseData {
+    fn default() -> Self {
+        DenoiseData {
+            state: NoisePrintState::None,
+            raw_db: Arc::new(Vec::new()),
+            denoised_db: Arc::new(Vec::new()),
+            noise_db: Arc::new(Vec::new()),
+            bin_hz: 0.0,
+            raw: Arc::new(Vec::new()),
+            denoised: Arc::new(Vec::new()),
+        }
+    }
+}
+
+impl NoisePrintState {
+    pub fn label(&self, env: &Env) -> String {
+        let (state, seconds) = match self {
+            NoisePrintState::None => ("none", 0.0),
+            NoisePrintState::Capturing => ("capturing", 0.0),
+            NoisePrintState::Captured(seconds) => ("captured", *seconds),
+        };
+        localize_with(
+            "noise-print",
+            &[
+                ("state", state.to_string()),
+                ("seconds", format!("{:.1}", seconds)),
+            ],
+            env,
+        )
+    }
+}
+
+pub struct SpectralDenoiser {
+    sample_rate: f32,
+    window: Vec<f32>,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    input: VecDeque<f32>,
+    /// Samples since the last frame
+    since_frame: usize,
+    /// Overlap-add accumulator, the first [`HOP`] samples are finished after each frame
+    output: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    /// Summed power per bin while capturing
+    noise_sum: Vec<f32>,
+    noise_frames: usize,
+    capture_frames: usize,
+    capturing: bool,
+    /// Mean power per bin, empty until a print is captured
+    noise: Vec<f32>,
+    raw_power: Vec<f32>,
+    denoised_power: Vec<f32>,
+    raw: VecDeque<f32>,
+    denoised: VecDeque<f32>,
+    preview_len: usize,
+}
+
+impl SpectralDenoiser {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut planner = FftPlanner::new();
+        let preview_len = (PREVIEW_SECONDS * sample_rate) as usize;
+        SpectralDenoiser {
+            sample_rate,
+            window: make_hann_vec(FFT_SIZE),
+            forward: planner.plan_fft_forward(FFT_SIZE),
+            inverse: planner.plan_fft_inverse(FFT_SIZE),
+            input: VecDeque::from(vec![0.0; FFT_SIZE]),
+            since_frame: 0,
+            output: vec![0.0; FFT_SIZE],
+            buffer: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            noise_sum: vec![0.0; FFT_SIZE / 2],
+            noise_frames: 0,
+            capture_frames: (CAPTURE_SECONDS * sample_rate / HOP as f32) as usize,
+            capturing: false,
+            noise: Vec::new(),
+            raw_power: vec![0.0; FFT_SIZE / 2],
+            denoised_power: vec![0.0; FFT_SIZE / 2],
+            raw: VecDeque::from(vec![0.0; preview_len]),
+            denoised: VecDeque::from(vec![0.0; preview_len]),
+            preview_len,
+        }
+    }
+
+    /// Start averaging a new noise print, replacing the old one once it's done
+    pub fn capture(&mut self) {
+        self.noise_sum.iter_mut().for_each(|sum| *sum = 0.0);
+        self.noise_frames = 0;
+        self.capturing = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.capturing = false;
+        self.noise.clear();
+    }
+
+    /// Push a sample, returning whether a frame was processed
+    pub fn process(&mut self, sample: f32) -> bool {
+        self.input.pop_front();
+        self.input.push_back(sample);
+        self.since_frame += 1;
+        if self.since_frame < HOP {
+            return false;
+        }
+        self.since_frame = 0;
+        self.process_frame();
+        true
+    }
+
+    fn process_frame(&mut self) {
+        for ((bin, sample), window) in self.buffer.iter_mut().zip(&self.input).zip(&self.window) {
+            *bin = Complex::new(sample * window, 0.0);
+        }
+        self.forward.process(&mut self.buffer);
+
+        for (power, bin) in self.raw_power.iter_mut().zip(&self.buffer) {
+            *power = bin.norm_sqr();
+        }
+        if self.capturing {
+            for (sum, power) in self.noise_sum.iter_mut().zip(&self.raw_power) {
+                *sum += power;
+            }
+            self.noise_frames += 1;
+            if self.noise_frames == self.capture_frames {
+                let frames = self.noise_frames as f32;
+                self.noise = self.noise_sum.iter().map(|sum| sum / frames).collect();
+                self.capturing = false;
+            }
+        }
+        if !self.noise.is_empty() {
+            // Up to and including Nyquist, which has no bin of its own in the print
+            for bin in 0..=FFT_SIZE / 2 {
+                let power = self.buffer[bin].norm_sqr().max(f32::MIN_POSITIVE);
+                let noise = self.noise[bin.min(FFT_SIZE / 2 - 1)];
+                let remaining = (1.0 - OVER_SUBTRACTION * noise / power).max(SPECTRAL_FLOOR);
+                self.buffer[bin] *= remaining.sqrt();
+                // Mirrored onto the negative frequencies so the output stays real
+                if bin > 0 && bin < FFT_SIZE / 2 {
+                    self.buffer[FFT_SIZE - bin] = self.buffer[bin].conj();
+                }
+            }
+        }
+        for (power, bin) in self.denoised_power.iter_mut().zip(&self.buffer) {
+            *power = bin.norm_sqr();
+        }
+        self.inverse.process(&mut self.buffer);
+
+        let scale = 1.0 / FFT_SIZE as f32;
+        for (output, bin) in self.output.iter_mut().zip(&self.buffer) {
+            *output += bin.re * scale;
+        }
+        // The first hop is finished, and lines up with the oldest hop of the frame's input
+        self.denoised.extend(self.output.drain(..HOP));
+        self.output.resize(FFT_SIZE, 0.0);
+        self.raw.extend(self.input.iter().take(HOP));
+        self.denoised
+            .drain(..self.denoised.len() - self.preview_len);
+        self.raw.drain(..self.raw.len() - self.preview_len);
+    }
+
+    pub fn data(&self) -> DenoiseData {
+        let state = if self.capturing {
+            NoisePrintState::Capturing
+        } else if self.noise.is_empty() {
+            NoisePrintState::None
+        } else {
+            NoisePrintState::Captured(self.capture_frames as f32 * HOP as f32 / self.sample_rate)
+        };
+        // A full-scale sine reads 0 dB through the Hann window
+        let gain = 2.0 / self.window.iter().sum::<f32>();
+        let to_db = |powers: &[f32]| -> Arc<Vec<f32>> {
+            Arc::new(
+                powers
+                    .iter()
+                    .map(|power| (10.0 * (power * gain * gain).log10()).max(FLOOR_DB))
+                    .collect(),
+            )
+        };
+        DenoiseData {
+            state,
+            raw_db: to_db(&self.raw_power),
+            denoised_db: to_db(&self.denoised_power),
+            noise_db: to_db(&self.noise),
+            bin_hz: self.sample_rate / FFT_SIZE as f32,
+            raw: Arc::new(self.raw.iter().copied().collect()),
+            denoised: Arc::new(self.denoised.iter().copied().collect()),
+        }
+    }
+}
+
+/// The raw and denoised waveform above, their spectra and the noise print below
+#[derive(Default)]
+pub struct DenoisePreview {}
+
+impl DenoisePreview {
+    /// Min and max per pixel column, centered in `area`
+    fn paint_wave(ctx: &mut PaintCtx, samples: &[f32], area: Rect, color: &Color) {
+        let width = area.width().max(1.0) as usize;
+        if samples.is_empty() {
+            return;
+        }
+        let middle = area.center().y;
+        let half_height = area.height() / 2.0;
+        for x in 0..width {
+            let start = (x * samples.len() / width).min(samples.len() - 1);
+            let end = ((x + 1) * samples.len() / width).clamp(start + 1, samples.len());
+            let bucket = &samples[start..end];
+            let (low, high) = bucket
+                .iter()
+                .fold((0.0_f32, 0.0_f32), |(low, high), sample| {
+                    (low.min(*sample), high.max(*sample))
+                });
+            let x_coord = area.x0 + x as f64 + 0.5;
+            ctx.stroke(
+                Line::new(
+                    (x_coord, middle - high.clamp(-1.0, 1.0) as f64 * half_height),
+                    (x_coord, middle - low.clamp(-1.0, 1.0) as f64 * half_height),
+                ),
+                color,
+                1.0,
+            );
+        }
+    }
+
+    fn paint_spectrum(ctx: &mut PaintCtx, levels: &[f32], bin_hz: f32, area: Rect, color: &Color) {
+        let nyquist = bin_hz * levels.len() as f32;
+        if levels.is_empty() || nyquist <= MIN_HZ {
+            return;
+        }
+        let mut shape = BezPath::new();
+        for (bin, level) in levels.iter().enumerate() {
+            let hz = bin as f32 * bin_hz;
+            if hz < MIN_HZ {
+                continue;
+            }
+            let x_coord = ((hz / MIN_HZ).ln() / (nyquist / MIN_HZ).ln()) as f64;
+            let y_coord = (level / FLOOR_DB).clamp(0.0, 1.0) as f64;
+            let point = Point::new(
+                area.x0 + x_coord * area.width(),
+                area.y0 + y_coord * area.height(),
+            );
+            if shape.elements().is_empty() {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        ctx.stroke(shape, color, 1.0);
+    }
+}
+
+impl Widget<DenoiseData> for DenoisePreview {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut DenoiseData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_DENOISE) {
+                *data = cmd.get_unchecked(DRAW_DENOISE).clone();
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &DenoiseData, _: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _: &DenoiseData, _: &DenoiseData, _: &Env) {
+        ctx.request_paint()
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &DenoiseData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DenoiseData, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::grey(0.1));
+        let wave_area = Rect::new(0.0, 0.0, size.width, size.height / 2.0);
+        let spectrum_area = Rect::new(0.0, size.height / 2.0, size.width, size.height);
+        ctx.stroke(
+            Line::new((0.0, wave_area.y1), (size.width, wave_area.y1)),
+            &Color::grey(0.3),
+            1.0,
+        );
+
+        let raw = Color::grey(0.5);
+        let denoised = Color::rgb8(0, 200, 120);
+        Self::paint_wave(ctx, &data.raw, wave_area, &raw);
+        Self::paint_wave(ctx, &data.denoised, wave_area, &denoised);
+
+        Self::paint_spectrum(ctx, &data.raw_db, data.bin_hz, spectrum_area, &raw);
+        Self::paint_spectrum(
+            ctx,
+            &data.noise_db,
+            data.bin_hz,
+            spectrum_area,
+            &Color::rgb8(255, 60, 60),
+        );
+        Self::paint_spectrum(
+            ctx,
+            &data.denoised_db,
+            data.bin_hz,
+            spectrum_area,
+            &denoised,
+        );
+    }
+}
@@ -0,0 +1,329 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! What the input device supports, probed without opening it, and where the stream asked of it
+//! falls outside that. The standalone host asks for a fixed rate and buffer size whatever the
+//! device supports, and drivers quietly adapt, so those mismatches get a warning.
+
+use std::fmt;
+use std::sync::Arc;
+
+use cpal::traits::DeviceTrait;
+use cpal::{Device, SupportedBufferSize, SupportedStreamConfigRange};
+use druid::widget::{CrossAxisAlignment, Flex, Label, LineBreaking};
+use druid::{Color, Data, Env, LocalizedString, Widget};
+
+use crate::i18n::{localize, localize_with};
+
+pub const WARNING_COLOR: Color = Color::rgb8(255, 160, 0);
+
+/// Rates listed when a device supports a range of them, besides the range's ends
+const COMMON_RATES: [u32; 7] = [22050, 32000, 44100, 48000, 88200, 96000, 192000];
+
+/// The configuration the input is opened with
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct StreamRequest {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// `None` leaves it to the host
+    pub buffer_size: Option<u32>,
+}
+
+#[derive(Clone, Data, Default, Debug)]
+pub struct DeviceFormats {
+    /// Empty when there is no input device
+    pub name: String,
+    pub sample_rates: Arc<Vec<u32>>,
+    pub channels: Arc<Vec<u16>>,
+    /// Smallest and largest callback, `None` when the host doesn't say
+    pub buffer_sizes: Option<(u32, u32)>,
+    pub sample_formats: Arc<Vec<String>>,
+    pub request: Option<StreamRequest>,
+    /// Where the request falls outside what the device supports
+    pub warnings: Arc<Vec<FormatWarning>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FormatWarning {
+    SampleRate(u32),
+    Channels {
+        requested: u16,
+        offered: String,
+    },
+    BufferSize {
+        requested: u32,
+        min: u32,
+        max: u32,
+    },
+    /// Callbacks bring another number of frames than was asked for
+    Delivered {
+        delivered: usize,
+        requested: usize,
+    },
+}
+
+impl FormatWarning {
+    pub fn message(&self, env: &Env) -> String {
+        match self {
+            FormatWarning::SampleRate(rate) => {
+                localize_with("format-rate-warning", &[("rate", rate.to_string())], env)
+            }
+            FormatWarning::Channels { requested, offered } => localize_with(
+                "format-channels-warning",
+                &[
+                    ("requested", requested.to_string()),
+                    ("offered", offered.clone()),
+                ],
+                env,
+            ),
+            FormatWarning::BufferSize {
+                requested,
+                min,
+                max,
+            } => localize_with(
+                "format-buffer-warning",
+                &[
+                    ("requested", requested.to_string()),
+                    ("min", min.to_string()),
+                    ("max", max.to_string()),
+                ],
+                env,
+            ),
+            FormatWarning::Delivered {
+                delivered,
+                requested,
+            } => localize_with(
+                "format-delivered-warning",
+                &[
+                    ("delivered", delivered.to_string()),
+                    ("requested", requested.to_string()),
+                ],
+                env,
+            ),
+        }
+    }
+}
+
+/// English, for the log
+impl fmt::Display for FormatWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatWarning::SampleRate(rate) => write!(
+                f,
+                "{} Hz isn't supported, the driver may resample or run at another rate",
+                rate
+            ),
+            FormatWarning::Channels { requested, offered } => write!(
+                f,
+                "{} channels asked for, the device offers {}",
+                requested, offered
+            ),
+            FormatWarning::BufferSize {
+                requested,
+                min,
+                max,
+            } => write!(
+                f,
+                "{} frame buffers asked for, the device takes {} to {}",
+                requested, min, max
+            ),
+            FormatWarning::Delivered {
+                delivered,
+                requested,
+            } => write!(
+                f,
+                "Callbacks bring {} frames, {} were asked for",
+                delivered, requested
+            ),
+        }
+    }
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(T::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl DeviceFormats {
+    /// The device's supported input configurations, checked against `request`
+    pub fn probe(device: &Device, request: StreamRequest) -> Self {
+        let ranges: Vec<SupportedStreamConfigRange> = device
+            .supported_input_configs()
+            .map(|configs| configs.collect())
+            .unwrap_or_default();
+        let rate_ranges: Vec<(u32, u32)> = ranges
+            .iter()
+            .map(|range| (range.min_sample_rate().0, range.max_sample_rate().0))
+            .collect();
+        let mut sample_rates: Vec<u32> = rate_ranges
+            .iter()
+            .flat_map(|&(min, max)| {
+                COMMON_RATES
+                    .iter()
+                    .copied()
+                    .filter(move |rate| (min..=max).contains(rate))
+                    .chain(vec![min, max])
+            })
+            .collect();
+        sample_rates.sort_unstable();
+        sample_rates.dedup();
+        let mut channels: Vec<u16> = ranges.iter().map(|range| range.channels()).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        let buffer_sizes = ranges
+            .iter()
+            .filter_map(|range| match *range.buffer_size() {
+                SupportedBufferSize::Range { min, max } => Some((min, max)),
+                SupportedBufferSize::Unknown => None,
+            })
+            .reduce(|(min, max), (range_min, range_max)| (min.min(range_min), max.max(range_max)));
+        let mut sample_formats: Vec<String> = ranges
+            .iter()
+            .map(|range| format!("{:?}", range.sample_format()).to_lowercase())
+            .collect();
+        sample_formats.sort();
+        sample_formats.dedup();
+
+        let mut warnings = Vec::new();
+        if !rate_ranges
+            .iter()
+            .any(|&(min, max)| (min..=max).contains(&request.sample_rate))
+        {
+            warnings.push(FormatWarning::SampleRate(request.sample_rate));
+        }
+        if !channels.contains(&request.channels) {
+            warnings.push(FormatWarning::Channels {
+                requested: request.channels,
+                offered: join(&channels),
+            });
+        }
+        if let (Some(size), Some((min, max))) = (request.buffer_size, buffer_sizes) {
+            if !(min..=max).contains(&size) {
+                warnings.push(FormatWarning::BufferSize {
+                    requested: size,
+                    min,
+                    max,
+                });
+            }
+        }
+        let name = device.name().unwrap_or_default();
+        for warning in &warnings {
+            log::warn!("{}: {}", name, warning);
+        }
+        DeviceFormats {
+            name,
+            sample_rates: Arc::new(sample_rates),
+            channels: Arc::new(channels),
+            buffer_sizes,
+            sample_formats: Arc::new(sample_formats),
+            request: Some(request),
+            warnings: Arc::new(warnings),
+        }
+    }
+
+    /// When callbacks bring another number of frames than was asked for
+    pub fn buffer_warning(&self, delivered: usize) -> Option<FormatWarning> {
+        let requested = self.request?.buffer_size? as usize;
+        (delivered != 0 && delivered != requested).then_some(FormatWarning::Delivered {
+            delivered,
+            requested,
+        })
+    }
+}
+
+fn line(text: impl Fn(&DeviceFormats, &Env) -> String + 'static) -> Label<DeviceFormats> {
+    Label::dynamic(text).with_line_break_mode(LineBreaking::WordWrap)
+}
+
+/// The device's supported formats, what was asked of it and how that falls outside them
+pub fn device_formats_panel() -> impl Widget<DeviceFormats> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(line(|data, env| {
+            if data.name.is_empty() {
+                localize(LocalizedString::new("no-input-device"), env)
+            } else {
+                data.name.clone()
+            }
+        }))
+        .with_child(line(|data, env| {
+            localize_with("format-rates", &[("rates", join(&data.sample_rates))], env)
+        }))
+        .with_child(line(|data, env| {
+            localize_with(
+                "format-channels",
+                &[("channels", join(&data.channels))],
+                env,
+            )
+        }))
+        .with_child(line(|data, env| {
+            let (min, max) = data.buffer_sizes.unwrap_or_default();
+            localize_with(
+                "format-buffers",
+                &[
+                    ("reported", data.buffer_sizes.is_some().to_string()),
+                    ("min", min.to_string()),
+                    ("max", max.to_string()),
+                ],
+                env,
+            )
+        }))
+        .with_child(line(|data, env| {
+            localize_with(
+                "format-formats",
+                &[("formats", join(&data.sample_formats))],
+                env,
+            )
+        }))
+        .with_child(line(|data, env| {
+            data.request.map_or("--".to_string(), |request| {
+                let buffer = match request.buffer_size {
+                    Some(size) => {
+                        localize_with("frame-count", &[("frames", size.to_string())], env)
+                    }
+                    None => localize(LocalizedString::new("host-buffer"), env),
+                };
+                localize_with(
+                    "format-opened",
+                    &[
+                        ("rate", request.sample_rate.to_string()),
+                        ("channels", request.channels.to_string()),
+                        ("buffer", buffer),
+                    ],
+                    env,
+                )
+            })
+        }))
+        .with_child(
+            line(|data, env| {
+                data.warnings
+                    .iter()
+                    .map(|warning| warning.message(env))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .with_text_color(WARNING_COLOR),
+        )
+}
@@ -0,0 +1,107 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Display AGC: a gain for the waveform and the bar meter that slowly follows the signal level,
+//! so quiet sources fill the view without touching the input gain. Only what's drawn changes,
+//! every reading stays in real dBFS.
+
+use druid::{Data, Key, LocalizedString, Selector};
+
+/// The gain in dB, sent with every meter update
+pub const DRAW_DISPLAY_GAIN: Selector<f32> = Selector::new("event-example.draw_display_gain");
+/// dB the bar meter is shifted up by, 0 unless display AGC is on
+pub const METER_DISPLAY_GAIN: Key<f64> = Key::new("event-example.meter_display_gain");
+
+/// Level the envelope is brought up to
+const TARGET_DB: f32 = -6.0;
+/// Most gain applied, so silence isn't blown up to noise
+const MAX_GAIN_DB: f32 = 30.0;
+/// How fast the gain comes down for a louder signal
+const ATTACK_SECONDS: f32 = 0.3;
+/// How slowly it goes back up once the signal gets quieter
+const RELEASE_SECONDS: f32 = 4.0;
+
+/// How the waveform's amplitude is scaled
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub enum DisplayScaling {
+    /// The gain slider
+    Manual,
+    /// Fit the peak in view, see [`crate::audio_wave::WAVE_AUTO_SCALE`]
+    FitPeak,
+    /// Follow the level slowly, the bar meter too
+    Agc,
+}
+
+impl DisplayScaling {
+    pub const ALL: [DisplayScaling; 3] = [
+        DisplayScaling::Manual,
+        DisplayScaling::FitPeak,
+        DisplayScaling::Agc,
+    ];
+
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            DisplayScaling::Manual => "scaling-manual",
+            DisplayScaling::FitPeak => "scaling-fit-peak",
+            DisplayScaling::Agc => "scaling-agc",
+        })
+    }
+}
+
+/// Peak envelope and the gain bringing it to [`TARGET_DB`], in the analysis thread
+pub struct DisplayAgc {
+    attack: f32,
+    release: f32,
+    envelope: f32,
+    gain_db: f32,
+}
+
+impl DisplayAgc {
+    pub fn new(sample_rate: f32) -> Self {
+        DisplayAgc {
+            attack: (-1.0 / (ATTACK_SECONDS * sample_rate)).exp(),
+            release: (-1.0 / (RELEASE_SECONDS * sample_rate)).exp(),
+            envelope: 0.0,
+            gain_db: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        let level = sample.abs();
+        let coefficient = if level > self.envelope {
+            self.attack
+        } else {
+            self.release
+        };
+        self.envelope = level + coefficient * (self.envelope - level);
+    }
+
+    /// The gain in whole dB, only moving once it's a dB off so panes don't redraw constantly
+    pub fn gain_db(&mut self) -> f32 {
+        let target =
+            (TARGET_DB - 20.0 * self.envelope.max(f32::EPSILON).log10()).clamp(0.0, MAX_GAIN_DB);
+        if (target - self.gain_db).abs() >= 1.0 {
+            self.gain_db = target.round();
+        }
+        self.gain_db
+    }
+}
@@ -0,0 +1,119 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Dropout detection for monitoring a broadcast chain: gaps of digital silence cut into a
+//! signal, and runs of a held sample value from a stalled buffer. Both are reported as events in
+//! the clip log.
+//!
+//! Repeated blocks, where a whole buffer is played twice, aren't detected.
+
+use crate::clip_log::{ClipEvent, ClipKind};
+
+/// Samples under this are taken as digital silence
+const SILENCE_LEVEL: f32 = 1e-6;
+/// Shortest gap reported, shorter ones are usually zero crossings of low-level material
+const MIN_GAP_MS: f32 = 1.0;
+/// Longer gaps are the programme going quiet rather than a dropout
+const MAX_GAP_MS: f32 = 500.0;
+/// The signal has to be at least this loud going into a gap
+const SIGNAL_DB: f32 = -50.0;
+/// Time constant of the level measured going into a gap
+const LEVEL_MS: f32 = 20.0;
+/// Held values are only looked for above this, quiet material repeats samples on its own
+const HELD_LEVEL: f32 = 0.01;
+/// Full-scale runs are clipping, which the clip detector reports
+const CLIP_LEVEL: f32 = 0.999;
+/// Shortest run of an identical sample value reported
+const MIN_HELD_SAMPLES: u64 = 64;
+
+pub struct DropoutDetector {
+    sample_rate: f32,
+    min_gap: u64,
+    max_gap: u64,
+    signal_threshold: f32,
+    coefficient: f32,
+    mean_square: f32,
+    position: u64,
+    /// Start of the current silent run and the mean square going into it
+    gap: Option<(u64, f32)>,
+    held_value: f32,
+    held_start: u64,
+}
+
+impl DropoutDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        DropoutDetector {
+            sample_rate,
+            min_gap: (sample_rate * MIN_GAP_MS / 1000.0) as u64,
+            max_gap: (sample_rate * MAX_GAP_MS / 1000.0) as u64,
+            signal_threshold: 10.0_f32.powf(SIGNAL_DB / 10.0),
+            coefficient: (-1000.0 / (LEVEL_MS * sample_rate)).exp(),
+            mean_square: 0.0,
+            position: 0,
+            gap: None,
+            held_value: 0.0,
+            held_start: 0,
+        }
+    }
+
+    /// Push a sample, returning a dropout once the signal has come back after it
+    pub fn process(&mut self, sample: f32) -> Option<ClipEvent> {
+        let position = self.position;
+        self.position += 1;
+
+        let mut event = None;
+        if sample.abs() < SILENCE_LEVEL {
+            self.gap.get_or_insert((position, self.mean_square));
+        } else if let Some((start, mean_square)) = self.gap.take() {
+            let len = position - start;
+            if (self.min_gap..=self.max_gap).contains(&len) && mean_square >= self.signal_threshold
+            {
+                event = Some(self.event(ClipKind::Dropout, start, len, mean_square));
+            }
+        }
+
+        if sample != self.held_value {
+            let len = position - self.held_start;
+            let level = self.held_value.abs();
+            if len >= MIN_HELD_SAMPLES && (HELD_LEVEL..CLIP_LEVEL).contains(&level) {
+                event =
+                    Some(self.event(ClipKind::HeldSamples, self.held_start, len, level * level));
+            }
+            self.held_value = sample;
+            self.held_start = position;
+        }
+
+        self.mean_square =
+            self.coefficient * self.mean_square + (1.0 - self.coefficient) * sample * sample;
+        event
+    }
+
+    fn event(&self, kind: ClipKind, start: u64, len: u64, mean_square: f32) -> ClipEvent {
+        ClipEvent {
+            kind,
+            channel: 0,
+            time: start as f32 / self.sample_rate,
+            level_db: 10.0 * mean_square.log10(),
+            duration_ms: len as f32 / self.sample_rate * 1000.0,
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Envelope follower: a peak or RMS detector with separate attack and release ballistics, drawn
+//! over the rectified signal it follows so the effect of each setting can be seen. The smoothing
+//! is `audio-processor-analysis`'s peak detector, fed the squared signal for RMS.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use audio_processor_analysis::peak_detector::{calculate_multiplier, PeakDetector};
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::{Color, Data, Lens, LocalizedString, Point, Selector};
+use serde::{Deserialize, Serialize};
+
+use crate::units::AmplitudeScale;
+
+pub const DRAW_ENVELOPE: Selector<EnvelopeData> = Selector::new("event-example.draw_envelope");
+
+/// Attack and release times offered in the settings
+pub const ATTACK_TIMES_MS: [f64; 4] = [1.0, 10.0, 50.0, 200.0];
+pub const RELEASE_TIMES_MS: [f64; 4] = [50.0, 200.0, 500.0, 2000.0];
+
+const COLUMN_MS: f32 = 10.0;
+/// Columns the view keeps, five seconds worth
+const HISTORY_COLUMNS: usize = 500;
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Detector {
+    /// Follows the rectified signal
+    Peak,
+    /// Follows the mean square, so the envelope tracks power rather than peaks
+    Rms,
+}
+
+impl Detector {
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            Detector::Peak => "detector-peak",
+            Detector::Rms => "detector-rms",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Data, Lens, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EnvelopeSettings {
+    pub detector: Detector,
+    pub attack_ms: f64,
+    pub release_ms: f64,
+}
+
+impl Default for EnvelopeSettings {
+    fn default() -> Self {
+        EnvelopeSettings {
+            detector: Detector::Peak,
+            attack_ms: ATTACK_TIMES_MS[1],
+            release_ms: RELEASE_TIMES_MS[1],
+        }
+    }
+}
+
+/// What the view shows, one value per column with the newest last
+#[derive(Clone, Data, Default)]
+pub struct EnvelopeData {
+    /// Peak of the rectified signal
+    pub signal: Arc<Vec<f32>>,
+    /// Highest envelope value
+    pub envelope: Arc<Vec<f32>>,
+}
+
+pub struct EnvelopeFollower {
+    sample_rate: f32,
+    detector: Detector,
+    attack: f32,
+    release: f32,
+    /// Follows the rectified sample, or the squared one for RMS
+    peak: PeakDetector,
+    column_len: usize,
+    column_position: usize,
+    column_signal: f32,
+    column_envelope: f32,
+    signal: VecDeque<f32>,
+    envelope: VecDeque<f32>,
+}
+
+impl EnvelopeFollower {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut follower = EnvelopeFollower {
+            sample_rate,
+            detector: Detector::Peak,
+            attack: 0.0,
+            release: 0.0,
+            peak: PeakDetector::default(),
+            column_len: (sample_rate * COLUMN_MS / 1000.0) as usize,
+            column_position: 0,
+            column_signal: 0.0,
+            column_envelope: 0.0,
+            signal: VecDeque::with_capacity(HISTORY_COLUMNS),
+            envelope: VecDeque::with_capacity(HISTORY_COLUMNS),
+        };
+        follower.set_settings(EnvelopeSettings::default());
+        follower
+    }
+
+    /// Takes effect from the next sample, the history drawn with the old settings is kept so
+    /// the change shows up side by side
+    pub fn set_settings(&mut self, settings: EnvelopeSettings) {
+        if settings.detector != self.detector {
+            self.peak = PeakDetector::default();
+        }
+        self.detector = settings.detector;
+        self.attack = calculate_multiplier(self.sample_rate, settings.attack_ms as f32);
+        self.release = calculate_multiplier(self.sample_rate, settings.release_ms as f32);
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        let input = match self.detector {
+            Detector::Peak => sample,
+            Detector::Rms => sample * sample,
+        };
+        self.peak.accept_frame(self.attack, self.release, &[input]);
+        let envelope = match self.detector {
+            Detector::Peak => self.peak.value(),
+            Detector::Rms => self.peak.value().sqrt(),
+        };
+
+        self.column_signal = self.column_signal.max(sample.abs());
+        self.column_envelope = self.column_envelope.max(envelope);
+        self.column_position += 1;
+        if self.column_position == self.column_len {
+            if self.signal.len() == HISTORY_COLUMNS {
+                self.signal.pop_front();
+                self.envelope.pop_front();
+            }
+            self.signal.push_back(self.column_signal);
+            self.envelope.push_back(self.column_envelope);
+            self.column_position = 0;
+            self.column_signal = 0.0;
+            self.column_envelope = 0.0;
+        }
+    }
+
+    pub fn data(&self) -> EnvelopeData {
+        EnvelopeData {
+            signal: Arc::new(self.signal.iter().copied().collect()),
+            envelope: Arc::new(self.envelope.iter().copied().collect()),
+        }
+    }
+}
+
+/// Scrolling plot of the envelope over the signal's peaks, in dB down to the display floor
+#[derive(Default)]
+pub struct EnvelopeView {}
+
+impl EnvelopeView {
+    fn y_coord(size: Size, amplitude: f32, floor_db: f64) -> f64 {
+        let db = (20.0 * (amplitude as f64).log10()).clamp(floor_db, 0.0);
+        db / floor_db * size.height
+    }
+
+    fn x_coord(size: Size, offset: usize, index: usize) -> f64 {
+        (offset + index) as f64 / HISTORY_COLUMNS as f64 * size.width
+    }
+}
+
+impl Widget<EnvelopeData> for EnvelopeView {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut EnvelopeData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_ENVELOPE) {
+                *data = cmd.get_unchecked(DRAW_ENVELOPE).clone();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &EnvelopeData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &EnvelopeData,
+        _data: &EnvelopeData,
+        _: &Env,
+    ) {
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &EnvelopeData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &EnvelopeData, env: &Env) {
+        let size = ctx.size();
+        let floor_db = AmplitudeScale::from_env(env).floor_db;
+
+        let grid = Color::grey(0.3);
+        let mut db = -10.0;
+        while db > floor_db {
+            let y_coord = db / floor_db * size.height;
+            ctx.stroke(Line::new((0.0, y_coord), (size.width, y_coord)), &grid, 1.0);
+            db -= 10.0;
+        }
+
+        // Newest column on the right edge
+        let offset = HISTORY_COLUMNS.saturating_sub(data.signal.len());
+        if !data.signal.is_empty() {
+            let mut signal = BezPath::new();
+            signal.move_to((Self::x_coord(size, offset, 0), size.height));
+            for (index, amplitude) in data.signal.iter().enumerate() {
+                let y_coord = Self::y_coord(size, *amplitude, floor_db);
+                signal.line_to((Self::x_coord(size, offset, index), y_coord));
+                signal.line_to((Self::x_coord(size, offset, index + 1), y_coord));
+            }
+            signal.line_to((Self::x_coord(size, offset, data.signal.len()), size.height));
+            signal.close_path();
+            ctx.fill(signal, &Color::grey(0.45));
+        }
+
+        let mut envelope = BezPath::new();
+        for (index, amplitude) in data.envelope.iter().enumerate() {
+            let point = Point::new(
+                Self::x_coord(size, offset, index),
+                Self::y_coord(size, *amplitude, floor_db),
+            );
+            if index == 0 {
+                envelope.move_to(point);
+            } else {
+                envelope.line_to(point);
+            }
+        }
+        ctx.stroke(envelope, &Color::rgb8(255, 200, 0), 2.0);
+    }
+}
@@ -0,0 +1,281 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Feedback detection for live sound: narrowband spectral peaks that keep sustaining and growing
+//! are flagged with their note name and a suggested notch filter.
+
+use std::sync::Arc;
+
+use druid::widget::prelude::*;
+use druid::widget::{Controller, Label, List};
+use druid::{Data, Selector, WidgetExt};
+
+use crate::spectrogram::{Spectrogram, SpectrogramOptions};
+use crate::units::AmplitudeScale;
+
+pub const DRAW_FEEDBACK: Selector<Arc<Vec<FeedbackCandidate>>> =
+    Selector::new("event-example.draw_feedback");
+
+const FFT_SIZE: usize = 4096;
+const OVERLAP_RATIO: f32 = 0.5;
+const MIN_HZ: f32 = 60.0;
+const MAX_HZ: f32 = 16_000.0;
+/// Peaks quieter than this are ignored
+const MIN_LEVEL_DB: f32 = -60.0;
+/// How far a peak has to stand above the bins around it to be narrowband
+const MIN_PROMINENCE_DB: f32 = 15.0;
+/// How long a peak has to last before it can be flagged
+const SUSTAIN_SECONDS: f32 = 1.0;
+/// How much a sustained peak has to grow from its quietest level to be flagged
+const MIN_GROWTH_DB: f32 = 3.0;
+/// Columns a peak may be missing for before its track is dropped
+const MAX_MISSED_COLUMNS: usize = 3;
+/// Suggested notch width, about a seventh of an octave
+const NOTCH_Q: f32 = 10.0;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Note name and cents offset of a frequency, e.g. `A4 +12c`
+pub fn note_name(frequency_hz: f32) -> String {
+    let midi = 69.0 + 12.0 * (frequency_hz / 440.0).log2();
+    let nearest = midi.round();
+    let cents = ((midi - nearest) * 100.0).round() as i32;
+    let note = nearest as i32;
+    format!(
+        "{}{} {:+}c",
+        NOTE_NAMES[note.rem_euclid(12) as usize],
+        note.div_euclid(12) - 1,
+        cents
+    )
+}
+
+#[derive(Clone, Data, Debug)]
+pub struct FeedbackCandidate {
+    pub frequency_hz: f32,
+    pub level_db: f32,
+    pub notch_q: f32,
+    pub notch_gain_db: f32,
+}
+
+impl FeedbackCandidate {
+    pub fn label(&self, scale: &AmplitudeScale) -> String {
+        format!(
+            "{:.0} Hz ({})  {}  notch Q {:.0} {:.0} dB",
+            self.frequency_hz,
+            note_name(self.frequency_hz),
+            scale.format_db(self.level_db),
+            self.notch_q,
+            self.notch_gain_db
+        )
+    }
+}
+
+struct PeakTrack {
+    bin: usize,
+    columns: usize,
+    missed: usize,
+    min_level_db: f32,
+    level_db: f32,
+    prominence_db: f32,
+    frequency_hz: f32,
+}
+
+pub struct FeedbackDetector {
+    spectrogram: Spectrogram,
+    bin_hz: f32,
+    sustain_columns: usize,
+    tracks: Vec<PeakTrack>,
+    /// Flagged frequencies, kept until [`FeedbackDetector::clear`]
+    candidates: Vec<FeedbackCandidate>,
+}
+
+impl FeedbackDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        let hop = FFT_SIZE as f32 * (1.0 - OVERLAP_RATIO);
+        FeedbackDetector {
+            spectrogram: Spectrogram::new(SpectrogramOptions {
+                fft_size: FFT_SIZE,
+                overlap_ratio: OVERLAP_RATIO,
+                ..SpectrogramOptions::default()
+            }),
+            bin_hz: sample_rate / FFT_SIZE as f32,
+            sustain_columns: (SUSTAIN_SECONDS * sample_rate / hop) as usize,
+            tracks: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Push a sample, returning true when the list of candidates changed
+    pub fn process(&mut self, sample: f32) -> bool {
+        let column = match self.spectrogram.process(sample) {
+            Some(column) => column.to_vec(),
+            None => return false,
+        };
+        let peaks = self.find_peaks(&column);
+
+        for track in &mut self.tracks {
+            track.missed += 1;
+        }
+        for (bin, level_db, prominence_db, frequency_hz) in peaks {
+            // Let a peak drift by a bin between columns
+            match self
+                .tracks
+                .iter_mut()
+                .find(|track| track.bin.abs_diff(bin) <= 1)
+            {
+                Some(track) => {
+                    track.bin = bin;
+                    track.columns += 1;
+                    track.missed = 0;
+                    // The first columns of a new sound are only partly filled
+                    if track.columns > 2 {
+                        track.min_level_db = track.min_level_db.min(level_db);
+                    }
+                    track.level_db = level_db;
+                    track.prominence_db = prominence_db;
+                    track.frequency_hz = frequency_hz;
+                }
+                None => self.tracks.push(PeakTrack {
+                    bin,
+                    columns: 1,
+                    missed: 0,
+                    min_level_db: f32::INFINITY,
+                    level_db,
+                    prominence_db,
+                    frequency_hz,
+                }),
+            }
+        }
+        self.tracks
+            .retain(|track| track.missed <= MAX_MISSED_COLUMNS);
+
+        let mut changed = false;
+        let bin_hz = self.bin_hz;
+        for track in &self.tracks {
+            let growing = track.level_db - track.min_level_db >= MIN_GROWTH_DB;
+            if track.columns < self.sustain_columns || !growing {
+                continue;
+            }
+            let candidate = FeedbackCandidate {
+                frequency_hz: track.frequency_hz,
+                level_db: track.level_db,
+                notch_q: NOTCH_Q,
+                notch_gain_db: -(track.prominence_db - 3.0).clamp(3.0, 18.0),
+            };
+            let same_frequency = |existing: &&mut FeedbackCandidate| {
+                (existing.frequency_hz - candidate.frequency_hz).abs() <= bin_hz * 1.5
+            };
+            match self.candidates.iter_mut().find(same_frequency) {
+                Some(existing) if existing.level_db >= candidate.level_db => {}
+                Some(existing) => {
+                    *existing = candidate;
+                    changed = true;
+                }
+                None => {
+                    self.candidates.push(candidate);
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    /// Narrowband peaks of a column as (bin, level, prominence, interpolated frequency)
+    fn find_peaks(&self, column: &[f32]) -> Vec<(usize, f32, f32, f32)> {
+        let first = ((MIN_HZ / self.bin_hz) as usize).max(6);
+        let last = ((MAX_HZ / self.bin_hz) as usize).min(column.len().saturating_sub(7));
+        (first..last)
+            .filter_map(|bin| {
+                let level = column[bin];
+                let is_peak = level > MIN_LEVEL_DB
+                    && level > column[bin - 1]
+                    && level > column[bin + 1]
+                    && level >= column[bin - 2]
+                    && level >= column[bin + 2];
+                if !is_peak {
+                    return None;
+                }
+                let surroundings = column[bin - 6..bin - 2]
+                    .iter()
+                    .chain(&column[bin + 3..bin + 7])
+                    .sum::<f32>()
+                    / 8.0;
+                let prominence = level - surroundings;
+                if prominence < MIN_PROMINENCE_DB {
+                    return None;
+                }
+                // Parabolic interpolation between the neighbouring bins
+                let (left, right) = (column[bin - 1], column[bin + 1]);
+                let denominator = left - 2.0 * level + right;
+                let offset = if denominator != 0.0 {
+                    0.5 * (left - right) / denominator
+                } else {
+                    0.0
+                };
+                Some((bin, level, prominence, (bin as f32 + offset) * self.bin_hz))
+            })
+            .collect()
+    }
+
+    pub fn candidates(&self) -> &[FeedbackCandidate] {
+        &self.candidates
+    }
+
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+    }
+}
+
+/// List of flagged feedback frequencies
+pub fn feedback_list() -> impl Widget<Arc<Vec<FeedbackCandidate>>> {
+    List::new(|| {
+        Label::dynamic(|candidate: &FeedbackCandidate, env| {
+            candidate.label(&AmplitudeScale::from_env(env))
+        })
+        .align_left()
+    })
+    .controller(FeedbackController)
+}
+
+struct FeedbackController;
+
+impl<W: Widget<Arc<Vec<FeedbackCandidate>>>> Controller<Arc<Vec<FeedbackCandidate>>, W>
+    for FeedbackController
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut Arc<Vec<FeedbackCandidate>>,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_FEEDBACK) => {
+                *data = cmd.get_unchecked(DRAW_FEEDBACK).clone();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
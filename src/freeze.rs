@@ -0,0 +1,85 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Per-pane freeze. A pane wrapped in a [`FreezeController`] ignores the draw command that updates
+//! it while its flag is set, holding its last frame while the other panes keep running. The
+//! waveform has its own flag, [`WaveData::frozen`](crate::audio_wave::WaveData), since it pulls
+//! snapshots rather than taking commands.
+
+use std::marker::PhantomData;
+
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use druid::{Data, Lens, Selector};
+
+#[derive(Clone, Copy, Data, Lens, Default, PartialEq, Debug)]
+pub struct FrozenPanes {
+    pub loudness: bool,
+    pub lag_views: bool,
+    pub phase: bool,
+    pub spectrum: bool,
+    pub envelope: bool,
+}
+
+impl FrozenPanes {
+    pub fn all(&self) -> bool {
+        self.loudness && self.lag_views && self.phase && self.spectrum && self.envelope
+    }
+
+    pub fn set_all(&mut self, frozen: bool) {
+        *self = FrozenPanes {
+            loudness: frozen,
+            lag_views: frozen,
+            phase: frozen,
+            spectrum: frozen,
+            envelope: frozen,
+        };
+    }
+}
+
+pub struct FreezeController<T, P> {
+    selector: Selector<P>,
+    is_frozen: fn(&T) -> bool,
+    _data: PhantomData<T>,
+}
+
+impl<T, P> FreezeController<T, P> {
+    /// Drop `selector` while `is_frozen` says so
+    pub fn new(selector: Selector<P>, is_frozen: fn(&T) -> bool) -> Self {
+        FreezeController {
+            selector,
+            is_frozen,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<T: Data, P: 'static, W: Widget<T>> Controller<T, W> for FreezeController<T, P> {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(self.selector) && (self.is_frozen)(data) {
+                return;
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+}
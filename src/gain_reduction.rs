@@ -0,0 +1,183 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Gain reduction of the dynamics processing in the monitor chain, plotted under the waveform on
+//! its time axis, so each dip lines up with the peak that caused it. The talkback limiter is the
+//! only one so far: the audio thread reports the deepest reduction of every callback it runs in,
+//! and the strip only shows while there are readings in view.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use druid::kurbo::{Line, Rect};
+use druid::piet::{Text, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Data, FontFamily, Point, Selector};
+
+use crate::i18n::localize_with;
+
+pub const DRAW_GAIN_REDUCTION: Selector<GainReductionData> =
+    Selector::new("event-example.draw_gain_reduction");
+
+/// Callback readings the queue holds, a few seconds' worth at small buffer sizes
+pub const GAIN_REDUCTION_QUEUE_LEN: usize = 1024;
+
+/// Reduction at the bottom of the strip
+const RANGE_DB: f32 = 12.0;
+/// Grid lines every this many dB
+const GRID_DB: f32 = 3.0;
+
+/// The deepest reduction over one callback, negative dB
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct GainReductionReading {
+    /// Stream position of the callback's first frame
+    pub position: u64,
+    pub frames: u64,
+    pub reduction_db: f32,
+}
+
+#[derive(Clone, Data, Default)]
+pub struct GainReductionData {
+    pub readings: Arc<Vec<GainReductionReading>>,
+    /// The stream positions the strip spans, the live waveform's
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Readings of the last `len` samples
+pub struct GainReductionHistory {
+    len: u64,
+    readings: VecDeque<GainReductionReading>,
+}
+
+impl GainReductionHistory {
+    pub fn new(len: usize) -> Self {
+        GainReductionHistory {
+            len: len as u64,
+            readings: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, reading: GainReductionReading) {
+        self.readings.push_back(reading);
+    }
+
+    /// The readings up to `end`, dropping older ones
+    pub fn data(&mut self, end: u64) -> GainReductionData {
+        let start = end.saturating_sub(self.len);
+        while self
+            .readings
+            .front()
+            .is_some_and(|reading| reading.position < start)
+        {
+            self.readings.pop_front();
+        }
+        GainReductionData {
+            readings: Arc::new(self.readings.iter().copied().collect()),
+            start: start as f64,
+            end: end as f64,
+        }
+    }
+}
+
+/// Reduction hanging down from the top, 0 dB at the top edge
+#[derive(Default)]
+pub struct GainReductionStrip;
+
+impl GainReductionStrip {
+    fn x_coord(size: Size, data: &GainReductionData, position: u64) -> f64 {
+        let span = (data.end - data.start).max(1.0);
+        ((position as f64 - data.start) / span * size.width).clamp(0.0, size.width)
+    }
+
+    fn y_coord(size: Size, reduction_db: f32) -> f64 {
+        (-reduction_db / RANGE_DB).clamp(0.0, 1.0) as f64 * size.height
+    }
+}
+
+impl Widget<GainReductionData> for GainReductionStrip {
+    fn event(&mut self, _: &mut EventCtx, _: &Event, _: &mut GainReductionData, _: &Env) {}
+
+    fn lifecycle(&mut self, _: &mut LifeCycleCtx, _: &LifeCycle, _: &GainReductionData, _: &Env) {}
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &GainReductionData,
+        data: &GainReductionData,
+        _: &Env,
+    ) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &GainReductionData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &GainReductionData, env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::grey(0.1));
+        let mut grid_db = GRID_DB;
+        while grid_db < RANGE_DB {
+            let y_coord = Self::y_coord(size, -grid_db);
+            ctx.stroke(
+                Line::new((0.0, y_coord), (size.width, y_coord)),
+                &Color::grey(0.25),
+                1.0,
+            );
+            grid_db += GRID_DB;
+        }
+
+        // A bar per callback, at least a pixel wide when zoomed out
+        let color = Color::rgb8(255, 90, 90).with_alpha(0.7);
+        for reading in data.readings.iter() {
+            let left = Self::x_coord(size, data, reading.position);
+            let right = Self::x_coord(size, data, reading.position + reading.frames);
+            let depth = Self::y_coord(size, reading.reduction_db);
+            if depth > 0.0 {
+                ctx.fill(Rect::new(left, 0.0, right.max(left + 1.0), depth), &color);
+            }
+        }
+
+        let layout = ctx
+            .text()
+            .new_text_layout(localize_with(
+                "gain-reduction-scale",
+                &[("range", format!("{:.0}", RANGE_DB))],
+                env,
+            ))
+            .font(FontFamily::SYSTEM_UI, 10.0)
+            .text_color(Color::grey(0.7))
+            .build();
+        if let Ok(layout) = layout {
+            ctx.draw_text(&layout, Point::new(4.0, size.height - 14.0));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Gain staging assistant: watches the input's peaks and RMS for a few seconds while the user
+//! talks or plays at performance level, then suggests how far to move the input gain so peaks
+//! land at the chosen headroom below full scale.
+
+use druid::widget::prelude::*;
+use druid::widget::{Controller, Label};
+use druid::{Data, LocalizedString, Selector, WidgetExt};
+
+use crate::vad::SILENCE_DB;
+
+pub const DRAW_GAIN_STAGING: Selector<GainStatus> =
+    Selector::new("event-example.draw_gain_staging");
+
+/// How long a check listens for
+const CHECK_SECONDS: f32 = 10.0;
+/// Peaks this close to the target need no change
+const TOLERANCE_DB: f32 = 1.0;
+/// Reported instead of minus infinity for digital silence
+const FLOOR_DB: f32 = -120.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct GainAdvice {
+    /// True peak over the check, dBFS
+    pub peak_db: f32,
+    pub rms_db: f32,
+    pub headroom_db: f32,
+}
+
+impl GainAdvice {
+    /// Gain change that puts the peaks at the target headroom
+    pub fn adjustment_db(&self) -> f32 {
+        -self.headroom_db - self.peak_db
+    }
+
+    /// What the suggestion says to do, a `gain-status` state
+    fn advice(&self) -> &'static str {
+        let adjustment = self.adjustment_db();
+        if self.rms_db < SILENCE_DB {
+            "no-signal"
+        } else if adjustment.abs() < TOLERANCE_DB {
+            "good"
+        } else if adjustment > 0.0 {
+            "raise"
+        } else {
+            "lower"
+        }
+    }
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug, Default)]
+pub enum GainStatus {
+    #[default]
+    Idle,
+    /// Share of the check done
+    Measuring(f32),
+    Done(GainAdvice),
+}
+
+impl GainStatus {
+    fn state(&self) -> &'static str {
+        match self {
+            GainStatus::Idle => "idle",
+            GainStatus::Measuring(_) => "measuring",
+            GainStatus::Done(advice) => advice.advice(),
+        }
+    }
+}
+
+/// One calibration period, fed every sample with its true peak
+pub struct GainCheck {
+    headroom_db: f32,
+    len: usize,
+    measured: usize,
+    peak: f32,
+    sum_squares: f64,
+}
+
+impl GainCheck {
+    pub fn new(sample_rate: f32, headroom_db: f32) -> Self {
+        GainCheck {
+            headroom_db,
+            len: (sample_rate * CHECK_SECONDS) as usize,
+            measured: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, sample: f32, true_peak: f32) {
+        if self.is_done() {
+            return;
+        }
+        self.peak = self.peak.max(true_peak);
+        self.sum_squares += (sample * sample) as f64;
+        self.measured += 1;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.measured >= self.len
+    }
+
+    pub fn status(&self) -> GainStatus {
+        if !self.is_done() {
+            return GainStatus::Measuring(self.measured as f32 / self.len as f32);
+        }
+        let mean_square = self.sum_squares / self.measured.max(1) as f64;
+        GainStatus::Done(GainAdvice {
+            peak_db: (20.0 * self.peak.log10()).max(FLOOR_DB),
+            rms_db: ((10.0 * mean_square.log10()) as f32).max(FLOOR_DB),
+            headroom_db: self.headroom_db,
+        })
+    }
+}
+
+/// The check's progress, then its suggestion
+pub fn gain_status_label() -> impl Widget<GainStatus> {
+    let advice = |status: &GainStatus| match status {
+        GainStatus::Done(advice) => Some(*advice),
+        _ => None,
+    };
+    let label = LocalizedString::new("gain-status")
+        .with_arg("state", |status: &GainStatus, _| status.state().into())
+        .with_arg("progress", |status: &GainStatus, _| match status {
+            GainStatus::Measuring(progress) => format!("{:.0}", progress * 100.0).into(),
+            _ => "".into(),
+        })
+        .with_arg("adjustment", move |status: &GainStatus, _| {
+            advice(status)
+                .map_or(String::new(), |advice| {
+                    format!("{:.0}", advice.adjustment_db().abs())
+                })
+                .into()
+        })
+        .with_arg("peak", move |status: &GainStatus, _| {
+            advice(status)
+                .map_or(String::new(), |advice| format!("{:.1}", advice.peak_db))
+                .into()
+        })
+        .with_arg("rms", move |status: &GainStatus, _| {
+            advice(status)
+                .map_or(String::new(), |advice| format!("{:.1}", advice.rms_db))
+                .into()
+        });
+    Label::new(label).controller(GainStagingController)
+}
+
+struct GainStagingController;
+
+impl<W: Widget<GainStatus>> Controller<GainStatus, W> for GainStagingController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut GainStatus,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_GAIN_STAGING) => {
+                *data = *cmd.get_unchecked(DRAW_GAIN_STAGING);
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
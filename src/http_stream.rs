@@ -0,0 +1,314 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The HTTP stream backend, for monitoring an Icecast or Shoutcast radio stream. One thread
+//! downloads and decodes the stream into a buffer, another plays it out of it into the analyser at
+//! the stream's rate. Playback waits for a couple of seconds of audio before starting and again
+//! after running dry, and a dropped connection is retried, which the buffering indicator shows.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::backend::{
+    fixed_formats, spawn_paced, AudioBackend, AudioStream, BufferState, BufferStatus,
+};
+use crate::buffer_analyser::BufferAnalyserProcessor;
+use crate::device_formats::{DeviceFormats, StreamRequest};
+
+/// Audio buffered before playback starts, and again after it ran dry
+const PREBUFFER_SECONDS: f64 = 2.0;
+/// The download pauses while this much is buffered, for servers that burst ahead
+const MAX_BUFFER_SECONDS: f64 = 10.0;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, PartialEq, Debug)]
+enum StreamState {
+    Connecting,
+    Buffering,
+    Playing,
+    /// Lost the connection, trying again
+    Reconnecting(String),
+}
+
+/// Decoded audio waiting to be played, shared by the download and playback threads
+struct StreamBuffer {
+    /// Interleaved
+    samples: VecDeque<f32>,
+    state: StreamState,
+}
+
+/// A connected stream, ready to decode
+struct Connection {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+}
+
+impl Connection {
+    fn open(url: &str) -> Result<Self, String> {
+        let response = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .timeout_read(READ_TIMEOUT)
+            .build()
+            .get(url)
+            .call()
+            .map_err(|err| err.to_string())?;
+        let mut hint = Hint::new();
+        hint.mime_type(response.content_type());
+        // Servers often send a generic type, the extension is a better guess then
+        if let Some(extension) = url.rsplit('.').next().filter(|ext| ext.len() <= 4) {
+            hint.with_extension(extension);
+        }
+        let source = ReadOnlySource::new(response.into_reader());
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                MediaSourceStream::new(Box::new(source), Default::default()),
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|err| err.to_string())?;
+        let track = probed
+            .format
+            .default_track()
+            .ok_or_else(|| "No audio track in the stream".to_string())?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|err| err.to_string())?;
+        Ok(Connection {
+            track_id: track.id,
+            format: probed.format,
+            decoder,
+        })
+    }
+
+    /// The next packet's interleaved samples with their rate and channel count, skipping packets
+    /// that don't decode. An error means the connection is done.
+    fn next(&mut self) -> Result<(Vec<f32>, u32, usize), String> {
+        loop {
+            let packet = self.format.next_packet().map_err(|err| err.to_string())?;
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                    buffer.copy_interleaved_ref(decoded);
+                    return Ok((buffer.samples().to_vec(), spec.rate, spec.channels.count()));
+                }
+                Err(Error::DecodeError(err)) => log::warn!("Skipped a stream packet: {}", err),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+}
+
+/// Stops the download thread when dropped without waiting for it, it may be stuck on a read
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Download and decode into `buffer` until `stop` is set, reconnecting whenever the stream ends
+fn download(
+    url: String,
+    sample_rate: u32,
+    channels: usize,
+    buffer: Arc<Mutex<StreamBuffer>>,
+    stop: Arc<AtomicBool>,
+) {
+    let set_state = |state| buffer.lock().expect("Failed to lock stream buffer").state = state;
+    while !stop.load(Ordering::Relaxed) {
+        let error = match Connection::open(&url) {
+            Ok(connection) => {
+                {
+                    let mut buffer = buffer.lock().expect("Failed to lock stream buffer");
+                    if buffer.state != StreamState::Playing {
+                        buffer.state = StreamState::Buffering;
+                    }
+                }
+                match decode_into(connection, sample_rate, channels, &buffer, &stop) {
+                    Some(error) => error,
+                    None => return,
+                }
+            }
+            Err(err) => err,
+        };
+        log::warn!("Stream {}: {}", url, error);
+        set_state(StreamState::Reconnecting(error));
+        thread::sleep(RECONNECT_DELAY);
+    }
+}
+
+/// Decode a connection into `buffer` until it fails, returning why, or `None` once `stop` is set
+fn decode_into(
+    mut connection: Connection,
+    sample_rate: u32,
+    channels: usize,
+    buffer: &Mutex<StreamBuffer>,
+    stop: &AtomicBool,
+) -> Option<String> {
+    let max_samples = (MAX_BUFFER_SECONDS * sample_rate as f64) as usize * channels;
+    let buffered = || {
+        buffer
+            .lock()
+            .expect("Failed to lock stream buffer")
+            .samples
+            .len()
+    };
+    while !stop.load(Ordering::Relaxed) {
+        let (samples, rate, packet_channels) = match connection.next() {
+            Ok(packet) => packet,
+            Err(err) => return Some(err),
+        };
+        if rate != sample_rate || packet_channels != channels {
+            return Some(format!(
+                "The stream changed to {} Hz, {} ch from {} Hz, {} ch",
+                rate, packet_channels, sample_rate, channels
+            ));
+        }
+        buffer
+            .lock()
+            .expect("Failed to lock stream buffer")
+            .samples
+            .extend(samples);
+        while !stop.load(Ordering::Relaxed) && buffered() > max_samples {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+    None
+}
+
+/// An Icecast, Shoutcast or other HTTP audio stream in any format the file reader decodes
+pub struct HttpStreamBackend {
+    url: String,
+    sample_rate: u32,
+    channels: usize,
+    /// Of the current run
+    buffer: Option<Arc<Mutex<StreamBuffer>>>,
+}
+
+impl HttpStreamBackend {
+    /// Connect once to learn the stream's rate and channel count
+    pub fn new(url: &str) -> Result<Self, String> {
+        let (_, sample_rate, channels) = Connection::open(url)?.next()?;
+        Ok(HttpStreamBackend {
+            url: url.to_string(),
+            sample_rate,
+            channels: channels.max(1),
+            buffer: None,
+        })
+    }
+}
+
+impl AudioBackend for HttpStreamBackend {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate as f32
+    }
+
+    fn formats(&self) -> DeviceFormats {
+        fixed_formats(
+            self.url.clone(),
+            StreamRequest {
+                sample_rate: self.sample_rate,
+                channels: self.channels as u16,
+                buffer_size: None,
+            },
+        )
+    }
+
+    fn buffer_status(&self) -> Option<BufferStatus> {
+        let buffer = self
+            .buffer
+            .as_ref()?
+            .lock()
+            .expect("Failed to lock stream buffer");
+        let seconds = buffer.samples.len() as f64 / self.channels as f64 / self.sample_rate as f64;
+        let state = match &buffer.state {
+            StreamState::Connecting => BufferState::Connecting,
+            StreamState::Buffering => BufferState::Buffering,
+            StreamState::Playing => BufferState::Playing { seconds },
+            StreamState::Reconnecting(error) => BufferState::Reconnecting(error.clone()),
+        };
+        Some(BufferStatus {
+            state,
+            fill: (seconds / PREBUFFER_SECONDS).min(1.0),
+        })
+    }
+
+    fn open(&mut self, processor: BufferAnalyserProcessor) -> io::Result<AudioStream> {
+        let buffer = Arc::new(Mutex::new(StreamBuffer {
+            samples: VecDeque::new(),
+            state: StreamState::Connecting,
+        }));
+        self.buffer = Some(buffer.clone());
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let url = self.url.clone();
+            let (sample_rate, channels) = (self.sample_rate, self.channels);
+            let buffer = buffer.clone();
+            let stop = stop.clone();
+            thread::spawn(move || download(url, sample_rate, channels, buffer, stop));
+        }
+        let prebuffer = (PREBUFFER_SECONDS * self.sample_rate as f64) as usize * self.channels;
+        let playback = spawn_paced(
+            processor,
+            self.sample_rate as f32,
+            self.channels,
+            move |block| {
+                let len = block.len();
+                let mut buffer = buffer.lock().expect("Failed to lock stream buffer");
+                if buffer.state != StreamState::Playing && buffer.samples.len() >= prebuffer {
+                    buffer.state = StreamState::Playing;
+                } else if buffer.state == StreamState::Playing && buffer.samples.len() < len {
+                    buffer.state = StreamState::Buffering;
+                }
+                if buffer.state == StreamState::Playing {
+                    for (sample, buffered) in block.iter_mut().zip(buffer.samples.drain(..len)) {
+                        *sample = buffered;
+                    }
+                } else {
+                    block.fill(0.0);
+                }
+            },
+        );
+        Ok(AudioStream::new((playback, StopOnDrop(stop))))
+    }
+}
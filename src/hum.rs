@@ -0,0 +1,112 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Mains hum detection. A 50 or 60 Hz fundamental counts as hum when enough of its first
+//! [`HUM_HARMONICS`] stand out of the spectrum around them, so a single low note isn't mistaken
+//! for it. The monitor output can notch the harmonics out to hear the difference.
+
+use std::sync::Arc;
+
+use druid::{Data, Env};
+
+use crate::i18n::localize_with;
+use crate::spectrum_view::{Peak, SpectrumData};
+
+/// Mains frequencies checked, in this order
+pub const MAINS_HZ: [f32; 2] = [50.0, 60.0];
+/// Harmonics checked and notched, counting the fundamental
+pub const HUM_HARMONICS: usize = 8;
+
+/// How far above the surrounding level a harmonic must be
+const PROMINENCE_DB: f32 = 10.0;
+/// Prominent harmonics needed to report hum
+const MIN_HARMONICS: usize = 3;
+/// Mains frequency tolerance as a share of each harmonic's frequency
+const MAX_DEVIATION: f32 = 0.02;
+/// Width either side of a harmonic the surrounding level is taken over
+const SURROUNDING_HZ: f32 = 20.0;
+
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct Hum {
+    pub mains_hz: f32,
+    /// The prominent harmonics, lowest first
+    pub harmonics: Arc<Vec<Peak>>,
+}
+
+impl Hum {
+    pub fn label(&self, env: &Env) -> String {
+        localize_with(
+            "hum-detected",
+            &[
+                ("mains", format!("{:.0}", self.mains_hz)),
+                ("harmonics", self.harmonics.len().to_string()),
+                ("total", HUM_HARMONICS.to_string()),
+            ],
+            env,
+        )
+    }
+}
+
+/// Median level of the bins around `hz`, leaving out the bins of the harmonic itself
+fn surrounding_db(spectrum: &SpectrumData, hz: f32) -> Option<f32> {
+    let bin_hz = spectrum.bin_hz;
+    let bins = &spectrum.magnitudes_db;
+    let center = (hz / bin_hz).round() as usize;
+    let span = (SURROUNDING_HZ / bin_hz).ceil() as usize;
+    let mut levels: Vec<f32> = (center.saturating_sub(span)..(center + span + 1).min(bins.len()))
+        .filter(|bin| bin.abs_diff(center) > 1)
+        .map(|bin| bins[bin])
+        .collect();
+    if levels.is_empty() {
+        return None;
+    }
+    levels.sort_by(f32::total_cmp);
+    Some(levels[levels.len() / 2])
+}
+
+fn prominent_harmonics(spectrum: &SpectrumData, mains_hz: f32) -> Vec<Peak> {
+    (1..=HUM_HARMONICS)
+        .map(|number| mains_hz * number as f32)
+        .take_while(|hz| *hz < spectrum.bin_hz * spectrum.magnitudes_db.len() as f32)
+        .filter_map(|hz| {
+            let peak = spectrum.peak_near(hz)?;
+            let surrounding = surrounding_db(spectrum, hz)?;
+            let on_frequency = (peak.hz - hz).abs() <= hz * MAX_DEVIATION;
+            (on_frequency && peak.level_db - surrounding >= PROMINENCE_DB).then_some(peak)
+        })
+        .collect()
+}
+
+/// The mains frequency with the most prominent harmonics, if there are enough of them
+pub fn detect(spectrum: &SpectrumData) -> Option<Hum> {
+    if spectrum.bin_hz <= 0.0 {
+        return None;
+    }
+    MAINS_HZ
+        .iter()
+        .map(|&mains_hz| Hum {
+            mains_hz,
+            harmonics: Arc::new(prominent_harmonics(spectrum, mains_hz)),
+        })
+        .filter(|hum| hum.harmonics.len() >= MIN_HARMONICS)
+        .max_by_key(|hum| hum.harmonics.len())
+}
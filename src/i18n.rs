@@ -0,0 +1,76 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The window strings in `resources/i18n`, one Fluent bundle per language. Labels look them up
+//! with druid's `LocalizedString`, and [`localize`] does the same for text pieced together in
+//! code, such as clipboard contents.
+
+use druid::{Env, LocalizedString};
+
+/// Look `string` up in the window's language outside of a label
+pub fn localize(mut string: LocalizedString<()>, env: &Env) -> String {
+    string.resolve(&(), env);
+    string.localized_str().to_string()
+}
+
+/// [`localize`] the message `key` with already formatted arguments
+pub fn localize_with(key: &'static str, args: &[(&'static str, String)], env: &Env) -> String {
+    let string = args
+        .iter()
+        .fold(LocalizedString::new(key), |string, (name, value)| {
+            let value = value.clone();
+            string.with_arg(name, move |_, _| value.clone().into())
+        });
+    localize(string, env)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::native::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::path::PathBuf;
+
+    use druid::{AppLauncher, Data};
+
+    /// `resources/i18n` in the nearest directory above the executable that has one: next to it
+    /// once installed, `Contents/Resources/i18n` in a macOS bundle, or the crate root when run from
+    /// `target`
+    fn i18n_dir() -> String {
+        let exe = std::env::current_exe().unwrap_or_default();
+        exe.ancestors()
+            .skip(1)
+            .flat_map(|dir| [dir.join("resources/i18n"), dir.join("Resources/i18n")])
+            .find(|dir| dir.is_dir())
+            .unwrap_or_else(|| PathBuf::from("resources/i18n"))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Load the bundle for every window the launcher opens
+    pub fn with_strings<T: Data>(launcher: AppLauncher<T>) -> AppLauncher<T> {
+        launcher.localization_resources(
+            vec!["builtin.ftl".to_string(), "audio-viz.ftl".to_string()],
+            i18n_dir(),
+        )
+    }
+}
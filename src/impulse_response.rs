@@ -0,0 +1,173 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Room impulse response measurement with an exponential sine sweep (Farina's method).
+//!
+//! The recording of the sweep is convolved with the sweep's inverse filter, the time-reversed
+//! sweep with a -6dB/octave envelope, which leaves the linear impulse response with harmonic
+//! distortion pushed ahead of it. Reverberation time comes from the Schroeder energy decay curve.
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use crate::biquad::Biquad;
+
+pub const SWEEP_START_HZ: f32 = 20.0;
+pub const SWEEP_END_HZ: f32 = 20_000.0;
+
+/// Octave band centers RT60 is estimated for
+pub const OCTAVE_BANDS_HZ: [f32; 6] = [125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0];
+
+/// Longest impulse response kept after the direct sound
+const MAX_IR_SECONDS: f32 = 3.0;
+/// Samples kept ahead of the direct sound peak
+const PRE_PEAK_MS: f32 = 1.0;
+/// The decay is fitted between these levels (T20), then extrapolated to 60dB
+const FIT_RANGE_DB: (f32, f32) = (-5.0, -25.0);
+
+/// The exponential sweep played into the room
+pub fn sweep(duration_seconds: f32, sample_rate: f32) -> Vec<f32> {
+    let len = (duration_seconds * sample_rate) as usize;
+    let rate = duration_seconds / (SWEEP_END_HZ / SWEEP_START_HZ).ln();
+    (0..len)
+        .map(|index| {
+            let time = index as f32 / sample_rate;
+            let phase =
+                2.0 * std::f32::consts::PI * SWEEP_START_HZ * rate * ((time / rate).exp() - 1.0);
+            phase.sin()
+        })
+        .collect()
+}
+
+fn inverse_filter(sweep: &[f32], sample_rate: f32) -> Vec<f32> {
+    let duration_seconds = sweep.len() as f32 / sample_rate;
+    let rate = duration_seconds / (SWEEP_END_HZ / SWEEP_START_HZ).ln();
+    sweep
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, sample)| sample * (-(index as f32 / sample_rate) / rate).exp())
+        .collect()
+}
+
+fn convolve(signal: &[f32], kernel: &[f32]) -> Vec<f32> {
+    let len = (signal.len() + kernel.len() - 1).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(len);
+    let ifft = planner.plan_fft_inverse(len);
+
+    let to_complex = |samples: &[f32]| -> Vec<Complex<f32>> {
+        samples
+            .iter()
+            .map(|sample| Complex::new(*sample, 0.0))
+            .chain(std::iter::repeat(Complex::new(0.0, 0.0)))
+            .take(len)
+            .collect()
+    };
+    let mut signal = to_complex(signal);
+    let mut kernel = to_complex(kernel);
+    fft.process(&mut signal);
+    fft.process(&mut kernel);
+    for (bin, kernel_bin) in signal.iter_mut().zip(&kernel) {
+        *bin *= kernel_bin;
+    }
+    ifft.process(&mut signal);
+    signal.iter().map(|value| value.re / len as f32).collect()
+}
+
+/// Deconvolve a recording of [`sweep`], returning the impulse response starting just before the
+/// direct sound.
+pub fn impulse_response(recording: &[f32], duration_seconds: f32, sample_rate: f32) -> Vec<f32> {
+    let sweep = sweep(duration_seconds, sample_rate);
+    let response = convolve(recording, &inverse_filter(&sweep, sample_rate));
+
+    // Distortion products land before the sweep's own length, so look for the direct sound after it
+    let linear = &response[(sweep.len() - 1).min(response.len())..];
+    let peak = linear
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map(|(index, _)| index)
+        .unwrap_or(0);
+    let start = peak.saturating_sub((PRE_PEAK_MS / 1000.0 * sample_rate) as usize);
+    let end = (peak + (MAX_IR_SECONDS * sample_rate) as usize).min(linear.len());
+
+    let scale = linear.get(peak).map(|value| value.abs()).unwrap_or(0.0);
+    linear[start..end]
+        .iter()
+        .map(|value| if scale > 0.0 { value / scale } else { 0.0 })
+        .collect()
+}
+
+/// Schroeder backwards integration of the squared impulse response, in dB relative to the total
+pub fn energy_decay_curve(impulse_response: &[f32]) -> Vec<f32> {
+    let mut remaining: f32 = impulse_response.iter().map(|value| value * value).sum();
+    let total = remaining;
+    impulse_response
+        .iter()
+        .map(|value| {
+            let level = 10.0 * (remaining / total).max(1e-12).log10();
+            remaining -= value * value;
+            level
+        })
+        .collect()
+}
+
+/// Octave band filtered impulse response
+pub fn band_filter(impulse_response: &[f32], center_hz: f32, sample_rate: f32) -> Vec<f32> {
+    let mut filter = Biquad::band_pass(center_hz, std::f32::consts::SQRT_2, sample_rate);
+    impulse_response
+        .iter()
+        .map(|sample| filter.process(*sample))
+        .collect()
+}
+
+/// Reverberation time in seconds, from a least-squares fit to the decay curve between
+/// [`FIT_RANGE_DB`]. `None` when the curve doesn't decay far enough.
+pub fn rt60(decay_curve: &[f32], sample_rate: f32) -> Option<f32> {
+    let start = decay_curve
+        .iter()
+        .position(|level| *level <= FIT_RANGE_DB.0)?;
+    let end = decay_curve
+        .iter()
+        .position(|level| *level <= FIT_RANGE_DB.1)?;
+    if end <= start + 1 {
+        return None;
+    }
+
+    let points = decay_curve[start..end]
+        .iter()
+        .enumerate()
+        .map(|(index, level)| (index as f64 / sample_rate as f64, *level as f64));
+    let count = (end - start) as f64;
+    let (sum_x, sum_y, sum_xy, sum_xx) = points.fold(
+        (0.0, 0.0, 0.0, 0.0),
+        |(sum_x, sum_y, sum_xy, sum_xx), (x, y)| {
+            (sum_x + x, sum_y + y, sum_xy + x * y, sum_xx + x * x)
+        },
+    );
+    let slope = (count * sum_xy - sum_x * sum_y) / (count * sum_xx - sum_x * sum_x);
+    if slope >= 0.0 {
+        return None;
+    }
+    Some((-60.0 / slope) as f32)
+}
@@ -0,0 +1,113 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! K-System meter scales for mastering: the bar meter's 0 moves down to -20, -14 or -12 dBFS,
+//! green below it, amber up to +4 and red above. Bob Katz pairs them with a slow RMS meter, so
+//! VU or slow ballistics read closest.
+
+use druid::{Color, Data, Env, Key};
+use serde::{Deserialize, Serialize};
+
+pub const METER_SCALE: Key<u64> = Key::new("event-example.meter_scale");
+
+/// Start of the red zone above the scale's 0
+const RED_ZONE_DB: f32 = 4.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeterScale {
+    /// Plain dBFS, 0 at full scale
+    Dbfs,
+    K20,
+    K14,
+    K12,
+}
+
+impl MeterScale {
+    pub const ALL: [MeterScale; 4] = [
+        MeterScale::Dbfs,
+        MeterScale::K20,
+        MeterScale::K14,
+        MeterScale::K12,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MeterScale::Dbfs => "dBFS",
+            MeterScale::K20 => "K-20",
+            MeterScale::K14 => "K-14",
+            MeterScale::K12 => "K-12",
+        }
+    }
+
+    /// dBFS that reads 0 on this scale
+    pub fn reference_db(&self) -> f32 {
+        match self {
+            MeterScale::Dbfs => 0.0,
+            MeterScale::K20 => -20.0,
+            MeterScale::K14 => -14.0,
+            MeterScale::K12 => -12.0,
+        }
+    }
+
+    /// Bar color for a level in dBFS
+    pub fn color(&self, level_db: f32) -> Color {
+        let (amber, red) = match self {
+            // Warn close to clipping when there is no reference level
+            MeterScale::Dbfs => (-9.0, -1.0),
+            _ => (self.reference_db(), self.reference_db() + RED_ZONE_DB),
+        };
+        if level_db > red {
+            Color::RED
+        } else if level_db > amber {
+            Color::rgb8(255, 170, 0)
+        } else {
+            Color::rgb8(0, 200, 120)
+        }
+    }
+
+    /// The reading relative to the scale's 0, `None` for dBFS, which the display units format
+    pub fn format(&self, level_db: f32) -> Option<String> {
+        match self {
+            MeterScale::Dbfs => None,
+            _ if !level_db.is_finite() => Some(format!("-inf {}", self.label())),
+            _ => Some(format!(
+                "{:+.1} {}",
+                level_db - self.reference_db(),
+                self.label()
+            )),
+        }
+    }
+
+    pub fn set_env(&self, env: &mut Env) {
+        env.set(METER_SCALE, *self as u64);
+    }
+
+    pub fn from_env(env: &Env) -> Self {
+        match env.try_get(METER_SCALE) {
+            Ok(1) => MeterScale::K20,
+            Ok(2) => MeterScale::K14,
+            Ok(3) => MeterScale::K12,
+            _ => MeterScale::Dbfs,
+        }
+    }
+}
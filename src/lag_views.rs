@@ -0,0 +1,286 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Real cepstrum and autocorrelation of the most recent window, plotted against lag so pitch
+//! periods and echoes show up as peaks.
+
+use std::sync::Arc;
+
+use audio_processor_analysis::window_functions::make_hann_vec;
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::widget::{Controller, Flex, Label};
+use druid::{Color, Data, Lens, LocalizedString, Point, Selector, WidgetExt};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::i18n::{localize, localize_with};
+
+pub const DRAW_LAG_VIEWS: Selector<LagViews> = Selector::new("event-example.draw_lag_views");
+
+/// Samples analysed on every update
+pub const WINDOW_LEN: usize = 2048;
+
+/// Lags under this are excluded from scaling, they are dominated by the spectral envelope
+const MIN_LAG_MS: f32 = 1.0;
+/// Peaks are searched for between these lags, covering 50Hz to 500Hz fundamentals
+const PEAK_SEARCH_MS: (f32, f32) = (2.0, 20.0);
+/// Normalized height a peak needs before it is reported
+const MIN_PEAK: f32 = 0.3;
+
+/// One lag-domain curve, normalized to the range -1 to 1
+#[derive(Clone, Data)]
+pub struct LagView {
+    pub values: Arc<Vec<f32>>,
+    /// Milliseconds per value
+    pub lag_step_ms: f32,
+    /// Lag of the strongest periodicity, if there is one
+    pub peak_ms: Option<f32>,
+}
+
+impl Default for LagView {
+    fn default() -> Self {
+        LagView {
+            values: Arc::new(Vec::new()),
+            lag_step_ms: 0.0,
+            peak_ms: None,
+        }
+    }
+}
+
+impl LagView {
+    fn new(mut values: Vec<f32>, sample_rate: f32) -> Self {
+        let lag_step_ms = 1000.0 / sample_rate;
+        let num_values = values.len();
+        let lag_index = |ms: f32| ((ms / lag_step_ms) as usize).min(num_values);
+
+        let scale = values[lag_index(MIN_LAG_MS)..]
+            .iter()
+            .fold(0.0_f32, |max, value| max.max(value.abs()));
+        if scale > 0.0 {
+            for value in &mut values {
+                *value = (*value / scale).clamp(-1.0, 1.0);
+            }
+        }
+
+        let search_start = lag_index(PEAK_SEARCH_MS.0);
+        let peak_ms = values[search_start..lag_index(PEAK_SEARCH_MS.1)]
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .filter(|(_, value)| **value >= MIN_PEAK)
+            .map(|(index, _)| (search_start + index) as f32 * lag_step_ms);
+
+        LagView {
+            values: Arc::new(values),
+            lag_step_ms,
+            peak_ms,
+        }
+    }
+}
+
+#[derive(Clone, Data, Lens, Default)]
+pub struct LagViews {
+    pub cepstrum: LagView,
+    pub autocorrelation: LagView,
+}
+
+pub struct LagAnalyser {
+    sample_rate: f32,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    // Autocorrelation is zero-padded to twice the window so it isn't circular
+    padded_fft: Arc<dyn Fft<f32>>,
+    padded_ifft: Arc<dyn Fft<f32>>,
+}
+
+impl LagAnalyser {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut planner = FftPlanner::new();
+        LagAnalyser {
+            sample_rate,
+            window: make_hann_vec(WINDOW_LEN),
+            fft: planner.plan_fft_forward(WINDOW_LEN),
+            ifft: planner.plan_fft_inverse(WINDOW_LEN),
+            padded_fft: planner.plan_fft_forward(WINDOW_LEN * 2),
+            padded_ifft: planner.plan_fft_inverse(WINDOW_LEN * 2),
+        }
+    }
+
+    /// Analyse the last [`WINDOW_LEN`] samples
+    pub fn analyse(&self, samples: &[f32]) -> LagViews {
+        LagViews {
+            cepstrum: LagView::new(self.cepstrum(samples), self.sample_rate),
+            autocorrelation: LagView::new(self.autocorrelation(samples), self.sample_rate),
+        }
+    }
+
+    /// Inverse FFT of the log magnitude spectrum
+    fn cepstrum(&self, samples: &[f32]) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, window)| Complex::new(sample * window, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        for bin in &mut buffer {
+            *bin = Complex::new((bin.norm() + 1e-9).ln(), 0.0);
+        }
+        self.ifft.process(&mut buffer);
+        buffer[..WINDOW_LEN / 2]
+            .iter()
+            .map(|value| value.re / WINDOW_LEN as f32)
+            .collect()
+    }
+
+    /// Inverse FFT of the power spectrum, normalized so lag 0 is 1
+    fn autocorrelation(&self, samples: &[f32]) -> Vec<f32> {
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .map(|sample| Complex::new(*sample, 0.0))
+            .chain(std::iter::repeat(Complex::new(0.0, 0.0)))
+            .take(WINDOW_LEN * 2)
+            .collect();
+        self.padded_fft.process(&mut buffer);
+        for bin in &mut buffer {
+            *bin = Complex::new(bin.norm_sqr(), 0.0);
+        }
+        self.padded_ifft.process(&mut buffer);
+        let energy = buffer[0].re;
+        buffer[..WINDOW_LEN / 2]
+            .iter()
+            .map(|value| if energy > 0.0 { value.re / energy } else { 0.0 })
+            .collect()
+    }
+}
+
+/// Line plot of a [`LagView`] with lag on the x axis
+#[derive(Default)]
+pub struct LagPlot {}
+
+impl Widget<LagView> for LagPlot {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut LagView, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &LagView, _: &Env) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &LagView, _data: &LagView, _: &Env) {
+        ctx.request_paint()
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &LagView, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LagView, _env: &Env) {
+        let size = ctx.size();
+        let y_coord = |value: f32| (1.0 - value as f64) * size.height / 2.0;
+
+        ctx.stroke(
+            Line::new((0.0, y_coord(0.0)), (size.width, y_coord(0.0))),
+            &Color::grey(0.3),
+            1.0,
+        );
+
+        let num_values = data.values.len();
+        if num_values == 0 {
+            return;
+        }
+        let x_coord = |index: f64| index / num_values as f64 * size.width;
+
+        if let Some(peak_ms) = data.peak_ms {
+            let x = x_coord((peak_ms / data.lag_step_ms) as f64);
+            ctx.stroke(
+                Line::new((x, 0.0), (x, size.height)),
+                &Color::rgb8(255, 200, 0),
+                1.0,
+            );
+        }
+
+        let mut shape = BezPath::new();
+        for (index, value) in data.values.iter().enumerate() {
+            let point = Point::new(x_coord(index as f64), y_coord(*value));
+            if index == 0 {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        ctx.stroke(shape, &Color::rgb8(0, 160, 255), 1.0);
+    }
+}
+
+/// `title` is the key of the plot's name
+fn titled_plot(title: &'static str, lens: impl Lens<LagViews, LagView>) -> impl Widget<LagViews> {
+    let label = Label::dynamic(move |data: &LagView, env| {
+        let title = localize(LocalizedString::new(title), env);
+        match data.peak_ms {
+            Some(peak_ms) => localize_with(
+                "lag-peak",
+                &[
+                    ("title", title),
+                    ("peak", format!("{:.2}", peak_ms)),
+                    ("frequency", format!("{:.0}", 1000.0 / peak_ms)),
+                ],
+                env,
+            ),
+            None => localize_with("lag-no-peak", &[("title", title)], env),
+        }
+    });
+    Flex::column()
+        .with_child(label)
+        .with_flex_child(LagPlot::default(), 1.0)
+        .lens(lens)
+}
+
+/// The cepstrum and autocorrelation side by side
+pub fn lag_views() -> impl Widget<LagViews> {
+    Flex::row()
+        .with_flex_child(titled_plot("cepstrum", LagViews::cepstrum), 1.0)
+        .with_default_spacer()
+        .with_flex_child(
+            titled_plot("autocorrelation", LagViews::autocorrelation),
+            1.0,
+        )
+        .controller(LagViewsController)
+}
+
+struct LagViewsController;
+
+impl<W: Widget<LagViews>> Controller<LagViews, W> for LagViewsController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LagViews,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_LAG_VIEWS) => {
+                *data = cmd.get_unchecked(DRAW_LAG_VIEWS).clone();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Extra input devices drawn in lanes under the live waveform, e.g. a USB mic next to the
+//! interface. Each device runs on its own clock, so its samples are resampled onto the main
+//! stream's timeline: the ratio follows the lane device's measured rate against the rate the
+//! waveform scrolls at, and a slow correction keeps the lane's position on the waveform's. The
+//! devices' input latencies aren't known, so lanes line up to within their difference.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use atomic_queue::Queue;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat, SupportedStreamConfig};
+use druid::piet::{FontFamily, Text, TextLayout, TextLayoutBuilder};
+use druid::{Color, PaintCtx, Rect, RenderContext};
+
+use crate::audio_wave::WaveSnapshot;
+use crate::buffer_analyser::{
+    ChunkWriter, InputSample, SampleChunk, SampleHistory, HISTORY_LEN, QUEUE_CHUNKS,
+};
+use crate::clock::{BatchStamp, DriftEstimator};
+use crate::resample::ANALYSIS_RATE;
+use crate::throttle::FrameTiming;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const STAMP_QUEUE_LEN: usize = 256;
+/// Seconds the position correction takes to catch a lane up with the waveform
+const CORRECTION_SECONDS: f64 = 2.0;
+/// Largest share the correction changes the ratio by
+const MAX_CORRECTION: f64 = 0.005;
+/// A lane further off than this jumps back into place, after its device or the main one stalled
+const RESYNC_SECONDS: f64 = 1.0;
+
+/// Draw a lane's name in its top left corner, nothing while it's unnamed
+pub fn paint_label(ctx: &mut PaintCtx, name: &str) {
+    if name.is_empty() {
+        return;
+    }
+    let layout = ctx
+        .text()
+        .new_text_layout(name.to_string())
+        .font(FontFamily::SYSTEM_UI, 11.0)
+        .text_color(Color::grey(0.8))
+        .build();
+    if let Ok(layout) = layout {
+        let background = Rect::from_origin_size((4.0, 2.0), layout.size()).inflate(3.0, 1.0);
+        ctx.fill(background, &Color::BLACK.with_alpha(0.6));
+        ctx.draw_text(&layout, (4.0, 2.0));
+    }
+}
+
+/// Linear interpolation at a ratio that can change between calls. Plenty for drawing, a lane isn't
+/// analysed.
+#[derive(Default)]
+struct DriftResampler {
+    previous: f32,
+    /// Where the next output sample falls past `previous`, in input samples
+    phase: f64,
+}
+
+impl DriftResampler {
+    /// Append `input` at `ratio` output samples per input sample
+    fn process(&mut self, input: &[f32], ratio: f64, output: &mut Vec<f32>) {
+        let step = 1.0 / ratio;
+        for &sample in input {
+            while self.phase < 1.0 {
+                output.push(self.previous + (sample - self.previous) * self.phase as f32);
+                self.phase += step;
+            }
+            self.phase -= 1.0;
+            self.previous = sample;
+        }
+    }
+}
+
+/// What the device's callback hands the lane thread
+struct LaneQueues {
+    samples: Queue<SampleChunk>,
+    stamps: Queue<BatchStamp>,
+}
+
+fn build_stream<S: InputSample + cpal::Sample>(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    queues: Arc<LaneQueues>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let num_channels = config.channels() as usize;
+    let start = Instant::now();
+    let mut position = 0;
+    let mut writer = ChunkWriter::default();
+    device.build_input_stream(
+        &config.config(),
+        move |data: &[S], _: &cpal::InputCallbackInfo| {
+            // The first channel, like the main input's waveform
+            for frame in data.chunks(num_channels) {
+                if let Some(chunk) = writer.write(frame[0].to_f32()) {
+                    let _ = queues.samples.push(chunk);
+                }
+            }
+            if let Some(chunk) = writer.flush() {
+                let _ = queues.samples.push(chunk);
+            }
+            position += (data.len() / num_channels) as u64;
+            let _ = queues.stamps.push(BatchStamp {
+                position,
+                time_micros: start.elapsed().as_micros() as u64,
+            });
+        },
+        |err| log::error!("Lane input error: {:?}", err),
+    )
+}
+
+/// An open lane device, drawn until it's stopped
+pub struct LaneInput {
+    name: String,
+    snapshot: WaveSnapshot,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    _stream: cpal::Stream,
+}
+
+impl LaneInput {
+    /// Open the named input device, following the waveform published to `main`
+    pub fn open(
+        name: &str,
+        main: WaveSnapshot,
+        frame_timing: Arc<FrameTiming>,
+    ) -> Result<Self, String> {
+        let device = cpal::default_host()
+            .input_devices()
+            .map_err(|err| err.to_string())?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+            .ok_or_else(|| format!("No input device named {}", name))?;
+        let config = device
+            .default_input_config()
+            .map_err(|err| err.to_string())?;
+        let queues = Arc::new(LaneQueues {
+            samples: Queue::new(QUEUE_CHUNKS),
+            stamps: Queue::new(STAMP_QUEUE_LEN),
+        });
+        let stream = match config.sample_format() {
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, queues.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, queues.clone()),
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, queues.clone()),
+        }
+        .map_err(|err| err.to_string())?;
+        stream.play().map_err(|err| err.to_string())?;
+
+        let snapshot = WaveSnapshot::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let snapshot = snapshot.clone();
+            let stop = stop.clone();
+            let device_rate = config.sample_rate().0 as f32;
+            thread::spawn(move || {
+                generate_lane_snapshots(queues, device_rate, main, snapshot, frame_timing, &stop)
+            })
+        };
+        Ok(LaneInput {
+            name: name.to_string(),
+            snapshot,
+            stop,
+            thread,
+            _stream: stream,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What the lane's pane reads
+    pub fn snapshot(&self) -> WaveSnapshot {
+        self.snapshot.clone()
+    }
+
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if self.thread.join().is_err() {
+            log::error!("Lane thread for {} panicked", self.name);
+        }
+    }
+}
+
+/// Resample the lane onto the main waveform's timeline and publish it the way
+/// [`crate::analysis::generate_waveform_snapshots`] publishes the waveform
+fn generate_lane_snapshots(
+    queues: Arc<LaneQueues>,
+    device_rate: f32,
+    main: WaveSnapshot,
+    snapshot: WaveSnapshot,
+    frame_timing: Arc<FrameTiming>,
+    stop: &AtomicBool,
+) {
+    let mut drift = DriftEstimator::new(device_rate);
+    let mut resampler = DriftResampler::default();
+    let mut history = SampleHistory::default();
+    let mut resampled = Vec::new();
+    // Main stream position of the lane's first sample
+    let mut offset = None;
+    snapshot.set_nominal_rate(ANALYSIS_RATE);
+
+    while !stop.load(Ordering::Relaxed) {
+        while let Some(stamp) = queues.stamps.pop() {
+            drift.push(stamp);
+        }
+        // What the waveform scrolls at, the main device's measured rate at the analysis rate
+        let timeline_rate = Some(main.sample_rate())
+            .filter(|rate| *rate > 0.0)
+            .unwrap_or(ANALYSIS_RATE) as f64;
+        let main_position = main.position() as f64;
+        let offset = offset.get_or_insert(main_position);
+        let mut error = main_position - (*offset + history.position() as f64);
+        if error.abs() > timeline_rate * RESYNC_SECONDS {
+            *offset += error;
+            error = 0.0;
+        }
+        let correction =
+            (error / (timeline_rate * CORRECTION_SECONDS)).clamp(-MAX_CORRECTION, MAX_CORRECTION);
+        let ratio = timeline_rate / drift.effective_rate() * (1.0 + correction);
+
+        resampled.clear();
+        while let Some(chunk) = queues.samples.pop() {
+            resampler.process(chunk.samples(), ratio, &mut resampled);
+        }
+        for &sample in &resampled {
+            history.push(sample);
+        }
+
+        let position = (*offset + history.position() as f64) as u64;
+        if !frame_timing.hidden() {
+            snapshot.publish(
+                history.latest(HISTORY_LEN),
+                None,
+                Vec::new(),
+                position,
+                timeline_rate as f32,
+                None,
+            );
+        } else {
+            snapshot.set_position(position);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,169 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Round-trip latency calibration: the monitor output plays a click and the audio thread counts
+//! the frames until it comes back on the input, through a loopback cable or from a speaker to
+//! the microphone. Half the round trip is taken as the input's share and the waveform's timecode
+//! is moved back by it, so it reads when a sound happened rather than when it was delivered.
+//! Only the f32 input path has an output to play the click on.
+
+use druid::{Data, Env, Selector};
+
+use crate::i18n::localize_with;
+
+pub const DRAW_LATENCY: Selector<LatencyStatus> = Selector::new("event-example.draw_latency");
+
+/// Measurements the queue holds, one per click
+pub const LATENCY_QUEUE_LEN: usize = 16;
+
+/// Samples in the click, short enough to have a sharp onset
+const CLICK_LEN: usize = 16;
+const CLICK_LEVEL: f32 = 0.8;
+/// The click has to come back this much louder than the input was before it
+const DETECT_RATIO: f32 = 8.0;
+/// Quietest return that counts as the click
+const MIN_DETECT_LEVEL: f32 = 0.02;
+/// Longest round trip waited for
+const TIMEOUT_SECONDS: f32 = 1.0;
+
+/// What the audio thread reports for each click
+#[derive(Clone, Copy, Debug)]
+pub enum LatencyMeasurement {
+    /// Frames from the callback the click was played in to the input frame it came back on
+    RoundTrip(u64),
+    /// Nothing came back before the timeout
+    NotHeard,
+}
+
+/// Audio thread side: plays the click and listens for it
+pub struct LatencyProbe {
+    sample_rate: f32,
+    /// Click samples still to be played
+    click_remaining: usize,
+    /// Stream position of the callback the click was played in, while waiting for it
+    played_at: Option<u64>,
+    /// Peak input before the click
+    threshold: f32,
+}
+
+impl Default for LatencyProbe {
+    fn default() -> Self {
+        LatencyProbe {
+            sample_rate: 44100.0,
+            click_remaining: 0,
+            played_at: None,
+            threshold: MIN_DETECT_LEVEL,
+        }
+    }
+}
+
+impl LatencyProbe {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Play the click from the start of this callback's output. `input_peak` is the peak of the
+    /// callback's input, which the click can't be in yet.
+    pub fn play(&mut self, position: u64, input_peak: f32) {
+        self.click_remaining = CLICK_LEN;
+        self.played_at = Some(position);
+        self.threshold = (input_peak * DETECT_RATIO).max(MIN_DETECT_LEVEL);
+    }
+
+    /// The click's next output sample, 0 once it has played
+    pub fn click_sample(&mut self) -> f32 {
+        if self.click_remaining == 0 {
+            return 0.0;
+        }
+        self.click_remaining -= 1;
+        CLICK_LEVEL
+    }
+
+    /// Look for the click in a callback's input starting at stream position `position`
+    pub fn listen(
+        &mut self,
+        samples: impl Iterator<Item = f32>,
+        position: u64,
+    ) -> Option<LatencyMeasurement> {
+        let played_at = self.played_at?;
+        let threshold = self.threshold;
+        let measurement = samples
+            .enumerate()
+            .find(|(_, sample)| sample.abs() >= threshold)
+            .map(|(index, _)| LatencyMeasurement::RoundTrip(position + index as u64 - played_at))
+            .or_else(|| {
+                let timeout = (TIMEOUT_SECONDS * self.sample_rate) as u64;
+                (position.saturating_sub(played_at) > timeout)
+                    .then_some(LatencyMeasurement::NotHeard)
+            });
+        if measurement.is_some() {
+            self.played_at = None;
+        }
+        measurement
+    }
+}
+
+/// The calibration as shown in the window
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub enum LatencyStatus {
+    Unmeasured,
+    Measuring,
+    Measured { round_trip_ms: f64 },
+    NotHeard,
+}
+
+impl LatencyStatus {
+    pub fn from_measurement(measurement: LatencyMeasurement, sample_rate: f32) -> Self {
+        match measurement {
+            LatencyMeasurement::RoundTrip(frames) => LatencyStatus::Measured {
+                round_trip_ms: frames as f64 / sample_rate as f64 * 1000.0,
+            },
+            LatencyMeasurement::NotHeard => LatencyStatus::NotHeard,
+        }
+    }
+
+    /// Seconds the input runs behind real time, assuming the round trip splits evenly between
+    /// input and output
+    pub fn input_latency(&self) -> Option<f64> {
+        match self {
+            LatencyStatus::Measured { round_trip_ms } => Some(round_trip_ms / 2000.0),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self, env: &Env) -> String {
+        let (state, round_trip_ms) = match self {
+            LatencyStatus::Unmeasured => ("unmeasured", 0.0),
+            LatencyStatus::Measuring => ("measuring", 0.0),
+            LatencyStatus::Measured { round_trip_ms } => ("measured", *round_trip_ms),
+            LatencyStatus::NotHeard => ("not-heard", 0.0),
+        };
+        localize_with(
+            "latency-status",
+            &[
+                ("state", state.to_string()),
+                ("round_trip", format!("{:.1}", round_trip_ms)),
+            ],
+            env,
+        )
+    }
+}
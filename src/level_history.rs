@@ -0,0 +1,269 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Peak and RMS once a second over the last hour, for reviewing long monitoring sessions.
+//!
+//! Readings are kept in tenths of a dB so the hour fits in about 14 kB.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::{Color, Data, Point, Selector};
+
+use crate::timeline::TimeSpan;
+
+pub const DRAW_LEVEL_HISTORY: Selector<LevelHistoryData> =
+    Selector::new("event-example.draw_level_history");
+
+/// Seconds the strip spans
+pub const LEVEL_HISTORY_LEN: usize = 60 * 60;
+
+/// Bottom of the chart's dBFS scale, the top is 0 dBFS
+const FLOOR_DB: f32 = -60.0;
+/// A vertical gridline every this many seconds
+const GRID_SECONDS: usize = 10 * 60;
+
+/// One second's peak and RMS in tenths of a dBFS
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LevelPoint {
+    peak: i16,
+    rms: i16,
+    /// A dropout happened during the second
+    pub dropout: bool,
+}
+
+impl LevelPoint {
+    fn new(peak: f32, mean_square: f32, dropout: bool) -> Self {
+        let quantize = |db: f32| (db.max(FLOOR_DB) * 10.0).round() as i16;
+        LevelPoint {
+            peak: quantize(20.0 * peak.log10()),
+            rms: quantize(10.0 * mean_square.log10()),
+            dropout,
+        }
+    }
+
+    pub fn peak_db(&self) -> f32 {
+        self.peak as f32 / 10.0
+    }
+
+    pub fn rms_db(&self) -> f32 {
+        self.rms as f32 / 10.0
+    }
+}
+
+/// What the strip shows, oldest second first
+#[derive(Clone, Data, Default)]
+pub struct LevelHistoryData {
+    pub points: Arc<Vec<LevelPoint>>,
+    /// Stream position the newest point ends at
+    pub end: u64,
+    pub samples_per_point: u64,
+}
+
+impl LevelHistoryData {
+    /// The hour the strip spans, for the timeline
+    pub fn time_span(&self) -> TimeSpan {
+        let end = self.end as f64;
+        TimeSpan {
+            start: end - (LEVEL_HISTORY_LEN as u64 * self.samples_per_point) as f64,
+            end,
+        }
+    }
+}
+
+/// Folds samples into one [`LevelPoint`] a second
+pub struct LevelHistory {
+    samples_per_point: usize,
+    count: usize,
+    peak: f32,
+    sum_squares: f64,
+    dropout: bool,
+    points: VecDeque<LevelPoint>,
+    /// Points completed since the stream started, including those dropped off the front
+    num_points: u64,
+}
+
+impl LevelHistory {
+    pub fn new(sample_rate: f32) -> Self {
+        LevelHistory {
+            samples_per_point: sample_rate as usize,
+            count: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            dropout: false,
+            points: VecDeque::with_capacity(LEVEL_HISTORY_LEN),
+            num_points: 0,
+        }
+    }
+
+    /// Flag the second in progress as having had a dropout
+    pub fn mark_dropout(&mut self) {
+        self.dropout = true;
+    }
+
+    /// Returns true when a second completed and the strip has a new point
+    pub fn process(&mut self, sample: f32) -> bool {
+        self.peak = self.peak.max(sample.abs());
+        self.sum_squares += (sample * sample) as f64;
+        self.count += 1;
+        if self.count < self.samples_per_point {
+            return false;
+        }
+        let mean_square = (self.sum_squares / self.count as f64) as f32;
+        if self.points.len() == LEVEL_HISTORY_LEN {
+            self.points.pop_front();
+        }
+        self.points
+            .push_back(LevelPoint::new(self.peak, mean_square, self.dropout));
+        self.num_points += 1;
+        self.count = 0;
+        self.dropout = false;
+        self.peak = 0.0;
+        self.sum_squares = 0.0;
+        true
+    }
+
+    pub fn data(&self) -> LevelHistoryData {
+        LevelHistoryData {
+            points: Arc::new(self.points.iter().copied().collect()),
+            end: self.num_points * self.samples_per_point as u64,
+            samples_per_point: self.samples_per_point as u64,
+        }
+    }
+}
+
+/// Strip chart of the last hour, peak as a line over the filled RMS, newest on the right, with
+/// a red line through seconds that had a dropout
+#[derive(Default)]
+pub struct LevelHistoryChart {}
+
+impl LevelHistoryChart {
+    fn y_coord(size: Size, db: f32) -> f64 {
+        (db.clamp(FLOOR_DB, 0.0) / FLOOR_DB) as f64 * size.height
+    }
+
+    fn x_coord(size: Size, len: usize, index: usize) -> f64 {
+        let offset = LEVEL_HISTORY_LEN.saturating_sub(len);
+        (offset + index) as f64 / LEVEL_HISTORY_LEN as f64 * size.width
+    }
+}
+
+impl Widget<LevelHistoryData> for LevelHistoryChart {
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LevelHistoryData,
+        _env: &Env,
+    ) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_LEVEL_HISTORY) {
+                *data = cmd.get_unchecked(DRAW_LEVEL_HISTORY).clone();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LevelHistoryData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &LevelHistoryData,
+        _data: &LevelHistoryData,
+        _: &Env,
+    ) {
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &LevelHistoryData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LevelHistoryData, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::grey(0.1));
+
+        let grid = Color::grey(0.3);
+        for db in [-12.0, -24.0, -48.0] {
+            let y_coord = Self::y_coord(size, db);
+            ctx.stroke(Line::new((0.0, y_coord), (size.width, y_coord)), &grid, 1.0);
+        }
+        // Counted back from the right edge, so each line marks ten more minutes ago
+        for seconds in (GRID_SECONDS..LEVEL_HISTORY_LEN).step_by(GRID_SECONDS) {
+            let x_coord = size.width * (1.0 - seconds as f64 / LEVEL_HISTORY_LEN as f64);
+            ctx.stroke(
+                Line::new((x_coord, 0.0), (x_coord, size.height)),
+                &grid,
+                1.0,
+            );
+        }
+
+        let points = &data.points;
+        if points.is_empty() {
+            return;
+        }
+        let mut rms = BezPath::new();
+        rms.move_to((Self::x_coord(size, points.len(), 0), size.height));
+        let mut peak = BezPath::new();
+        for (index, point) in points.iter().enumerate() {
+            let x_coord = Self::x_coord(size, points.len(), index);
+            rms.line_to((x_coord, Self::y_coord(size, point.rms_db())));
+            let peak_point = Point::new(x_coord, Self::y_coord(size, point.peak_db()));
+            if index == 0 {
+                peak.move_to(peak_point);
+            } else {
+                peak.line_to(peak_point);
+            }
+        }
+        rms.line_to((
+            Self::x_coord(size, points.len(), points.len() - 1),
+            size.height,
+        ));
+        rms.close_path();
+        ctx.fill(rms, &Color::rgba8(0, 160, 255, 140));
+        ctx.stroke(peak, &Color::rgb8(255, 200, 0), 1.0);
+
+        for (index, _) in points.iter().enumerate().filter(|(_, point)| point.dropout) {
+            let x_coord = Self::x_coord(size, points.len(), index);
+            ctx.stroke(
+                Line::new((x_coord, 0.0), (x_coord, size.height)),
+                &Color::rgb8(255, 60, 60),
+                1.0,
+            );
+        }
+    }
+}
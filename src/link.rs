@@ -0,0 +1,89 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Tempo sync over [Ableton Link](https://www.ableton.com/link/), built with `--features link`.
+//! Without the feature there is no session and no beat grid.
+//!
+//! Link is licensed under the GPL-2.0, so binaries built with the feature are GPL licensed too.
+
+use druid::Data;
+
+/// Beats per bar, shared with the other peers so bar lines agree
+pub const QUANTUM: f64 = 4.0;
+
+/// Where beats fall in a waveform snapshot
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct BeatGrid {
+    /// Session phase at the newest sample, in beats from 0 to [`QUANTUM`]. It's captured when the
+    /// snapshot is published, so the grid trails the audio by the input latency.
+    pub phase_at_end: f64,
+    pub samples_per_beat: f64,
+}
+
+#[cfg(feature = "link")]
+pub struct LinkSession {
+    link: rusty_link::AblLink,
+    state: rusty_link::SessionState,
+}
+
+#[cfg(feature = "link")]
+impl LinkSession {
+    /// Join or start a session on the local network
+    pub fn new() -> Self {
+        let link = rusty_link::AblLink::new(120.0);
+        link.enable(true);
+        LinkSession {
+            link,
+            state: rusty_link::SessionState::new(),
+        }
+    }
+
+    /// The grid for a snapshot whose newest sample is arriving now
+    pub fn beat_grid(&mut self, sample_rate: f32) -> Option<BeatGrid> {
+        self.link.capture_app_session_state(&mut self.state);
+        let now = self.link.clock_micros();
+        Some(BeatGrid {
+            phase_at_end: self.state.phase_at_time(now, QUANTUM),
+            samples_per_beat: sample_rate as f64 * 60.0 / self.state.tempo(),
+        })
+    }
+}
+
+#[cfg(not(feature = "link"))]
+pub struct LinkSession;
+
+#[cfg(not(feature = "link"))]
+impl LinkSession {
+    pub fn new() -> Self {
+        LinkSession
+    }
+
+    pub fn beat_grid(&mut self, _sample_rate: f32) -> Option<BeatGrid> {
+        None
+    }
+}
+
+impl Default for LinkSession {
+    fn default() -> Self {
+        LinkSession::new()
+    }
+}
@@ -0,0 +1,374 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! ITU-R BS.1770 / EBU R128 loudness measurement.
+//!
+//! Each channel is K-weighted and the channels' powers are summed into 100ms blocks. Momentary
+//! loudness covers the last 400ms, short-term the last 3s. Integrated loudness and loudness range
+//! are computed from every block since the last [`LoudnessMeter::reset`], applying the absolute
+//! and relative gates.
+
+use std::collections::VecDeque;
+
+use crate::biquad::Biquad;
+
+const BLOCK_MS: f32 = 100.0;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+const ABSOLUTE_GATE_LUFS: f32 = -70.0;
+const INTEGRATED_RELATIVE_GATE_LU: f32 = -10.0;
+const RANGE_RELATIVE_GATE_LU: f32 = -20.0;
+
+/// The two-stage "K" pre-filter: a high-shelf modelling the head followed by an RLB high-pass.
+#[derive(Clone, Copy)]
+pub struct KWeightingFilter {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeightingFilter {
+    /// Coefficients are derived for any sample rate, matching the 48kHz values in BS.1770
+    pub fn new(sample_rate: f32) -> Self {
+        let sample_rate = sample_rate as f64;
+
+        let f0 = 1_681.974_450_955_533;
+        let gain_db = 3.999_843_853_973_347;
+        let q = 0.707_175_236_955_419_6;
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let vh = 10.0_f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b0: ((vh + vb * k / q + k * k) / a0) as f32,
+            b1: (2.0 * (k * k - vh) / a0) as f32,
+            b2: ((vh - vb * k / q + k * k) / a0) as f32,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+            ..Default::default()
+        };
+
+        let f0 = 38.135_470_876_024_44;
+        let q = 0.500_327_037_323_877_3;
+        let k = (std::f64::consts::PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad {
+            b0: 1.0,
+            b1: -2.0,
+            b2: 1.0,
+            a1: (2.0 * (k * k - 1.0) / a0) as f32,
+            a2: ((1.0 - k / q + k * k) / a0) as f32,
+            ..Default::default()
+        };
+
+        KWeightingFilter { shelf, high_pass }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+/// Convert a K-weighted mean square into LUFS
+pub fn power_to_lufs(power: f32) -> f32 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn lufs_to_power(lufs: f32) -> f32 {
+    10.0_f32.powf((lufs + 0.691) / 10.0)
+}
+
+/// Loudness values published every block
+#[derive(Clone, Copy, Debug)]
+pub struct LoudnessReading {
+    pub momentary: f32,
+    pub short_term: f32,
+}
+
+pub struct LoudnessMeter {
+    /// One per channel
+    filters: Vec<KWeightingFilter>,
+    block_len: usize,
+    block_sum: f32,
+    block_position: usize,
+    /// Mean squares of the most recent blocks, enough to cover the short-term window
+    recent_blocks: VecDeque<f32>,
+    /// Power of each 400ms gating block since the last reset
+    momentary_powers: Vec<f32>,
+    /// Short-term loudness of each block since the last reset, used for loudness range
+    short_term_values: Vec<f32>,
+}
+
+/// BS.1770 channel weights: the surrounds of a 5.1 layout count 1.5dB more and the LFE not at all
+fn channel_weight(channel: usize, channels: usize) -> f32 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+impl LoudnessMeter {
+    pub fn with_channels(sample_rate: f32, channels: usize) -> Self {
+        LoudnessMeter {
+            filters: vec![KWeightingFilter::new(sample_rate); channels.max(1)],
+            block_len: (sample_rate * BLOCK_MS / 1000.0) as usize,
+            block_sum: 0.0,
+            block_position: 0,
+            recent_blocks: VecDeque::with_capacity(SHORT_TERM_BLOCKS),
+            momentary_powers: Vec::new(),
+            short_term_values: Vec::new(),
+        }
+    }
+
+    /// Push one sample per channel, returning a new reading whenever a 100ms block completes.
+    /// Channels past the meter's are ignored.
+    pub fn process_frame(&mut self, frame: &[f32]) -> Option<LoudnessReading> {
+        let channels = self.filters.len();
+        for (channel, (filter, sample)) in self.filters.iter_mut().zip(frame).enumerate() {
+            let weighted = filter.process(*sample);
+            self.block_sum += channel_weight(channel, channels) * weighted * weighted;
+        }
+        self.block_position += 1;
+        if self.block_position < self.block_len {
+            return None;
+        }
+
+        let block_power = self.block_sum / self.block_len as f32;
+        self.block_sum = 0.0;
+        self.block_position = 0;
+        if self.recent_blocks.len() == SHORT_TERM_BLOCKS {
+            self.recent_blocks.pop_front();
+        }
+        self.recent_blocks.push_back(block_power);
+
+        let momentary_power = self.mean_of_last(MOMENTARY_BLOCKS);
+        let short_term = power_to_lufs(self.mean_of_last(SHORT_TERM_BLOCKS));
+        if self.recent_blocks.len() >= MOMENTARY_BLOCKS {
+            self.momentary_powers.push(momentary_power);
+        }
+        if self.recent_blocks.len() >= SHORT_TERM_BLOCKS {
+            self.short_term_values.push(short_term);
+        }
+
+        Some(LoudnessReading {
+            momentary: power_to_lufs(momentary_power),
+            short_term,
+        })
+    }
+
+    fn mean_of_last(&self, blocks: usize) -> f32 {
+        let blocks = blocks.min(self.recent_blocks.len());
+        let sum: f32 = self.recent_blocks.iter().rev().take(blocks).sum();
+        sum / blocks.max(1) as f32
+    }
+
+    /// Gated integrated loudness in LUFS, `-inf` until a block passes the absolute gate
+    pub fn integrated(&self) -> f32 {
+        let mean_power = |threshold: f32| {
+            let gated: Vec<f32> = self
+                .momentary_powers
+                .iter()
+                .copied()
+                .filter(|power| power_to_lufs(*power) > threshold)
+                .collect();
+            gated.iter().sum::<f32>() / gated.len() as f32
+        };
+
+        let relative_gate =
+            power_to_lufs(mean_power(ABSOLUTE_GATE_LUFS)) + INTEGRATED_RELATIVE_GATE_LU;
+        let gated_power = mean_power(relative_gate.max(ABSOLUTE_GATE_LUFS));
+        if gated_power.is_nan() {
+            f32::NEG_INFINITY
+        } else {
+            power_to_lufs(gated_power)
+        }
+    }
+
+    /// Loudness range (EBU Tech 3342) in LU: the spread between the 10th and 95th percentiles of
+    /// the gated short-term loudness distribution
+    pub fn loudness_range(&self) -> f32 {
+        let above_absolute: Vec<f32> = self
+            .short_term_values
+            .iter()
+            .copied()
+            .filter(|lufs| *lufs > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if above_absolute.is_empty() {
+            return 0.0;
+        }
+
+        let mean_power = above_absolute
+            .iter()
+            .map(|lufs| lufs_to_power(*lufs))
+            .sum::<f32>()
+            / above_absolute.len() as f32;
+        let relative_gate = power_to_lufs(mean_power) + RANGE_RELATIVE_GATE_LU;
+        let mut gated: Vec<f32> = above_absolute
+            .into_iter()
+            .filter(|lufs| *lufs > relative_gate)
+            .collect();
+        gated.sort_by(f32::total_cmp);
+
+        let percentile = |p: f32| gated[((gated.len() - 1) as f32 * p).round() as usize];
+        percentile(0.95) - percentile(0.1)
+    }
+
+    /// Restart integration, keeping the filter state and the momentary/short-term windows
+    pub fn reset(&mut self) {
+        self.momentary_powers.clear();
+        self.short_term_values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn stereo_meter() -> LoudnessMeter {
+        LoudnessMeter::with_channels(SAMPLE_RATE, 2)
+    }
+
+    /// Feed a 1kHz tone to both channels, at the level each channel of the EBU stereo test
+    /// signals carries
+    fn tone(meter: &mut LoudnessMeter, dbfs: f32, seconds: f32) -> Option<LoudnessReading> {
+        tone_in(meter, [true, true], dbfs, seconds)
+    }
+
+    fn tone_in(
+        meter: &mut LoudnessMeter,
+        channels: [bool; 2],
+        dbfs: f32,
+        seconds: f32,
+    ) -> Option<LoudnessReading> {
+        let amplitude = 10.0_f32.powf(dbfs / 20.0);
+        let mut reading = None;
+        for index in 0..(SAMPLE_RATE * seconds) as usize {
+            let phase = 2.0 * std::f32::consts::PI * 1_000.0 * index as f32 / SAMPLE_RATE;
+            let sample = amplitude * phase.sin();
+            let frame = channels.map(|on| if on { sample } else { 0.0 });
+            reading = meter.process_frame(&frame).or(reading);
+        }
+        reading
+    }
+
+    fn assert_near(value: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            value,
+            tolerance,
+            expected
+        );
+    }
+
+    /// Tech 3341 cases 1 and 2, a steady tone reads its own level on every meter
+    #[test]
+    fn steady_tone_reads_its_level() {
+        for dbfs in [-23.0, -33.0] {
+            let mut meter = stereo_meter();
+            let reading = tone(&mut meter, dbfs, 20.0).unwrap();
+            assert_near(reading.momentary, dbfs, 0.1);
+            assert_near(reading.short_term, dbfs, 0.1);
+            assert_near(meter.integrated(), dbfs, 0.1);
+        }
+    }
+
+    /// Tech 3341 case 3, the quiet ends fall below the relative gate
+    #[test]
+    fn relative_gate() {
+        let mut meter = stereo_meter();
+        tone(&mut meter, -36.0, 10.0);
+        tone(&mut meter, -23.0, 60.0);
+        tone(&mut meter, -36.0, 10.0);
+        assert_near(meter.integrated(), -23.0, 0.1);
+    }
+
+    /// Tech 3341 case 4, the -72dBFS ends fall below the absolute gate
+    #[test]
+    fn absolute_gate() {
+        let mut meter = stereo_meter();
+        tone(&mut meter, -72.0, 10.0);
+        tone(&mut meter, -36.0, 10.0);
+        tone(&mut meter, -23.0, 60.0);
+        tone(&mut meter, -36.0, 10.0);
+        tone(&mut meter, -72.0, 10.0);
+        assert_near(meter.integrated(), -23.0, 0.1);
+    }
+
+    /// Each channel's power counts, so one channel alone is 3.01dB quieter than both
+    #[test]
+    fn sums_channel_powers() {
+        let mut meter = stereo_meter();
+        tone_in(&mut meter, [true, false], -23.0, 10.0);
+        assert_near(meter.integrated(), -26.01, 0.1);
+
+        let mut mono = LoudnessMeter::with_channels(SAMPLE_RATE, 1);
+        tone(&mut mono, -23.0, 10.0);
+        assert_near(mono.integrated(), -26.01, 0.1);
+    }
+
+    #[test]
+    fn silence_is_ungated() {
+        let mut meter = stereo_meter();
+        tone(&mut meter, -80.0, 5.0);
+        assert_eq!(meter.integrated(), f32::NEG_INFINITY);
+        assert_eq!(meter.loudness_range(), 0.0);
+    }
+
+    /// Tech 3342 cases 1 to 4
+    #[test]
+    fn loudness_range() {
+        let cases: [(&[(f32, f32)], f32); 4] = [
+            (&[(-20.0, 20.0), (-30.0, 20.0)], 10.0),
+            (&[(-20.0, 20.0), (-15.0, 20.0)], 5.0),
+            (&[(-40.0, 20.0), (-20.0, 20.0)], 20.0),
+            (
+                &[
+                    (-50.0, 20.0),
+                    (-35.0, 20.0),
+                    (-20.0, 20.0),
+                    (-35.0, 20.0),
+                    (-50.0, 20.0),
+                ],
+                15.0,
+            ),
+        ];
+        for (segments, expected) in cases {
+            let mut meter = stereo_meter();
+            for (dbfs, seconds) in segments {
+                tone(&mut meter, *dbfs, *seconds);
+            }
+            assert_near(meter.loudness_range(), expected, 1.0);
+        }
+    }
+
+    #[test]
+    fn reset_restarts_integration() {
+        let mut meter = stereo_meter();
+        tone(&mut meter, -33.0, 10.0);
+        meter.reset();
+        tone(&mut meter, -23.0, 10.0);
+        assert_near(meter.integrated(), -23.0, 0.1);
+    }
+}
@@ -0,0 +1,161 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The loudness pane: momentary and short-term loudness over the last three minutes.
+
+use std::sync::Arc;
+
+use druid::kurbo::{BezPath, Line};
+use druid::piet::StrokeStyle;
+use druid::widget::prelude::*;
+use druid::{Color, Data, Point, Selector};
+
+pub const DRAW_LOUDNESS: Selector<LoudnessData> = Selector::new("event-example.draw_loudness");
+
+/// How many readings (one every 100ms) the graph keeps, three minutes worth
+pub const LOUDNESS_HISTORY_LEN: usize = 3 * 60 * 10;
+
+/// Bottom of the graph's LUFS scale, the top is 0 LUFS
+const FLOOR_LUFS: f64 = -60.0;
+
+#[derive(Clone, Data)]
+pub struct LoudnessData {
+    pub momentary: Arc<Vec<f32>>,
+    pub short_term: Arc<Vec<f32>>,
+    pub integrated: f32,
+    pub range: f32,
+}
+
+impl Default for LoudnessData {
+    fn default() -> Self {
+        LoudnessData {
+            momentary: Arc::new(Vec::new()),
+            short_term: Arc::new(Vec::new()),
+            integrated: f32::NEG_INFINITY,
+            range: 0.0,
+        }
+    }
+}
+
+pub fn format_db(value: f32) -> String {
+    if value.is_finite() {
+        format!("{:.1}", value)
+    } else {
+        "-inf".to_string()
+    }
+}
+
+/// Scrolling line chart of momentary and short-term loudness, with the integrated value drawn as a
+/// dashed line.
+#[derive(Default)]
+pub struct LoudnessGraph {}
+
+impl LoudnessGraph {
+    fn y_coord(size: Size, lufs: f32) -> f64 {
+        let lufs = (lufs as f64).clamp(FLOOR_LUFS, 0.0);
+        lufs / FLOOR_LUFS * size.height
+    }
+
+    fn line(size: Size, values: &[f32]) -> BezPath {
+        let mut shape = BezPath::new();
+        // Newest reading on the right edge
+        let offset = LOUDNESS_HISTORY_LEN.saturating_sub(values.len());
+        for (index, value) in values.iter().enumerate() {
+            let x_coord = (offset + index) as f64 / LOUDNESS_HISTORY_LEN as f64 * size.width;
+            let point = Point::new(x_coord, Self::y_coord(size, *value));
+            if index == 0 {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        shape
+    }
+}
+
+impl Widget<LoudnessData> for LoudnessGraph {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut LoudnessData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_LOUDNESS) {
+                *data = cmd.get_unchecked(DRAW_LOUDNESS).clone();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LoudnessData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &LoudnessData,
+        _data: &LoudnessData,
+        _: &Env,
+    ) {
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &LoudnessData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LoudnessData, _env: &Env) {
+        let size = ctx.size();
+
+        let grid = Color::grey(0.3);
+        let mut lufs = -10.0;
+        while lufs > FLOOR_LUFS as f32 {
+            let y_coord = Self::y_coord(size, lufs);
+            ctx.stroke(Line::new((0.0, y_coord), (size.width, y_coord)), &grid, 1.0);
+            lufs -= 10.0;
+        }
+
+        ctx.stroke(
+            Self::line(size, &data.momentary),
+            &Color::rgb8(0, 160, 255),
+            1.0,
+        );
+        ctx.stroke(Self::line(size, &data.short_term), &Color::RED, 2.0);
+
+        if data.integrated.is_finite() {
+            let y_coord = Self::y_coord(size, data.integrated);
+            ctx.stroke_styled(
+                Line::new((0.0, y_coord), (size.width, y_coord)),
+                &Color::WHITE,
+                1.0,
+                &StrokeStyle::new().dash_pattern(&[4.0, 4.0]),
+            );
+        }
+    }
+}
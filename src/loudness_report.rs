@@ -0,0 +1,90 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `loudness-report` sub-command: measures a file and prints or writes a compliance report.
+
+use std::path::Path;
+
+use audio_processor_file::InMemoryAudioFile;
+use audio_processor_traits::{AudioBuffer, AudioProcessorSettings};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::loudness::LoudnessMeter;
+use crate::report::{LoudnessReport, LoudnessTarget};
+use crate::true_peak::TruePeakMeter;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("loudness-report")
+        .about("Measure integrated loudness, loudness range and true peak of an audio file")
+        .arg(Arg::from_usage("<INPUT_PATH> 'The audio file to analyse'"))
+        .arg(
+            Arg::from_usage("--target=[TARGET] 'Loudness target in LUFS'")
+                .possible_values(&LoudnessTarget::NAMES)
+                .allow_hyphen_values(true)
+                .default_value("-23"),
+        )
+        .arg(Arg::from_usage(
+            "-o, --output=[OUTPUT_PATH] 'Write the report here (JSON if it ends in .json)'",
+        ))
+        .arg(Arg::from_usage(
+            "--json 'Print JSON instead of text when no output is given'",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_path = matches.value_of("INPUT_PATH").unwrap();
+    let target: LoudnessTarget = matches.value_of("target").unwrap().parse().unwrap();
+
+    let settings = AudioProcessorSettings::default();
+    let buffer = InMemoryAudioFile::from_path(input_path)
+        .and_then(|mut file| file.read_into_vec_audio_buffer(&settings))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", input_path, err);
+            std::process::exit(1);
+        });
+
+    let channels = buffer.num_channels();
+    let mut loudness = LoudnessMeter::with_channels(settings.sample_rate(), channels);
+    let mut true_peak = TruePeakMeter::with_channels(channels);
+    for frame in buffer.frames() {
+        loudness.process_frame(frame);
+        true_peak.process_frame(frame);
+    }
+
+    let report = LoudnessReport::new(
+        input_path.to_string(),
+        buffer.num_samples() as f32 / settings.sample_rate(),
+        &loudness,
+        &true_peak,
+        target,
+    );
+    match matches.value_of("output") {
+        Some(output_path) => {
+            if let Err(err) = report.write(Path::new(output_path)) {
+                eprintln!("Failed to write the report to {}: {}", output_path, err);
+                std::process::exit(1);
+            }
+        }
+        None if matches.is_present("json") => println!("{}", report.to_json()),
+        None => print!("{}", report.to_text()),
+    }
+}
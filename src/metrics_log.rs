@@ -0,0 +1,314 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Recording of the live metrics to CSV for plotting a session afterwards, one row per 100ms
+//! loudness block.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::loudness::{LoudnessMeter, LoudnessReading};
+
+pub const CSV_HEADER: &str = "time_s,peak_dbfs,true_peak_dbtp,rms_dbfs,momentary_lufs,\
+short_term_lufs,integrated_lufs,loudness_range_lu,spl_dbfs,pitch_hz,tilt_db_per_octave";
+
+/// One block's metrics, levels in dB
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MetricsRow {
+    /// Since recording started
+    pub time_s: f64,
+    pub peak_dbfs: f32,
+    pub true_peak_dbtp: f32,
+    pub rms_dbfs: f32,
+    pub momentary_lufs: f32,
+    pub short_term_lufs: f32,
+    pub integrated_lufs: f32,
+    pub loudness_range_lu: f32,
+    pub spl_dbfs: f32,
+    /// The cepstrum's pitch estimate, left empty in the log when there's no clear peak
+    pub pitch_hz: Option<f32>,
+    /// Spectral tilt, empty for a silent spectrum
+    pub tilt_db_per_octave: Option<f32>,
+}
+
+fn optional_csv(value: Option<f32>) -> String {
+    value
+        .map(|value| format!("{:.2}", value))
+        .unwrap_or_default()
+}
+
+impl MetricsRow {
+    fn to_csv(self) -> String {
+        format!(
+            "{:.3},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{},{}",
+            self.time_s,
+            self.peak_dbfs,
+            self.true_peak_dbtp,
+            self.rms_dbfs,
+            self.momentary_lufs,
+            self.short_term_lufs,
+            self.integrated_lufs,
+            self.loudness_range_lu,
+            self.spl_dbfs,
+            optional_csv(self.pitch_hz),
+            optional_csv(self.tilt_db_per_octave)
+        )
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split(',');
+        let time_s = fields.next()?.parse().ok()?;
+        let mut level = || fields.next()?.parse::<f32>().ok();
+        let row = MetricsRow {
+            time_s,
+            peak_dbfs: level()?,
+            true_peak_dbtp: level()?,
+            rms_dbfs: level()?,
+            momentary_lufs: level()?,
+            short_term_lufs: level()?,
+            integrated_lufs: level()?,
+            loudness_range_lu: level()?,
+            spl_dbfs: level()?,
+            pitch_hz: None,
+            tilt_db_per_octave: None,
+        };
+        let mut optional = || match fields.next()? {
+            "" => Some(None),
+            value => value.parse::<f32>().ok().map(Some),
+        };
+        let row = MetricsRow {
+            pitch_hz: optional()?,
+            tilt_db_per_octave: optional()?,
+            ..row
+        };
+        // More columns than the header
+        fields.next().is_none().then_some(row)
+    }
+}
+
+/// Read a log written by [`MetricsRecorder`]
+pub fn read(path: &Path) -> Result<Vec<MetricsRow>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut lines = text.lines();
+    if lines.next() != Some(CSV_HEADER) {
+        return Err("Not a metrics log, the header doesn't match".to_string());
+    }
+    lines
+        .enumerate()
+        .map(|(index, line)| {
+            MetricsRow::parse(line).ok_or_else(|| format!("Malformed row on line {}", index + 2))
+        })
+        .collect()
+}
+
+/// Levels of the current loudness block, turned into a row each time the loudness meter finishes
+/// one. Runs all the time so alerts and recordings see the same rows.
+pub struct BlockMetrics {
+    sample_rate: f32,
+    /// Samples since the analysis started
+    num_samples: u64,
+    // Over the block so far
+    peak: f32,
+    true_peak: f32,
+    sum_squares: f32,
+    block_len: usize,
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+impl BlockMetrics {
+    pub fn new(sample_rate: f32) -> Self {
+        BlockMetrics {
+            sample_rate,
+            num_samples: 0,
+            peak: 0.0,
+            true_peak: 0.0,
+            sum_squares: 0.0,
+            block_len: 0,
+        }
+    }
+
+    pub fn time_s(&self) -> f64 {
+        self.num_samples as f64 / self.sample_rate as f64
+    }
+
+    /// Add a sample and its true-peak amplitude to the current block
+    pub fn process(&mut self, sample: f32, sample_true_peak: f32) {
+        self.num_samples += 1;
+        self.peak = self.peak.max(sample.abs());
+        self.true_peak = self.true_peak.max(sample_true_peak);
+        self.sum_squares += sample * sample;
+        self.block_len += 1;
+    }
+
+    /// The row for the block the loudness meter just finished, starting the next one
+    pub fn row(
+        &mut self,
+        reading: LoudnessReading,
+        loudness: &LoudnessMeter,
+        spl_dbfs: f32,
+        pitch_hz: Option<f32>,
+        tilt_db_per_octave: Option<f32>,
+    ) -> MetricsRow {
+        let rms = (self.sum_squares / self.block_len.max(1) as f32).sqrt();
+        let row = MetricsRow {
+            time_s: self.time_s(),
+            peak_dbfs: to_db(self.peak),
+            true_peak_dbtp: to_db(self.true_peak),
+            rms_dbfs: to_db(rms),
+            momentary_lufs: reading.momentary,
+            short_term_lufs: reading.short_term,
+            integrated_lufs: loudness.integrated(),
+            loudness_range_lu: loudness.loudness_range(),
+            spl_dbfs,
+            pitch_hz,
+            tilt_db_per_octave,
+        };
+        self.start_block();
+        row
+    }
+
+    /// Drop the current block without a row, when nothing reads it
+    pub fn start_block(&mut self) {
+        self.peak = 0.0;
+        self.true_peak = 0.0;
+        self.sum_squares = 0.0;
+        self.block_len = 0;
+    }
+}
+
+pub struct MetricsRecorder {
+    writer: BufWriter<File>,
+    /// Analysis time recording started at, rows are written relative to it
+    start_s: f64,
+}
+
+impl MetricsRecorder {
+    pub fn create(path: &Path, start_s: f64) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "{}", CSV_HEADER)?;
+        Ok(MetricsRecorder { writer, start_s })
+    }
+
+    pub fn write_row(&mut self, row: MetricsRow) -> io::Result<()> {
+        let row = MetricsRow {
+            time_s: row.time_s - self.start_s,
+            ..row
+        };
+        writeln!(self.writer, "{}", row.to_csv())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels with two decimals, which is what the log keeps
+    fn row() -> MetricsRow {
+        MetricsRow {
+            time_s: 12.3,
+            peak_dbfs: -3.25,
+            true_peak_dbtp: -2.5,
+            rms_dbfs: -18.75,
+            momentary_lufs: -20.5,
+            short_term_lufs: -21.25,
+            integrated_lufs: -23.0,
+            loudness_range_lu: 6.5,
+            spl_dbfs: -19.0,
+            pitch_hz: Some(220.5),
+            tilt_db_per_octave: Some(-4.5),
+        }
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        for row in [
+            row(),
+            MetricsRow {
+                pitch_hz: None,
+                tilt_db_per_octave: None,
+                ..row()
+            },
+        ] {
+            assert_eq!(MetricsRow::parse(&row.to_csv()), Some(row));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        let line = row().to_csv();
+        let fields: Vec<&str> = line.split(',').collect();
+        for malformed in [
+            String::new(),
+            fields[..fields.len() - 1].join(","),
+            format!("{},1.0", line),
+            line.replacen("-3.25", "loud", 1),
+            line.replacen("-3.25", "", 1),
+            line.replacen("220.50", "high", 1),
+        ] {
+            assert_eq!(
+                MetricsRow::parse(&malformed),
+                None,
+                "'{}' parsed",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn reads_a_recorded_log() {
+        let path = std::env::temp_dir().join(format!("metrics-log-{}.csv", std::process::id()));
+        let mut recorder = MetricsRecorder::create(&path, 10.0).unwrap();
+        let rows = [
+            MetricsRow {
+                time_s: 10.0,
+                ..row()
+            },
+            MetricsRow {
+                time_s: 10.1,
+                pitch_hz: None,
+                ..row()
+            },
+        ];
+        for row in rows {
+            recorder.write_row(row).unwrap();
+        }
+        recorder.finish().unwrap();
+        let read_back = read(&path);
+        fs::write(&path, "time,level\n0.0,-3\n").unwrap();
+        let not_a_log = read(&path);
+        let _ = fs::remove_file(&path);
+
+        let read_back = read_back.unwrap();
+        assert_eq!(read_back.len(), 2);
+        assert!((read_back[1].time_s - 0.1).abs() < 1e-9);
+        assert_eq!(read_back[1].pitch_hz, None);
+        assert!(not_a_log.is_err());
+    }
+}
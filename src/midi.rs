@@ -0,0 +1,294 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! MIDI input activity: notes and CC messages stamped with the stream position they arrived at,
+//! drawn as a piano-roll strip covering the same span as the live waveform.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use druid::kurbo::{Line, Rect};
+use druid::widget::prelude::*;
+use druid::{Color, Data, LocalizedString, Selector};
+use serde::{Deserialize, Serialize};
+
+pub const DRAW_MIDI: Selector<MidiRollData> = Selector::new("event-example.draw_midi");
+/// The newest value of a controller, for MIDI learn and mapped parameters
+pub const MIDI_CONTROL: Selector<ControlPoint> = Selector::new("event-example.midi_control");
+
+/// Capacity of the queue from the audio thread
+pub const MIDI_QUEUE_LEN: usize = 1024;
+
+/// Fewest semitones the roll spans, so a single note doesn't fill the strip
+const MIN_NOTE_RANGE: u8 = 12;
+/// Share of the strip's height given to the CC lane under the notes
+const CONTROL_LANE: f64 = 0.25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MidiMessage {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+    ControlChange { controller: u8, value: u8 },
+}
+
+impl MidiMessage {
+    /// Channel voice messages the roll shows, the rest are skipped
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+                Some(MidiMessage::NoteOn { note, velocity })
+            }
+            [status, note, _] if status & 0xF0 == 0x90 || status & 0xF0 == 0x80 => {
+                Some(MidiMessage::NoteOff { note })
+            }
+            [status, controller, value] if status & 0xF0 == 0xB0 => {
+                Some(MidiMessage::ControlChange { controller, value })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A message and the stream position of the callback it was delivered with
+#[derive(Clone, Copy, Debug)]
+pub struct MidiEvent {
+    pub position: u64,
+    pub message: MidiMessage,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoteSpan {
+    pub note: u8,
+    pub velocity: u8,
+    pub start: u64,
+    /// `None` while the note is held
+    pub end: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ControlPoint {
+    pub controller: u8,
+    pub value: u8,
+    pub position: u64,
+}
+
+/// App parameters a controller can be mapped to
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MidiTarget {
+    Gain,
+    Zoom,
+    Freeze,
+    /// Picks which of the optional views is shown
+    View,
+}
+
+impl MidiTarget {
+    pub const ALL: [MidiTarget; 4] = [
+        MidiTarget::Gain,
+        MidiTarget::Zoom,
+        MidiTarget::Freeze,
+        MidiTarget::View,
+    ];
+
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            MidiTarget::Gain => "midi-gain",
+            MidiTarget::Zoom => "midi-zoom",
+            MidiTarget::Freeze => "midi-freeze",
+            MidiTarget::View => "midi-view",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MidiMapping {
+    pub controller: u8,
+    pub target: MidiTarget,
+}
+
+/// What the roll shows, positions in samples
+#[derive(Clone, Data, Default)]
+pub struct MidiRollData {
+    pub notes: Arc<Vec<NoteSpan>>,
+    pub controls: Arc<Vec<ControlPoint>>,
+    /// Right edge of the strip
+    pub end: u64,
+    /// Samples the strip spans
+    pub len: u64,
+}
+
+/// Notes and CC messages of the last `len` samples
+pub struct MidiHistory {
+    len: u64,
+    notes: VecDeque<NoteSpan>,
+    controls: VecDeque<ControlPoint>,
+}
+
+impl MidiHistory {
+    pub fn new(len: usize) -> Self {
+        MidiHistory {
+            len: len as u64,
+            notes: VecDeque::new(),
+            controls: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: MidiEvent) {
+        match event.message {
+            MidiMessage::NoteOn { note, velocity } => self.notes.push_back(NoteSpan {
+                note,
+                velocity,
+                start: event.position,
+                end: None,
+            }),
+            MidiMessage::NoteOff { note } => {
+                if let Some(span) = self
+                    .notes
+                    .iter_mut()
+                    .rev()
+                    .find(|span| span.note == note && span.end.is_none())
+                {
+                    span.end = Some(event.position);
+                }
+            }
+            MidiMessage::ControlChange { controller, value } => {
+                self.controls.push_back(ControlPoint {
+                    controller,
+                    value,
+                    position: event.position,
+                })
+            }
+        }
+    }
+
+    /// Forget whatever ended before the strip spanning up to `end`
+    pub fn data(&mut self, end: u64) -> MidiRollData {
+        let start = end.saturating_sub(self.len);
+        self.notes
+            .retain(|span| span.end.is_none_or(|span_end| span_end >= start));
+        while self
+            .controls
+            .front()
+            .is_some_and(|point| point.position < start)
+        {
+            self.controls.pop_front();
+        }
+        MidiRollData {
+            notes: Arc::new(self.notes.iter().copied().collect()),
+            controls: Arc::new(self.controls.iter().copied().collect()),
+            end,
+            len: self.len,
+        }
+    }
+}
+
+/// Piano-roll strip of held and released notes with CC values underneath, newest on the right
+#[derive(Default)]
+pub struct MidiRoll {}
+
+impl MidiRoll {
+    fn x_coord(size: Size, data: &MidiRollData, position: u64) -> f64 {
+        let start = data.end.saturating_sub(data.len) as f64;
+        ((position as f64 - start) / data.len.max(1) as f64 * size.width).clamp(0.0, size.width)
+    }
+}
+
+impl Widget<MidiRollData> for MidiRoll {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut MidiRollData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_MIDI) {
+                *data = cmd.get_unchecked(DRAW_MIDI).clone();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &MidiRollData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &MidiRollData,
+        _data: &MidiRollData,
+        _: &Env,
+    ) {
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &MidiRollData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &MidiRollData, _env: &Env) {
+        let size = ctx.size();
+        let notes_height = size.height * (1.0 - CONTROL_LANE);
+        ctx.fill(size.to_rect(), &Color::grey(0.1));
+        ctx.stroke(
+            Line::new((0.0, notes_height), (size.width, notes_height)),
+            &Color::grey(0.3),
+            1.0,
+        );
+
+        let lowest = data.notes.iter().map(|span| span.note).min().unwrap_or(60);
+        let highest = data.notes.iter().map(|span| span.note).max().unwrap_or(60);
+        let padding = MIN_NOTE_RANGE.saturating_sub(highest - lowest) / 2;
+        let lowest = lowest.saturating_sub(padding);
+        let range = (highest.saturating_add(padding) - lowest + 1).max(MIN_NOTE_RANGE) as f64;
+        let row_height = notes_height / range;
+        for span in data.notes.iter() {
+            let row = range - 1.0 - (span.note - lowest) as f64;
+            let rect = Rect::new(
+                Self::x_coord(size, data, span.start),
+                row * row_height,
+                Self::x_coord(size, data, span.end.unwrap_or(data.end)),
+                (row + 1.0) * row_height,
+            );
+            let brightness = 0.3 + 0.7 * span.velocity as f64 / 127.0;
+            ctx.fill(rect, &Color::rgb(0.0, 0.6 * brightness, brightness));
+        }
+
+        // One tick per message, as tall as its value
+        let lane_height = size.height - notes_height;
+        for point in data.controls.iter() {
+            let x_coord = Self::x_coord(size, data, point.position);
+            let top = size.height - lane_height * point.value as f64 / 127.0;
+            ctx.stroke(
+                Line::new((x_coord, size.height), (x_coord, top)),
+                &Color::rgb8(255, 160, 0),
+                1.0,
+            );
+        }
+    }
+}
@@ -0,0 +1,314 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Monitor output. It's silent unless the input is being listened to or a band is soloed, in
+//! which case the input is sent to every output channel. A soloed band is band-passed so a hum or
+//! whine can be hunted by ear while watching it, and detected hum can be notched out to hear the
+//! difference. Holding the talkback button plays the input for as long as it's held, faded in and
+//! out and soft-limited well below full scale, to check by ear what an odd looking waveform is
+//! without a loud surprise. Only the f32 input path has an output to monitor on.
+//!
+//! The UI sets these through [`MonitorControl`] and the audio thread picks them up at the start of
+//! each callback, rebuilding its filters without allocating.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use druid::widget::Controller;
+use druid::{Data, Env, Event, EventCtx, Key, Selector, Widget};
+
+use crate::biquad::Biquad;
+use crate::hum::HUM_HARMONICS;
+
+/// Start soloing a band, or stop with `None`
+pub const SOLO_BAND: Selector<Option<SoloBand>> = Selector::new("event-example.solo_band");
+
+pub const SOLO_LOW: Key<f64> = Key::new("event-example.solo_low");
+/// 0 while nothing is soloed
+pub const SOLO_HIGH: Key<f64> = Key::new("event-example.solo_high");
+
+/// Narrowest band that can be soloed, as a ratio between its edges
+const MIN_BAND_RATIO: f32 = 1.05;
+/// Cascaded sections on each edge, 24 dB per octave
+const STAGES: usize = 2;
+/// Narrow enough to leave the rest of a harmonic's octave alone
+const NOTCH_Q: f32 = 30.0;
+/// Talkback's gain, -12 dB
+const TALKBACK_GAIN: f32 = 0.25;
+/// Talkback's soft limit, -12 dBFS
+const TALKBACK_CEILING: f32 = 0.25;
+/// Talkback's fade in and out
+const TALKBACK_FADE_SECONDS: f32 = 0.02;
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct SoloBand {
+    pub low_hz: f32,
+    pub high_hz: f32,
+}
+
+impl SoloBand {
+    /// The band between two frequencies in either order, widened to [`MIN_BAND_RATIO`]
+    pub fn between(a_hz: f32, b_hz: f32) -> Self {
+        let low_hz = a_hz.min(b_hz).max(1.0);
+        SoloBand {
+            low_hz,
+            high_hz: a_hz.max(b_hz).max(low_hz * MIN_BAND_RATIO),
+        }
+    }
+
+    pub fn set_env(band: Option<SoloBand>, env: &mut Env) {
+        let (low_hz, high_hz) = band.map_or((0.0, 0.0), |band| (band.low_hz, band.high_hz));
+        env.set(SOLO_LOW, low_hz as f64);
+        env.set(SOLO_HIGH, high_hz as f64);
+    }
+
+    /// The soloed band, for highlighting it
+    pub fn from_env(env: &Env) -> Option<SoloBand> {
+        let high_hz = env.try_get(SOLO_HIGH).unwrap_or(0.0) as f32;
+        (high_hz > 0.0).then(|| SoloBand {
+            low_hz: env.try_get(SOLO_LOW).unwrap_or(0.0) as f32,
+            high_hz,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        let format_hz = |hz: f32| {
+            if hz >= 1000.0 {
+                format!("{:.2} kHz", hz / 1000.0)
+            } else {
+                format!("{:.0} Hz", hz)
+            }
+        };
+        format!("{} – {}", format_hz(self.low_hz), format_hz(self.high_hz))
+    }
+}
+
+/// What the monitor plays, frequencies as f32 bits shared with the audio thread. A high edge or
+/// notch frequency of 0 means off.
+#[derive(Default)]
+pub struct MonitorControl {
+    low_hz: AtomicU32,
+    high_hz: AtomicU32,
+    listen: AtomicBool,
+    /// Set while the talkback button is held
+    talkback: AtomicBool,
+    notch_hz: AtomicU32,
+    /// Set until the audio thread plays the latency calibration click
+    click: AtomicBool,
+}
+
+impl MonitorControl {
+    pub fn set_band(&self, band: Option<SoloBand>) {
+        let (low_hz, high_hz) = band.map_or((0.0, 0.0), |band| (band.low_hz, band.high_hz));
+        self.low_hz.store(low_hz.to_bits(), Ordering::Relaxed);
+        self.high_hz.store(high_hz.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Play the whole input, not only while a band is soloed
+    pub fn set_listen(&self, listen: bool) {
+        self.listen.store(listen, Ordering::Relaxed);
+    }
+
+    /// Play the input at a safe level while the talkback button is held
+    pub fn set_talkback(&self, talkback: bool) {
+        self.talkback.store(talkback, Ordering::Relaxed);
+    }
+
+    /// Notch out a mains frequency and its harmonics
+    pub fn set_notch(&self, mains_hz: Option<f32>) {
+        self.notch_hz
+            .store(mains_hz.unwrap_or(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Play a latency calibration click on the next callback
+    pub fn request_click(&self) {
+        self.click.store(true, Ordering::Relaxed);
+    }
+
+    fn band(&self) -> Option<SoloBand> {
+        let high_hz = f32::from_bits(self.high_hz.load(Ordering::Relaxed));
+        (high_hz > 0.0).then(|| SoloBand {
+            low_hz: f32::from_bits(self.low_hz.load(Ordering::Relaxed)),
+            high_hz,
+        })
+    }
+
+    fn notch(&self) -> Option<f32> {
+        let mains_hz = f32::from_bits(self.notch_hz.load(Ordering::Relaxed));
+        (mains_hz > 0.0).then_some(mains_hz)
+    }
+}
+
+/// Audio thread side, filters the captured channel for the outputs
+pub struct MonitorFilter {
+    control: Arc<MonitorControl>,
+    sample_rate: f32,
+    listen: bool,
+    talkback: bool,
+    /// Ramps towards [`TALKBACK_GAIN`] while talkback is held and back to 0 after
+    talkback_gain: f32,
+    /// The limiter's deepest reduction since it was last taken, `None` while it hasn't run
+    gain_reduction_db: Option<f32>,
+    band: Option<SoloBand>,
+    high_pass: [Biquad; STAGES],
+    low_pass: [Biquad; STAGES],
+    notch_hz: Option<f32>,
+    notches: [Biquad; HUM_HARMONICS],
+}
+
+impl MonitorFilter {
+    pub fn new(control: Arc<MonitorControl>) -> Self {
+        MonitorFilter {
+            control,
+            sample_rate: 44100.0,
+            listen: false,
+            talkback: false,
+            talkback_gain: 0.0,
+            gain_reduction_db: None,
+            band: None,
+            high_pass: [Biquad::default(); STAGES],
+            low_pass: [Biquad::default(); STAGES],
+            notch_hz: None,
+            notches: [Biquad::identity(); HUM_HARMONICS],
+        }
+    }
+
+    /// Whether a calibration click was requested since the last call
+    pub fn take_click(&self) -> bool {
+        self.control.click.swap(false, Ordering::Relaxed)
+    }
+
+    /// A filter for a new stream following the same controls
+    pub fn restarted(&self) -> Self {
+        MonitorFilter::new(self.control.clone())
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.band = None;
+        self.notch_hz = None;
+    }
+
+    /// Pick up changed settings, called once per callback
+    pub fn update(&mut self) {
+        self.listen = self.control.listen.load(Ordering::Relaxed);
+        self.talkback = self.control.talkback.load(Ordering::Relaxed);
+        let nyquist = self.sample_rate / 2.0;
+        let band = self.control.band();
+        if band != self.band {
+            if let Some(band) = band {
+                let q = std::f32::consts::FRAC_1_SQRT_2;
+                let high_hz = band.high_hz.min(nyquist * 0.95);
+                let low_hz = band.low_hz.min(high_hz);
+                self.high_pass = [Biquad::high_pass(low_hz, q, self.sample_rate); STAGES];
+                self.low_pass = [Biquad::low_pass(high_hz, q, self.sample_rate); STAGES];
+            }
+            self.band = band;
+        }
+        let notch_hz = self.control.notch();
+        if notch_hz != self.notch_hz {
+            if let Some(mains_hz) = notch_hz {
+                for (number, notch) in self.notches.iter_mut().enumerate() {
+                    let hz = mains_hz * (number + 1) as f32;
+                    *notch = if hz < nyquist * 0.95 {
+                        Biquad::notch(hz, NOTCH_Q, self.sample_rate)
+                    } else {
+                        Biquad::identity()
+                    };
+                }
+            }
+            self.notch_hz = notch_hz;
+        }
+    }
+
+    /// What to send to the outputs for an input sample, `None` while the monitor is silent
+    pub fn process(&mut self, mut sample: f32) -> Option<f32> {
+        if !self.listen && self.band.is_none() {
+            return self.talkback(sample);
+        }
+        if self.notch_hz.is_some() {
+            sample = self
+                .notches
+                .iter_mut()
+                .fold(sample, |sample, notch| notch.process(sample));
+        }
+        if self.band.is_some() {
+            sample = self
+                .high_pass
+                .iter_mut()
+                .chain(self.low_pass.iter_mut())
+                .fold(sample, |sample, stage| stage.process(sample));
+        }
+        Some(sample)
+    }
+
+    /// Talkback's share of the output while it's held or fading out, the input as it's drawn
+    fn talkback(&mut self, sample: f32) -> Option<f32> {
+        let step = TALKBACK_GAIN / (TALKBACK_FADE_SECONDS * self.sample_rate);
+        self.talkback_gain = if self.talkback {
+            (self.talkback_gain + step).min(TALKBACK_GAIN)
+        } else {
+            (self.talkback_gain - step).max(0.0)
+        };
+        if self.talkback_gain <= 0.0 {
+            return None;
+        }
+        let driven = sample * self.talkback_gain;
+        let limited = TALKBACK_CEILING * (driven / TALKBACK_CEILING).tanh();
+        let reduction_db = if driven.abs() > f32::EPSILON {
+            20.0 * (limited / driven).log10()
+        } else {
+            0.0
+        };
+        self.gain_reduction_db = Some(
+            self.gain_reduction_db
+                .map_or(reduction_db, |deepest| deepest.min(reduction_db)),
+        );
+        Some(limited)
+    }
+
+    /// The talkback limiter's deepest reduction since the last call, `None` if it didn't run
+    pub fn take_gain_reduction(&mut self) -> Option<f32> {
+        self.gain_reduction_db.take()
+    }
+}
+
+/// Holds a `bool` true while the widget is pressed, for the talkback button
+pub struct PushToListen;
+
+impl<W: Widget<bool>> Controller<bool, W> for PushToListen {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut bool,
+        env: &Env,
+    ) {
+        match event {
+            Event::MouseDown(mouse) if mouse.button.is_left() => *data = true,
+            Event::MouseUp(_) if ctx.is_active() => *data = false,
+            _ => {}
+        }
+        child.event(ctx, event, data, env)
+    }
+}
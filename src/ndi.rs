@@ -0,0 +1,188 @@
+//! NDI output of the rendered waveform, `--ndi NAME`, so OBS (with obs-ndi), Resolume or other VJ
+//! software on the network can composite the view live without screen capture. Only built with
+//! the `ndi` feature, without it [`NdiSender::new`] fails.
+//!
+//! The NDI runtime is loaded when the sender starts rather than linked, since its license doesn't
+//! allow redistributing it. Install it from ndi.video, or point `NDILIB_REDIST_FOLDER` at it.
+
+use std::io;
+
+#[cfg(feature = "ndi")]
+mod sys {
+    use std::ffi::{c_char, c_float, c_int, c_void};
+
+    pub type Instance = *mut c_void;
+
+    #[repr(C)]
+    pub struct SendCreate {
+        pub ndi_name: *const c_char,
+        pub groups: *const c_char,
+        pub clock_video: bool,
+        pub clock_audio: bool,
+    }
+
+    #[repr(C)]
+    pub struct VideoFrame {
+        pub xres: c_int,
+        pub yres: c_int,
+        pub four_cc: u32,
+        pub frame_rate_n: c_int,
+        pub frame_rate_d: c_int,
+        pub picture_aspect_ratio: c_float,
+        pub frame_format_type: c_int,
+        pub timecode: i64,
+        pub data: *const u8,
+        pub line_stride_in_bytes: c_int,
+        pub metadata: *const c_char,
+        pub timestamp: i64,
+    }
+
+    pub const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+    pub const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+    /// Let the runtime stamp each frame as it's sent
+    pub const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+    pub type Initialize = unsafe extern "C" fn() -> bool;
+    pub type SendCreateFn = unsafe extern "C" fn(*const SendCreate) -> Instance;
+    pub type SendDestroy = unsafe extern "C" fn(Instance);
+    pub type SendVideo = unsafe extern "C" fn(Instance, *const VideoFrame);
+
+    /// Where the runtime installer puts the library on each platform
+    pub fn library_name() -> &'static str {
+        if cfg!(target_os = "windows") {
+            "Processing.NDI.Lib.x64.dll"
+        } else if cfg!(target_os = "macos") {
+            "libndi.dylib"
+        } else {
+            "libndi.so.5"
+        }
+    }
+}
+
+#[cfg(feature = "ndi")]
+pub struct NdiSender {
+    library: libloading::Library,
+    instance: sys::Instance,
+    /// Keeps the name the instance was created with alive
+    _name: std::ffi::CString,
+}
+
+// The NDI SDK allows a sender to be used from any thread, one at a time
+#[cfg(feature = "ndi")]
+unsafe impl Send for NdiSender {}
+
+#[cfg(feature = "ndi")]
+impl NdiSender {
+    /// Load the runtime and announce a source called `name` on the network
+    pub fn new(name: &str) -> io::Result<Self> {
+        let error = io::Error::other::<String>;
+        let path = match std::env::var_os("NDILIB_REDIST_FOLDER") {
+            Some(folder) => std::path::Path::new(&folder).join(sys::library_name()),
+            None => sys::library_name().into(),
+        };
+        let library = unsafe { libloading::Library::new(&path) }
+            .map_err(|err| error(format!("Failed to load the NDI runtime: {}", err)))?;
+        let name = std::ffi::CString::new(name)
+            .map_err(|_| error("NDI source names can't contain NUL".to_string()))?;
+        let instance = unsafe {
+            let initialize = library
+                .get::<sys::Initialize>(b"NDIlib_initialize\0")
+                .map_err(|err| error(err.to_string()))?;
+            if !initialize() {
+                return Err(error("This CPU isn't supported by NDI".to_string()));
+            }
+            let create = library
+                .get::<sys::SendCreateFn>(b"NDIlib_send_create\0")
+                .map_err(|err| error(err.to_string()))?;
+            create(&sys::SendCreate {
+                ndi_name: name.as_ptr(),
+                groups: std::ptr::null(),
+                // Frames are already paced by the writer
+                clock_video: false,
+                clock_audio: false,
+            })
+        };
+        if instance.is_null() {
+            return Err(error("Failed to create the NDI sender".to_string()));
+        }
+        Ok(NdiSender {
+            library,
+            instance,
+            _name: name,
+        })
+    }
+
+    /// Send a `width x height` RGBA frame, copied before this returns
+    pub fn send(&mut self, frame: &[u8], width: usize, height: usize, fps: u32) -> io::Result<()> {
+        if frame.len() != width * height * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A {}x{} RGBA frame is {} bytes, not {}",
+                    width,
+                    height,
+                    width * height * 4,
+                    frame.len()
+                ),
+            ));
+        }
+        unsafe {
+            let send = self
+                .library
+                .get::<sys::SendVideo>(b"NDIlib_send_send_video_v2\0")
+                .map_err(|err| io::Error::other(err.to_string()))?;
+            send(
+                self.instance,
+                &sys::VideoFrame {
+                    xres: width as i32,
+                    yres: height as i32,
+                    four_cc: sys::FOURCC_RGBA,
+                    frame_rate_n: fps as i32,
+                    frame_rate_d: 1,
+                    picture_aspect_ratio: width as f32 / height as f32,
+                    frame_format_type: sys::FRAME_FORMAT_PROGRESSIVE,
+                    timecode: sys::TIMECODE_SYNTHESIZE,
+                    data: frame.as_ptr(),
+                    line_stride_in_bytes: (width * 4) as i32,
+                    metadata: std::ptr::null(),
+                    timestamp: 0,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ndi")]
+impl Drop for NdiSender {
+    fn drop(&mut self) {
+        unsafe {
+            if let Ok(destroy) = self
+                .library
+                .get::<sys::SendDestroy>(b"NDIlib_send_destroy\0")
+            {
+                destroy(self.instance);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "ndi"))]
+pub struct NdiSender;
+
+#[cfg(not(feature = "ndi"))]
+impl NdiSender {
+    pub fn new(_name: &str) -> io::Result<Self> {
+        Err(io::Error::other("Built without the `ndi` feature"))
+    }
+
+    pub fn send(
+        &mut self,
+        _frame: &[u8],
+        _width: usize,
+        _height: usize,
+        _fps: u32,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
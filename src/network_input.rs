@@ -0,0 +1,290 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The network backend: PCM received over UDP, bare or in RTP packets, so a remote machine's
+//! audio can be watched live. RTP carries L16 or L24, big-endian 16 or 24-bit samples as in
+//! RFC 3551 and AES67, and packets lost on the way are filled with silence so the stream position
+//! keeps time. There's no jitter buffer, packets are analysed as they arrive. Opus streams aren't
+//! decoded, and listening for one fails with an error rather than analysing the compressed bytes.
+
+use std::io;
+use std::net::UdpSocket;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::backend::{fixed_formats, AudioBackend, AudioStream, InputThread};
+use crate::buffer_analyser::{BufferAnalyserProcessor, InputSample, I24};
+use crate::device_formats::{DeviceFormats, StreamRequest};
+
+/// How often the receiving thread checks whether it should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// Largest UDP datagram
+const MAX_PACKET_LEN: usize = 65536;
+/// Most lost packets filled with silence at once, a longer gap is taken as the sender restarting
+const MAX_LOST_PACKETS: u16 = 16;
+const RTP_HEADER_LEN: usize = 12;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PacketFormat {
+    /// RTP with an L16 payload
+    RtpL16,
+    /// RTP with an L24 payload
+    RtpL24,
+    /// RTP with an Opus payload, which isn't decoded
+    RtpOpus,
+    /// Bare interleaved 16-bit little-endian samples
+    S16Le,
+    /// Bare interleaved 24-bit little-endian samples, packed in three bytes
+    S24Le,
+    /// Bare interleaved 32-bit float little-endian samples
+    F32Le,
+    /// Bare interleaved 64-bit float little-endian samples
+    F64Le,
+}
+
+impl PacketFormat {
+    pub const NAMES: [&'static str; 7] = [
+        "rtp-l16", "rtp-l24", "rtp-opus", "s16le", "s24le", "f32le", "f64le",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PacketFormat::RtpL16 => "rtp-l16",
+            PacketFormat::RtpL24 => "rtp-l24",
+            PacketFormat::RtpOpus => "rtp-opus",
+            PacketFormat::S16Le => "s16le",
+            PacketFormat::S24Le => "s24le",
+            PacketFormat::F32Le => "f32le",
+            PacketFormat::F64Le => "f64le",
+        }
+    }
+
+    /// Whether packets carry an RTP header
+    fn is_rtp(&self) -> bool {
+        matches!(
+            self,
+            PacketFormat::RtpL16 | PacketFormat::RtpL24 | PacketFormat::RtpOpus
+        )
+    }
+}
+
+impl FromStr for PacketFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rtp-l16" => Ok(PacketFormat::RtpL16),
+            "rtp-l24" => Ok(PacketFormat::RtpL24),
+            "rtp-opus" => Ok(PacketFormat::RtpOpus),
+            "s16le" => Ok(PacketFormat::S16Le),
+            "s24le" => Ok(PacketFormat::S24Le),
+            "f32le" => Ok(PacketFormat::F32Le),
+            "f64le" => Ok(PacketFormat::F64Le),
+            _ => Err(format!(
+                "Unknown packet format '{}', expected one of {}",
+                s,
+                PacketFormat::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// The payload of an RTP packet and its sequence number
+fn rtp_payload(packet: &[u8]) -> Option<(u16, &[u8])> {
+    let first = *packet.first()?;
+    if packet.len() < RTP_HEADER_LEN || first >> 6 != 2 {
+        return None;
+    }
+    let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+    let mut start = RTP_HEADER_LEN + 4 * (first & 0x0F) as usize;
+    if first & 0x10 != 0 {
+        let words = packet.get(start + 2..start + 4)?;
+        start += 4 + 4 * u16::from_be_bytes([words[0], words[1]]) as usize;
+    }
+    let mut end = packet.len();
+    if first & 0x20 != 0 {
+        end = end.checked_sub(*packet.last()? as usize)?;
+    }
+    packet.get(start..end).map(|payload| (sequence, payload))
+}
+
+/// Turns packets into samples, keeping track of RTP sequence numbers
+struct PacketDecoder {
+    format: PacketFormat,
+    last_sequence: Option<u16>,
+    /// Samples in the last packet, what a lost one is filled with
+    last_len: usize,
+}
+
+impl PacketDecoder {
+    fn new(format: PacketFormat) -> Self {
+        PacketDecoder {
+            format,
+            last_sequence: None,
+            last_len: 0,
+        }
+    }
+
+    /// Append a packet's interleaved samples to `samples`, after silence for any lost before it.
+    /// Late or duplicate RTP packets add nothing.
+    fn decode(&mut self, packet: &[u8], samples: &mut Vec<f32>) {
+        let payload = if self.format.is_rtp() {
+            match rtp_payload(packet) {
+                Some((sequence, payload)) => {
+                    let lost = self
+                        .last_sequence
+                        .map_or(0, |last| sequence.wrapping_sub(last).wrapping_sub(1));
+                    if lost >= u16::MAX / 2 {
+                        return;
+                    }
+                    if lost > 0 && lost <= MAX_LOST_PACKETS {
+                        samples.resize(samples.len() + lost as usize * self.last_len, 0.0);
+                    }
+                    self.last_sequence = Some(sequence);
+                    payload
+                }
+                None => return,
+            }
+        } else {
+            packet
+        };
+        let start = samples.len();
+        match self.format {
+            PacketFormat::RtpL16 => samples.extend(
+                payload
+                    .chunks_exact(2)
+                    .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]]).to_f32()),
+            ),
+            PacketFormat::RtpL24 => samples.extend(
+                payload
+                    .chunks_exact(3)
+                    .map(|bytes| I24::from_be_bytes([bytes[0], bytes[1], bytes[2]]).to_f32()),
+            ),
+            // Refused in `open`, there's no decoder to hand the payload to
+            PacketFormat::RtpOpus => {}
+            PacketFormat::S16Le => samples.extend(
+                payload
+                    .chunks_exact(2)
+                    .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]).to_f32()),
+            ),
+            PacketFormat::S24Le => samples.extend(
+                payload
+                    .chunks_exact(3)
+                    .map(|bytes| I24::from_le_bytes([bytes[0], bytes[1], bytes[2]]).to_f32()),
+            ),
+            PacketFormat::F32Le => samples.extend(
+                payload
+                    .chunks_exact(4)
+                    .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            ),
+            PacketFormat::F64Le => samples.extend(payload.chunks_exact(8).map(|bytes| {
+                let mut eight = [0; 8];
+                eight.copy_from_slice(bytes);
+                f64::from_le_bytes(eight).to_f32()
+            })),
+        }
+        self.last_len = samples.len() - start;
+    }
+}
+
+/// Listens for packets on a UDP address, at a rate and channel count agreed with the sender
+pub struct NetworkBackend {
+    address: String,
+    format: PacketFormat,
+    sample_rate: f32,
+    channels: usize,
+}
+
+impl NetworkBackend {
+    pub fn new(address: String, format: PacketFormat, sample_rate: f32, channels: usize) -> Self {
+        NetworkBackend {
+            address,
+            format,
+            sample_rate,
+            channels: channels.max(1),
+        }
+    }
+}
+
+impl AudioBackend for NetworkBackend {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn formats(&self) -> DeviceFormats {
+        let mut formats = fixed_formats(
+            format!("udp://{}", self.address),
+            StreamRequest {
+                sample_rate: self.sample_rate as u32,
+                channels: self.channels as u16,
+                buffer_size: None,
+            },
+        );
+        formats.sample_formats = Arc::new(vec![self.format.name().to_string()]);
+        formats
+    }
+
+    fn open(&mut self, mut processor: BufferAnalyserProcessor) -> io::Result<AudioStream> {
+        if self.format == PacketFormat::RtpOpus {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "Opus isn't decoded, send {} or {} to {} instead",
+                    PacketFormat::RtpL16.name(),
+                    PacketFormat::RtpL24.name(),
+                    self.address
+                ),
+            ));
+        }
+        let socket = UdpSocket::bind(&self.address).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Failed to listen on {}: {}", self.address, err),
+            )
+        })?;
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+        log::info!("Listening for {:?} audio on {}", self.format, self.address);
+        processor.set_sample_rate(self.sample_rate);
+        let channels = self.channels;
+        let mut decoder = PacketDecoder::new(self.format);
+        Ok(InputThread::spawn(move |stop| {
+            let mut packet = vec![0; MAX_PACKET_LEN];
+            let mut samples = Vec::new();
+            while !stop.load(Ordering::Relaxed) {
+                // Timeouts only wake the loop up to check the flag
+                let len = match socket.recv(&mut packet) {
+                    Ok(len) => len,
+                    Err(_) => continue,
+                };
+                samples.clear();
+                decoder.decode(&packet[..len], &mut samples);
+                // Partial frames at the end of a packet are dropped
+                samples.truncate(samples.len() / channels * channels);
+                if !samples.is_empty() {
+                    processor.process_input(&samples, channels);
+                }
+            }
+        }))
+    }
+}
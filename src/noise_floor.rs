@@ -0,0 +1,97 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Running noise floor estimate: a low percentile of recent frame energies, which tracks the
+//! quiet gaps between speech or notes without needing a separate silence detector.
+
+use std::collections::VecDeque;
+
+/// Frame energies are measured over this long
+const FRAME_SECONDS: f32 = 0.02;
+/// How much history the percentile is taken over
+const HISTORY_SECONDS: f32 = 10.0;
+/// Frames at or below this share of the history count as the floor
+const FLOOR_PERCENTILE: f32 = 0.1;
+/// The signal level the SNR is measured at, the mean over this long
+const SIGNAL_SECONDS: f32 = 0.4;
+/// Below this a frame is digital silence rather than noise
+const MIN_DB: f32 = -150.0;
+
+pub struct NoiseFloorEstimator {
+    frame_len: usize,
+    signal_frames: usize,
+    max_frames: usize,
+    frame_energy: f32,
+    frame_samples: usize,
+    /// Mean square of each recent frame, oldest first
+    frames: VecDeque<f32>,
+}
+
+impl NoiseFloorEstimator {
+    pub fn new(sample_rate: f32) -> Self {
+        let frame_len = (sample_rate * FRAME_SECONDS) as usize;
+        NoiseFloorEstimator {
+            frame_len,
+            signal_frames: (SIGNAL_SECONDS / FRAME_SECONDS) as usize,
+            max_frames: (HISTORY_SECONDS / FRAME_SECONDS) as usize,
+            frame_energy: 0.0,
+            frame_samples: 0,
+            frames: VecDeque::new(),
+        }
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        self.frame_energy += sample * sample;
+        self.frame_samples += 1;
+        if self.frame_samples == self.frame_len {
+            if self.frames.len() == self.max_frames {
+                self.frames.pop_front();
+            }
+            self.frames
+                .push_back(self.frame_energy / self.frame_len as f32);
+            self.frame_energy = 0.0;
+            self.frame_samples = 0;
+        }
+    }
+
+    /// Noise floor in dBFS RMS, `None` until a frame has been measured
+    pub fn floor_db(&self) -> Option<f32> {
+        let mut energies: Vec<f32> = self.frames.iter().copied().collect();
+        if energies.is_empty() {
+            return None;
+        }
+        let index = ((energies.len() - 1) as f32 * FLOOR_PERCENTILE) as usize;
+        let (_, floor, _) = energies.select_nth_unstable_by(index, f32::total_cmp);
+        Some(to_db(*floor))
+    }
+
+    /// Recent signal level in dBFS RMS
+    pub fn signal_db(&self) -> Option<f32> {
+        let recent = self.frames.iter().rev().take(self.signal_frames);
+        let count = recent.len();
+        (count > 0).then(|| to_db(recent.sum::<f32>() / count as f32))
+    }
+}
+
+fn to_db(mean_square: f32) -> f32 {
+    (10.0 * mean_square.log10()).max(MIN_DB)
+}
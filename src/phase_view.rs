@@ -0,0 +1,209 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Phase spectrum and group delay of the latest window, on a log frequency axis.
+//!
+//! Bins more than [`DYNAMIC_RANGE_DB`] below the loudest bin are left out, their phase is noise.
+
+use std::sync::Arc;
+
+use audio_processor_analysis::window_functions::make_hann_vec;
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::{Color, Data, Point, Selector};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+pub const DRAW_PHASE: Selector<PhaseData> = Selector::new("event-example.draw_phase");
+
+pub const WINDOW_LEN: usize = 2048;
+
+const DYNAMIC_RANGE_DB: f32 = 60.0;
+const MIN_HZ: f32 = 20.0;
+/// Group delay is drawn between plus and minus this many milliseconds
+const GROUP_DELAY_RANGE_MS: f32 = 25.0;
+
+#[derive(Clone, Data)]
+pub struct PhaseData {
+    /// Wrapped phase per FFT bin in radians, NaN where the bin is too quiet
+    pub phase: Arc<Vec<f32>>,
+    /// Group delay per FFT bin in milliseconds, NaN where the bin is too quiet
+    pub group_delay_ms: Arc<Vec<f32>>,
+    pub bin_hz: f32,
+}
+
+impl Default for PhaseData {
+    fn default() -> Self {
+        PhaseData {
+            phase: Arc::new(Vec::new()),
+            group_delay_ms: Arc::new(Vec::new()),
+            bin_hz: 0.0,
+        }
+    }
+}
+
+fn wrap_phase(phase: f32) -> f32 {
+    let tau = 2.0 * std::f32::consts::PI;
+    phase - tau * (phase / tau).round()
+}
+
+pub struct PhaseAnalyser {
+    sample_rate: f32,
+    window: Vec<f32>,
+    fft: Arc<dyn Fft<f32>>,
+}
+
+impl PhaseAnalyser {
+    pub fn new(sample_rate: f32) -> Self {
+        PhaseAnalyser {
+            sample_rate,
+            window: make_hann_vec(WINDOW_LEN),
+            fft: FftPlanner::new().plan_fft_forward(WINDOW_LEN),
+        }
+    }
+
+    /// Analyse the last [`WINDOW_LEN`] samples
+    pub fn analyse(&self, samples: &[f32]) -> PhaseData {
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, window)| Complex::new(sample * window, 0.0))
+            .collect();
+        self.fft.process(&mut buffer);
+        let bins = &buffer[..WINDOW_LEN / 2];
+
+        let loudest = bins.iter().fold(0.0_f32, |max, bin| max.max(bin.norm()));
+        let threshold = loudest * 10.0_f32.powf(-DYNAMIC_RANGE_DB / 20.0);
+        let audible = |index: usize| loudest > 0.0 && bins[index].norm() > threshold;
+
+        let phase = (0..bins.len())
+            .map(|index| {
+                if audible(index) {
+                    bins[index].arg()
+                } else {
+                    f32::NAN
+                }
+            })
+            .collect();
+
+        // Group delay is -dφ/dω, estimated with a central difference
+        let bin_radians_per_second =
+            2.0 * std::f32::consts::PI * self.sample_rate / WINDOW_LEN as f32;
+        let group_delay_ms = (0..bins.len())
+            .map(|index| {
+                if index == 0 || index + 1 == bins.len() || !audible(index) {
+                    return f32::NAN;
+                }
+                let phase_step = wrap_phase(bins[index + 1].arg() - bins[index - 1].arg());
+                -phase_step / (2.0 * bin_radians_per_second) * 1000.0
+            })
+            .collect();
+
+        PhaseData {
+            phase: Arc::new(phase),
+            group_delay_ms: Arc::new(group_delay_ms),
+            bin_hz: self.sample_rate / WINDOW_LEN as f32,
+        }
+    }
+}
+
+/// Wrapped phase (white, -π to π) and group delay (orange) against log frequency
+#[derive(Default)]
+pub struct PhasePlot {}
+
+impl PhasePlot {
+    /// Line through the values that aren't NaN, breaking the line at gaps
+    fn curve(
+        size: Size,
+        data: &PhaseData,
+        values: &[f32],
+        y_coord: impl Fn(f32) -> f64,
+    ) -> BezPath {
+        let nyquist = data.bin_hz * values.len() as f32;
+        let mut shape = BezPath::new();
+        let mut drawing = false;
+        for (index, value) in values.iter().enumerate() {
+            let hz = index as f32 * data.bin_hz;
+            if hz < MIN_HZ || value.is_nan() {
+                drawing = false;
+                continue;
+            }
+            let x_coord = ((hz / MIN_HZ).ln() / (nyquist / MIN_HZ).ln()) as f64 * size.width;
+            let point = Point::new(x_coord, y_coord(*value));
+            if drawing {
+                shape.line_to(point);
+            } else {
+                shape.move_to(point);
+                drawing = true;
+            }
+        }
+        shape
+    }
+}
+
+impl Widget<PhaseData> for PhasePlot {
+    fn event(&mut self, _ctx: &mut EventCtx, event: &Event, data: &mut PhaseData, _env: &Env) {
+        if let Event::Command(cmd) = event {
+            if cmd.is(DRAW_PHASE) {
+                *data = cmd.get_unchecked(DRAW_PHASE).clone();
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &PhaseData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &PhaseData, _data: &PhaseData, _: &Env) {
+        ctx.request_paint()
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &PhaseData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &PhaseData, _env: &Env) {
+        let size = ctx.size();
+        let center = size.height / 2.0;
+        ctx.stroke(
+            Line::new((0.0, center), (size.width, center)),
+            &Color::grey(0.3),
+            1.0,
+        );
+
+        let phase = Self::curve(size, data, &data.phase, |phase| {
+            center - phase as f64 / std::f64::consts::PI * center
+        });
+        ctx.stroke(phase, &Color::grey(0.8), 1.0);
+
+        let group_delay = Self::curve(size, data, &data.group_delay_ms, |delay| {
+            let delay = delay.clamp(-GROUP_DELAY_RANGE_MS, GROUP_DELAY_RANGE_MS);
+            center - (delay / GROUP_DELAY_RANGE_MS) as f64 * center
+        });
+        ctx.stroke(group_delay, &Color::rgb8(255, 160, 0), 1.5);
+    }
+}
@@ -0,0 +1,109 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Piano keyboard drawn beside a rendered spectrogram, with gridlines at note boundaries, so peaks
+//! read as notes rather than Hz. Works with any [`FrequencyScale`](crate::spectrogram::FrequencyScale),
+//! keys just get taller up the linear one.
+
+pub const KEYBOARD_WIDTH: usize = 48;
+/// Share of the keyboard's width the black keys cover
+const BLACK_KEY_LENGTH: f32 = 0.6;
+/// A0 and C8, the ends of an 88-key piano
+const LOWEST_NOTE: i32 = 21;
+const HIGHEST_NOTE: i32 = 108;
+/// Semitone gridlines are only drawn where they're at least this many rows apart
+const MIN_SEMITONE_ROWS: f32 = 6.0;
+/// How much of the gridline color is blended over the spectrogram
+const OCTAVE_LINE_OPACITY: f32 = 0.5;
+const SEMITONE_LINE_OPACITY: f32 = 0.15;
+
+const WHITE_KEY: [u8; 3] = [235, 235, 235];
+/// C keys are shaded so octaves can be counted
+const C_KEY: [u8; 3] = [195, 200, 215];
+const BLACK_KEY: [u8; 3] = [20, 20, 20];
+const KEY_EDGE: [u8; 3] = [120, 120, 120];
+const OFF_KEYBOARD: [u8; 3] = [60, 60, 60];
+
+/// MIDI note number, fractional between notes
+fn note_number(hz: f32) -> f32 {
+    69.0 + 12.0 * (hz / 440.0).log2()
+}
+
+fn is_black_key(note: i32) -> bool {
+    matches!(note.rem_euclid(12), 1 | 3 | 6 | 8 | 10)
+}
+
+fn blend(channel: u8, opacity: f32) -> u8 {
+    (channel as f32 + (255.0 - channel as f32) * opacity) as u8
+}
+
+/// Put a keyboard to the left of a `width x height` RGB image from
+/// [`render_rgb`](crate::spectrogram::render_rgb), given the center frequency of each band,
+/// lowest first. The result is [`KEYBOARD_WIDTH`] pixels wider.
+pub fn with_keyboard(pixels: &[u8], width: usize, height: usize, band_hz: &[f32]) -> Vec<u8> {
+    let notes: Vec<f32> = (0..height)
+        .map(|y| note_number(band_hz[(height - 1 - y) * band_hz.len() / height]))
+        .collect();
+    let output_width = width + KEYBOARD_WIDTH;
+    let mut output = vec![0; output_width * height * 3];
+    for (y, row) in output.chunks_exact_mut(output_width * 3).enumerate() {
+        let note = notes[y];
+        let key = note.round() as i32;
+        // Rows go down in pitch, so this is the bottom row of the key when the next one differs
+        let below = notes.get(y + 1).copied();
+        let bottom_edge = below.is_some_and(|below| below.round() as i32 != key);
+        let semitone_rows = below.map_or(0.0, |below| 1.0 / (note - below).max(f32::EPSILON));
+
+        let on_keyboard = (LOWEST_NOTE..=HIGHEST_NOTE).contains(&key);
+
+        let (keyboard, spectrum) = row.split_at_mut(KEYBOARD_WIDTH * 3);
+        for (x, pixel) in keyboard.chunks_exact_mut(3).enumerate() {
+            let color = if !on_keyboard {
+                OFF_KEYBOARD
+            } else if is_black_key(key) && (x as f32) < BLACK_KEY_LENGTH * KEYBOARD_WIDTH as f32 {
+                BLACK_KEY
+            } else if bottom_edge {
+                KEY_EDGE
+            } else if key.rem_euclid(12) == 0 {
+                C_KEY
+            } else {
+                WHITE_KEY
+            };
+            pixel.copy_from_slice(&color);
+        }
+
+        spectrum.copy_from_slice(&pixels[y * width * 3..(y + 1) * width * 3]);
+        let opacity = if !on_keyboard || !bottom_edge {
+            continue;
+        } else if key.rem_euclid(12) == 0 {
+            OCTAVE_LINE_OPACITY
+        } else if semitone_rows >= MIN_SEMITONE_ROWS {
+            SEMITONE_LINE_OPACITY
+        } else {
+            continue;
+        };
+        for channel in spectrum.iter_mut() {
+            *channel = blend(*channel, opacity);
+        }
+    }
+    output
+}
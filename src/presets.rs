@@ -0,0 +1,161 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Named presets bundling the input device, which views are shown, display units and the
+//! analysis settings. They're kept next to the config file, and can be exported to JSON files to
+//! share them.
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio_wave::{Interpolation, STROKE_WIDTHS};
+use crate::balance::DEFAULT_WINDOW_SECONDS;
+use crate::ballistics::Ballistics;
+use crate::config;
+use crate::envelope::EnvelopeSettings;
+use crate::k_system::MeterScale;
+use crate::spectrogram::DisplayRange;
+use crate::spectrum_view::SpectrumWindow;
+use crate::spl::Weighting;
+use crate::throttle::PaneRates;
+use crate::transients::TransientSettings;
+use crate::units::AmplitudeUnit;
+
+const FILE_NAME: &str = "example-druid-audio-viz-presets.json";
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// The default device when unset
+    #[serde(default)]
+    pub input_device: Option<String>,
+    pub show_phase: bool,
+    #[serde(default)]
+    pub show_spectrum: bool,
+    #[serde(default)]
+    pub show_spectrogram: bool,
+    #[serde(default)]
+    pub show_denoise: bool,
+    pub show_envelope: bool,
+    #[serde(default)]
+    pub show_level_history: bool,
+    pub show_midi: bool,
+    pub amplitude_unit: AmplitudeUnit,
+    pub db_floor: f64,
+    pub db_waveform: bool,
+    pub interpolation: Interpolation,
+    pub update_rate_hz: f64,
+    #[serde(default)]
+    pub pane_rates: PaneRates,
+    pub weighting: Weighting,
+    #[serde(default = "default_spl_ballistics")]
+    pub spl_ballistics: Ballistics,
+    #[serde(default = "default_meter_ballistics")]
+    pub meter_ballistics: Ballistics,
+    #[serde(default = "default_meter_scale")]
+    pub meter_scale: MeterScale,
+    #[serde(default = "default_balance_window")]
+    pub balance_window: f64,
+    #[serde(default)]
+    pub spectrogram_range: DisplayRange,
+    pub envelope: EnvelopeSettings,
+    #[serde(default)]
+    pub spectrum_window: SpectrumWindow,
+    #[serde(default)]
+    pub transients: TransientSettings,
+    #[serde(default)]
+    pub pink_reference: bool,
+    #[serde(default)]
+    pub spectrum_smoothing: u64,
+    #[serde(default)]
+    pub processed_smoothing: u64,
+    /// Index into the waveform colors, the first when unset
+    #[serde(default)]
+    pub wave_color: usize,
+    #[serde(default = "default_wave_stroke_width")]
+    pub wave_stroke_width: f64,
+    #[serde(default = "default_wave_antialias")]
+    pub wave_antialias: bool,
+    /// The waveform's name, then one per lane device
+    #[serde(default)]
+    pub lane_names: Vec<String>,
+}
+
+/// What presets saved before ballistics were selectable get, the meters' previous behaviour
+fn default_spl_ballistics() -> Ballistics {
+    Ballistics::Fast
+}
+
+fn default_meter_ballistics() -> Ballistics {
+    Ballistics::SamplePeak
+}
+
+fn default_meter_scale() -> MeterScale {
+    MeterScale::Dbfs
+}
+
+fn default_balance_window() -> f64 {
+    DEFAULT_WINDOW_SECONDS
+}
+
+fn default_wave_stroke_width() -> f64 {
+    STROKE_WIDTHS[2]
+}
+
+fn default_wave_antialias() -> bool {
+    true
+}
+
+/// The saved presets, none when there's no file yet
+pub fn load() -> Vec<Preset> {
+    let path = match config::path(FILE_NAME) {
+        Some(path) => path,
+        None => return Vec::new(),
+    };
+    match config::read_json(&path) {
+        Ok(presets) => presets,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            log::error!("Failed to parse presets {:?}: {}", path, err);
+            Vec::new()
+        }
+    }
+}
+
+pub fn save(presets: &[Preset]) {
+    if let Some(path) = config::path(FILE_NAME) {
+        if let Err(err) = config::write_json(&path, &presets) {
+            log::error!("Failed to write presets to {:?}: {}", path, err);
+        }
+    }
+}
+
+/// Read a preset written by [`export`]
+pub fn import(path: &Path) -> io::Result<Preset> {
+    config::read_json(path)
+}
+
+pub fn export(path: &Path, preset: &Preset) -> io::Result<()> {
+    config::write_json(path, preset)
+}
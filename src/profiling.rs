@@ -0,0 +1,75 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Optional [puffin](https://github.com/EmbarkStudios/puffin) instrumentation, built with
+//! `--features profiling`. Without the feature the scopes compile to nothing.
+//!
+//! While the app runs, connect `puffin_viewer` to the address logged at startup to get a flame
+//! graph of the audio callback, the analysis thread and painting. It only listens on localhost
+//! unless `--profile-address` says otherwise.
+
+#[cfg(feature = "profiling")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        puffin::profile_scope!($name);
+    };
+}
+
+#[cfg(not(feature = "profiling"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {};
+}
+
+pub use crate::profile_scope;
+
+/// Where the profile server listens without `--profile-address`, puffin's default port on localhost
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8585";
+
+/// Start recording scopes and serving them to `puffin_viewer` on `address`
+#[cfg(feature = "profiling")]
+pub fn start(address: &str) {
+    puffin::set_scopes_on(true);
+    match puffin_http::Server::new(address) {
+        Ok(server) => {
+            log::info!("Serving profiles on {}", address);
+            // Serves for as long as the app runs
+            std::mem::forget(server);
+        }
+        Err(err) => log::error!("Failed to start profile server on {}: {}", address, err),
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn start(_address: &str) {}
+
+/// Mark the start of a UI frame, scopes are grouped by frame in the viewer
+#[cfg(feature = "profiling")]
+pub fn new_frame() {
+    puffin::GlobalProfiler::lock().new_frame();
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn new_frame() {}
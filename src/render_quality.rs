@@ -0,0 +1,164 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! One-click render quality: each preset sets the update rates, the waveform's decimation
+//! resolution and antialiasing, and the spectrum's persistence together. On laptops the battery
+//! saver can also be switched in automatically while running on battery, and back out once
+//! plugged in.
+
+use std::thread;
+use std::time::Duration;
+
+use druid::{Data, ExtEventSink, LocalizedString, Selector, Target};
+
+use crate::throttle::{PaneRates, PANE_RATES_HZ, UPDATE_RATES_HZ};
+
+/// Sent when the machine switches between battery and mains power, true on battery
+pub const ON_BATTERY: Selector<bool> = Selector::new("event-example.on_battery");
+
+/// How often the power source is checked
+const POWER_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug)]
+pub enum RenderQuality {
+    BatterySaver,
+    Balanced,
+    Quality,
+}
+
+impl RenderQuality {
+    pub const ALL: [RenderQuality; 3] = [
+        RenderQuality::BatterySaver,
+        RenderQuality::Balanced,
+        RenderQuality::Quality,
+    ];
+
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            RenderQuality::BatterySaver => "quality-battery-saver",
+            RenderQuality::Balanced => "quality-balanced",
+            RenderQuality::Quality => "quality-quality",
+        })
+    }
+
+    pub fn profile(self) -> RenderProfile {
+        match self {
+            RenderQuality::BatterySaver => RenderProfile {
+                update_rate_hz: UPDATE_RATES_HZ[0],
+                pane_rates: PaneRates {
+                    spectrum: Some(PANE_RATES_HZ[0]),
+                    phase: Some(PANE_RATES_HZ[0]),
+                    lag_views: Some(PANE_RATES_HZ[0]),
+                    spectrogram: Some(PANE_RATES_HZ[1]),
+                },
+                wave_antialias: false,
+                wave_resolution: 0.5,
+                spectrum_persistence: 0,
+            },
+            RenderQuality::Balanced => RenderProfile {
+                update_rate_hz: UPDATE_RATES_HZ[2],
+                pane_rates: PaneRates {
+                    spectrum: Some(PANE_RATES_HZ[2]),
+                    phase: Some(PANE_RATES_HZ[1]),
+                    lag_views: Some(PANE_RATES_HZ[1]),
+                    spectrogram: None,
+                },
+                wave_antialias: true,
+                wave_resolution: 1.0,
+                spectrum_persistence: 0,
+            },
+            RenderQuality::Quality => RenderProfile {
+                update_rate_hz: UPDATE_RATES_HZ[3],
+                pane_rates: PaneRates::default(),
+                wave_antialias: true,
+                wave_resolution: 2.0,
+                spectrum_persistence: 8,
+            },
+        }
+    }
+}
+
+/// The settings a [`RenderQuality`] sets
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderProfile {
+    pub update_rate_hz: f64,
+    pub pane_rates: PaneRates,
+    pub wave_antialias: bool,
+    /// See [`crate::audio_wave::WAVE_RESOLUTION`]
+    pub wave_resolution: f64,
+    /// See [`crate::spectrum_view::SPECTRUM_PERSISTENCE`]
+    pub spectrum_persistence: u64,
+}
+
+/// Whether the machine is running on battery, `None` where that can't be told, such as on
+/// desktops without a battery
+#[cfg(target_os = "linux")]
+fn on_battery() -> Option<bool> {
+    let mut mains_online = None;
+    for supply in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = supply.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        if kind.trim() == "Mains" {
+            let online = std::fs::read_to_string(path.join("online")).unwrap_or_default();
+            mains_online = Some(mains_online.unwrap_or(false) || online.trim() == "1");
+        }
+    }
+    mains_online.map(|online| !online)
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> Option<bool> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let source = output.lines().next()?;
+    Some(source.contains("Battery Power"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn on_battery() -> Option<bool> {
+    None
+}
+
+/// Check the power source every [`POWER_POLL_INTERVAL`] on a thread of its own, sending
+/// [`ON_BATTERY`] at the first reading and on every change. Nothing is sent where the source
+/// can't be told.
+pub fn watch_power_source(event_sink: ExtEventSink) {
+    thread::spawn(move || {
+        let mut last = None;
+        loop {
+            let current = on_battery();
+            if let Some(battery) = current.filter(|_| current != last) {
+                if event_sink
+                    .submit_command(ON_BATTERY, battery, Target::Auto)
+                    .is_err()
+                {
+                    return;
+                }
+                last = current;
+            }
+            thread::sleep(POWER_POLL_INTERVAL);
+        }
+    });
+}
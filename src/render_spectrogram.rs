@@ -0,0 +1,199 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `render-spectrogram` sub-command: renders a file's spectrogram into a PNG without opening a
+//! window.
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use audio_processor_file::InMemoryAudioFile;
+use audio_processor_traits::{AudioBuffer, AudioProcessorSettings};
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colormap::Colormap;
+use crate::piano_axis::{self, KEYBOARD_WIDTH};
+use crate::spectrogram::{
+    self, DisplayRange, DisplayWeighting, FrequencyMapping, FrequencyScale, SpectrogramOptions,
+};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("render-spectrogram")
+        .about("Render the spectrogram of an audio file into a PNG image")
+        .arg(Arg::from_usage("<INPUT_PATH> 'The audio file to analyse'"))
+        .arg(Arg::from_usage(
+            "--channel=[CHANNEL] 'Channel to analyse, counting from 1. All channels are mixed down without it'",
+        ))
+        .arg(Arg::from_usage(
+            "-o, --output=<OUTPUT_PATH> 'Where to write the PNG image'",
+        ))
+        .arg(Arg::from_usage("--fft=[FFT_SIZE] 'FFT size in samples'").default_value("2048"))
+        .arg(
+            Arg::from_usage("--colormap=[COLORMAP] 'Colormap to use'")
+                .possible_values(&Colormap::NAMES)
+                .default_value("magma"),
+        )
+        .arg(
+            Arg::from_usage("--scale=[SCALE] 'Frequency axis: linear FFT bins, mel or constant-Q'")
+                .possible_values(&FrequencyScale::NAMES)
+                .default_value("linear"),
+        )
+        .arg(
+            Arg::from_usage(
+                "--weighting=[WEIGHTING] 'Display weighting: flat, +3dB/octave so pink noise reads flat, or A-weighting'",
+            )
+            .possible_values(&DisplayWeighting::NAMES)
+            .default_value("flat"),
+        )
+        .arg(
+            Arg::from_usage(
+                "--axis=[AXIS] 'Frequency axis: none, or a piano keyboard with gridlines at each note'",
+            )
+            .possible_values(&["none", "piano"])
+            .default_value("none"),
+        )
+        .arg(
+            Arg::from_usage("--floor=[FLOOR_DB] 'Level at the bottom of the colormap'")
+                .default_value("-120")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::from_usage("--ceiling=[CEILING_DB] 'Level at the top of the colormap'")
+                .default_value("0")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::from_usage("--gamma=[GAMMA] 'Below 1 brightens quiet content, above 1 darkens it'")
+                .default_value("1"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_path = matches.value_of("INPUT_PATH").unwrap();
+    let output_path = matches.value_of("output").unwrap();
+    let fft = matches.value_of("fft").unwrap();
+    let fft_size = match fft.parse::<usize>() {
+        Ok(fft_size) if fft_size >= 2 && fft_size.is_power_of_two() => fft_size,
+        _ => {
+            eprintln!("--fft must be a power of two of at least 2, got {}", fft);
+            std::process::exit(1);
+        }
+    };
+    let colormap: Colormap = matches.value_of("colormap").unwrap().parse().unwrap();
+    let scale: FrequencyScale = matches.value_of("scale").unwrap().parse().unwrap();
+    let weighting: DisplayWeighting = matches.value_of("weighting").unwrap().parse().unwrap();
+    let level = |name: &str| -> f64 {
+        let value = matches.value_of(name).unwrap();
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("--{} must be a number, got {}", name, value);
+            std::process::exit(1);
+        })
+    };
+    let range = DisplayRange {
+        floor_db: level("floor"),
+        ceiling_db: level("ceiling"),
+        gamma: level("gamma"),
+    };
+    if range.floor_db >= range.ceiling_db {
+        invalid_value(&format!(
+            "--floor ({}) must be below --ceiling ({})",
+            range.floor_db, range.ceiling_db
+        ));
+    }
+    if !(range.gamma > 0.0 && range.gamma.is_finite()) {
+        invalid_value(&format!("--gamma must be above 0, got {}", range.gamma));
+    }
+
+    let settings = AudioProcessorSettings::default();
+    let buffer = InMemoryAudioFile::from_path(input_path)
+        .and_then(|mut file| file.read_into_vec_audio_buffer(&settings))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", input_path, err);
+            std::process::exit(1);
+        });
+    let num_channels = buffer.num_channels();
+    let channel = matches
+        .value_of("channel")
+        .map(|channel| match channel.parse::<usize>() {
+            Ok(number) if (1..=num_channels).contains(&number) => number - 1,
+            _ => invalid_value(&format!(
+                "--channel must be between 1 and {} for {}, got {}",
+                num_channels, input_path, channel
+            )),
+        });
+    let samples = buffer.frames().map(|frame| match channel {
+        Some(channel) => frame[channel],
+        None => frame.iter().sum::<f32>() / frame.len() as f32,
+    });
+
+    let defaults = SpectrogramOptions::default();
+    let options = SpectrogramOptions {
+        fft_size,
+        // Levels under a lowered display floor still need resolving
+        floor_db: defaults.floor_db.min(range.floor_db as f32),
+        ..defaults
+    };
+    let floor_db = options.floor_db;
+    let height = fft_size / 2;
+    let mapping = FrequencyMapping::new(scale, height, fft_size, settings.sample_rate());
+    let gains = weighting.bin_gains(fft_size, settings.sample_rate());
+    let columns: Vec<Vec<f32>> = spectrogram::analyse(samples, options)
+        .iter_mut()
+        .map(|column| {
+            DisplayWeighting::apply(&gains, column, floor_db);
+            mapping.apply(column)
+        })
+        .collect();
+    if columns.is_empty() {
+        eprintln!(
+            "{} is shorter than one {} sample FFT, there's nothing to render",
+            input_path, fft_size
+        );
+        std::process::exit(1);
+    }
+    let mut width = columns.len();
+    let mut pixels = spectrogram::render_rgb(&columns, height, colormap, range);
+    if matches.value_of("axis") == Some("piano") {
+        pixels = piano_axis::with_keyboard(&pixels, width, height, &mapping.center_hz());
+        width += KEYBOARD_WIDTH;
+    }
+
+    let file = File::create(output_path).unwrap_or_else(|err| {
+        eprintln!("Failed to create {}: {}", output_path, err);
+        std::process::exit(1);
+    });
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Err(err) = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+    {
+        eprintln!("Failed to write {}: {}", output_path, err);
+        std::process::exit(1);
+    }
+}
+
+/// Exit with clap's usage error for an option that parsed but makes no sense
+fn invalid_value(message: &str) -> ! {
+    clap::Error::with_description(message, clap::ErrorKind::InvalidValue).exit()
+}
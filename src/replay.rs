@@ -0,0 +1,217 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `replay` sub-command: plays back a metrics log recorded from the live view, at its original
+//! speed or faster, with play/pause, seeking and the loudness graph as it looked at the time.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use druid::widget::prelude::*;
+use druid::widget::{Button, Controller, Flex, Label, RadioGroup, Slider};
+use druid::{AppLauncher, Data, Lens, LocalizedString, WidgetExt, WindowDesc};
+
+use crate::i18n::{self, localize, localize_with};
+use crate::loudness_graph::{format_db, LoudnessData, LoudnessGraph, LOUDNESS_HISTORY_LEN};
+use crate::metrics_log::{self, MetricsRow};
+
+/// Playback speeds offered in the transport
+const SPEEDS: [f64; 4] = [1.0, 2.0, 4.0, 10.0];
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("replay")
+        .about("Replay a metrics log recorded from the live view")
+        .arg(Arg::from_usage(
+            "<INPUT_PATH> 'The metrics CSV written by \"Record metrics...\"'",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_path = matches.value_of("INPUT_PATH").unwrap();
+    let rows = metrics_log::read(Path::new(input_path)).unwrap_or_else(|err| {
+        eprintln!("Failed to read {}: {}", input_path, err);
+        std::process::exit(1)
+    });
+    let duration = rows.last().map_or(0.0, |row| row.time_s);
+
+    let mut data = ReplayData {
+        rows: Arc::new(rows),
+        time_s: 0.0,
+        playing: false,
+        speed: SPEEDS[0],
+        loudness: LoudnessData::default(),
+    };
+    data.update_loudness();
+    let window = WindowDesc::new(make_ui(duration)).title(LocalizedString::new("replay-title"));
+    i18n::with_strings(AppLauncher::with_window(window))
+        .launch(data)
+        .expect("launch failed");
+}
+
+#[derive(Clone, Data, Lens)]
+struct ReplayData {
+    rows: Arc<Vec<MetricsRow>>,
+    /// Playback position
+    time_s: f64,
+    playing: bool,
+    speed: f64,
+    /// The graph as it was at `time_s`
+    loudness: LoudnessData,
+}
+
+impl ReplayData {
+    fn duration(&self) -> f64 {
+        self.rows.last().map_or(0.0, |row| row.time_s)
+    }
+
+    /// Rows recorded up to the playback position
+    fn played(&self) -> usize {
+        self.rows.partition_point(|row| row.time_s <= self.time_s)
+    }
+
+    fn current(&self) -> Option<&MetricsRow> {
+        self.played().checked_sub(1).map(|index| &self.rows[index])
+    }
+
+    fn update_loudness(&mut self) {
+        let end = self.played();
+        let rows = &self.rows[end.saturating_sub(LOUDNESS_HISTORY_LEN)..end];
+        let current = self.current();
+        self.loudness = LoudnessData {
+            momentary: Arc::new(rows.iter().map(|row| row.momentary_lufs).collect()),
+            short_term: Arc::new(rows.iter().map(|row| row.short_term_lufs).collect()),
+            integrated: current.map_or(f32::NEG_INFINITY, |row| row.integrated_lufs),
+            range: current.map_or(0.0, |row| row.loudness_range_lu),
+        };
+    }
+}
+
+/// Advances playback on animation frames and keeps the graph in step with seeking
+struct ReplayController;
+
+impl<W: Widget<ReplayData>> Controller<ReplayData, W> for ReplayController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut ReplayData,
+        env: &Env,
+    ) {
+        let (time_s, playing) = (data.time_s, data.playing);
+        match event {
+            Event::AnimFrame(elapsed_nanos) if data.playing => {
+                data.time_s += *elapsed_nanos as f64 / 1e9 * data.speed;
+                if data.time_s >= data.duration() {
+                    data.time_s = data.duration();
+                    data.playing = false;
+                } else {
+                    ctx.request_anim_frame();
+                }
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+        if data.playing && !playing {
+            // Playing from the end starts over
+            if data.time_s >= data.duration() {
+                data.time_s = 0.0;
+            }
+            ctx.request_anim_frame();
+        }
+        if data.time_s != time_s {
+            data.update_loudness();
+        }
+    }
+}
+
+fn make_ui(duration: f64) -> impl Widget<ReplayData> {
+    let readouts = Label::dynamic(|data: &ReplayData, env| match data.current() {
+        Some(row) => localize_with(
+            "replay-readouts",
+            &[
+                ("peak", format_db(row.peak_dbfs)),
+                ("true_peak", format_db(row.true_peak_dbtp)),
+                ("rms", format_db(row.rms_dbfs)),
+                ("momentary", format_db(row.momentary_lufs)),
+                ("short_term", format_db(row.short_term_lufs)),
+                ("integrated", format_db(row.integrated_lufs)),
+                ("range", format!("{:.1}", row.loudness_range_lu)),
+                ("spl", format_db(row.spl_dbfs)),
+                (
+                    "pitch",
+                    row.pitch_hz
+                        .map_or("-".to_string(), |pitch_hz| format!("{:.0} Hz", pitch_hz)),
+                ),
+                (
+                    "tilt",
+                    row.tilt_db_per_octave
+                        .map_or("-".to_string(), |tilt| format!("{:+.1} dB/oct", tilt)),
+                ),
+            ],
+            env,
+        ),
+        None => localize(LocalizedString::new("no-metrics"), env),
+    });
+    let play = Button::new(
+        LocalizedString::new("replay-play").with_arg("playing", |data: &ReplayData, _| {
+            data.playing.to_string().into()
+        }),
+    )
+    .on_click(|_, data: &mut ReplayData, _| data.playing = !data.playing);
+    let position = Label::dynamic(|data: &ReplayData, _| {
+        format!("{:.1} / {:.1} s", data.time_s, data.duration())
+    });
+    let speeds = RadioGroup::row(
+        SPEEDS
+            .iter()
+            .map(|speed| (format!("{}x", speed), *speed))
+            .collect::<Vec<_>>(),
+    );
+
+    Flex::column()
+        .with_flex_child(
+            LoudnessGraph::default()
+                .lens(ReplayData::loudness)
+                .padding(10.0),
+            1.0,
+        )
+        .with_child(readouts.padding(10.0))
+        .with_child(
+            Flex::row()
+                .with_child(play)
+                .with_default_spacer()
+                .with_flex_child(
+                    Slider::new()
+                        .with_range(0.0, duration.max(f64::EPSILON))
+                        .expand_width()
+                        .lens(ReplayData::time_s),
+                    1.0,
+                )
+                .with_default_spacer()
+                .with_child(position)
+                .with_default_spacer()
+                .with_child(speeds.lens(ReplayData::speed))
+                .padding(10.0),
+        )
+        .controller(ReplayController)
+}
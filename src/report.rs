@@ -0,0 +1,309 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! EBU R128 style compliance reports, with pass/fail against a selectable loudness target.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+
+use druid::{Data, LocalizedString};
+use serde::Serialize;
+
+use crate::loudness::LoudnessMeter;
+use crate::loudness_graph::format_db;
+use crate::true_peak::TruePeakMeter;
+
+/// Highest true-peak level allowed by every supported target
+pub const MAX_TRUE_PEAK_DBTP: f32 = -1.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize)]
+pub enum LoudnessTarget {
+    /// Music streaming platforms, -14 LUFS
+    Streaming,
+    /// Podcasts and spoken word, -16 LUFS
+    Podcast,
+    /// EBU R128 broadcast, -23 LUFS
+    Broadcast,
+}
+
+impl LoudnessTarget {
+    pub const NAMES: [&'static str; 3] = ["-14", "-16", "-23"];
+
+    pub fn lufs(&self) -> f32 {
+        match self {
+            LoudnessTarget::Streaming => -14.0,
+            LoudnessTarget::Podcast => -16.0,
+            LoudnessTarget::Broadcast => -23.0,
+        }
+    }
+
+    /// Allowed deviation from the target in LU
+    pub fn tolerance(&self) -> f32 {
+        match self {
+            LoudnessTarget::Broadcast => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            LoudnessTarget::Streaming => "target-streaming",
+            LoudnessTarget::Podcast => "target-podcast",
+            LoudnessTarget::Broadcast => "target-broadcast",
+        })
+    }
+
+    /// English name for the text report, which isn't localized
+    pub fn description(&self) -> &'static str {
+        match self {
+            LoudnessTarget::Streaming => "Streaming (-14 LUFS)",
+            LoudnessTarget::Podcast => "Podcast (-16 LUFS)",
+            LoudnessTarget::Broadcast => "Broadcast (-23 LUFS)",
+        }
+    }
+}
+
+impl FromStr for LoudnessTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "-14" => Ok(LoudnessTarget::Streaming),
+            "-16" => Ok(LoudnessTarget::Podcast),
+            "-23" => Ok(LoudnessTarget::Broadcast),
+            _ => Err(format!(
+                "Unknown target '{}', expected one of {}",
+                s,
+                LoudnessTarget::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TargetResult {
+    pub target: LoudnessTarget,
+    pub target_lufs: f32,
+    pub tolerance_lu: f32,
+    pub max_true_peak_dbtp: f32,
+    pub loudness_passed: bool,
+    pub true_peak_passed: bool,
+    pub passed: bool,
+}
+
+#[derive(Serialize)]
+pub struct LoudnessReport {
+    pub source: String,
+    pub duration_seconds: f32,
+    pub integrated_lufs: f32,
+    pub loudness_range_lu: f32,
+    pub true_peak_dbtp: f32,
+    pub result: TargetResult,
+}
+
+impl LoudnessReport {
+    pub fn new(
+        source: String,
+        duration_seconds: f32,
+        loudness: &LoudnessMeter,
+        true_peak: &TruePeakMeter,
+        target: LoudnessTarget,
+    ) -> Self {
+        Self::from_levels(
+            source,
+            duration_seconds,
+            loudness.integrated(),
+            loudness.loudness_range(),
+            true_peak.peak_dbtp(),
+            target,
+        )
+    }
+
+    fn from_levels(
+        source: String,
+        duration_seconds: f32,
+        integrated_lufs: f32,
+        loudness_range_lu: f32,
+        true_peak_dbtp: f32,
+        target: LoudnessTarget,
+    ) -> Self {
+        let loudness_passed = (integrated_lufs - target.lufs()).abs() <= target.tolerance();
+        let true_peak_passed = true_peak_dbtp <= MAX_TRUE_PEAK_DBTP;
+
+        LoudnessReport {
+            source,
+            duration_seconds,
+            integrated_lufs,
+            loudness_range_lu,
+            true_peak_dbtp,
+            result: TargetResult {
+                target,
+                target_lufs: target.lufs(),
+                tolerance_lu: target.tolerance(),
+                max_true_peak_dbtp: MAX_TRUE_PEAK_DBTP,
+                loudness_passed,
+                true_peak_passed,
+                passed: loudness_passed && true_peak_passed,
+            },
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let pass_fail = |passed: bool| if passed { "PASS" } else { "FAIL" };
+        let mut text = String::new();
+        let _ = writeln!(text, "Loudness report for {}", self.source);
+        let _ = writeln!(text, "Duration:           {:.1} s", self.duration_seconds);
+        let _ = writeln!(
+            text,
+            "Integrated:         {} LUFS",
+            format_db(self.integrated_lufs)
+        );
+        let _ = writeln!(text, "Loudness range:     {:.1} LU", self.loudness_range_lu);
+        let _ = writeln!(
+            text,
+            "True peak:          {} dBTP",
+            format_db(self.true_peak_dbtp)
+        );
+        let _ = writeln!(
+            text,
+            "Target:             {}",
+            self.result.target.description()
+        );
+        let _ = writeln!(
+            text,
+            "Loudness:           {} (±{:.1} LU)",
+            pass_fail(self.result.loudness_passed),
+            self.result.tolerance_lu
+        );
+        let _ = writeln!(
+            text,
+            "True peak:          {} (max {:.1} dBTP)",
+            pass_fail(self.result.true_peak_passed),
+            self.result.max_true_peak_dbtp
+        );
+        let _ = writeln!(
+            text,
+            "Result:             {}",
+            pass_fail(self.result.passed)
+        );
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize report")
+    }
+
+    /// Write the report, as JSON if the path ends in `.json` and as text otherwise
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let contents = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            self.to_json()
+        } else {
+            self.to_text()
+        };
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGETS: [LoudnessTarget; 3] = [
+        LoudnessTarget::Streaming,
+        LoudnessTarget::Podcast,
+        LoudnessTarget::Broadcast,
+    ];
+
+    fn report(integrated_lufs: f32, true_peak_dbtp: f32, target: LoudnessTarget) -> LoudnessReport {
+        LoudnessReport::from_levels(
+            "programme.wav".to_string(),
+            90.0,
+            integrated_lufs,
+            6.5,
+            true_peak_dbtp,
+            target,
+        )
+    }
+
+    #[test]
+    fn passes_within_each_targets_tolerance() {
+        for target in TARGETS {
+            let margin = target.tolerance() + 0.1;
+            for lufs in [target.lufs(), target.lufs() - target.tolerance() + 0.05] {
+                let passed = report(lufs, -2.0, target).result;
+                assert!(
+                    passed.loudness_passed && passed.passed,
+                    "{:?} at {}",
+                    target,
+                    lufs
+                );
+            }
+            for lufs in [target.lufs() - margin, target.lufs() + margin] {
+                let failed = report(lufs, -2.0, target).result;
+                assert!(!failed.loudness_passed, "{:?} at {}", target, lufs);
+                assert!(failed.true_peak_passed && !failed.passed);
+            }
+
+            let over = report(target.lufs(), MAX_TRUE_PEAK_DBTP + 0.5, target).result;
+            assert!(over.loudness_passed && !over.true_peak_passed && !over.passed);
+        }
+    }
+
+    #[test]
+    fn json_round_trips() {
+        for target in TARGETS {
+            let measured = report(-23.3, -1.5, target);
+            let json: serde_json::Value = serde_json::from_str(&measured.to_json()).unwrap();
+            assert_eq!(json["source"], "programme.wav");
+            assert_eq!(json["duration_seconds"].as_f64(), Some(90.0));
+            assert_eq!(
+                json["integrated_lufs"].as_f64().map(|lufs| lufs as f32),
+                Some(-23.3)
+            );
+            assert_eq!(json["loudness_range_lu"].as_f64(), Some(6.5));
+            assert_eq!(json["true_peak_dbtp"].as_f64(), Some(-1.5));
+            let result = &json["result"];
+            assert_eq!(result["target"], format!("{:?}", target));
+            assert_eq!(result["target_lufs"].as_f64(), Some(target.lufs() as f64));
+            assert_eq!(
+                result["tolerance_lu"].as_f64(),
+                Some(target.tolerance() as f64)
+            );
+            assert_eq!(result["loudness_passed"], measured.result.loudness_passed);
+            assert_eq!(result["true_peak_passed"], measured.result.true_peak_passed);
+            assert_eq!(result["passed"], measured.result.passed);
+        }
+    }
+
+    #[test]
+    fn text_shows_each_verdict() {
+        let text = report(-14.2, -0.5, LoudnessTarget::Streaming).to_text();
+        assert!(text.contains("Target:             Streaming (-14 LUFS)"));
+        assert!(text.contains("Loudness:           PASS (±1.0 LU)"));
+        assert!(text.contains("True peak:          FAIL (max -1.0 dBTP)"));
+        assert!(text.contains("Result:             FAIL"));
+    }
+}
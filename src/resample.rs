@@ -0,0 +1,105 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Converts the device's stream to [`ANALYSIS_RATE`] before it's analysed, so FFT bin widths,
+//! time constants and the waveform's time axis come out the same whatever rate the device runs at.
+
+use std::borrow::Cow;
+
+use samplerate::{ConverterType, Samplerate};
+
+pub const ANALYSIS_RATE: f32 = 48_000.0;
+
+pub struct StreamResampler {
+    device_rate: f32,
+    channels: usize,
+    /// `None` when the device already runs at the analysis rate, or the converter failed
+    converter: Option<Samplerate>,
+}
+
+impl StreamResampler {
+    /// For a single channel
+    pub fn new(device_rate: f32) -> Self {
+        Self::with_channels(device_rate, 1)
+    }
+
+    /// For interleaved frames of `channels` samples
+    pub fn with_channels(device_rate: f32, channels: usize) -> Self {
+        let channels = channels.max(1);
+        let converter = (device_rate != ANALYSIS_RATE)
+            .then(|| {
+                Samplerate::new(
+                    ConverterType::SincFastest,
+                    device_rate as u32,
+                    ANALYSIS_RATE as u32,
+                    channels,
+                )
+                .map_err(|err| {
+                    log::error!(
+                        "Failed to resample {} Hz, analysing at the device rate: {}",
+                        device_rate,
+                        err
+                    )
+                })
+                .ok()
+            })
+            .flatten();
+        StreamResampler {
+            device_rate,
+            channels,
+            converter,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The rate of the samples [`process`](Self::process) returns
+    pub fn output_rate(&self) -> f32 {
+        if self.converter.is_some() {
+            ANALYSIS_RATE
+        } else {
+            self.device_rate
+        }
+    }
+
+    /// The next run of the stream at the output rate, interleaved like it came in. The converter
+    /// keeps its state between calls, so runs can be any number of frames.
+    pub fn process<'a>(&mut self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        match &self.converter {
+            Some(converter) => match converter.process(samples) {
+                Ok(resampled) => Cow::Owned(resampled),
+                Err(err) => {
+                    log::error!("Failed to resample: {}", err);
+                    Cow::Borrowed(samples)
+                }
+            },
+            None => Cow::Borrowed(samples),
+        }
+    }
+
+    /// A rate or a position counted in device samples, in output samples
+    pub fn scale(&self, device_value: f64) -> f64 {
+        device_value * self.output_rate() as f64 / self.device_rate as f64
+    }
+}
@@ -0,0 +1,158 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! The app's own footprint for the stats panel: CPU time of all its threads as a share of one
+//! core, resident memory, and the time spent in audio callbacks against the audio it covered.
+
+use std::time::{Duration, Instant};
+
+/// Readings are taken over at least this long, shorter windows mostly show scheduling noise
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// User and system CPU time of the process so far
+#[cfg(unix)]
+fn cpu_time() -> Option<Duration> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // getrusage fills in the struct when it succeeds
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    Some(duration(usage.ru_utime) + duration(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Resident set size in bytes
+#[cfg(target_os = "linux")]
+fn resident_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * page_size.max(0) as u64)
+}
+
+/// Resident set size in bytes
+#[cfg(target_os = "macos")]
+fn resident_bytes() -> Option<u64> {
+    let mut info = std::mem::MaybeUninit::<libc::proc_taskinfo>::uninit();
+    let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+    // Returns the bytes written, the whole struct when it succeeds
+    let written = unsafe {
+        libc::proc_pidinfo(
+            libc::getpid(),
+            libc::PROC_PIDTASKINFO,
+            0,
+            info.as_mut_ptr() as *mut libc::c_void,
+            size,
+        )
+    };
+    (written == size).then(|| unsafe { info.assume_init() }.pti_resident_size)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn resident_bytes() -> Option<u64> {
+    None
+}
+
+/// `None` where the platform doesn't report it
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ResourceUsage {
+    /// Share of one core, above 100 when several threads are busy
+    pub cpu_percent: Option<f32>,
+    pub memory_bytes: Option<u64>,
+    /// Time spent in audio callbacks as a share of the audio they processed, so 100 is the point
+    /// the callbacks can't keep up
+    pub dsp_load_percent: Option<f32>,
+}
+
+struct Reading {
+    time: Instant,
+    cpu_time: Option<Duration>,
+    callback_nanos: u64,
+    position: u64,
+}
+
+impl Reading {
+    fn now(callback_nanos: u64, position: u64) -> Self {
+        Reading {
+            time: Instant::now(),
+            cpu_time: cpu_time(),
+            callback_nanos,
+            position,
+        }
+    }
+}
+
+/// Turns the running totals into rates, once per [`MEASURE_INTERVAL`]
+pub struct ResourceMonitor {
+    sample_rate: f32,
+    last: Reading,
+    usage: ResourceUsage,
+}
+
+impl ResourceMonitor {
+    pub fn new(sample_rate: f32) -> Self {
+        ResourceMonitor {
+            sample_rate,
+            last: Reading::now(0, 0),
+            usage: ResourceUsage::default(),
+        }
+    }
+
+    /// The usage over the last full interval, given the audio thread's callback time and stream
+    /// position so far
+    pub fn measure(&mut self, callback_nanos: u64, position: u64) -> ResourceUsage {
+        if self.last.time.elapsed() < MEASURE_INTERVAL {
+            return self.usage;
+        }
+        let reading = Reading::now(callback_nanos, position);
+        let wall_time = (reading.time - self.last.time).as_secs_f32();
+        let cpu_percent = reading
+            .cpu_time
+            .zip(self.last.cpu_time)
+            .map(|(now, then)| now.saturating_sub(then).as_secs_f32() / wall_time * 100.0);
+        // Nothing to compare against while the engine is stopped
+        let audio_time =
+            reading.position.saturating_sub(self.last.position) as f32 / self.sample_rate;
+        let dsp_load_percent = (audio_time > 0.0).then(|| {
+            let busy = reading
+                .callback_nanos
+                .saturating_sub(self.last.callback_nanos) as f32
+                / 1e9;
+            busy / audio_time * 100.0
+        });
+        self.usage = ResourceUsage {
+            cpu_percent,
+            memory_bytes: resident_bytes(),
+            dsp_load_percent,
+        };
+        self.last = reading;
+        self.usage
+    }
+}
@@ -0,0 +1,266 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `room-response` sub-command: writes the measurement sweep, or turns a recording of it into an
+//! impulse response, energy decay curves and per-octave RT60.
+
+use std::sync::Arc;
+
+use audio_processor_file::{InMemoryAudioFile, OutputAudioFileProcessor};
+use audio_processor_traits::{AudioBuffer, AudioProcessorSettings};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::widget::{Flex, Label};
+use druid::{AppLauncher, Color, Data, Lens, LocalizedString, Point, WidgetExt, WindowDesc};
+
+use crate::audio_wave::{AudioData, AudioWave, WaveData};
+use crate::i18n::{self, localize, localize_with};
+use crate::impulse_response::{self, OCTAVE_BANDS_HZ};
+
+/// Silence appended to the written sweep so the recording captures the room's decay
+const TAIL_SECONDS: f32 = 2.0;
+/// Bottom of the decay plot
+const FLOOR_DB: f64 = -60.0;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("room-response")
+        .about("Measure a room's impulse response and reverberation time with a sine sweep")
+        .arg(
+            Arg::from_usage("[RECORDING] 'A recording of the sweep played back in the room'")
+                .required_unless("write-sweep"),
+        )
+        .arg(Arg::from_usage(
+            "--write-sweep=[SWEEP_PATH] 'Write the sweep to play back into this WAV file'",
+        ))
+        .arg(
+            Arg::from_usage("--duration=[SECONDS] 'Sweep length, must match the recorded sweep'")
+                .default_value("5"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let value = matches.value_of("duration").unwrap();
+    let duration = match value.parse::<f32>() {
+        Ok(duration) if duration.is_finite() && duration > 0.0 => duration,
+        _ => {
+            eprintln!("--duration must be a number of seconds, got {}", value);
+            std::process::exit(1);
+        }
+    };
+    let settings = AudioProcessorSettings::default();
+
+    if let Some(sweep_path) = matches.value_of("write-sweep") {
+        write_sweep(sweep_path, duration, settings);
+        return;
+    }
+
+    let recording_path = matches.value_of("RECORDING").unwrap();
+    let buffer = InMemoryAudioFile::from_path(recording_path)
+        .and_then(|mut file| file.read_into_vec_audio_buffer(&settings))
+        .unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", recording_path, err);
+            std::process::exit(1);
+        });
+    let recording: Vec<f32> = buffer.frames().map(|frame| frame[0]).collect();
+
+    let sample_rate = settings.sample_rate();
+    let impulse_response = impulse_response::impulse_response(&recording, duration, sample_rate);
+    let mut curves = vec![DecayCurve::new(None, &impulse_response, sample_rate)];
+    for center_hz in OCTAVE_BANDS_HZ {
+        let band = impulse_response::band_filter(&impulse_response, center_hz, sample_rate);
+        curves.push(DecayCurve::new(Some(center_hz), &band, sample_rate));
+    }
+
+    println!("Band       RT60");
+    for curve in &curves {
+        println!(
+            "{:<10} {}",
+            curve.band_label("Broadband"),
+            curve.rt60_label()
+        );
+    }
+
+    let window = WindowDesc::new(make_ui()).title(LocalizedString::new("room-response-title"));
+    let len = impulse_response.len();
+    i18n::with_strings(AppLauncher::with_window(window))
+        .launch(RoomData {
+            impulse_response: WaveData {
+                audio: AudioData::new(impulse_response),
+                ..WaveData::new(len)
+            },
+            curves: Arc::new(curves),
+            sample_rate,
+        })
+        .expect("launch failed");
+}
+
+fn write_sweep(path: &str, duration: f32, settings: AudioProcessorSettings) {
+    let sweep = impulse_response::sweep(duration, settings.sample_rate());
+    let tail = (TAIL_SECONDS * settings.sample_rate()) as usize;
+    let mut output = OutputAudioFileProcessor::from_path(settings, path);
+    output.prepare(settings);
+    let mut interleaved: Vec<f32> = sweep
+        .iter()
+        .chain(std::iter::repeat_n(&0.0, tail))
+        .flat_map(|sample| std::iter::repeat_n(*sample * 0.5, settings.output_channels()))
+        .collect();
+    output.process(&mut interleaved);
+}
+
+pub struct DecayCurve {
+    /// Octave band center, `None` for the broadband curve
+    band_hz: Option<f32>,
+    levels: Vec<f32>,
+    rt60: Option<f32>,
+}
+
+impl DecayCurve {
+    fn new(band_hz: Option<f32>, impulse_response: &[f32], sample_rate: f32) -> Self {
+        let levels = impulse_response::energy_decay_curve(impulse_response);
+        let rt60 = impulse_response::rt60(&levels, sample_rate);
+        DecayCurve {
+            band_hz,
+            levels,
+            rt60,
+        }
+    }
+
+    /// The band's center, or `broadband` for the unfiltered curve
+    fn band_label(&self, broadband: &str) -> String {
+        match self.band_hz {
+            Some(hz) if hz >= 1000.0 => format!("{} kHz", hz / 1000.0),
+            Some(hz) => format!("{} Hz", hz),
+            None => broadband.to_string(),
+        }
+    }
+
+    fn rt60_label(&self) -> String {
+        match self.rt60 {
+            Some(rt60) => format!("{:.2} s", rt60),
+            None => "--".to_string(),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self.band_hz {
+            None => Color::WHITE,
+            Some(hz) => {
+                // Low bands red through to high bands blue
+                let position =
+                    (hz / OCTAVE_BANDS_HZ[0]).log2() as f64 / (OCTAVE_BANDS_HZ.len() - 1) as f64;
+                Color::hlc(position * 240.0, 60.0, 80.0)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Data, Lens)]
+struct RoomData {
+    impulse_response: WaveData,
+    curves: Arc<Vec<DecayCurve>>,
+    sample_rate: f32,
+}
+
+/// Energy decay curves against time, 0dB to [`FLOOR_DB`]
+#[derive(Default)]
+struct DecayPlot {}
+
+impl Widget<RoomData> for DecayPlot {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut RoomData, _env: &Env) {}
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &RoomData,
+        _: &Env,
+    ) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &RoomData, _data: &RoomData, _: &Env) {
+        ctx.request_paint()
+    }
+
+    fn layout(&mut self, _: &mut LayoutCtx, bc: &BoxConstraints, _: &RoomData, _: &Env) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &RoomData, _env: &Env) {
+        let size = ctx.size();
+        let y_coord = |level: f64| level.clamp(FLOOR_DB, 0.0) / FLOOR_DB * size.height;
+
+        let grid = Color::grey(0.3);
+        for level in (1..6).map(|step| step as f64 * -10.0) {
+            let y = y_coord(level);
+            ctx.stroke(Line::new((0.0, y), (size.width, y)), &grid, 1.0);
+        }
+
+        for curve in data.curves.iter() {
+            let len = curve.levels.len();
+            let step = ((len as f64 / size.width) as usize).max(1);
+            let mut shape = BezPath::new();
+            for index in (0..len).step_by(step) {
+                let point = Point::new(
+                    index as f64 / len as f64 * size.width,
+                    y_coord(curve.levels[index] as f64),
+                );
+                if index == 0 {
+                    shape.move_to(point);
+                } else {
+                    shape.line_to(point);
+                }
+            }
+            ctx.stroke(shape, &curve.color(), 1.5);
+        }
+    }
+}
+
+fn make_ui() -> impl Widget<RoomData> {
+    let summary = Label::dynamic(|data: &RoomData, env| {
+        let duration = data.impulse_response.audio.0.len() as f32 / data.sample_rate;
+        let broadband = localize(LocalizedString::new("broadband"), env);
+        let bands: Vec<String> = data
+            .curves
+            .iter()
+            .map(|curve| format!("{}: {}", curve.band_label(&broadband), curve.rt60_label()))
+            .collect();
+        localize_with(
+            "room-response-summary",
+            &[
+                ("duration", format!("{:.1}", duration)),
+                ("bands", bands.join("    ")),
+            ],
+            env,
+        )
+    });
+
+    Flex::column()
+        .with_flex_child(
+            AudioWave::default()
+                .lens(RoomData::impulse_response)
+                .padding(10.0),
+            1.0,
+        )
+        .with_flex_child(DecayPlot::default().padding(10.0), 1.0)
+        .with_child(summary.padding(10.0))
+}
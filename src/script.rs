@@ -0,0 +1,240 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! User scripts, written in [Rhai](https://rhai.rs), that compute custom meters and overlay lines
+//! from each analysis frame without recompiling.
+//!
+//! A script defines `fn frame(values)`, called on every UI update with a map of the current
+//! measurements. It returns a map: numbers are shown as meters under their key, and an `overlay`
+//! array of `#{ level: 0.5, label: "Threshold" }` maps draws lines at those amplitudes over the
+//! waveform. Functions can keep state between frames on `this`:
+//!
+//! ```rhai
+//! fn frame(values) {
+//!     this.loudest = max(this.loudest ?? 0.0, values.peak);
+//!     #{ crest_db: 20.0 * (values.peak / values.rms).log(), overlay: [#{ level: this.loudest }] }
+//! }
+//! ```
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use druid::kurbo::Line;
+use druid::piet::{Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::widget::Label;
+use druid::{Color, Data, FontFamily, Selector};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use crate::audio_wave::WaveScale;
+use crate::i18n::localize_with;
+use crate::loudness_graph::LoudnessData;
+use crate::spl::SplData;
+use crate::stats::StatsData;
+
+pub const DRAW_SCRIPT: Selector<ScriptOutput> = Selector::new("event-example.draw_script");
+
+/// Name of the function called with each frame
+const FRAME_FN: &str = "frame";
+
+/// Limits on a single call of the frame function, so a runaway script can't stall the analysis
+/// thread. Hitting one stops the script like any other error.
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const FRAME_TIME_LIMIT: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct OverlayLine {
+    /// Linear amplitude, drawn above and below the center line
+    pub level: f64,
+    pub label: Option<String>,
+}
+
+/// What the script returned for the latest frame
+#[derive(Clone, Data, Default)]
+pub struct ScriptOutput {
+    pub meters: Arc<Vec<(String, f64)>>,
+    pub overlay: Arc<Vec<OverlayLine>>,
+    /// Why the script stopped, it isn't run again until it's reloaded
+    pub error: Option<String>,
+}
+
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    /// Bound to `this`, kept between frames
+    state: Dynamic,
+    /// When the current call started, checked against [`FRAME_TIME_LIMIT`]
+    call_start: Arc<Mutex<Instant>>,
+}
+
+impl ScriptHost {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let call_start = Arc::new(Mutex::new(Instant::now()));
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS);
+        let progress_start = call_start.clone();
+        engine.on_progress(move |_| {
+            let start = *progress_start
+                .lock()
+                .expect("Failed to lock script call start");
+            (start.elapsed() > FRAME_TIME_LIMIT).then(Dynamic::default)
+        });
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| err.to_string())?;
+        Ok(ScriptHost {
+            engine,
+            ast,
+            state: Dynamic::from_map(Map::new()),
+            call_start,
+        })
+    }
+
+    pub fn run(&mut self, values: Map) -> Result<ScriptOutput, String> {
+        *self
+            .call_start
+            .lock()
+            .expect("Failed to lock script call start") = Instant::now();
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let output: Map = self
+            .engine
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, FRAME_FN, (values,))
+            .map_err(|err| match *err {
+                EvalAltResult::ErrorTerminated(..) => format!(
+                    "`{}` took longer than {} ms",
+                    FRAME_FN,
+                    FRAME_TIME_LIMIT.as_millis()
+                ),
+                err => err.to_string(),
+            })?;
+
+        let mut meters = Vec::new();
+        let mut overlay = Vec::new();
+        for (key, value) in output {
+            if key == "overlay" {
+                let lines = value
+                    .try_cast::<Array>()
+                    .ok_or("`overlay` must be an array")?;
+                for line in lines {
+                    overlay.push(overlay_line(line)?);
+                }
+            } else if let Some(value) = number(&value) {
+                meters.push((key.to_string(), value));
+            }
+        }
+        Ok(ScriptOutput {
+            meters: Arc::new(meters),
+            overlay: Arc::new(overlay),
+            error: None,
+        })
+    }
+}
+
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|value| value as f64))
+}
+
+fn overlay_line(line: Dynamic) -> Result<OverlayLine, String> {
+    let line = line
+        .try_cast::<Map>()
+        .ok_or("`overlay` entries must be maps")?;
+    let level = line
+        .get("level")
+        .and_then(number)
+        .ok_or("`overlay` entries need a numeric `level`")?;
+    Ok(OverlayLine {
+        level,
+        label: line.get("label").map(|label| label.to_string()),
+    })
+}
+
+/// The measurements a script is given, amplitudes linear and levels in dB
+pub fn frame_values(stats: &StatsData, loudness: &LoudnessData, spl: &SplData) -> Map {
+    let last = |readings: &[f32]| readings.last().copied().unwrap_or(f32::NEG_INFINITY);
+    [
+        ("peak", stats.peak),
+        ("rms", stats.rms),
+        ("min", stats.min),
+        ("max", stats.max),
+        ("dc_offset", stats.dc_offset),
+        ("sample_rate", stats.sample_rate),
+        ("momentary_lufs", last(&loudness.momentary)),
+        ("short_term_lufs", last(&loudness.short_term)),
+        ("integrated_lufs", loudness.integrated),
+        ("loudness_range", loudness.range),
+        ("spl_dbfs", spl.level_dbfs),
+    ]
+    .iter()
+    .map(|(name, value)| ((*name).into(), Dynamic::from_float(*value as f64)))
+    .collect()
+}
+
+/// The script's meters, one per line, or why it stopped
+pub fn script_meters() -> impl Widget<ScriptOutput> {
+    Label::dynamic(|data: &ScriptOutput, env| {
+        if let Some(error) = &data.error {
+            return localize_with("script-error", &[("error", error.clone())], env);
+        }
+        data.meters
+            .iter()
+            .map(|(name, value)| format!("{}: {:.3}", name, value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Draw the script's overlay lines, as the background of a waveform using the same scale
+pub fn paint_overlay(ctx: &mut PaintCtx, overlay: &[OverlayLine], env: &Env) {
+    let size = ctx.size();
+    let scale = WaveScale::from_env(env);
+    let color = Color::rgb8(0, 200, 255);
+    for line in overlay {
+        let offset = scale.position(line.level as f32) * size.height / 2.0;
+        for y_coord in [size.height / 2.0 - offset, size.height / 2.0 + offset] {
+            ctx.stroke(
+                Line::new((0.0, y_coord), (size.width, y_coord)),
+                &color,
+                1.0,
+            );
+        }
+        let layout = line.label.as_ref().map(|label| {
+            ctx.text()
+                .new_text_layout(label.clone())
+                .font(FontFamily::SYSTEM_UI, 10.0)
+                .text_color(color)
+                .build()
+        });
+        if let Some(Ok(layout)) = layout {
+            let y_coord = (size.height / 2.0 - offset - 12.0).max(0.0);
+            ctx.draw_text(&layout, (size.width - layout.size().width - 2.0, y_coord));
+        }
+    }
+}
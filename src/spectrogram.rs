@@ -0,0 +1,324 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Shared spectrogram pipeline.
+//!
+//! [`Spectrogram`] consumes mono samples one at a time and emits a column of dB magnitudes every
+//! hop, so the same code can be fed from the live audio queue or from a file read into memory.
+//! [`FrequencyMapping`] optionally resamples the linear FFT bins onto a mel or log-frequency axis,
+//! and [`render_rgb`] turns a list of columns into an RGB image, colored over a [`DisplayRange`].
+
+use std::str::FromStr;
+
+use audio_processor_analysis::fft_processor::{FftProcessor, FftProcessorOptions};
+use audio_processor_analysis::window_functions::WindowFunctionType;
+use audio_processor_traits::simple_processor::MonoAudioProcessor;
+use audio_processor_traits::AudioContext;
+use druid::{Data, Lens};
+use serde::{Deserialize, Serialize};
+
+use crate::colormap::Colormap;
+use crate::spl;
+
+pub struct SpectrogramOptions {
+    pub fft_size: usize,
+    pub overlap_ratio: f32,
+    pub window_function: WindowFunctionType,
+    /// Magnitudes below this level are clamped
+    pub floor_db: f32,
+}
+
+impl Default for SpectrogramOptions {
+    fn default() -> Self {
+        Self {
+            fft_size: 2048,
+            overlap_ratio: 0.75,
+            window_function: WindowFunctionType::Hann,
+            floor_db: -120.0,
+        }
+    }
+}
+
+pub struct Spectrogram {
+    context: AudioContext,
+    fft: FftProcessor,
+    column: Vec<f32>,
+    floor_db: f32,
+}
+
+impl Spectrogram {
+    pub fn new(options: SpectrogramOptions) -> Self {
+        let fft = FftProcessor::new(FftProcessorOptions {
+            size: options.fft_size,
+            overlap_ratio: options.overlap_ratio,
+            window_function: options.window_function,
+            ..Default::default()
+        });
+        Spectrogram {
+            context: AudioContext::default(),
+            column: vec![options.floor_db; fft.size() / 2],
+            fft,
+            floor_db: options.floor_db,
+        }
+    }
+
+    /// Push a sample, returning the new column when an FFT was performed on this sample.
+    pub fn process(&mut self, sample: f32) -> Option<&[f32]> {
+        self.fft.m_process(&mut self.context, sample);
+        if !self.fft.has_changed() {
+            return None;
+        }
+
+        // Scale so a full-scale sine reads close to 0dB with a Hann window
+        let scale = 4.0 / self.fft.size() as f32;
+        for (output, bin) in self.column.iter_mut().zip(self.fft.buffer()) {
+            let magnitude = bin.norm() * scale;
+            *output = (20.0 * magnitude.log10()).max(self.floor_db);
+        }
+        Some(&self.column)
+    }
+}
+
+/// Levels mapped onto the colormap, so quiet content can be brought up without recomputing the
+/// FFTs
+#[derive(Clone, Copy, Data, Lens, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DisplayRange {
+    /// Bottom of the colormap, levels at or below it are drawn in its first color
+    pub floor_db: f64,
+    /// Top of the colormap
+    pub ceiling_db: f64,
+    /// Exponent applied to the level's position in the range, below 1 brightens quiet content
+    pub gamma: f64,
+}
+
+impl Default for DisplayRange {
+    fn default() -> Self {
+        DisplayRange {
+            floor_db: -120.0,
+            ceiling_db: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl DisplayRange {
+    /// Position of a level on the colormap between 0 and 1
+    pub fn value(&self, level_db: f32) -> f32 {
+        let span = (self.ceiling_db - self.floor_db).max(1.0);
+        let position = ((level_db as f64 - self.floor_db) / span).clamp(0.0, 1.0);
+        position.powf(self.gamma) as f32
+    }
+}
+
+/// Frequency axis of a rendered spectrogram
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrequencyScale {
+    /// FFT bins as they are
+    Linear,
+    /// Bands evenly spaced on the mel scale
+    Mel,
+    /// Log-spaced bands with a constant frequency-to-bandwidth ratio. This is derived from the FFT
+    /// bins, so bands below the FFT's resolution are interpolated.
+    ConstantQ,
+}
+
+impl FrequencyScale {
+    pub const NAMES: [&'static str; 3] = ["linear", "mel", "cqt"];
+}
+
+impl FromStr for FrequencyScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(FrequencyScale::Linear),
+            "mel" => Ok(FrequencyScale::Mel),
+            "cqt" => Ok(FrequencyScale::ConstantQ),
+            _ => Err(format!(
+                "Unknown frequency scale '{}', expected one of {}",
+                s,
+                FrequencyScale::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Gain applied to each bin before drawing, so a reference signal other than white noise reads
+/// flat
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayWeighting {
+    Flat,
+    /// +3dB per octave around 1kHz, cancelling the slope of pink noise
+    Pink,
+    /// The A-weighting curve, showing bins roughly as loud as they're heard
+    A,
+}
+
+impl DisplayWeighting {
+    pub const NAMES: [&'static str; 3] = ["flat", "pink", "a"];
+
+    /// Gain in dB of each of the `fft_size / 2` bins
+    pub fn bin_gains(self, fft_size: usize, sample_rate: f32) -> Vec<f32> {
+        let num_bins = fft_size / 2;
+        (0..num_bins)
+            .map(|bin| {
+                let hz = bin as f32 / num_bins as f32 * sample_rate / 2.0;
+                match self {
+                    DisplayWeighting::Flat => 0.0,
+                    DisplayWeighting::Pink => 10.0 * (hz / 1000.0).log10(),
+                    DisplayWeighting::A => spl::a_weighting_db(hz),
+                }
+            })
+            .collect()
+    }
+
+    /// Add `gains` from [`DisplayWeighting::bin_gains`] to a column, clamping at `floor_db`
+    pub fn apply(gains: &[f32], column: &mut [f32], floor_db: f32) {
+        for (value, gain) in column.iter_mut().zip(gains) {
+            *value = (*value + gain).max(floor_db);
+        }
+    }
+}
+
+impl FromStr for DisplayWeighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(DisplayWeighting::Flat),
+            "pink" => Ok(DisplayWeighting::Pink),
+            "a" => Ok(DisplayWeighting::A),
+            _ => Err(format!(
+                "Unknown weighting '{}', expected one of {}",
+                s,
+                DisplayWeighting::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// Lowest band of the constant-Q scale, C1
+const CONSTANT_Q_MIN_HZ: f32 = 32.703;
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10.0_f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// Resamples columns of FFT bins onto the bands of a [`FrequencyScale`], lowest band first.
+pub struct FrequencyMapping {
+    /// Each band's edges as fractional FFT bin positions
+    bands: Vec<(f32, f32)>,
+    bin_hz: f32,
+}
+
+impl FrequencyMapping {
+    pub fn new(scale: FrequencyScale, num_bands: usize, fft_size: usize, sample_rate: f32) -> Self {
+        let num_bins = fft_size / 2;
+        let nyquist = sample_rate / 2.0;
+        let hz_to_bin = |hz: f32| hz / nyquist * num_bins as f32;
+        let edge = |index: usize| {
+            let position = index as f32 / num_bands as f32;
+            match scale {
+                FrequencyScale::Linear => position * num_bins as f32,
+                FrequencyScale::Mel => hz_to_bin(mel_to_hz(position * hz_to_mel(nyquist))),
+                FrequencyScale::ConstantQ => {
+                    hz_to_bin(CONSTANT_Q_MIN_HZ * (nyquist / CONSTANT_Q_MIN_HZ).powf(position))
+                }
+            }
+        };
+        FrequencyMapping {
+            bands: (0..num_bands)
+                .map(|index| (edge(index), edge(index + 1)))
+                .collect(),
+            bin_hz: nyquist / num_bins as f32,
+        }
+    }
+
+    /// Center frequency of each band, lowest first
+    pub fn center_hz(&self) -> Vec<f32> {
+        self.bands
+            .iter()
+            .map(|&(low, high)| (low + high) / 2.0 * self.bin_hz)
+            .collect()
+    }
+
+    /// Map a column of dB magnitudes, taking the loudest bin of each band and interpolating
+    /// bands narrower than a bin.
+    pub fn apply(&self, column: &[f32]) -> Vec<f32> {
+        let last_bin = column.len() - 1;
+        self.bands
+            .iter()
+            .map(|&(low, high)| {
+                if high - low < 1.0 {
+                    let center = ((low + high) / 2.0).min(last_bin as f32);
+                    let bin = center as usize;
+                    let fraction = center - bin as f32;
+                    let next = column[(bin + 1).min(last_bin)];
+                    column[bin] + (next - column[bin]) * fraction
+                } else {
+                    let first = (low as usize).min(last_bin);
+                    let last = (high.ceil() as usize).clamp(first + 1, column.len());
+                    column[first..last]
+                        .iter()
+                        .copied()
+                        .fold(f32::NEG_INFINITY, f32::max)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Run a whole signal through the pipeline, collecting every column.
+pub fn analyse(samples: impl Iterator<Item = f32>, options: SpectrogramOptions) -> Vec<Vec<f32>> {
+    let mut spectrogram = Spectrogram::new(options);
+    let mut columns = Vec::new();
+    for sample in samples {
+        if let Some(column) = spectrogram.process(sample) {
+            columns.push(column.to_vec());
+        }
+    }
+    columns
+}
+
+/// Render columns into a `columns.len() x height` RGB image, with low frequencies at the bottom.
+pub fn render_rgb(
+    columns: &[Vec<f32>],
+    height: usize,
+    colormap: Colormap,
+    range: DisplayRange,
+) -> Vec<u8> {
+    let width = columns.len();
+    let mut pixels = vec![0; width * height * 3];
+    for (x, column) in columns.iter().enumerate() {
+        for y in 0..height {
+            let bin = (height - 1 - y) * column.len() / height;
+            let value = range.value(column[bin]);
+            let offset = (y * width + x) * 3;
+            pixels[offset..offset + 3].copy_from_slice(&colormap.color(value));
+        }
+    }
+    pixels
+}
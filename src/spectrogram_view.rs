@@ -0,0 +1,568 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Live spectrogram on a constant-Q axis, newest column on the right. Hovering shows the
+//! frequency, note, age and level under the pointer, and clicking pins that readout to the column
+//! so it scrolls along with it. Clicking a pin removes it, and dragging up or down solos that band
+//! on the monitor output. The colors follow the
+//! [`DisplayRange`] in the environment, so changing it only repaints the columns.
+//!
+//! The last few minutes are kept as a ring of fixed-size tiles in tenths of a dB, so an update
+//! only copies the tile being filled. Scrolling pauses the view and moves back through them.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use druid::kurbo::{Circle, Line, Rect};
+use druid::piet::{ImageFormat, InterpolationMode, Text, TextLayout, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Data, FontFamily, Key, MouseButton, Point, Selector};
+
+use crate::colormap::Colormap;
+use crate::feedback::note_name;
+use crate::i18n::localize_with;
+use crate::monitor::{SoloBand, SOLO_BAND};
+use crate::spectrogram::{
+    DisplayRange, FrequencyMapping, FrequencyScale, Spectrogram, SpectrogramOptions,
+};
+use crate::timeline::TimeSpan;
+use crate::units::AmplitudeScale;
+
+pub const DRAW_SPECTROGRAM: Selector<SpectrogramData> =
+    Selector::new("event-example.draw_spectrogram");
+pub const SPECTROGRAM_FLOOR: Key<f64> = Key::new("event-example.spectrogram_floor");
+pub const SPECTROGRAM_CEILING: Key<f64> = Key::new("event-example.spectrogram_ceiling");
+pub const SPECTROGRAM_GAMMA: Key<f64> = Key::new("event-example.spectrogram_gamma");
+
+/// Columns across the view until it's pinched, about 5 s at 48 kHz
+pub const SPECTROGRAM_LEN: usize = 512;
+/// How far pinching zooms in and out, in columns across the view
+const MIN_VISIBLE_COLUMNS: f64 = 32.0;
+const MAX_VISIBLE_COLUMNS: f64 = (SPECTROGRAM_LEN * 4) as f64;
+
+const FFT_SIZE: usize = 4096;
+/// A column every 512 samples
+const OVERLAP_RATIO: f32 = 0.875;
+const NUM_BANDS: usize = 256;
+/// Quietest level analysed, the display floor can't go below it
+pub const FLOOR_DB: f32 = -160.0;
+/// Columns per history tile, half a screenful
+const TILE_LEN: usize = SPECTROGRAM_LEN / 2;
+/// Full tiles kept, about five minutes at 48 kHz in 14 MB
+const MAX_TILES: usize = 110;
+/// Columns scrolled per pixel of wheel movement
+const SCROLL_COLUMNS: f64 = 0.25;
+/// Readouts pinned at once, the oldest is dropped past this
+const MAX_PINS: usize = 4;
+/// How close to a pin a click removes it rather than adding another
+const PIN_RADIUS: f64 = 6.0;
+/// Pointer movement below which a press is a click rather than a drag
+const DRAG_THRESHOLD: f64 = 3.0;
+
+/// Consecutive columns of [`NUM_BANDS`] levels each, in tenths of a dBFS
+#[derive(Clone, Default, Debug)]
+pub struct Tile {
+    levels: Vec<i16>,
+}
+
+impl Tile {
+    fn len(&self) -> usize {
+        self.levels.len() / NUM_BANDS
+    }
+
+    fn column(&self, index: usize) -> Option<&[i16]> {
+        self.levels.get(index * NUM_BANDS..(index + 1) * NUM_BANDS)
+    }
+
+    fn push(&mut self, column: &[f32]) {
+        self.levels
+            .extend(column.iter().map(|level| (level * 10.0).round() as i16));
+    }
+}
+
+/// The kept history, levels per band with the lowest band first
+#[derive(Clone, Data)]
+pub struct SpectrogramData {
+    /// Full tiles, oldest first
+    pub tiles: Arc<VecDeque<Arc<Tile>>>,
+    /// The tile being filled, after the full ones
+    pub open: Arc<Tile>,
+    pub center_hz: Arc<Vec<f32>>,
+    pub hop_seconds: f32,
+    /// Columns analysed so far, the newest is number `end - 1`
+    pub end: u64,
+    /// Where the view ends while paused, it follows `end` when `None`
+    pub view_end: Option<u64>,
+    /// Columns across the view, fractional so small pinches add up
+    pub visible_columns: f64,
+}
+
+impl Default for SpectrogramData {
+    fn default() -> Self {
+        SpectrogramData {
+            tiles: Arc::new(VecDeque::new()),
+            open: Arc::new(Tile::default()),
+            center_hz: Arc::new(Vec::new()),
+            hop_seconds: 0.0,
+            end: 0,
+            view_end: None,
+            visible_columns: SPECTROGRAM_LEN as f64,
+        }
+    }
+}
+
+impl SpectrogramData {
+    fn columns_in_view(&self) -> usize {
+        self.visible_columns.round() as usize
+    }
+
+    /// Oldest column still kept
+    fn start(&self) -> u64 {
+        self.end - (self.tiles.len() * TILE_LEN + self.open.len()) as u64
+    }
+
+    /// Stream positions of the columns in view, for the timeline
+    pub fn time_span(&self) -> TimeSpan {
+        let hop_len = FFT_SIZE as f64 * (1.0 - OVERLAP_RATIO as f64);
+        let end = self.visible_end() as f64 * hop_len;
+        TimeSpan {
+            start: end - self.columns_in_view() as f64 * hop_len,
+            end,
+        }
+    }
+
+    /// Right edge of the view, no further back than a screenful after the oldest column
+    pub fn visible_end(&self) -> u64 {
+        let earliest = (self.start() + self.columns_in_view() as u64).min(self.end);
+        self.view_end.unwrap_or(self.end).clamp(earliest, self.end)
+    }
+
+    fn column(&self, column: u64) -> Option<&[i16]> {
+        let index = column.checked_sub(self.start())? as usize;
+        match self.tiles.get(index / TILE_LEN) {
+            Some(tile) => tile.column(index % TILE_LEN),
+            None => self.open.column(index - self.tiles.len() * TILE_LEN),
+        }
+    }
+
+    /// Level of a band in an absolute column, `None` once it has left the history
+    fn level_db(&self, column: u64, band: usize) -> Option<f32> {
+        Some(*self.column(column)?.get(band)? as f32 / 10.0)
+    }
+}
+
+/// Runs the shared [`Spectrogram`] pipeline on the live input and keeps the history
+pub struct LiveSpectrogram {
+    spectrogram: Spectrogram,
+    mapping: FrequencyMapping,
+    tiles: VecDeque<Arc<Tile>>,
+    open: Tile,
+    center_hz: Arc<Vec<f32>>,
+    hop_seconds: f32,
+    end: u64,
+}
+
+impl LiveSpectrogram {
+    pub fn new(sample_rate: f32) -> Self {
+        let mapping =
+            FrequencyMapping::new(FrequencyScale::ConstantQ, NUM_BANDS, FFT_SIZE, sample_rate);
+        LiveSpectrogram {
+            spectrogram: Spectrogram::new(SpectrogramOptions {
+                fft_size: FFT_SIZE,
+                overlap_ratio: OVERLAP_RATIO,
+                floor_db: FLOOR_DB,
+                ..SpectrogramOptions::default()
+            }),
+            center_hz: Arc::new(mapping.center_hz()),
+            mapping,
+            tiles: VecDeque::with_capacity(MAX_TILES),
+            open: Tile::default(),
+            hop_seconds: FFT_SIZE as f32 * (1.0 - OVERLAP_RATIO) / sample_rate,
+            end: 0,
+        }
+    }
+
+    /// Push a sample, returning whether a column was added
+    pub fn process(&mut self, sample: f32) -> bool {
+        let column = match self.spectrogram.process(sample) {
+            Some(column) => self.mapping.apply(column),
+            None => return false,
+        };
+        self.open.push(&column);
+        if self.open.len() == TILE_LEN {
+            if self.tiles.len() == MAX_TILES {
+                self.tiles.pop_front();
+            }
+            self.tiles
+                .push_back(Arc::new(std::mem::take(&mut self.open)));
+        }
+        self.end += 1;
+        true
+    }
+
+    pub fn data(&self) -> SpectrogramData {
+        SpectrogramData {
+            tiles: Arc::new(self.tiles.clone()),
+            open: Arc::new(self.open.clone()),
+            center_hz: self.center_hz.clone(),
+            hop_seconds: self.hop_seconds,
+            end: self.end,
+            view_end: None,
+            visible_columns: SPECTROGRAM_LEN as f64,
+        }
+    }
+}
+
+pub fn set_display_range(env: &mut Env, range: &DisplayRange) {
+    env.set(SPECTROGRAM_FLOOR, range.floor_db);
+    env.set(SPECTROGRAM_CEILING, range.ceiling_db);
+    env.set(SPECTROGRAM_GAMMA, range.gamma);
+}
+
+fn display_range(env: &Env) -> DisplayRange {
+    let default = DisplayRange::default();
+    DisplayRange {
+        floor_db: env.try_get(SPECTROGRAM_FLOOR).unwrap_or(default.floor_db),
+        ceiling_db: env
+            .try_get(SPECTROGRAM_CEILING)
+            .unwrap_or(default.ceiling_db),
+        gamma: env.try_get(SPECTROGRAM_GAMMA).unwrap_or(default.gamma),
+    }
+}
+
+/// A readout fixed to a band of an absolute column
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Pin {
+    column: u64,
+    band: usize,
+}
+
+#[derive(Default)]
+pub struct SpectrogramView {
+    hover: Option<Point>,
+    pins: Vec<Pin>,
+    pixels: Vec<u8>,
+    /// Wheel movement short of a whole column
+    scroll: f64,
+    /// Where a press started while the button is held
+    press: Option<Point>,
+}
+
+impl SpectrogramView {
+    /// The absolute column and band under a point
+    fn cell_at(size: Size, data: &SpectrogramData, point: Point) -> Option<Pin> {
+        let columns = data.columns_in_view();
+        let slot = (point.x / size.width * columns as f64).floor();
+        let band = ((1.0 - point.y / size.height) * NUM_BANDS as f64).floor();
+        if !(0.0..columns as f64).contains(&slot) || !(0.0..NUM_BANDS as f64).contains(&band) {
+            return None;
+        }
+        let column = (data.visible_end() + slot as u64).checked_sub(columns as u64)?;
+        Some(Pin {
+            column,
+            band: band as usize,
+        })
+    }
+
+    /// Center of a cell, where its pin is drawn
+    fn point_of(size: Size, data: &SpectrogramData, pin: Pin) -> Point {
+        let columns = data.columns_in_view() as f64;
+        let slot = pin.column as f64 + columns - data.visible_end() as f64;
+        Point::new(
+            (slot + 0.5) / columns * size.width,
+            (1.0 - (pin.band as f64 + 0.5) / NUM_BANDS as f64) * size.height,
+        )
+    }
+
+    fn band_at(size: Size, y_coord: f64) -> usize {
+        let band = (1.0 - y_coord / size.height) * NUM_BANDS as f64;
+        band.clamp(0.0, (NUM_BANDS - 1) as f64) as usize
+    }
+
+    /// Bottom edge of the band holding `hz`
+    fn y_of_hz(size: Size, data: &SpectrogramData, hz: f32) -> f64 {
+        let band = data.center_hz.partition_point(|center| *center < hz);
+        (1.0 - band as f64 / NUM_BANDS as f64) * size.height
+    }
+
+    /// Remove the pin under a click, or pin the cell under it
+    fn toggle_pin(&mut self, size: Size, data: &SpectrogramData, point: Point) {
+        let near = self
+            .pins
+            .iter()
+            .position(|pin| Self::point_of(size, data, *pin).distance(point) <= PIN_RADIUS);
+        match near {
+            Some(index) => {
+                self.pins.remove(index);
+            }
+            None => {
+                if let Some(pin) = Self::cell_at(size, data, point) {
+                    if self.pins.len() == MAX_PINS {
+                        self.pins.remove(0);
+                    }
+                    self.pins.push(pin);
+                }
+            }
+        }
+    }
+
+    fn readout(data: &SpectrogramData, pin: Pin, scale: &AmplitudeScale) -> Option<String> {
+        let level = data.level_db(pin.column, pin.band)?;
+        let hz = *data.center_hz.get(pin.band)?;
+        let age = (data.end - 1 - pin.column) as f32 * data.hop_seconds;
+        let frequency = if hz >= 1000.0 {
+            format!("{:.2} kHz", hz / 1000.0)
+        } else {
+            format!("{:.1} Hz", hz)
+        };
+        Some(format!(
+            "{}  {}  -{:.2} s  {}",
+            frequency,
+            note_name(hz),
+            age,
+            scale.format_db(level)
+        ))
+    }
+
+    fn label(ctx: &mut PaintCtx, text: String, color: Color, mut origin: Point) {
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 10.0)
+            .text_color(color)
+            .build();
+        if let Ok(layout) = layout {
+            // Keep the text inside the view near the right and top edges
+            let size = ctx.size();
+            let text_size = layout.size();
+            if origin.x + text_size.width > size.width {
+                origin.x -= text_size.width + 12.0;
+            }
+            origin.y = origin
+                .y
+                .clamp(0.0, (size.height - text_size.height).max(0.0));
+            ctx.draw_text(&layout, origin);
+        }
+    }
+
+    fn paint_columns(&mut self, ctx: &mut PaintCtx, data: &SpectrogramData, range: DisplayRange) {
+        let size = ctx.size();
+        let columns = data.columns_in_view();
+        self.pixels.clear();
+        self.pixels.resize(columns * NUM_BANDS * 3, 0);
+        // Right-aligned until the history fills a screen
+        let visible_end = data.visible_end();
+        for slot in 0..columns {
+            let column = match (visible_end + slot as u64)
+                .checked_sub(columns as u64)
+                .and_then(|column| data.column(column))
+            {
+                Some(column) => column,
+                None => continue,
+            };
+            for (band, level) in column.iter().enumerate() {
+                let y = NUM_BANDS - 1 - band;
+                let offset = (y * columns + slot) * 3;
+                let color = Colormap::Magma.color(range.value(*level as f32 / 10.0));
+                self.pixels[offset..offset + 3].copy_from_slice(&color);
+            }
+        }
+        match ctx.make_image(columns, NUM_BANDS, &self.pixels, ImageFormat::Rgb) {
+            Ok(image) => ctx.draw_image(&image, size.to_rect(), InterpolationMode::Bilinear),
+            Err(err) => log::error!("Failed to draw spectrogram: {}", err),
+        }
+    }
+}
+
+impl Widget<SpectrogramData> for SpectrogramView {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut SpectrogramData, _env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_SPECTROGRAM) => {
+                let (view_end, visible_columns) = (data.view_end, data.visible_columns);
+                *data = cmd.get_unchecked(DRAW_SPECTROGRAM).clone();
+                data.view_end = view_end;
+                data.visible_columns = visible_columns;
+                let start = data.start();
+                self.pins.retain(|pin| pin.column >= start);
+            }
+            Event::MouseMove(mouse) => {
+                self.hover = Some(mouse.pos);
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse) if mouse.button == MouseButton::Left => {
+                self.press = Some(mouse.pos);
+                ctx.set_active(true);
+            }
+            Event::MouseUp(mouse) if mouse.button == MouseButton::Left => {
+                if let Some(press) = self.press.take() {
+                    let size = ctx.size();
+                    if (mouse.pos.y - press.y).abs() > DRAG_THRESHOLD {
+                        let hz_at = |y_coord| {
+                            let band = Self::band_at(size, y_coord);
+                            data.center_hz.get(band).copied()
+                        };
+                        if let Some((start, end)) = hz_at(press.y).zip(hz_at(mouse.pos.y)) {
+                            let band = SoloBand::between(start, end);
+                            ctx.submit_command(SOLO_BAND.with(Some(band)));
+                        }
+                    } else {
+                        self.toggle_pin(size, data, press);
+                    }
+                    ctx.set_active(false);
+                    ctx.request_paint();
+                }
+            }
+            Event::Wheel(mouse) => {
+                // Either axis scrolls through time, pausing the view
+                let delta = mouse.wheel_delta;
+                let amount = if delta.x.abs() > delta.y.abs() {
+                    delta.x
+                } else {
+                    delta.y
+                };
+                self.scroll += amount * SCROLL_COLUMNS;
+                let columns = self.scroll.trunc();
+                self.scroll -= columns;
+                let view_end = data.visible_end() as i64 + columns as i64;
+                data.view_end = Some(view_end.max(0) as u64);
+                // Kept within the history
+                data.view_end = Some(data.visible_end());
+                ctx.set_handled();
+            }
+            // Pinching zooms in time around the right edge, like the waveform
+            Event::Zoom(magnification) => {
+                data.visible_columns = (data.visible_columns * (-magnification).exp())
+                    .clamp(MIN_VISIBLE_COLUMNS, MAX_VISIBLE_COLUMNS);
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &SpectrogramData,
+        _: &Env,
+    ) {
+        if let LifeCycle::HotChanged(false) = event {
+            self.hover = None;
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &SpectrogramData,
+        _data: &SpectrogramData,
+        _: &Env,
+    ) {
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &SpectrogramData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &SpectrogramData, env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::BLACK);
+        self.paint_columns(ctx, data, display_range(env));
+
+        let selection = match (self.press, self.hover) {
+            (Some(press), Some(hover)) if (hover.y - press.y).abs() > DRAG_THRESHOLD => {
+                Some((press.y, hover.y))
+            }
+            _ => SoloBand::from_env(env).map(|band| {
+                (
+                    Self::y_of_hz(size, data, band.low_hz),
+                    Self::y_of_hz(size, data, band.high_hz),
+                )
+            }),
+        };
+        if let Some((start, end)) = selection {
+            let rect = Rect::new(0.0, start.min(end), size.width, start.max(end));
+            ctx.stroke(rect, &Color::rgb8(0, 200, 255).with_alpha(0.8), 1.0);
+        }
+
+        if data.view_end.is_some() {
+            let behind = (data.end - data.visible_end()) as f32 * data.hop_seconds;
+            Self::label(
+                ctx,
+                localize_with(
+                    "spectrogram-paused",
+                    &[("behind", format!("{:.1}", behind))],
+                    env,
+                ),
+                Color::rgb8(255, 160, 0),
+                Point::new(4.0, 2.0),
+            );
+        }
+
+        let scale = AmplitudeScale::from_env(env);
+        let marker = Color::rgb8(0, 200, 255);
+        for pin in self.pins.clone() {
+            let point = Self::point_of(size, data, pin);
+            if !size.to_rect().contains(point) {
+                continue;
+            }
+            ctx.stroke(Circle::new(point, 3.0), &marker, 1.5);
+            if let Some(text) = Self::readout(data, pin, &scale) {
+                Self::label(ctx, text, marker, Point::new(point.x + 6.0, point.y - 6.0));
+            }
+        }
+
+        let hovered = self
+            .hover
+            .and_then(|point| Self::cell_at(size, data, point).map(|cell| (point, cell)));
+        if let Some((point, cell)) = hovered {
+            let crosshair = Color::grey(0.8).with_alpha(0.6);
+            ctx.stroke(
+                Line::new((point.x, 0.0), (point.x, size.height)),
+                &crosshair,
+                1.0,
+            );
+            ctx.stroke(
+                Line::new((0.0, point.y), (size.width, point.y)),
+                &crosshair,
+                1.0,
+            );
+            if let Some(text) = Self::readout(data, cell, &scale) {
+                Self::label(
+                    ctx,
+                    text,
+                    Color::WHITE,
+                    Point::new(point.x + 6.0, point.y - 16.0),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,653 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Magnitude spectrum of the latest window on a log frequency axis, with a harmonic cursor:
+//! hovering a peak marks 2f, 3f, 4f... and labels each with its level relative to the
+//! fundamental, for reading distortion at a glance. Dragging across it solos that band on the
+//! monitor output, and a click without dragging stops. Detected mains hum is flagged at each of
+//! its harmonics, and the spectral tilt's fitted line is drawn over its range, as can be a pink
+//! noise reference at the same level. The drawn curve can be smoothed over a fraction of an
+//! octave. While the monitor output filters the input, its spectrum is drawn as a second trace
+//! with its own smoothing, and the difference between the two around the plot's middle.
+//! [`peak_list`] is a table of the strongest peaks beside it.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use audio_processor_analysis::window_functions::{make_window_vec, WindowFunctionType};
+use druid::kurbo::{BezPath, Line, Rect};
+use druid::piet::{Text, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::widget::{Label, List};
+use druid::{Color, Data, FontFamily, Key, MouseButton, Point, Selector, WidgetExt};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::feedback::note_name;
+use crate::hum;
+use crate::i18n::localize_with;
+use crate::monitor::{SoloBand, SOLO_BAND};
+use crate::tilt;
+use crate::units::AmplitudeScale;
+
+pub const DRAW_SPECTRUM: Selector<SpectrumData> = Selector::new("event-example.draw_spectrum");
+/// Earlier spectra drawn fading out behind the newest one, none when unset
+pub const SPECTRUM_PERSISTENCE: Key<u64> = Key::new("event-example.spectrum_persistence");
+/// Fraction of an octave the drawn spectrum is smoothed over, 0 or unset for none
+pub const SPECTRUM_SMOOTHING: Key<u64> = Key::new("event-example.spectrum_smoothing");
+/// The processed trace's smoothing, as [`SPECTRUM_SMOOTHING`] is the input's
+pub const PROCESSED_SMOOTHING: Key<u64> = Key::new("event-example.processed_smoothing");
+/// The smoothing choices, 0 is off
+pub const SMOOTHING_FRACTIONS: [u64; 7] = [0, 1, 3, 6, 12, 24, 48];
+/// Draw the pink noise reference, off when unset
+pub const SPECTRUM_PINK_REFERENCE: Key<bool> = Key::new("event-example.spectrum_pink_reference");
+
+/// About 6 Hz per bin at the analysis rate, fine enough to separate low harmonics
+pub const WINDOW_LEN: usize = 8192;
+
+const MIN_HZ: f32 = 20.0;
+/// Bottom of the plot, the top is 0 dBFS
+const FLOOR_DB: f32 = -120.0;
+/// Harmonics marked past the fundamental, stopping early at Nyquist
+const MAX_HARMONIC: usize = 10;
+/// How far either side of the cursor, or of a harmonic's expected frequency, to look for a peak
+const PEAK_SEARCH_RATIO: f32 = 0.03;
+/// Pointer movement below which a press is a click rather than a drag
+const DRAG_THRESHOLD: f64 = 3.0;
+/// Bins either side a peak must be the loudest of, wide enough to skip the Hann window's
+/// sidelobes
+const PEAK_NEIGHBOURHOOD: usize = 2;
+/// Difference between the processed and input traces at the top and bottom of the plot
+const DIFFERENCE_RANGE_DB: f32 = 24.0;
+/// Points a reference curve is drawn through
+const REFERENCE_STEPS: usize = 96;
+
+#[derive(Clone, Data)]
+pub struct SpectrumData {
+    /// dBFS per FFT bin, a full-scale sine reads 0
+    pub magnitudes_db: Arc<Vec<f32>>,
+    pub bin_hz: f32,
+    /// The same window of the monitor's output while it's filtering, in the same bins
+    pub processed_db: Option<Arc<Vec<f32>>>,
+}
+
+impl Default for SpectrumData {
+    fn default() -> Self {
+        SpectrumData {
+            magnitudes_db: Arc::new(Vec::new()),
+            bin_hz: 0.0,
+            processed_db: None,
+        }
+    }
+}
+
+/// A spectral peak, refined between bins
+#[derive(Clone, Copy, Data, Debug, PartialEq)]
+pub struct Peak {
+    pub hz: f32,
+    pub level_db: f32,
+}
+
+impl SpectrumData {
+    fn nyquist(&self) -> f32 {
+        self.bin_hz * self.magnitudes_db.len() as f32
+    }
+
+    /// The loudest bin within [`PEAK_SEARCH_RATIO`] of `hz`, with its frequency and level
+    /// interpolated from its neighbours
+    pub fn peak_near(&self, hz: f32) -> Option<Peak> {
+        let bins = &self.magnitudes_db;
+        if bins.len() < 3 || self.bin_hz <= 0.0 {
+            return None;
+        }
+        let bin_of = |hz: f32| ((hz / self.bin_hz) as usize).clamp(1, bins.len() - 2);
+        let low = bin_of(hz * (1.0 - PEAK_SEARCH_RATIO));
+        let high = bin_of(hz * (1.0 + PEAK_SEARCH_RATIO)).max(low);
+        let bin = (low..=high).max_by(|a, b| bins[*a].total_cmp(&bins[*b]))?;
+        Some(self.refine(bin))
+    }
+
+    /// Each bin's level averaged in power over the `1 / fraction` octave around it, as measurement
+    /// software draws a smoothed response. Peaks and readouts use the unsmoothed bins.
+    pub fn smoothed(&self, fraction: u64) -> Arc<Vec<f32>> {
+        smooth(&self.magnitudes_db, fraction)
+    }
+
+    /// The `count` loudest local maxima above [`MIN_HZ`], loudest first
+    pub fn peaks(&self, count: usize) -> Vec<Peak> {
+        let bins = &self.magnitudes_db;
+        let first = ((MIN_HZ / self.bin_hz.max(f32::EPSILON)) as usize).max(PEAK_NEIGHBOURHOOD);
+        let last = bins.len().saturating_sub(PEAK_NEIGHBOURHOOD);
+        let mut peaks: Vec<Peak> = (first..last)
+            .filter(|&bin| {
+                let neighbourhood = &bins[bin - PEAK_NEIGHBOURHOOD..=bin + PEAK_NEIGHBOURHOOD];
+                bins[bin] > FLOOR_DB && neighbourhood.iter().all(|level| *level <= bins[bin])
+            })
+            .map(|bin| self.refine(bin))
+            .collect();
+        peaks.sort_by(|a, b| b.level_db.total_cmp(&a.level_db));
+        peaks.truncate(count);
+        peaks
+    }
+
+    /// Parabolic interpolation through a bin and its neighbours
+    pub fn refine(&self, bin: usize) -> Peak {
+        let bins = &self.magnitudes_db;
+        let (before, at, after) = (bins[bin - 1], bins[bin], bins[bin + 1]);
+        let curvature = before - 2.0 * at + after;
+        let offset = if curvature < 0.0 {
+            0.5 * (before - after) / curvature
+        } else {
+            0.0
+        };
+        Peak {
+            hz: (bin as f32 + offset) * self.bin_hz,
+            level_db: at - 0.25 * (before - after) * offset,
+        }
+    }
+}
+
+/// Smooth dB bins over `1 / fraction` of an octave, see [`SpectrumData::smoothed`]
+fn smooth(bins: &Arc<Vec<f32>>, fraction: u64) -> Arc<Vec<f32>> {
+    if fraction == 0 {
+        return bins.clone();
+    }
+    // Running power sums so each bin's average is one subtraction
+    let mut sums = Vec::with_capacity(bins.len() + 1);
+    sums.push(0.0_f64);
+    for level_db in bins.iter() {
+        let power = 10.0_f64.powf(*level_db as f64 / 10.0);
+        sums.push(sums[sums.len() - 1] + power);
+    }
+    let half_width = 2.0_f64.powf(0.5 / fraction as f64);
+    let smoothed = (0..bins.len())
+        .map(|bin| {
+            let low = ((bin as f64 / half_width).floor() as usize).min(bin);
+            let high = ((bin as f64 * half_width).ceil() as usize + 1).clamp(bin + 1, bins.len());
+            let power = (sums[high] - sums[low]) / (high - low) as f64;
+            ((10.0 * power.log10()) as f32).max(FLOOR_DB)
+        })
+        .collect();
+    Arc::new(smoothed)
+}
+
+impl Peak {
+    pub fn label(&self, scale: &AmplitudeScale) -> String {
+        format!(
+            "{:.1} Hz  {}  {}",
+            self.hz,
+            note_name(self.hz),
+            scale.format_db(self.level_db)
+        )
+    }
+}
+
+/// Every bin's level, for the spectrum pane's copy action
+pub fn spectrum_to_csv(spectrum: &SpectrumData) -> String {
+    let mut csv = String::from("frequency_hz,level_dbfs\n");
+    for (bin, level_db) in spectrum.magnitudes_db.iter().enumerate() {
+        csv += &format!("{:.2},{:.2}\n", bin as f32 * spectrum.bin_hz, level_db);
+    }
+    csv
+}
+
+pub fn peaks_to_csv(peaks: &[Peak]) -> String {
+    let mut csv = String::from("frequency_hz,note,level_dbfs\n");
+    for peak in peaks {
+        csv += &format!(
+            "{:.2},{},{:.2}\n",
+            peak.hz,
+            note_name(peak.hz),
+            peak.level_db
+        );
+    }
+    csv
+}
+
+/// Table of the strongest peaks, loudest first
+pub fn peak_list() -> impl Widget<Arc<Vec<Peak>>> {
+    List::new(|| {
+        Label::dynamic(|peak: &Peak, env| peak.label(&AmplitudeScale::from_env(env))).align_left()
+    })
+}
+
+/// The analysis window, trading the Hann window's balance for narrower peaks or lower leakage
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpectrumWindow {
+    #[default]
+    Hann,
+    BlackmanHarris,
+    Rectangular,
+}
+
+impl SpectrumWindow {
+    pub const ALL: [SpectrumWindow; 3] = [
+        SpectrumWindow::Hann,
+        SpectrumWindow::BlackmanHarris,
+        SpectrumWindow::Rectangular,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpectrumWindow::Hann => "Hann",
+            SpectrumWindow::BlackmanHarris => "Blackman-Harris",
+            SpectrumWindow::Rectangular => "Rectangular",
+        }
+    }
+
+    fn function_type(self) -> WindowFunctionType {
+        match self {
+            SpectrumWindow::Hann => WindowFunctionType::Hann,
+            SpectrumWindow::BlackmanHarris => WindowFunctionType::BlackmanHarris,
+            SpectrumWindow::Rectangular => WindowFunctionType::Rectangular,
+        }
+    }
+}
+
+pub struct SpectrumAnalyser {
+    sample_rate: f32,
+    window: Vec<f32>,
+    /// Scales a full-scale sine's bin to 1
+    gain: f32,
+    fft: Arc<dyn Fft<f32>>,
+}
+
+impl SpectrumAnalyser {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut analyser = SpectrumAnalyser {
+            sample_rate,
+            window: Vec::new(),
+            gain: 1.0,
+            fft: FftPlanner::new().plan_fft_forward(WINDOW_LEN),
+        };
+        analyser.set_window(SpectrumWindow::default());
+        analyser
+    }
+
+    pub fn set_window(&mut self, window: SpectrumWindow) {
+        self.window = make_window_vec(WINDOW_LEN, window.function_type());
+        self.gain = 2.0 / self.window.iter().sum::<f32>();
+    }
+
+    /// Analyse the last [`WINDOW_LEN`] samples
+    pub fn analyse(&self, samples: &[f32]) -> SpectrumData {
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, window)| Complex::new(sample * window, 0.0))
+            .collect();
+        buffer.resize(WINDOW_LEN, Complex::new(0.0, 0.0));
+        self.fft.process(&mut buffer);
+        let magnitudes_db = buffer[..WINDOW_LEN / 2]
+            .iter()
+            .map(|bin| (20.0 * (bin.norm() * self.gain).log10()).max(FLOOR_DB))
+            .collect();
+        SpectrumData {
+            magnitudes_db: Arc::new(magnitudes_db),
+            bin_hz: self.sample_rate / WINDOW_LEN as f32,
+            processed_db: None,
+        }
+    }
+}
+
+/// Magnitude against log frequency, with the hovered peak's harmonics marked
+#[derive(Default)]
+pub struct SpectrumPlot {
+    /// Cursor x while the pointer is over the plot
+    hover: Option<f64>,
+    /// Where a press started while the button is held
+    drag_start: Option<f64>,
+    /// Spectra shown before the current one, newest last
+    trail: VecDeque<SpectrumData>,
+}
+
+impl SpectrumPlot {
+    fn x_coord(size: Size, data: &SpectrumData, hz: f32) -> f64 {
+        ((hz / MIN_HZ).ln() / (data.nyquist() / MIN_HZ).ln()) as f64 * size.width
+    }
+
+    fn hz_at(size: Size, data: &SpectrumData, x_coord: f64) -> f32 {
+        MIN_HZ * (data.nyquist() / MIN_HZ).powf((x_coord / size.width) as f32)
+    }
+
+    fn y_coord(size: Size, level_db: f32) -> f64 {
+        (level_db / FLOOR_DB).clamp(0.0, 1.0) as f64 * size.height
+    }
+
+    /// A trace of bins laid out like `data`'s
+    fn curve(size: Size, data: &SpectrumData, bins: &[f32]) -> BezPath {
+        let mut shape = BezPath::new();
+        for (index, level) in bins.iter().enumerate() {
+            let hz = index as f32 * data.bin_hz;
+            if hz < MIN_HZ {
+                continue;
+            }
+            let point = Point::new(Self::x_coord(size, data, hz), Self::y_coord(size, *level));
+            if shape.elements().is_empty() {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        shape
+    }
+
+    fn label(ctx: &mut PaintCtx, text: String, color: Color, origin: Point) {
+        let layout = ctx
+            .text()
+            .new_text_layout(text)
+            .font(FontFamily::SYSTEM_UI, 10.0)
+            .text_color(color)
+            .build();
+        if let Ok(layout) = layout {
+            ctx.draw_text(&layout, origin);
+        }
+    }
+
+    /// The processed trace, and the difference from the input around the plot's middle, both
+    /// smoothed alike so the difference isn't the smoothing's
+    fn paint_processed(
+        &self,
+        ctx: &mut PaintCtx,
+        data: &SpectrumData,
+        processed_db: &Arc<Vec<f32>>,
+        smoothing: u64,
+        env: &Env,
+    ) {
+        let size = ctx.size();
+        let color = Color::rgb8(255, 150, 60);
+        let processed = smooth(processed_db, smoothing);
+        ctx.stroke(Self::curve(size, data, &processed), &color, 1.0);
+
+        let middle = size.height / 2.0;
+        let difference_color = Color::rgb8(200, 120, 255);
+        ctx.stroke(
+            Line::new((0.0, middle), (size.width, middle)),
+            &difference_color.with_alpha(0.3),
+            1.0,
+        );
+        let input = data.smoothed(smoothing);
+        let mut shape = BezPath::new();
+        for (index, (input_db, processed_db)) in input.iter().zip(processed.iter()).enumerate() {
+            let hz = index as f32 * data.bin_hz;
+            if hz < MIN_HZ {
+                continue;
+            }
+            let difference = ((processed_db - input_db) / DIFFERENCE_RANGE_DB).clamp(-1.0, 1.0);
+            let point = Point::new(
+                Self::x_coord(size, data, hz),
+                middle - difference as f64 * middle,
+            );
+            if shape.elements().is_empty() {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        ctx.stroke(shape, &difference_color, 1.0);
+        Self::label(
+            ctx,
+            localize_with(
+                "processed-difference",
+                &[("range", format!("{:.0}", DIFFERENCE_RANGE_DB))],
+                env,
+            ),
+            difference_color,
+            Point::new(4.0, middle + 2.0),
+        );
+    }
+
+    /// Draw a reference curve over the plot between two frequencies, labelled at its end
+    pub fn paint_reference(
+        ctx: &mut PaintCtx,
+        data: &SpectrumData,
+        (low_hz, high_hz): (f32, f32),
+        level_at: impl Fn(f32) -> f32,
+        color: Color,
+        label: String,
+    ) {
+        let size = ctx.size();
+        let high_hz = high_hz.min(data.nyquist());
+        if data.bin_hz <= 0.0 || low_hz >= high_hz {
+            return;
+        }
+        let mut shape = BezPath::new();
+        for step in 0..=REFERENCE_STEPS {
+            let hz = low_hz * (high_hz / low_hz).powf(step as f32 / REFERENCE_STEPS as f32);
+            let point = Point::new(
+                Self::x_coord(size, data, hz),
+                Self::y_coord(size, level_at(hz)),
+            );
+            if step == 0 {
+                shape.move_to(point);
+            } else {
+                shape.line_to(point);
+            }
+        }
+        let end = Point::new(
+            Self::x_coord(size, data, high_hz),
+            Self::y_coord(size, level_at(high_hz)),
+        );
+        ctx.stroke(shape, &color.with_alpha(0.8), 1.5);
+        let origin = Point::new((end.x + 3.0).min(size.width - 90.0), end.y - 12.0);
+        Self::label(ctx, label, color, origin);
+    }
+
+    fn paint_harmonics(&self, ctx: &mut PaintCtx, data: &SpectrumData, x_coord: f64) {
+        let size = ctx.size();
+        let fundamental = match data.peak_near(Self::hz_at(size, data, x_coord)) {
+            Some(peak) if peak.hz >= MIN_HZ => peak,
+            _ => return,
+        };
+        let marker = Color::rgb8(255, 160, 0);
+        let x_coord = Self::x_coord(size, data, fundamental.hz);
+        ctx.stroke(
+            Line::new((x_coord, 0.0), (x_coord, size.height)),
+            &marker,
+            1.0,
+        );
+        Self::label(
+            ctx,
+            format!("{:.1} Hz  {:.1} dBFS", fundamental.hz, fundamental.level_db),
+            marker,
+            Point::new(x_coord + 3.0, 2.0),
+        );
+
+        let harmonics = (2..=MAX_HARMONIC)
+            .map(|number| (number, fundamental.hz * number as f32))
+            .take_while(|(_, hz)| *hz < data.nyquist());
+        for (number, hz) in harmonics {
+            let level = data.peak_near(hz).map_or(FLOOR_DB, |peak| peak.level_db);
+            let x_coord = Self::x_coord(size, data, hz);
+            let y_coord = Self::y_coord(size, level);
+            ctx.stroke(
+                Line::new((x_coord, y_coord), (x_coord, size.height)),
+                &marker.with_alpha(0.5),
+                1.0,
+            );
+            Self::label(
+                ctx,
+                format!("{}f {:.1}", number, level - fundamental.level_db),
+                Color::grey(0.8),
+                Point::new(x_coord + 2.0, (y_coord - 12.0).max(14.0)),
+            );
+        }
+    }
+}
+
+impl Widget<SpectrumData> for SpectrumPlot {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut SpectrumData, _env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_SPECTRUM) => {
+                *data = cmd.get_unchecked(DRAW_SPECTRUM).clone();
+            }
+            Event::MouseMove(mouse) => {
+                self.hover = Some(mouse.pos.x);
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse) if mouse.button == MouseButton::Left => {
+                self.drag_start = Some(mouse.pos.x);
+                ctx.set_active(true);
+            }
+            Event::MouseUp(mouse) if mouse.button == MouseButton::Left => {
+                if let Some(start) = self.drag_start.take() {
+                    let size = ctx.size();
+                    let dragged = (mouse.pos.x - start).abs() > DRAG_THRESHOLD;
+                    let band = (dragged && data.bin_hz > 0.0).then(|| {
+                        SoloBand::between(
+                            Self::hz_at(size, data, start),
+                            Self::hz_at(size, data, mouse.pos.x),
+                        )
+                    });
+                    ctx.submit_command(SOLO_BAND.with(band));
+                    ctx.set_active(false);
+                    ctx.request_paint();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &SpectrumData,
+        _: &Env,
+    ) {
+        if let LifeCycle::HotChanged(false) = event {
+            self.hover = None;
+            ctx.request_paint();
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &SpectrumData,
+        data: &SpectrumData,
+        env: &Env,
+    ) {
+        let persistence = env.try_get(SPECTRUM_PERSISTENCE).unwrap_or(0) as usize;
+        if !old_data.same(data) && persistence > 0 {
+            self.trail.push_back(old_data.clone());
+        }
+        while self.trail.len() > persistence {
+            self.trail.pop_front();
+        }
+        ctx.request_paint()
+    }
+
+    fn layout(
+        &mut self,
+        _: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _: &SpectrumData,
+        _: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &SpectrumData, env: &Env) {
+        let size = ctx.size();
+        let selection = self.drag_start.zip(self.hover).or_else(|| {
+            SoloBand::from_env(env).map(|band| {
+                (
+                    Self::x_coord(size, data, band.low_hz),
+                    Self::x_coord(size, data, band.high_hz),
+                )
+            })
+        });
+        if let Some((start, end)) = selection {
+            let rect = Rect::new(start.min(end), 0.0, start.max(end), size.height);
+            ctx.fill(rect, &Color::rgb8(0, 200, 255).with_alpha(0.15));
+        }
+
+        let smoothing = env.try_get(SPECTRUM_SMOOTHING).unwrap_or(0);
+        // Oldest faintest
+        let trail_len = self.trail.len() as f64;
+        for (index, earlier) in self.trail.iter().enumerate() {
+            let color = Color::grey(0.8).with_alpha(0.4 * (index + 1) as f64 / (trail_len + 1.0));
+            let trace = Self::curve(size, earlier, &earlier.smoothed(smoothing));
+            ctx.stroke(trace, &color, 1.0);
+        }
+        ctx.stroke(
+            Self::curve(size, data, &data.smoothed(smoothing)),
+            &Color::grey(0.8),
+            1.0,
+        );
+        if let Some(processed_db) = &data.processed_db {
+            let smoothing = env.try_get(PROCESSED_SMOOTHING).unwrap_or(0);
+            self.paint_processed(ctx, data, processed_db, smoothing, env);
+        }
+
+        if env.try_get(SPECTRUM_PINK_REFERENCE).unwrap_or(false) {
+            if let Some(pink) = tilt::pink_reference(data) {
+                Self::paint_reference(
+                    ctx,
+                    data,
+                    (MIN_HZ, data.nyquist()),
+                    |hz| pink.level_at(hz),
+                    Color::rgb8(255, 120, 200),
+                    "Pink noise".to_string(),
+                );
+            }
+        }
+        if let Some(tilt) = tilt::fit(data) {
+            Self::paint_reference(
+                ctx,
+                data,
+                (tilt::LOW_HZ, tilt::HIGH_HZ),
+                |hz| tilt.level_at(hz),
+                Color::rgb8(120, 220, 120),
+                tilt.label(),
+            );
+        }
+
+        if let Some(hum) = hum::detect(data) {
+            let flag = Color::rgb8(255, 60, 60);
+            for (index, peak) in hum.harmonics.iter().enumerate() {
+                let x_coord = Self::x_coord(size, data, peak.hz);
+                let y_coord = Self::y_coord(size, peak.level_db);
+                ctx.stroke(
+                    Line::new((x_coord, y_coord - 16.0), (x_coord, y_coord - 4.0)),
+                    &flag,
+                    2.0,
+                );
+                if index == 0 {
+                    Self::label(
+                        ctx,
+                        format!("{:.0} Hz hum", hum.mains_hz),
+                        flag,
+                        Point::new(x_coord + 3.0, (y_coord - 28.0).max(14.0)),
+                    );
+                }
+            }
+        }
+
+        if let Some(x_coord) = self.hover {
+            self.paint_harmonics(ctx, data, x_coord);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Sound pressure level metering: A/C/Z frequency weighting, time weighting from
+//! [`crate::ballistics`] (fast unless another is chosen) and a calibration offset taken from a calibrator tone of known level.
+
+use druid::widget::prelude::*;
+use druid::widget::{Controller, Label};
+use druid::{Data, LocalizedString, Selector, WidgetExt};
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::ballistics::{BallisticMeter, Ballistics};
+use crate::biquad::Biquad;
+use crate::i18n::localize;
+use crate::loudness_graph::format_db;
+use crate::units::AmplitudeScale;
+
+pub const DRAW_SPL: Selector<SplData> = Selector::new("event-example.draw_spl");
+
+/// How long the calibrator tone is measured for
+const CALIBRATION_SECONDS: f32 = 2.0;
+
+/// Analog pole frequencies of the IEC 61672 weighting curves
+const POLE_1_HZ: f32 = 20.598_997;
+const POLE_2_HZ: f32 = 107.652_65;
+const POLE_3_HZ: f32 = 737.862_23;
+const POLE_4_HZ: f32 = 12_194.217;
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    A,
+    C,
+    Z,
+}
+
+impl Weighting {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Weighting::A => "A",
+            Weighting::C => "C",
+            Weighting::Z => "Z",
+        }
+    }
+}
+
+/// Gain of the analog A-weighting curve in dB, 0 at 1kHz
+pub fn a_weighting_db(frequency_hz: f32) -> f32 {
+    let response = |hz: f32| {
+        let squared = hz * hz;
+        POLE_4_HZ.powi(2) * squared * squared
+            / ((squared + POLE_1_HZ.powi(2))
+                * ((squared + POLE_2_HZ.powi(2)) * (squared + POLE_3_HZ.powi(2))).sqrt()
+                * (squared + POLE_4_HZ.powi(2)))
+    };
+    20.0 * (response(frequency_hz) / response(1000.0)).log10()
+}
+
+/// First-order section from the bilinear transform of `s / (s + ω)` or `ω / (s + ω)`, with the
+/// pole pre-warped so it lands on the analog frequency
+fn first_order(pole_hz: f32, high_pass: bool, sample_rate: f32) -> Biquad {
+    let k = (std::f32::consts::PI * pole_hz / sample_rate).tan();
+    let a0 = 1.0 + k;
+    let (b0, b1) = if high_pass {
+        (1.0 / a0, -1.0 / a0)
+    } else {
+        (k / a0, k / a0)
+    };
+    Biquad {
+        b0,
+        b1,
+        a1: (k - 1.0) / a0,
+        ..Default::default()
+    }
+}
+
+fn magnitude_at(sections: &[Biquad], frequency_hz: f32, sample_rate: f32) -> f32 {
+    let omega = 2.0 * std::f32::consts::PI * frequency_hz / sample_rate;
+    let z1 = Complex::from_polar(1.0, -omega);
+    let z2 = z1 * z1;
+    sections
+        .iter()
+        .map(|section| {
+            ((section.b0 + z1 * section.b1 + z2 * section.b2)
+                / (1.0 + z1 * section.a1 + z2 * section.a2))
+                .norm()
+        })
+        .product()
+}
+
+/// Cascade of first-order sections normalized to unity gain at 1kHz
+pub struct WeightingFilter {
+    sections: Vec<Biquad>,
+    gain: f32,
+}
+
+impl WeightingFilter {
+    pub fn new(weighting: Weighting, sample_rate: f32) -> Self {
+        let poles: &[(f32, bool)] = match weighting {
+            Weighting::A => &[
+                (POLE_1_HZ, true),
+                (POLE_1_HZ, true),
+                (POLE_2_HZ, true),
+                (POLE_3_HZ, true),
+                (POLE_4_HZ, false),
+                (POLE_4_HZ, false),
+            ],
+            Weighting::C => &[
+                (POLE_1_HZ, true),
+                (POLE_1_HZ, true),
+                (POLE_4_HZ, false),
+                (POLE_4_HZ, false),
+            ],
+            Weighting::Z => &[],
+        };
+        let sections: Vec<Biquad> = poles
+            .iter()
+            .map(|(pole_hz, high_pass)| first_order(*pole_hz, *high_pass, sample_rate))
+            .collect();
+        let gain = 1.0 / magnitude_at(&sections, 1000.0, sample_rate);
+        WeightingFilter { sections, gain }
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        self.sections
+            .iter_mut()
+            .fold(sample, |sample, section| section.process(sample))
+            * self.gain
+    }
+}
+
+/// What the meter row shows
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct SplData {
+    /// Weighted level in dBFS, with `ballistics` applied
+    pub level_dbfs: f32,
+    pub ballistics: Ballistics,
+    /// dB SPL of full scale, once calibrated
+    pub calibration_offset: Option<f32>,
+    pub calibrating: bool,
+    pub weighting: Weighting,
+}
+
+impl Default for SplData {
+    fn default() -> Self {
+        SplData {
+            level_dbfs: f32::NEG_INFINITY,
+            ballistics: Ballistics::Fast,
+            calibration_offset: None,
+            calibrating: false,
+            weighting: Weighting::A,
+        }
+    }
+}
+
+impl SplData {
+    /// Which of the `spl-reading` variants to show
+    fn state(&self) -> &'static str {
+        match (self.calibrating, self.calibration_offset) {
+            (true, _) => "calibrating",
+            (false, Some(_)) => "calibrated",
+            (false, None) => "uncalibrated",
+        }
+    }
+
+    /// In dB SPL once calibrated, otherwise in the chosen amplitude unit
+    fn level(&self, scale: &AmplitudeScale) -> String {
+        match self.calibration_offset {
+            Some(offset) => format_db(self.level_dbfs + offset),
+            None => scale.format_db(self.level_dbfs),
+        }
+    }
+}
+
+struct Calibration {
+    reference_db: f32,
+    remaining: usize,
+    sum: f32,
+    count: usize,
+}
+
+pub struct SplMeter {
+    sample_rate: f32,
+    weighting: Weighting,
+    filter: WeightingFilter,
+    ballistics: Ballistics,
+    meter: BallisticMeter,
+    calibration_offset: Option<f32>,
+    calibration: Option<Calibration>,
+}
+
+impl SplMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        SplMeter {
+            sample_rate,
+            weighting: Weighting::A,
+            filter: WeightingFilter::new(Weighting::A, sample_rate),
+            ballistics: Ballistics::Fast,
+            meter: BallisticMeter::new(Ballistics::Fast, sample_rate),
+            calibration_offset: None,
+            calibration: None,
+        }
+    }
+
+    pub fn set_weighting(&mut self, weighting: Weighting) {
+        self.weighting = weighting;
+        self.filter = WeightingFilter::new(weighting, self.sample_rate);
+    }
+
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.ballistics = ballistics;
+        self.meter.set_ballistics(ballistics);
+    }
+
+    /// Start measuring a calibrator tone that produces `reference_db` dB SPL at the microphone.
+    /// Calibrators play 1kHz, where every weighting is flat, so the weighted signal is used.
+    pub fn calibrate(&mut self, reference_db: f32) {
+        self.calibration = Some(Calibration {
+            reference_db,
+            remaining: (CALIBRATION_SECONDS * self.sample_rate) as usize,
+            sum: 0.0,
+            count: 0,
+        });
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        let weighted = self.filter.process(sample);
+        let square = weighted * weighted;
+        self.meter.process(weighted);
+
+        if let Some(calibration) = &mut self.calibration {
+            calibration.sum += square;
+            calibration.count += 1;
+            calibration.remaining -= 1;
+            if calibration.remaining == 0 {
+                let level_dbfs = 10.0 * (calibration.sum / calibration.count as f32).log10();
+                if level_dbfs.is_finite() {
+                    self.calibration_offset = Some(calibration.reference_db - level_dbfs);
+                }
+                self.calibration = None;
+            }
+        }
+    }
+
+    pub fn data(&self) -> SplData {
+        SplData {
+            level_dbfs: self.meter.level_db(),
+            ballistics: self.ballistics,
+            calibration_offset: self.calibration_offset,
+            calibrating: self.calibration.is_some(),
+            weighting: self.weighting,
+        }
+    }
+}
+
+/// Level readout, in dB SPL once calibrated
+pub fn spl_meter() -> impl Widget<SplData> {
+    let reading = LocalizedString::new("spl-reading")
+        .with_arg("state", |data: &SplData, _| data.state().into())
+        .with_arg("level", |data: &SplData, env| {
+            data.level(&AmplitudeScale::from_env(env)).into()
+        })
+        .with_arg("weighting", |data: &SplData, _| {
+            data.weighting.label().into()
+        })
+        .with_arg("ballistics", |data: &SplData, env| {
+            localize(data.ballistics.label(), env).into()
+        });
+    Label::new(reading).controller(SplController)
+}
+
+struct SplController;
+
+impl<W: Widget<SplData>> Controller<SplData, W> for SplController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut SplData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_SPL) => {
+                *data = cmd.get_unchecked(DRAW_SPL).clone();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! A two-pane split whose ratio lives in the app data rather than the widget, so a layout can be
+//! saved and restored along with everything else.
+
+use druid::widget::prelude::*;
+use druid::widget::Axis;
+use druid::{Color, Cursor, Lens, Point, Rect, WidgetPod};
+
+/// Thickness of the draggable bar between the panes
+const BAR_SIZE: f64 = 6.0;
+/// Neither pane shrinks below this share
+const MIN_RATIO: f64 = 0.1;
+
+pub struct DataSplit<T, L> {
+    /// Direction the panes are laid out along
+    axis: Axis,
+    first: WidgetPod<T, Box<dyn Widget<T>>>,
+    second: WidgetPod<T, Box<dyn Widget<T>>>,
+    /// Share of the space given to the first pane
+    ratio: L,
+}
+
+impl<T: Data, L: Lens<T, f64>> DataSplit<T, L> {
+    /// Side by side
+    pub fn columns(
+        first: impl Widget<T> + 'static,
+        second: impl Widget<T> + 'static,
+        ratio: L,
+    ) -> Self {
+        Self::new(Axis::Horizontal, first, second, ratio)
+    }
+
+    /// One above the other
+    pub fn rows(
+        first: impl Widget<T> + 'static,
+        second: impl Widget<T> + 'static,
+        ratio: L,
+    ) -> Self {
+        Self::new(Axis::Vertical, first, second, ratio)
+    }
+
+    fn new(
+        axis: Axis,
+        first: impl Widget<T> + 'static,
+        second: impl Widget<T> + 'static,
+        ratio: L,
+    ) -> Self {
+        DataSplit {
+            axis,
+            first: WidgetPod::new(Box::new(first)),
+            second: WidgetPod::new(Box::new(second)),
+            ratio,
+        }
+    }
+
+    fn bar_rect(&self, size: Size, data: &T) -> Rect {
+        let start = self.first_len(size, data);
+        let (x0, y0) = self.axis.pack(start, 0.0);
+        let (x1, y1) = self.axis.pack(start + BAR_SIZE, self.axis.minor(size));
+        Rect::new(x0, y0, x1, y1)
+    }
+
+    fn first_len(&self, size: Size, data: &T) -> f64 {
+        let ratio = self.ratio.with(data, |ratio| *ratio);
+        (self.axis.major(size) - BAR_SIZE).max(0.0) * ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO)
+    }
+
+    fn cursor(&self) -> Cursor {
+        match self.axis {
+            Axis::Horizontal => Cursor::ResizeLeftRight,
+            Axis::Vertical => Cursor::ResizeUpDown,
+        }
+    }
+}
+
+impl<T: Data, L: Lens<T, f64>> Widget<T> for DataSplit<T, L> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::MouseDown(mouse) if self.bar_rect(ctx.size(), data).contains(mouse.pos) => {
+                ctx.set_active(true);
+                ctx.set_handled();
+            }
+            Event::MouseMove(mouse) if ctx.is_active() => {
+                let available = (self.axis.major(ctx.size()) - BAR_SIZE).max(1.0);
+                let ratio = ((self.axis.major_pos(mouse.pos) - BAR_SIZE / 2.0) / available)
+                    .clamp(MIN_RATIO, 1.0 - MIN_RATIO);
+                self.ratio.with_mut(data, |value| *value = ratio);
+                ctx.set_cursor(&self.cursor());
+                ctx.set_handled();
+            }
+            Event::MouseUp(_) if ctx.is_active() => {
+                ctx.set_active(false);
+                ctx.set_handled();
+            }
+            _ => {
+                if let Event::MouseMove(mouse) = event {
+                    if self.bar_rect(ctx.size(), data).contains(mouse.pos) {
+                        ctx.set_cursor(&self.cursor());
+                    } else {
+                        ctx.clear_cursor();
+                    }
+                }
+                self.first.event(ctx, event, data, env);
+                self.second.event(ctx, event, data, env);
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.first.lifecycle(ctx, event, data, env);
+        self.second.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
+        let old_ratio = self.ratio.with(old_data, |ratio| *ratio);
+        if self.ratio.with(data, |ratio| *ratio != old_ratio) {
+            ctx.request_layout();
+        }
+        self.first.update(ctx, data, env);
+        self.second.update(ctx, data, env);
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        let size = bc.max();
+        let first_len = self.first_len(size, data);
+        let second_len = (self.axis.major(size) - first_len - BAR_SIZE).max(0.0);
+        let minor = self.axis.minor(size);
+
+        let first_size = Size::from(self.axis.pack(first_len, minor));
+        self.first
+            .layout(ctx, &BoxConstraints::tight(first_size), data, env);
+        self.first.set_origin(ctx, Point::ORIGIN);
+
+        let second_size = Size::from(self.axis.pack(second_len, minor));
+        self.second
+            .layout(ctx, &BoxConstraints::tight(second_size), data, env);
+        self.second
+            .set_origin(ctx, Point::from(self.axis.pack(first_len + BAR_SIZE, 0.0)));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        let bar = self.bar_rect(ctx.size(), data);
+        ctx.fill(bar, &Color::grey(0.2));
+        self.first.paint(ctx, data, env);
+        self.second.paint(ctx, data, env);
+    }
+}
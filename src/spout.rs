@@ -0,0 +1,339 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Spout output of the rendered waveform on Windows, for `--spout`. Frames go through
+//! SpoutLibrary.dll's `SendImage`, which shares them as a DirectX texture with OBS (the Spout2
+//! plugin) or VJ software. The DLL is loaded at runtime from `SPOUT_LIBRARY` or the `PATH`. Spout
+//! needs an OpenGL context, so the thread sending frames makes one on a hidden window the first
+//! time it sends.
+
+use std::io;
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+mod sys {
+    use std::ffi::{c_char, c_void};
+
+    pub type Handle = *mut c_void;
+
+    pub const LIBRARY: &str = "SpoutLibrary.dll";
+
+    /// `SPOUTLIBRARY` is a C++ interface, called through its vtable in the order SpoutLibrary.h
+    /// declares it
+    pub const SET_SENDER_NAME: usize = 0;
+    pub const RELEASE_SENDER: usize = 2;
+    pub const SEND_IMAGE: usize = 5;
+
+    pub type GetSpout = unsafe extern "system" fn() -> *mut *const *const c_void;
+    pub type SetSenderName = unsafe extern "system" fn(*mut c_void, *const c_char);
+    pub type ReleaseSender = unsafe extern "system" fn(*mut c_void, u32);
+    pub type SendImage =
+        unsafe extern "system" fn(*mut c_void, *const u8, u32, u32, u32, bool, u32) -> bool;
+
+    #[repr(C)]
+    pub struct PixelFormatDescriptor {
+        pub size: u16,
+        pub version: u16,
+        pub flags: u32,
+        pub pixel_type: u8,
+        pub color_bits: u8,
+        pub channel_bits: [u8; 6],
+        pub alpha_bits: u8,
+        pub alpha_shift: u8,
+        pub accum_bits: [u8; 5],
+        pub depth_bits: u8,
+        pub stencil_bits: u8,
+        pub aux_buffers: u8,
+        pub layer_type: u8,
+        pub reserved: u8,
+        pub layer_mask: u32,
+        pub visible_mask: u32,
+        pub damage_mask: u32,
+    }
+
+    pub const PFD_DOUBLEBUFFER: u32 = 0x01;
+    pub const PFD_DRAW_TO_WINDOW: u32 = 0x04;
+    pub const PFD_SUPPORT_OPENGL: u32 = 0x20;
+    pub const GL_RGBA: u32 = 0x1908;
+
+    pub type CreateWindowEx = unsafe extern "system" fn(
+        u32,
+        *const c_char,
+        *const c_char,
+        u32,
+        i32,
+        i32,
+        i32,
+        i32,
+        Handle,
+        Handle,
+        Handle,
+        *mut c_void,
+    ) -> Handle;
+    pub type DestroyWindow = unsafe extern "system" fn(Handle) -> i32;
+    pub type GetDc = unsafe extern "system" fn(Handle) -> Handle;
+    pub type ReleaseDc = unsafe extern "system" fn(Handle, Handle) -> i32;
+    pub type ChoosePixelFormat =
+        unsafe extern "system" fn(Handle, *const PixelFormatDescriptor) -> i32;
+    pub type SetPixelFormat =
+        unsafe extern "system" fn(Handle, i32, *const PixelFormatDescriptor) -> i32;
+    pub type CreateContext = unsafe extern "system" fn(Handle) -> Handle;
+    pub type MakeCurrent = unsafe extern "system" fn(Handle, Handle) -> i32;
+    pub type DeleteContext = unsafe extern "system" fn(Handle) -> i32;
+}
+
+/// A hidden window's OpenGL context, current on the thread that made it
+#[cfg(all(feature = "spout", target_os = "windows"))]
+struct GlContext {
+    user32: libloading::Library,
+    opengl32: libloading::Library,
+    window: sys::Handle,
+    dc: sys::Handle,
+    context: sys::Handle,
+}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+impl GlContext {
+    fn new() -> io::Result<Self> {
+        let error = |err: libloading::Error| io::Error::other(err.to_string());
+        unsafe {
+            let user32 = libloading::Library::new("user32.dll").map_err(error)?;
+            let gdi32 = libloading::Library::new("gdi32.dll").map_err(error)?;
+            let opengl32 = libloading::Library::new("opengl32.dll").map_err(error)?;
+            let create_window = *user32
+                .get::<sys::CreateWindowEx>(b"CreateWindowExA\0")
+                .map_err(error)?;
+            let get_dc = *user32.get::<sys::GetDc>(b"GetDC\0").map_err(error)?;
+            let choose_pixel_format = *gdi32
+                .get::<sys::ChoosePixelFormat>(b"ChoosePixelFormat\0")
+                .map_err(error)?;
+            let set_pixel_format = *gdi32
+                .get::<sys::SetPixelFormat>(b"SetPixelFormat\0")
+                .map_err(error)?;
+            let create_context = *opengl32
+                .get::<sys::CreateContext>(b"wglCreateContext\0")
+                .map_err(error)?;
+            let make_current = *opengl32
+                .get::<sys::MakeCurrent>(b"wglMakeCurrent\0")
+                .map_err(error)?;
+
+            // A built-in window class, never shown
+            let window = create_window(
+                0,
+                b"STATIC\0".as_ptr().cast(),
+                b"Spout\0".as_ptr().cast(),
+                0,
+                0,
+                0,
+                1,
+                1,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            );
+            if window.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let dc = get_dc(window);
+            let descriptor = sys::PixelFormatDescriptor {
+                size: std::mem::size_of::<sys::PixelFormatDescriptor>() as u16,
+                version: 1,
+                flags: sys::PFD_DRAW_TO_WINDOW | sys::PFD_SUPPORT_OPENGL | sys::PFD_DOUBLEBUFFER,
+                pixel_type: 0,
+                color_bits: 32,
+                channel_bits: [0; 6],
+                alpha_bits: 8,
+                alpha_shift: 0,
+                accum_bits: [0; 5],
+                depth_bits: 0,
+                stencil_bits: 0,
+                aux_buffers: 0,
+                layer_type: 0,
+                reserved: 0,
+                layer_mask: 0,
+                visible_mask: 0,
+                damage_mask: 0,
+            };
+            let format = choose_pixel_format(dc, &descriptor);
+            let context = if format != 0 && set_pixel_format(dc, format, &descriptor) != 0 {
+                create_context(dc)
+            } else {
+                std::ptr::null_mut()
+            };
+            let gl = GlContext {
+                user32,
+                opengl32,
+                window,
+                dc,
+                context,
+            };
+            if context.is_null() || make_current(dc, context) == 0 {
+                return Err(io::Error::other(
+                    "Failed to create an OpenGL context for Spout",
+                ));
+            }
+            Ok(gl)
+        }
+    }
+}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+impl Drop for GlContext {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.context.is_null() {
+                if let Ok(make_current) = self.opengl32.get::<sys::MakeCurrent>(b"wglMakeCurrent\0")
+                {
+                    make_current(std::ptr::null_mut(), std::ptr::null_mut());
+                }
+                if let Ok(delete) = self
+                    .opengl32
+                    .get::<sys::DeleteContext>(b"wglDeleteContext\0")
+                {
+                    delete(self.context);
+                }
+            }
+            if let Ok(release_dc) = self.user32.get::<sys::ReleaseDc>(b"ReleaseDC\0") {
+                release_dc(self.window, self.dc);
+            }
+            if let Ok(destroy) = self.user32.get::<sys::DestroyWindow>(b"DestroyWindow\0") {
+                destroy(self.window);
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+pub struct SpoutSender {
+    /// Made by the sending thread, and dropped on it with the sender. Fields drop in order, so
+    /// the context goes after the sender is released and before the library.
+    gl: Option<GlContext>,
+    instance: *mut *const *const std::ffi::c_void,
+    _library: libloading::Library,
+}
+
+// Only the thread sending frames calls into Spout
+#[cfg(all(feature = "spout", target_os = "windows"))]
+unsafe impl Send for SpoutSender {}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+impl SpoutSender {
+    /// Load SpoutLibrary.dll and name the sender `name`, created with the first frame
+    pub fn new(name: &str) -> io::Result<Self> {
+        let error = io::Error::other::<String>;
+        let path = match std::env::var_os("SPOUT_LIBRARY") {
+            Some(path) => path.into(),
+            None => std::path::PathBuf::from(sys::LIBRARY),
+        };
+        let library = unsafe { libloading::Library::new(&path) }
+            .map_err(|err| error(format!("Failed to load SpoutLibrary: {}", err)))?;
+        let name = std::ffi::CString::new(name)
+            .map_err(|_| error("Spout sender names can't contain NUL".to_string()))?;
+        let instance = unsafe {
+            let get_spout = library
+                .get::<sys::GetSpout>(b"GetSpout\0")
+                .map_err(|err| error(err.to_string()))?;
+            get_spout()
+        };
+        if instance.is_null() {
+            return Err(error("Failed to create the Spout sender".to_string()));
+        }
+        let sender = SpoutSender {
+            gl: None,
+            instance,
+            _library: library,
+        };
+        unsafe {
+            let set_name: sys::SetSenderName = sender.method(sys::SET_SENDER_NAME);
+            set_name(instance.cast(), name.as_ptr());
+        }
+        Ok(sender)
+    }
+
+    /// The vtable entry at `index`, cast to its signature
+    unsafe fn method<F: Copy>(&self, index: usize) -> F {
+        let vtable = *self.instance;
+        std::mem::transmute_copy(&*vtable.add(index))
+    }
+
+    /// Share a `width x height` RGBA frame, top row first
+    pub fn send(&mut self, frame: &[u8], width: usize, height: usize) -> io::Result<()> {
+        if frame.len() != width * height * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A {}x{} RGBA frame is {} bytes, not {}",
+                    width,
+                    height,
+                    width * height * 4,
+                    frame.len()
+                ),
+            ));
+        }
+        if self.gl.is_none() {
+            self.gl = Some(GlContext::new()?);
+        }
+        let sent = unsafe {
+            let send_image: sys::SendImage = self.method(sys::SEND_IMAGE);
+            send_image(
+                self.instance.cast(),
+                frame.as_ptr(),
+                width as u32,
+                height as u32,
+                sys::GL_RGBA,
+                false,
+                0,
+            )
+        };
+        if sent {
+            Ok(())
+        } else {
+            Err(io::Error::other("Spout failed to share the frame"))
+        }
+    }
+}
+
+#[cfg(all(feature = "spout", target_os = "windows"))]
+impl Drop for SpoutSender {
+    fn drop(&mut self) {
+        unsafe {
+            let release: sys::ReleaseSender = self.method(sys::RELEASE_SENDER);
+            release(self.instance.cast(), 0);
+        }
+    }
+}
+
+#[cfg(not(all(feature = "spout", target_os = "windows")))]
+pub struct SpoutSender;
+
+#[cfg(not(all(feature = "spout", target_os = "windows")))]
+impl SpoutSender {
+    pub fn new(_name: &str) -> io::Result<Self> {
+        Err(io::Error::other(
+            "Built without the `spout` feature, which needs Windows",
+        ))
+    }
+
+    pub fn send(&mut self, _frame: &[u8], _width: usize, _height: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Numeric readouts of the waveform window and the audio stream, each with a copy button.
+
+use druid::widget::prelude::*;
+use druid::widget::{Button, Controller, CrossAxisAlignment, Flex, Label};
+use druid::{Application, Data, LocalizedString, Selector, WidgetExt};
+
+use crate::decimate;
+use crate::i18n::localize;
+use crate::units::AmplitudeScale;
+
+pub const DRAW_STATS: Selector<StatsData> = Selector::new("event-example.draw_stats");
+
+#[derive(Clone, Data, Default, Debug)]
+pub struct StatsData {
+    pub peak: f32,
+    pub rms: f32,
+    pub min: f32,
+    pub max: f32,
+    pub dc_offset: f32,
+    pub sample_rate: f32,
+    pub buffer_size: usize,
+    pub dropped_frames: usize,
+    /// Rate the analysis thread is updating the UI at, after throttling
+    pub update_rate_hz: f32,
+    /// How far the audio clock runs from its nominal rate
+    pub drift_ppm: f32,
+    /// dBFS RMS, `None` until measured
+    pub noise_floor_db: Option<f32>,
+    /// Recent signal level above the noise floor
+    pub snr_db: Option<f32>,
+    /// The app's CPU use as a share of one core
+    pub cpu_percent: Option<f32>,
+    pub memory_bytes: Option<u64>,
+    /// Audio callback time as a share of the audio it processed
+    pub dsp_load_percent: Option<f32>,
+}
+
+impl StatsData {
+    /// Fill in the amplitude statistics of a window of samples
+    pub fn measure(&mut self, samples: &[f32]) {
+        let summary = decimate::summary(samples);
+        self.min = summary.min;
+        self.max = summary.max;
+        self.peak = summary.max.max(-summary.min);
+        self.rms = summary.rms();
+        self.dc_offset = summary.mean();
+    }
+}
+
+fn readout(
+    name: &'static str,
+    value: impl Fn(&StatsData, &AmplitudeScale, &Env) -> String + Clone + 'static,
+) -> impl Widget<StatsData> {
+    let copy_value = value.clone();
+    Flex::row()
+        .with_child(Label::new(LocalizedString::new(name)).fix_width(90.0))
+        .with_flex_child(
+            Label::dynamic(move |data: &StatsData, env| {
+                value(data, &AmplitudeScale::from_env(env), env)
+            })
+            .align_left(),
+            1.0,
+        )
+        .with_child(Button::new(LocalizedString::new("copy")).on_click(
+            move |_, data: &mut StatsData, env| {
+                let text = copy_value(data, &AmplitudeScale::from_env(env), env);
+                Application::global().clipboard().put_string(text);
+            },
+        ))
+}
+
+/// Peak, RMS, min/max and DC offset of the waveform window, the noise floor, and the stream's
+/// format and health
+pub fn stats_panel() -> impl Widget<StatsData> {
+    readouts()
+        .iter()
+        .fold(
+            Flex::column().cross_axis_alignment(CrossAxisAlignment::Start),
+            |column, &(name, value)| column.with_child(readout(name, value)),
+        )
+        .controller(StatsController)
+}
+
+/// Every readout on its own `name: value` line, for copying the whole panel
+pub fn to_text(data: &StatsData, scale: &AmplitudeScale, env: &Env) -> String {
+    readouts()
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "{}: {}\n",
+                localize(LocalizedString::new(name), env),
+                value(data, scale, env)
+            )
+        })
+        .collect()
+}
+
+type Format = fn(&StatsData, &AmplitudeScale, &Env) -> String;
+
+fn frames(frames: usize, env: &Env) -> String {
+    localize(
+        LocalizedString::new("frame-count")
+            .with_arg("frames", move |_, _| frames.to_string().into()),
+        env,
+    )
+}
+
+/// Readout names are ftl keys
+fn readouts() -> [(&'static str, Format); 15] {
+    [
+        ("stats-peak", |data, scale, _| scale.format(data.peak)),
+        ("stats-rms", |data, scale, _| scale.format(data.rms)),
+        ("stats-min", |data, _, _| format!("{:.4}", data.min)),
+        ("stats-max", |data, _, _| format!("{:.4}", data.max)),
+        ("stats-dc-offset", |data, _, _| {
+            format!("{:.5}", data.dc_offset)
+        }),
+        ("stats-noise-floor", |data, scale, _| {
+            data.noise_floor_db
+                .map_or("--".to_string(), |floor| scale.format_db(floor))
+        }),
+        ("stats-snr", |data, _, _| {
+            data.snr_db
+                .map_or("--".to_string(), |snr| format!("{:.1} dB", snr))
+        }),
+        ("stats-sample-rate", |data, _, _| {
+            format!("{} Hz", data.sample_rate)
+        }),
+        ("stats-buffer-size", |data, _, env| {
+            frames(data.buffer_size, env)
+        }),
+        ("stats-clock-drift", |data, _, _| {
+            format!("{:+.1} ppm", data.drift_ppm)
+        }),
+        ("stats-dropped", |data, _, env| {
+            frames(data.dropped_frames, env)
+        }),
+        ("stats-update-rate", |data, _, _| {
+            format!("{:.0} Hz", data.update_rate_hz)
+        }),
+        ("stats-cpu", |data, _, _| {
+            data.cpu_percent
+                .map_or("--".to_string(), |cpu| format!("{:.1} %", cpu))
+        }),
+        ("stats-memory", |data, _, _| {
+            data.memory_bytes.map_or("--".to_string(), |bytes| {
+                format!("{:.1} MB", bytes as f64 / 1_000_000.0)
+            })
+        }),
+        ("stats-dsp-load", |data, _, _| {
+            data.dsp_load_percent
+                .map_or("--".to_string(), |load| format!("{:.1} %", load))
+        }),
+    ]
+}
+
+struct StatsController;
+
+impl<W: Widget<StatsData>> Controller<StatsData, W> for StatsController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut StatsData,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(DRAW_STATS) => {
+                *data = cmd.get_unchecked(DRAW_STATS).clone();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+}
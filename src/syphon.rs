@@ -0,0 +1,344 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Syphon output of the rendered waveform on macOS, for `--syphon`. Frames are uploaded to a
+//! rectangle texture in an offscreen CGL context and published with a `SyphonOpenGLServer`, so
+//! OBS (a Syphon Client source) or VJ software can composite the view. Syphon.framework is loaded
+//! at runtime from `SYPHON_FRAMEWORK` or `/Library/Frameworks`, and the Objective-C calls go
+//! through the runtime's C API rather than bindings.
+
+use std::io;
+
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+mod sys {
+    use std::ffi::{c_char, c_void};
+
+    pub type Id = *mut c_void;
+    pub type Sel = *mut c_void;
+    pub type CglContext = *mut c_void;
+    pub type CglPixelFormat = *mut c_void;
+
+    #[repr(C)]
+    pub struct NsPoint {
+        pub x: f64,
+        pub y: f64,
+    }
+
+    #[repr(C)]
+    pub struct NsSize {
+        pub width: f64,
+        pub height: f64,
+    }
+
+    #[repr(C)]
+    pub struct NsRect {
+        pub origin: NsPoint,
+        pub size: NsSize,
+    }
+
+    pub const OBJC: &str = "/usr/lib/libobjc.A.dylib";
+    pub const OPENGL: &str = "/System/Library/Frameworks/OpenGL.framework/OpenGL";
+    pub const SYPHON: &str = "/Library/Frameworks/Syphon.framework/Syphon";
+
+    pub const CGL_PFA_ACCELERATED: i32 = 73;
+    pub const CGL_PFA_ALLOW_OFFLINE_RENDERERS: i32 = 96;
+    pub const GL_TEXTURE_RECTANGLE: u32 = 0x84F5;
+    pub const GL_TEXTURE_MIN_FILTER: u32 = 0x2801;
+    pub const GL_TEXTURE_MAG_FILTER: u32 = 0x2800;
+    pub const GL_LINEAR: i32 = 0x2601;
+    pub const GL_RGBA8: i32 = 0x8058;
+    pub const GL_RGBA: u32 = 0x1908;
+    pub const GL_UNSIGNED_BYTE: u32 = 0x1401;
+
+    pub type GetClass = unsafe extern "C" fn(*const c_char) -> Id;
+    pub type RegisterSel = unsafe extern "C" fn(*const c_char) -> Sel;
+    pub type PoolPush = unsafe extern "C" fn() -> *mut c_void;
+    pub type PoolPop = unsafe extern "C" fn(*mut c_void);
+    // `objc_msgSend` cast to each message's signature
+    pub type Send = unsafe extern "C" fn(Id, Sel) -> Id;
+    pub type SendStr = unsafe extern "C" fn(Id, Sel, *const c_char) -> Id;
+    pub type SendInit = unsafe extern "C" fn(Id, Sel, Id, CglContext, Id) -> Id;
+    pub type SendPublish = unsafe extern "C" fn(Id, Sel, u32, u32, NsRect, NsSize, bool);
+
+    pub type ChoosePixelFormat =
+        unsafe extern "C" fn(*const i32, *mut CglPixelFormat, *mut i32) -> i32;
+    pub type CreateContext =
+        unsafe extern "C" fn(CglPixelFormat, CglContext, *mut CglContext) -> i32;
+    pub type DestroyPixelFormat = unsafe extern "C" fn(CglPixelFormat) -> i32;
+    pub type SetCurrentContext = unsafe extern "C" fn(CglContext) -> i32;
+    pub type DestroyContext = unsafe extern "C" fn(CglContext) -> i32;
+    pub type GenTextures = unsafe extern "C" fn(i32, *mut u32);
+    pub type DeleteTextures = unsafe extern "C" fn(i32, *const u32);
+    pub type BindTexture = unsafe extern "C" fn(u32, u32);
+    pub type TexParameteri = unsafe extern "C" fn(u32, u32, i32);
+    pub type TexImage2D =
+        unsafe extern "C" fn(u32, i32, i32, i32, i32, i32, u32, u32, *const c_void);
+    pub type Flush = unsafe extern "C" fn();
+}
+
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+pub struct SyphonServer {
+    // Kept loaded for the function pointers below
+    _objc: libloading::Library,
+    _opengl: libloading::Library,
+    _syphon: libloading::Library,
+    msg_send: *const std::ffi::c_void,
+    register_sel: sys::RegisterSel,
+    pool_push: sys::PoolPush,
+    pool_pop: sys::PoolPop,
+    set_current_context: sys::SetCurrentContext,
+    destroy_context: sys::DestroyContext,
+    delete_textures: sys::DeleteTextures,
+    bind_texture: sys::BindTexture,
+    tex_image_2d: sys::TexImage2D,
+    flush: sys::Flush,
+    context: sys::CglContext,
+    texture: u32,
+    server: sys::Id,
+}
+
+// The context is made current on whichever thread sends, and the server is only messaged there
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+unsafe impl Send for SyphonServer {}
+
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+impl SyphonServer {
+    /// Load Syphon and announce a server called `name` to clients
+    pub fn new(name: &str) -> io::Result<Self> {
+        use std::ffi::CString;
+
+        let error = io::Error::other::<String>;
+        let load = |path: &std::ffi::OsStr| {
+            unsafe { libloading::Library::new(path) }
+                .map_err(|err| error(format!("Failed to load {:?}: {}", path, err)))
+        };
+        let objc = load(sys::OBJC.as_ref())?;
+        let opengl = load(sys::OPENGL.as_ref())?;
+        let syphon = match std::env::var_os("SYPHON_FRAMEWORK") {
+            Some(path) => load(std::path::Path::new(&path).join("Syphon").as_os_str())?,
+            None => load(sys::SYPHON.as_ref())?,
+        };
+        let name = CString::new(name)
+            .map_err(|_| error("Syphon server names can't contain NUL".to_string()))?;
+        unsafe {
+            macro_rules! symbol {
+                ($library:expr, $type:ty, $name:literal) => {
+                    *$library
+                        .get::<$type>($name)
+                        .map_err(|err| error(err.to_string()))?
+                };
+            }
+            let get_class = symbol!(objc, sys::GetClass, b"objc_getClass\0");
+            let msg_send = symbol!(objc, *const std::ffi::c_void, b"objc_msgSend\0");
+            let register_sel = symbol!(objc, sys::RegisterSel, b"sel_registerName\0");
+            let pool_push = symbol!(objc, sys::PoolPush, b"objc_autoreleasePoolPush\0");
+            let pool_pop = symbol!(objc, sys::PoolPop, b"objc_autoreleasePoolPop\0");
+            let choose_pixel_format =
+                symbol!(opengl, sys::ChoosePixelFormat, b"CGLChoosePixelFormat\0");
+            let create_context = symbol!(opengl, sys::CreateContext, b"CGLCreateContext\0");
+            let destroy_pixel_format =
+                symbol!(opengl, sys::DestroyPixelFormat, b"CGLDestroyPixelFormat\0");
+            let set_current_context =
+                symbol!(opengl, sys::SetCurrentContext, b"CGLSetCurrentContext\0");
+            let destroy_context = symbol!(opengl, sys::DestroyContext, b"CGLDestroyContext\0");
+            let gen_textures = symbol!(opengl, sys::GenTextures, b"glGenTextures\0");
+            let bind_texture = symbol!(opengl, sys::BindTexture, b"glBindTexture\0");
+            let tex_parameteri = symbol!(opengl, sys::TexParameteri, b"glTexParameteri\0");
+
+            // Newer frameworks name the OpenGL server apart from the Metal one
+            let class = [b"SyphonOpenGLServer\0".as_ptr(), b"SyphonServer\0".as_ptr()]
+                .iter()
+                .map(|&class| get_class(class.cast()))
+                .find(|class| !class.is_null())
+                .ok_or_else(|| error("Syphon.framework has no server class".to_string()))?;
+
+            let attributes = [
+                sys::CGL_PFA_ACCELERATED,
+                sys::CGL_PFA_ALLOW_OFFLINE_RENDERERS,
+                0,
+            ];
+            let mut pixel_format = std::ptr::null_mut();
+            let mut num_formats = 0;
+            choose_pixel_format(attributes.as_ptr(), &mut pixel_format, &mut num_formats);
+            if pixel_format.is_null() {
+                return Err(error("No OpenGL pixel format for Syphon".to_string()));
+            }
+            let mut context = std::ptr::null_mut();
+            create_context(pixel_format, std::ptr::null_mut(), &mut context);
+            destroy_pixel_format(pixel_format);
+            if context.is_null() {
+                return Err(error("Failed to create an OpenGL context".to_string()));
+            }
+            set_current_context(context);
+            let mut texture = 0;
+            gen_textures(1, &mut texture);
+            bind_texture(sys::GL_TEXTURE_RECTANGLE, texture);
+            tex_parameteri(
+                sys::GL_TEXTURE_RECTANGLE,
+                sys::GL_TEXTURE_MIN_FILTER,
+                sys::GL_LINEAR,
+            );
+            tex_parameteri(
+                sys::GL_TEXTURE_RECTANGLE,
+                sys::GL_TEXTURE_MAG_FILTER,
+                sys::GL_LINEAR,
+            );
+
+            let pool = pool_push();
+            let send: sys::Send = std::mem::transmute(msg_send);
+            let send_str: sys::SendStr = std::mem::transmute(msg_send);
+            let send_init: sys::SendInit = std::mem::transmute(msg_send);
+            let ns_string = get_class(b"NSString\0".as_ptr().cast());
+            let server_name = send_str(
+                send(ns_string, register_sel(b"alloc\0".as_ptr().cast())),
+                register_sel(b"initWithUTF8String:\0".as_ptr().cast()),
+                name.as_ptr(),
+            );
+            let server = send_init(
+                send(class, register_sel(b"alloc\0".as_ptr().cast())),
+                register_sel(b"initWithName:context:options:\0".as_ptr().cast()),
+                server_name,
+                context,
+                std::ptr::null_mut(),
+            );
+            send(server_name, register_sel(b"release\0".as_ptr().cast()));
+            pool_pop(pool);
+            set_current_context(std::ptr::null_mut());
+            if server.is_null() {
+                destroy_context(context);
+                return Err(error("Failed to create the Syphon server".to_string()));
+            }
+
+            Ok(SyphonServer {
+                msg_send,
+                register_sel,
+                pool_push,
+                pool_pop,
+                set_current_context,
+                destroy_context,
+                delete_textures: symbol!(opengl, sys::DeleteTextures, b"glDeleteTextures\0"),
+                bind_texture,
+                tex_image_2d: symbol!(opengl, sys::TexImage2D, b"glTexImage2D\0"),
+                flush: symbol!(opengl, sys::Flush, b"glFlush\0"),
+                _objc: objc,
+                _opengl: opengl,
+                _syphon: syphon,
+                context,
+                texture,
+                server,
+            })
+        }
+    }
+
+    /// Publish a `width x height` RGBA frame, top row first
+    pub fn send(&mut self, frame: &[u8], width: usize, height: usize) -> io::Result<()> {
+        if frame.len() != width * height * 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "A {}x{} RGBA frame is {} bytes, not {}",
+                    width,
+                    height,
+                    width * height * 4,
+                    frame.len()
+                ),
+            ));
+        }
+        unsafe {
+            (self.set_current_context)(self.context);
+            (self.bind_texture)(sys::GL_TEXTURE_RECTANGLE, self.texture);
+            (self.tex_image_2d)(
+                sys::GL_TEXTURE_RECTANGLE,
+                0,
+                sys::GL_RGBA8,
+                width as i32,
+                height as i32,
+                0,
+                sys::GL_RGBA,
+                sys::GL_UNSIGNED_BYTE,
+                frame.as_ptr().cast(),
+            );
+            (self.flush)();
+            let pool = (self.pool_push)();
+            let publish: sys::SendPublish = std::mem::transmute(self.msg_send);
+            let size = || sys::NsSize {
+                width: width as f64,
+                height: height as f64,
+            };
+            // Rows were uploaded top first, so GL sees the frame upside down
+            publish(
+                self.server,
+                (self.register_sel)(
+                    b"publishFrameTexture:textureTarget:imageRegion:textureDimensions:flipped:\0"
+                        .as_ptr()
+                        .cast(),
+                ),
+                self.texture,
+                sys::GL_TEXTURE_RECTANGLE,
+                sys::NsRect {
+                    origin: sys::NsPoint { x: 0.0, y: 0.0 },
+                    size: size(),
+                },
+                size(),
+                true,
+            );
+            (self.pool_pop)(pool);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "syphon", target_os = "macos"))]
+impl Drop for SyphonServer {
+    fn drop(&mut self) {
+        unsafe {
+            let send: sys::Send = std::mem::transmute(self.msg_send);
+            let pool = (self.pool_push)();
+            (self.set_current_context)(self.context);
+            send(self.server, (self.register_sel)(b"stop\0".as_ptr().cast()));
+            send(
+                self.server,
+                (self.register_sel)(b"release\0".as_ptr().cast()),
+            );
+            (self.delete_textures)(1, &self.texture);
+            (self.set_current_context)(std::ptr::null_mut());
+            (self.destroy_context)(self.context);
+            (self.pool_pop)(pool);
+        }
+    }
+}
+
+#[cfg(not(all(feature = "syphon", target_os = "macos")))]
+pub struct SyphonServer;
+
+#[cfg(not(all(feature = "syphon", target_os = "macos")))]
+impl SyphonServer {
+    pub fn new(_name: &str) -> io::Result<Self> {
+        Err(io::Error::other(
+            "Built without the `syphon` feature, which needs macOS",
+        ))
+    }
+
+    pub fn send(&mut self, _frame: &[u8], _width: usize, _height: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Target EQ curves, such as the Harman or X-curve, imported from a two column frequency and level
+//! file, and the measured spectrum's deviation from them. The target is shifted to the
+//! measurement's level, and then each third-octave band between [`LOW_HZ`] and [`HIGH_HZ`] is
+//! compared with it, so a single narrow dip doesn't swamp the error.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use druid::{Color, Data, PaintCtx};
+
+use crate::spectrum_view::{SpectrumData, SpectrumPlot};
+use crate::tilt::{HIGH_HZ, LOW_HZ};
+
+/// Third-octave bands compared, either side of 1 kHz
+const BANDS_PER_SIDE: i32 = 10;
+
+#[derive(Clone, Data, Debug)]
+pub struct TargetCurve {
+    /// The file's name, shown with the deviation
+    pub name: Arc<str>,
+    /// Frequency and level, sorted by frequency
+    pub points: Arc<Vec<(f32, f32)>>,
+}
+
+impl TargetCurve {
+    /// Read a target from lines of frequency and level separated by commas, semicolons or
+    /// whitespace, as written by REW and most measurement tools. Headers and comments are skipped,
+    /// frequencies out of order are an error.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let points =
+            parse_points(&contents).map_err(|err| format!("{} in {}", err, path.display()))?;
+        let name = path
+            .file_stem()
+            .map_or_else(|| "Target".into(), |stem| stem.to_string_lossy());
+        Ok(TargetCurve {
+            name: name.into(),
+            points: Arc::new(points),
+        })
+    }
+
+    /// The target's level at a frequency, interpolated in log frequency and held past its ends
+    pub fn level_at(&self, hz: f32) -> f32 {
+        let points = &self.points;
+        let after = points.partition_point(|(point_hz, _)| *point_hz < hz);
+        if after == 0 {
+            return points[0].1;
+        }
+        if after == points.len() {
+            return points[points.len() - 1].1;
+        }
+        let (low_hz, low_db) = points[after - 1];
+        let (high_hz, high_db) = points[after];
+        let share = (hz / low_hz).ln() / (high_hz / low_hz).ln().max(f32::EPSILON);
+        low_db + (high_db - low_db) * share
+    }
+
+    /// How far the spectrum is from the target, `None` while it's silent in range
+    pub fn deviation(&self, spectrum: &SpectrumData) -> Option<TargetDeviation> {
+        if spectrum.bin_hz <= 0.0 {
+            return None;
+        }
+        // Measured minus target per band
+        let differences: Vec<(f32, f32)> = (-BANDS_PER_SIDE..=BANDS_PER_SIDE)
+            .map(|band| 1000.0 * 2.0_f32.powf(band as f32 / 3.0))
+            .filter(|hz| (LOW_HZ * 0.99..=HIGH_HZ * 1.01).contains(hz))
+            .filter_map(|hz| {
+                let edge = 2.0_f32.powf(1.0 / 6.0);
+                let first = ((hz / edge / spectrum.bin_hz).ceil() as usize).max(1);
+                let last =
+                    ((hz * edge / spectrum.bin_hz) as usize).min(spectrum.magnitudes_db.len());
+                let bins = spectrum.magnitudes_db.get(first..last)?;
+                if bins.is_empty() {
+                    return None;
+                }
+                let power = bins
+                    .iter()
+                    .map(|level_db| 10.0_f32.powf(level_db / 10.0))
+                    .sum::<f32>()
+                    / bins.len() as f32;
+                let level_db = 10.0 * power.log10();
+                (level_db > -119.0).then(|| (hz, level_db - self.level_at(hz)))
+            })
+            .collect();
+        if differences.is_empty() {
+            return None;
+        }
+        let offset_db = differences
+            .iter()
+            .map(|(_, difference)| difference)
+            .sum::<f32>()
+            / differences.len() as f32;
+        let rms_db = (differences
+            .iter()
+            .map(|(_, difference)| (difference - offset_db).powi(2))
+            .sum::<f32>()
+            / differences.len() as f32)
+            .sqrt();
+        let (worst_hz, worst_db) = differences
+            .iter()
+            .map(|(hz, difference)| (*hz, difference - offset_db))
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))?;
+        Some(TargetDeviation {
+            offset_db,
+            rms_db,
+            worst_hz,
+            worst_db,
+        })
+    }
+}
+
+/// The frequency and level pairs of a target file. Lines that don't start with a number are
+/// headers or comments, the rest need a positive frequency above the previous line's and a level.
+fn parse_points(contents: &str) -> Result<Vec<(f32, f32)>, String> {
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let mut fields = line
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .filter(|field| !field.is_empty());
+        let Some(hz) = fields.next().and_then(|field| field.parse::<f32>().ok()) else {
+            continue;
+        };
+        let level_db = fields.next().and_then(|field| field.parse::<f32>().ok());
+        let (hz, level_db) = match level_db {
+            Some(level_db) if hz > 0.0 && hz.is_finite() && level_db.is_finite() => (hz, level_db),
+            _ => return Err(format!("Malformed row on line {}", index + 1)),
+        };
+        if let Some((previous_hz, _)) = points.last() {
+            if hz <= *previous_hz {
+                return Err(format!(
+                    "Frequencies have to rise, {} Hz on line {} follows {} Hz",
+                    hz,
+                    index + 1,
+                    previous_hz
+                ));
+            }
+        }
+        points.push((hz, level_db));
+    }
+    if points.len() < 2 {
+        return Err("No frequency and level pairs".to_string());
+    }
+    Ok(points)
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct TargetDeviation {
+    /// Added to the target to bring it to the measurement's level
+    pub offset_db: f32,
+    /// Across the third-octave bands
+    pub rms_db: f32,
+    /// Center of the band furthest off, and how far
+    pub worst_hz: f32,
+    pub worst_db: f32,
+}
+
+impl TargetDeviation {
+    pub fn label(&self) -> String {
+        format!(
+            "Target: {:.1} dB RMS, worst {:+.1} dB at {:.0} Hz",
+            self.rms_db, self.worst_db, self.worst_hz
+        )
+    }
+}
+
+/// Draw the target over the spectrum plot at the measurement's level
+pub fn paint(
+    ctx: &mut PaintCtx,
+    target: &TargetCurve,
+    deviation: Option<TargetDeviation>,
+    spectrum: &SpectrumData,
+) {
+    if let Some(deviation) = deviation {
+        SpectrumPlot::paint_reference(
+            ctx,
+            spectrum,
+            (LOW_HZ, HIGH_HZ),
+            |hz| target.level_at(hz) + deviation.offset_db,
+            Color::rgb8(255, 200, 60),
+            target.name.to_string(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rew_exports() {
+        let contents =
+            "* Measurement exported by REW\nFreq(Hz) SPL(dB)\n20, 6.5\n1000;0\n\n20000\t-4\n";
+        assert_eq!(
+            parse_points(contents),
+            Ok(vec![(20.0, 6.5), (1000.0, 0.0), (20000.0, -4.0)])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        for (contents, line) in [
+            ("20,0\n1000\n", 2),
+            ("20,0\n1000,loud\n", 2),
+            ("20,0\n1000,inf\n", 2),
+            ("0,0\n1000,0\n", 1),
+            ("20,0\n-1000,0\n", 2),
+        ] {
+            assert_eq!(
+                parse_points(contents),
+                Err(format!("Malformed row on line {}", line)),
+                "{:?}",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unsorted_frequencies() {
+        assert!(parse_points("20,0\n1000,0\n500,0\n").is_err());
+        assert!(parse_points("20,0\n1000,0\n1000,1\n").is_err());
+    }
+
+    #[test]
+    fn rejects_empty_files() {
+        for contents in ["", "Freq(Hz) SPL(dB)\n", "1000,0\n"] {
+            assert_eq!(
+                parse_points(contents),
+                Err("No frequency and level pairs".to_string())
+            );
+        }
+    }
+}
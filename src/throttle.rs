@@ -0,0 +1,184 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! How often the analysis thread updates the UI: the rate picked in the settings, lowered while
+//! the window isn't drawing frames or painting the waveform runs over budget. Heavy panes can
+//! be capped lower still, each on its own schedule within those updates.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use druid::{Data, Lens};
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+/// Update rates offered in the settings
+pub const UPDATE_RATES_HZ: [f64; 4] = [10.0, 20.0, 30.0, 60.0];
+/// Caps offered for a single pane, besides following the update rate
+pub const PANE_RATES_HZ: [f64; 3] = [2.0, 5.0, 10.0];
+
+/// Without an animation frame for this long the window is taken to be minimized or occluded
+const HIDDEN_AFTER: Duration = Duration::from_millis(500);
+/// Update interval while hidden
+const HIDDEN_INTERVAL: Duration = Duration::from_secs(1);
+/// Waveform paint time above which updates slow down, half a 60 fps frame
+const PAINT_BUDGET: Duration = Duration::from_millis(8);
+/// Slowest interval throttling for paint time goes to
+const MAX_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Written by the UI on every frame, read by the analysis thread
+pub struct FrameTiming {
+    start: Instant,
+    /// Microseconds from `start` to the last frame
+    last_frame_micros: AtomicU64,
+    /// Smoothed waveform paint time in microseconds
+    paint_micros: AtomicU64,
+}
+
+impl FrameTiming {
+    pub fn new() -> Arc<Self> {
+        Arc::new(FrameTiming {
+            start: Instant::now(),
+            last_frame_micros: AtomicU64::new(0),
+            paint_micros: AtomicU64::new(0),
+        })
+    }
+
+    pub fn record_frame(&self) {
+        let now = self.start.elapsed().as_micros() as u64;
+        self.last_frame_micros.store(now, Ordering::Relaxed);
+    }
+
+    pub fn record_paint(&self, paint_time: Duration) {
+        let previous = self.paint_micros.load(Ordering::Relaxed);
+        let smoothed = (previous * 7 + paint_time.as_micros() as u64) / 8;
+        self.paint_micros.store(smoothed, Ordering::Relaxed);
+    }
+
+    /// Whether the window has stopped drawing frames
+    pub fn hidden(&self) -> bool {
+        self.since_last_frame() > HIDDEN_AFTER
+    }
+
+    fn since_last_frame(&self) -> Duration {
+        let last_frame = Duration::from_micros(self.last_frame_micros.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last_frame)
+    }
+
+    fn paint_time(&self) -> Duration {
+        Duration::from_micros(self.paint_micros.load(Ordering::Relaxed))
+    }
+}
+
+pub struct UpdateThrottle {
+    timing: Arc<FrameTiming>,
+    interval: Duration,
+    /// Current interval when painting is over budget, backs off and recovers gradually
+    throttled_interval: Duration,
+}
+
+impl UpdateThrottle {
+    pub fn new(timing: Arc<FrameTiming>) -> Self {
+        let interval = Duration::from_secs_f64(1.0 / UPDATE_RATES_HZ[0]);
+        UpdateThrottle {
+            timing,
+            interval,
+            throttled_interval: interval,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate_hz: f64) {
+        self.interval = Duration::from_secs_f64(1.0 / rate_hz);
+        self.throttled_interval = self.interval;
+    }
+
+    /// The interval to wait before the next update, called once per update
+    pub fn next_interval(&mut self) -> Duration {
+        if self.timing.hidden() {
+            return HIDDEN_INTERVAL;
+        }
+        self.throttled_interval = if self.timing.paint_time() > PAINT_BUDGET {
+            (self.throttled_interval * 2).min(MAX_INTERVAL)
+        } else {
+            self.throttled_interval.mul_f64(0.9).max(self.interval)
+        };
+        self.throttled_interval
+    }
+}
+
+/// Panes of the spectrum frame that can be capped below the update rate
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pane {
+    Spectrum,
+    Phase,
+    LagViews,
+    Spectrogram,
+}
+
+/// Per-pane caps in Hz, `None` updates the pane with every update
+#[derive(Clone, Copy, Data, Lens, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct PaneRates {
+    pub spectrum: Option<f64>,
+    pub phase: Option<f64>,
+    pub lag_views: Option<f64>,
+    pub spectrogram: Option<f64>,
+}
+
+impl PaneRates {
+    fn get(&self, pane: Pane) -> Option<f64> {
+        match pane {
+            Pane::Spectrum => self.spectrum,
+            Pane::Phase => self.phase,
+            Pane::LagViews => self.lag_views,
+            Pane::Spectrogram => self.spectrogram,
+        }
+    }
+}
+
+/// When each pane is next due. Panes are only checked on updates, so a cap above the throttled
+/// update rate has no effect and the heaviest pane never runs more often than the meters.
+#[derive(Default)]
+pub struct PaneSchedule {
+    rates: PaneRates,
+    next: [Option<Instant>; 4],
+}
+
+impl PaneSchedule {
+    pub fn set_rates(&mut self, rates: PaneRates) {
+        self.rates = rates;
+        self.next = [None; 4];
+    }
+
+    /// Whether the pane gets analysed and sent with the update at `now`
+    pub fn due(&mut self, pane: Pane, now: Instant) -> bool {
+        let next = &mut self.next[pane as usize];
+        if next.is_some_and(|next| now < next) {
+            return false;
+        }
+        let interval = self.rates.get(pane).map_or(Duration::ZERO, |rate_hz| {
+            Duration::from_secs_f64(1.0 / rate_hz)
+        });
+        *next = Some(now + interval);
+        true
+    }
+}
@@ -0,0 +1,115 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Broadband spectral tilt: a straight line fitted through the spectrum in dB against octaves
+//! between [`LOW_HZ`] and [`HIGH_HZ`], for a quick read of the tonal balance. Bins are weighted by
+//! the inverse of their frequency so each octave counts the same, rather than the top octaves'
+//! many bins drowning out the bottom. Pink noise reads -3 dB per octave, white noise 0, and
+//! [`pink_reference`] is where ideal pink noise at the same level would lie, for system tuning.
+
+use druid::Data;
+
+use crate::spectrum_view::SpectrumData;
+
+pub const LOW_HZ: f32 = 100.0;
+pub const HIGH_HZ: f32 = 10_000.0;
+/// Pink noise's power halves every octave
+pub const PINK_SLOPE_DB_PER_OCTAVE: f32 = -3.01;
+/// Where the fitted line's level is given
+const REFERENCE_HZ: f32 = 1000.0;
+/// Bins at or below this are silence rather than spectrum
+const SILENCE_DB: f32 = -119.0;
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+pub struct Tilt {
+    pub slope_db_per_octave: f32,
+    /// The line's level at 1 kHz
+    pub level_db: f32,
+}
+
+impl Tilt {
+    /// The fitted line's level at a frequency
+    pub fn level_at(&self, hz: f32) -> f32 {
+        self.level_db + self.slope_db_per_octave * (hz / REFERENCE_HZ).log2()
+    }
+
+    pub fn label(&self) -> String {
+        format!("Tilt: {:+.1} dB/oct", self.slope_db_per_octave)
+    }
+}
+
+/// Each bin in range that isn't silent, as its weight, octaves from [`REFERENCE_HZ`] and level
+fn weighted_bins(spectrum: &SpectrumData) -> impl Iterator<Item = (f64, f64, f64)> + '_ {
+    let bin_hz = spectrum.bin_hz as f64;
+    let first = ((LOW_HZ as f64 / bin_hz).ceil() as usize).max(1);
+    let last = ((HIGH_HZ as f64 / bin_hz) as usize).min(spectrum.magnitudes_db.len());
+    (first..last).filter_map(move |bin| {
+        let level_db = spectrum.magnitudes_db[bin];
+        (level_db > SILENCE_DB).then(|| {
+            let hz = bin as f64 * bin_hz;
+            (1.0 / hz, (hz / REFERENCE_HZ as f64).log2(), level_db as f64)
+        })
+    })
+}
+
+/// Weighted least squares fit, `None` for a silent spectrum
+pub fn fit(spectrum: &SpectrumData) -> Option<Tilt> {
+    if spectrum.bin_hz <= 0.0 {
+        return None;
+    }
+    let (mut weights, mut sum_x, mut sum_y, mut sum_xx, mut sum_xy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (weight, octaves, level_db) in weighted_bins(spectrum) {
+        weights += weight;
+        sum_x += weight * octaves;
+        sum_y += weight * level_db;
+        sum_xx += weight * octaves * octaves;
+        sum_xy += weight * octaves * level_db;
+    }
+    let variance = weights * sum_xx - sum_x * sum_x;
+    if weights == 0.0 || variance <= f64::EPSILON * weights * weights {
+        return None;
+    }
+    let slope = (weights * sum_xy - sum_x * sum_y) / variance;
+    Some(Tilt {
+        slope_db_per_octave: slope as f32,
+        level_db: ((sum_y - slope * sum_x) / weights) as f32,
+    })
+}
+
+/// The pink noise line at the spectrum's level, fitted the same way with its slope held
+pub fn pink_reference(spectrum: &SpectrumData) -> Option<Tilt> {
+    if spectrum.bin_hz <= 0.0 {
+        return None;
+    }
+    let slope = PINK_SLOPE_DB_PER_OCTAVE as f64;
+    let (weights, sum) =
+        weighted_bins(spectrum).fold((0.0, 0.0), |(weights, sum), (weight, octaves, level_db)| {
+            (
+                weights + weight,
+                sum + weight * (level_db - slope * octaves),
+            )
+        });
+    (weights > 0.0).then(|| Tilt {
+        slope_db_per_octave: PINK_SLOPE_DB_PER_OCTAVE,
+        level_db: (sum / weights) as f32,
+    })
+}
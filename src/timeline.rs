@@ -0,0 +1,278 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Positions shared by the time-based panes, so the waveform, the spectrogram and the level
+//! history mark the same moments: markers, a loop range, the pointer's cursor, the last clip or
+//! dropout, detected transients and the waveform's playhead. They're stream positions in samples at the analysis rate,
+//! whatever column width a pane draws at.
+//!
+//! Alt-click adds a marker or removes the one under the pointer, two Shift-clicks set the loop.
+
+use std::sync::Arc;
+
+use druid::kurbo::{Line, Rect};
+use druid::piet::{Text, TextLayoutBuilder};
+use druid::widget::prelude::*;
+use druid::{Color, Data, FontFamily, MouseButton, Selector};
+
+/// Start of the newest clip or dropout event
+pub const DRAW_TRIGGER: Selector<u64> = Selector::new("event-example.draw_trigger");
+
+/// A click this many pixels from a marker removes it
+const HIT_DISTANCE: f64 = 4.0;
+/// Transients kept, the oldest are dropped after these
+const MAX_TRANSIENTS: usize = 512;
+/// Share of the pane's height a transient's tick covers, from the bottom
+const TRANSIENT_TICK: f64 = 0.15;
+
+const MARKER_COLOR: Color = Color::rgb8(255, 200, 0);
+const TRIGGER_COLOR: Color = Color::rgb8(255, 60, 60);
+const PLAYHEAD_COLOR: Color = Color::rgb8(0, 220, 120);
+const TRANSIENT_COLOR: Color = Color::rgb8(0, 200, 255);
+
+/// The stream positions a pane spans from its left edge to its right one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimeSpan {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeSpan {
+    fn x_of(&self, position: f64, width: f64) -> Option<f64> {
+        let x_coord = (position - self.start) / (self.end - self.start) * width;
+        (0.0..=width).contains(&x_coord).then_some(x_coord)
+    }
+
+    fn position_at(&self, x_coord: f64, width: f64) -> u64 {
+        (self.start + x_coord / width * (self.end - self.start))
+            .max(0.0)
+            .round() as u64
+    }
+
+    fn samples_per_pixel(&self, width: f64) -> f64 {
+        (self.end - self.start) / width
+    }
+}
+
+#[derive(Clone, Data, Default)]
+pub struct Timeline {
+    /// Sorted
+    pub markers: Arc<Vec<u64>>,
+    pub loop_range: Option<(u64, u64)>,
+    /// The first Shift-click of a loop, waiting for the second
+    loop_anchor: Option<u64>,
+    /// Under the pointer in whichever pane it's over
+    pub cursor: Option<u64>,
+    pub trigger: Option<u64>,
+    /// Found by the transient detector, oldest first
+    pub transients: Arc<Vec<u64>>,
+}
+
+impl Timeline {
+    /// Add a marker, or remove the nearest one if it's within `tolerance` samples
+    fn toggle_marker(&mut self, position: u64, tolerance: u64) {
+        let markers = Arc::make_mut(&mut self.markers);
+        match markers
+            .iter()
+            .position(|marker| marker.abs_diff(position) <= tolerance)
+        {
+            Some(index) => {
+                markers.remove(index);
+            }
+            None => {
+                let index = markers.partition_point(|marker| *marker < position);
+                markers.insert(index, position);
+            }
+        }
+    }
+
+    /// Start a loop, or finish the one started
+    fn place_loop_point(&mut self, position: u64) {
+        match self.loop_anchor.take() {
+            Some(anchor) => self.loop_range = Some((anchor.min(position), anchor.max(position))),
+            None => {
+                self.loop_anchor = Some(position);
+                self.loop_range = None;
+            }
+        }
+    }
+
+    pub fn add_transients(&mut self, positions: &[u64]) {
+        let transients = Arc::make_mut(&mut self.transients);
+        transients.extend_from_slice(positions);
+        let excess = transients.len().saturating_sub(MAX_TRANSIENTS);
+        transients.drain(..excess);
+    }
+
+    pub fn clear(&mut self) {
+        self.markers = Arc::new(Vec::new());
+        self.transients = Arc::new(Vec::new());
+        self.loop_range = None;
+        self.loop_anchor = None;
+    }
+}
+
+/// App data carrying a [`Timeline`], and the waveform position the other panes line up with
+pub trait TimelineData {
+    fn timeline(&self) -> &Timeline;
+    fn timeline_mut(&mut self) -> &mut Timeline;
+    fn playhead(&self) -> Option<f64>;
+}
+
+/// Draws the timeline over a pane spanning `span`, and places markers and loops on it
+pub struct TimelineOverlay<T, W> {
+    child: W,
+    span: fn(&T) -> Option<TimeSpan>,
+}
+
+impl<T, W> TimelineOverlay<T, W> {
+    pub fn new(child: W, span: fn(&T) -> Option<TimeSpan>) -> Self {
+        TimelineOverlay { child, span }
+    }
+}
+
+fn vertical_line(ctx: &mut PaintCtx, x_coord: f64, color: &Color) {
+    let height = ctx.size().height;
+    ctx.stroke(Line::new((x_coord, 0.0), (x_coord, height)), color, 1.0);
+}
+
+impl<T: TimelineData + Data, W: Widget<T>> Widget<T> for TimelineOverlay<T, W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        let width = ctx.size().width;
+        match event {
+            Event::MouseDown(mouse)
+                if mouse.button == MouseButton::Left
+                    && (mouse.mods.alt() || mouse.mods.shift()) =>
+            {
+                if let Some(span) = (self.span)(data) {
+                    let position = span.position_at(mouse.pos.x, width);
+                    let timeline = data.timeline_mut();
+                    if mouse.mods.alt() {
+                        let tolerance = span.samples_per_pixel(width) * HIT_DISTANCE;
+                        timeline.toggle_marker(position, tolerance as u64);
+                    } else {
+                        timeline.place_loop_point(position);
+                    }
+                    ctx.set_handled();
+                    return;
+                }
+            }
+            Event::MouseMove(mouse) => {
+                let cursor = (self.span)(data)
+                    .filter(|_| ctx.is_hot())
+                    .map(|span| span.position_at(mouse.pos.x, width));
+                if data.timeline().cursor != cursor {
+                    data.timeline_mut().cursor = cursor;
+                }
+            }
+            _ => (),
+        }
+        self.child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        self.child.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
+        if !old_data.timeline().same(data.timeline()) {
+            ctx.request_paint();
+        }
+        self.child.update(ctx, old_data, data, env)
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        self.child.layout(ctx, bc, data, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.child.paint(ctx, data, env);
+        let span = match (self.span)(data) {
+            Some(span) if span.end > span.start => span,
+            _ => return,
+        };
+        let size = ctx.size();
+        let timeline = data.timeline();
+        let x_of = |position: u64| span.x_of(position as f64, size.width);
+
+        if let Some((start, end)) = timeline.loop_range {
+            let left = span.x_of(start as f64, size.width).unwrap_or(0.0);
+            let right = span.x_of(end as f64, size.width).unwrap_or(size.width);
+            if start as f64 <= span.end && end as f64 >= span.start {
+                ctx.fill(
+                    Rect::new(left, 0.0, right, size.height),
+                    &MARKER_COLOR.with_alpha(0.12),
+                );
+            }
+        }
+        for edge in timeline
+            .loop_range
+            .iter()
+            .flat_map(|(start, end)| [*start, *end])
+            .chain(timeline.loop_anchor)
+        {
+            if let Some(x_coord) = x_of(edge) {
+                vertical_line(ctx, x_coord, &MARKER_COLOR.with_alpha(0.6));
+            }
+        }
+        for (index, marker) in timeline.markers.iter().enumerate() {
+            if let Some(x_coord) = x_of(*marker) {
+                vertical_line(ctx, x_coord, &MARKER_COLOR);
+                let layout = ctx
+                    .text()
+                    .new_text_layout((index + 1).to_string())
+                    .font(FontFamily::SYSTEM_UI, 10.0)
+                    .text_color(MARKER_COLOR)
+                    .build();
+                if let Ok(layout) = layout {
+                    ctx.draw_text(&layout, (x_coord + 2.0, 2.0));
+                }
+            }
+        }
+        for x_coord in timeline
+            .transients
+            .iter()
+            .filter_map(|position| x_of(*position))
+        {
+            ctx.stroke(
+                Line::new(
+                    (x_coord, size.height * (1.0 - TRANSIENT_TICK)),
+                    (x_coord, size.height),
+                ),
+                &TRANSIENT_COLOR,
+                1.0,
+            );
+        }
+        if let Some(x_coord) = timeline.trigger.and_then(x_of) {
+            vertical_line(ctx, x_coord, &TRIGGER_COLOR);
+        }
+        if let Some(x_coord) = data
+            .playhead()
+            .and_then(|playhead| span.x_of(playhead, size.width))
+        {
+            vertical_line(ctx, x_coord, &PLAYHEAD_COLOR);
+        }
+        if let Some(x_coord) = timeline.cursor.and_then(x_of) {
+            vertical_line(ctx, x_coord, &Color::WHITE.with_alpha(0.5));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Transient markers from `audio-processor-analysis`'s iterative STFT detector. It isn't real-time
+//! safe and wants a whole buffer, so the analysis thread collects blocks of input and a worker
+//! thread finds the onsets in them, which the time-based panes draw next to the user's markers.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use audio_processor_analysis::transient_detection::stft::markers::build_markers;
+use audio_processor_analysis::transient_detection::stft::IterativeTransientDetectionParams;
+use audio_processor_traits::AudioProcessorSettings;
+use druid::{Data, Lens, Selector};
+use serde::{Deserialize, Serialize};
+
+/// Onsets found since the last update, as stream positions
+pub const DRAW_TRANSIENTS: Selector<Vec<u64>> = Selector::new("event-example.draw_transients");
+
+/// The detector's `β`, how far a bin's change has to rise over its neighbours'. Higher is less
+/// sensitive.
+pub const THRESHOLD_FACTORS: [f64; 3] = [1.5, 2.0, 3.0];
+/// Level the followed transient signal has to reach to start a marker
+pub const GATES_DB: [f64; 3] = [-40.0, -30.0, -20.0];
+
+/// Input handed to the worker at a time
+const BLOCK_SECONDS: f32 = 2.0;
+
+#[derive(Clone, Copy, Data, Lens, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TransientSettings {
+    pub enabled: bool,
+    pub threshold_factor: f64,
+    pub gate_db: f64,
+}
+
+impl Default for TransientSettings {
+    fn default() -> Self {
+        TransientSettings {
+            enabled: false,
+            threshold_factor: THRESHOLD_FACTORS[1],
+            gate_db: GATES_DB[1],
+        }
+    }
+}
+
+struct Block {
+    /// Stream position of the first sample
+    start: u64,
+    /// Samples repeated from the previous block so onsets at its end aren't lost, markers in
+    /// them were reported already
+    lead_in: usize,
+    samples: Vec<f32>,
+    settings: TransientSettings,
+}
+
+fn find_markers(sample_rate: f32, mut block: Block) -> Vec<u64> {
+    let settings = AudioProcessorSettings {
+        sample_rate,
+        input_channels: 1,
+        output_channels: 1,
+        ..AudioProcessorSettings::default()
+    };
+    let params = IterativeTransientDetectionParams {
+        threshold_time_spread_factor: block.settings.threshold_factor as f32,
+        ..IterativeTransientDetectionParams::default()
+    };
+    let gate = 10.0_f32.powf(block.settings.gate_db as f32 / 20.0);
+    build_markers(&settings, &mut block.samples, params, gate)
+        .into_iter()
+        .filter(|marker| marker.position_samples >= block.lead_in)
+        .map(|marker| block.start + marker.position_samples as u64)
+        .collect()
+}
+
+pub struct TransientDetector {
+    settings: TransientSettings,
+    block_len: usize,
+    lead_in: usize,
+    /// Stream position of the next sample
+    position: u64,
+    samples: Vec<f32>,
+    /// Whether `samples` starts with the previous block's last `lead_in` samples, rather than
+    /// being the first block since start-up or switching on
+    carried_over: bool,
+    blocks: Sender<Block>,
+    markers: Receiver<Vec<u64>>,
+}
+
+impl TransientDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        let (blocks, block_receiver) = channel::<Block>();
+        let (marker_sender, markers) = channel();
+        thread::spawn(move || {
+            for block in block_receiver {
+                if marker_sender
+                    .send(find_markers(sample_rate, block))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        TransientDetector {
+            settings: TransientSettings::default(),
+            block_len: (sample_rate * BLOCK_SECONDS) as usize,
+            lead_in: IterativeTransientDetectionParams::default().fft_size,
+            position: 0,
+            samples: Vec::new(),
+            carried_over: false,
+            blocks,
+            markers,
+        }
+    }
+
+    /// Switching off drops the block being collected
+    pub fn set_settings(&mut self, settings: TransientSettings) {
+        if !settings.enabled {
+            self.samples.clear();
+            self.carried_over = false;
+        }
+        self.settings = settings;
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        self.position += 1;
+        if !self.settings.enabled {
+            return;
+        }
+        self.samples.push(sample);
+        if self.samples.len() == self.block_len {
+            let lead_in = self.samples[self.block_len - self.lead_in..].to_vec();
+            let samples = std::mem::replace(&mut self.samples, lead_in);
+            let _ = self.blocks.send(Block {
+                start: self.position - self.block_len as u64,
+                lead_in: if self.carried_over { self.lead_in } else { 0 },
+                samples,
+                settings: self.settings,
+            });
+            self.carried_over = true;
+        }
+    }
+
+    /// Markers the worker has found since the last call
+    pub fn take(&mut self) -> Vec<u64> {
+        self.markers.try_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    #[test]
+    fn reports_onsets_at_the_start_of_the_first_block() {
+        let mut detector = TransientDetector::new(SAMPLE_RATE);
+        // The most sensitive settings, the detector needs them for a lone click
+        detector.set_settings(TransientSettings {
+            enabled: true,
+            threshold_factor: THRESHOLD_FACTORS[0],
+            gate_db: GATES_DB[0],
+        });
+        // Well inside the lead-in a later block would skip
+        let click = detector.lead_in / 4;
+        for position in 0..detector.block_len {
+            detector.process(if position == click { 1.0 } else { 0.0 });
+        }
+        let markers = detector
+            .markers
+            .recv_timeout(Duration::from_secs(30))
+            .expect("Worker didn't finish the block");
+        assert!(
+            markers
+                .iter()
+                .any(|marker| *marker < detector.lead_in as u64),
+            "No marker in the first {} samples: {:?}",
+            detector.lead_in,
+            markers
+        );
+    }
+}
@@ -0,0 +1,175 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Tray icon drawing a tiny live level meter, with a menu to pause the waveform, start or stop
+//! recording and quit, so the app stays useful while its window is hidden. Only built with the
+//! `tray` feature, without it [`Tray::new`] returns `None`.
+
+use druid::{Env, ExtEventSink, Selector};
+
+#[cfg(feature = "tray")]
+use crate::i18n::localize;
+#[cfg(feature = "tray")]
+use druid::LocalizedString;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+pub enum TrayAction {
+    /// Freeze or unfreeze the waveform
+    Pause,
+    Record,
+    Quit,
+}
+
+pub const TRAY_ACTION: Selector<TrayAction> = Selector::new("event-example.tray_action");
+
+#[cfg(feature = "tray")]
+mod icon {
+    /// Edge of the square icon in pixels
+    pub const SIZE: u32 = 22;
+    /// Top rows of the bar drawn red
+    const HOT_ROWS: u32 = 4;
+
+    /// A bar filled up from the bottom to `rows`, grey outline otherwise
+    pub fn meter(rows: u32) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let row = SIZE - y;
+                let inside = (6..SIZE - 6).contains(&x);
+                let pixel = if inside && row <= rows {
+                    if row > SIZE - HOT_ROWS {
+                        [230, 40, 40, 255]
+                    } else {
+                        [40, 200, 80, 255]
+                    }
+                } else if inside {
+                    [90, 90, 90, 255]
+                } else {
+                    [0, 0, 0, 0]
+                };
+                rgba.extend_from_slice(&pixel);
+            }
+        }
+        rgba
+    }
+}
+
+#[cfg(feature = "tray")]
+pub struct Tray {
+    icon: tray_icon::TrayIcon,
+    pause: tray_icon::menu::MenuItem,
+    record: tray_icon::menu::MenuItem,
+    /// What the icon and menu show, so they're only replaced on a change
+    shown: Option<(u32, bool, bool)>,
+}
+
+#[cfg(feature = "tray")]
+impl Tray {
+    /// Create the icon, called on the UI thread once the window is up. `None` with a warning
+    /// where the platform has no tray.
+    pub fn new(event_sink: ExtEventSink, env: &Env) -> Option<Self> {
+        use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+        use tray_icon::{Icon, TrayIconBuilder};
+
+        let pause = MenuItem::new(pause_label(false, env), true, None);
+        let record = MenuItem::new(record_label(false, env), true, None);
+        let quit = MenuItem::new(localize(LocalizedString::new("quit"), env), true, None);
+        let menu = Menu::new();
+        menu.append_items(&[&pause, &record, &PredefinedMenuItem::separator(), &quit])
+            .map_err(|err| log::warn!("Failed to build the tray menu: {}", err))
+            .ok()?;
+
+        let actions = [
+            (pause.id().clone(), TrayAction::Pause),
+            (record.id().clone(), TrayAction::Record),
+            (quit.id().clone(), TrayAction::Quit),
+        ];
+        MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+            if let Some((_, action)) = actions.iter().find(|(id, _)| *id == event.id) {
+                let _ = event_sink.submit_command(TRAY_ACTION, *action, druid::Target::Auto);
+            }
+        }));
+
+        let icon = Icon::from_rgba(icon::meter(0), icon::SIZE, icon::SIZE)
+            .map_err(|err| log::warn!("Failed to draw the tray icon: {}", err))
+            .ok()?;
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(localize(LocalizedString::new("tray-tooltip"), env))
+            .with_icon(icon)
+            .build()
+            // Common on Linux desktops without an appindicator host, the app carries on without it
+            .map_err(|err| log::warn!("Failed to create tray icon: {}", err))
+            .ok()?;
+        Some(Tray {
+            icon,
+            pause,
+            record,
+            shown: None,
+        })
+    }
+
+    /// Redraw the meter for a level between 0 and 1, and relabel the menu
+    pub fn update(&mut self, level: f32, paused: bool, recording: bool, env: &Env) {
+        let rows = (level.clamp(0.0, 1.0) * icon::SIZE as f32).round() as u32;
+        if self.shown == Some((rows, paused, recording)) {
+            return;
+        }
+        self.shown = Some((rows, paused, recording));
+        if let Ok(icon) = tray_icon::Icon::from_rgba(icon::meter(rows), icon::SIZE, icon::SIZE) {
+            let _ = self.icon.set_icon(Some(icon));
+        }
+        self.pause.set_text(pause_label(paused, env));
+        self.record.set_text(record_label(recording, env));
+    }
+}
+
+#[cfg(feature = "tray")]
+fn pause_label(paused: bool, env: &Env) -> String {
+    localize(
+        LocalizedString::new("tray-pause")
+            .with_arg("paused", move |_, _| paused.to_string().into()),
+        env,
+    )
+}
+
+#[cfg(feature = "tray")]
+fn record_label(recording: bool, env: &Env) -> String {
+    localize(
+        LocalizedString::new("record-metrics")
+            .with_arg("recording", move |_, _| recording.to_string().into()),
+        env,
+    )
+}
+
+#[cfg(not(feature = "tray"))]
+pub struct Tray;
+
+#[cfg(not(feature = "tray"))]
+impl Tray {
+    pub fn new(_event_sink: ExtEventSink, _env: &Env) -> Option<Self> {
+        None
+    }
+
+    pub fn update(&mut self, _level: f32, _paused: bool, _recording: bool, _env: &Env) {}
+}
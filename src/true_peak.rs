@@ -0,0 +1,172 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! True-peak measurement (ITU-R BS.1770 Annex 2): the signal is 4x oversampled with a polyphase
+//! windowed-sinc interpolator and the highest absolute inter-sample value of any channel is
+//! tracked.
+
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+pub struct TruePeakMeter {
+    phases: Vec<[f32; TAPS_PER_PHASE]>,
+    /// One per channel, all written at `position`
+    histories: Vec<[f32; TAPS_PER_PHASE]>,
+    /// Each channel's true-peak amplitude in the frame pushed last
+    frame_peaks: Vec<f32>,
+    position: usize,
+    peak: f32,
+}
+
+impl TruePeakMeter {
+    pub fn with_channels(channels: usize) -> Self {
+        let num_taps = OVERSAMPLING * TAPS_PER_PHASE;
+        let center = (num_taps - 1) as f32 / 2.0;
+        let mut phases = vec![[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+        for tap in 0..num_taps {
+            let x = (tap as f32 - center) / OVERSAMPLING as f32;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (std::f32::consts::PI * x).sin() / (std::f32::consts::PI * x)
+            };
+            let window =
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * tap as f32 / (num_taps - 1) as f32).cos();
+            phases[tap % OVERSAMPLING][tap / OVERSAMPLING] = sinc * window;
+        }
+
+        TruePeakMeter {
+            phases,
+            histories: vec![[0.0; TAPS_PER_PHASE]; channels.max(1)],
+            frame_peaks: vec![0.0; channels.max(1)],
+            position: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Push one sample per channel, returning the loudest channel's true-peak amplitude (the
+    /// loudest of its sample and the interpolated values leading up to it). Channels past the
+    /// meter's are ignored.
+    pub fn process_frame(&mut self, frame: &[f32]) -> f32 {
+        let position = self.position;
+        self.position = (position + 1) % TAPS_PER_PHASE;
+
+        let mut frame_peak = 0.0_f32;
+        for ((history, channel_peak), sample) in self
+            .histories
+            .iter_mut()
+            .zip(&mut self.frame_peaks)
+            .zip(frame)
+        {
+            history[position] = *sample;
+            let mut sample_peak = sample.abs();
+            for phase in &self.phases {
+                let mut value = 0.0;
+                for (tap, coefficient) in phase.iter().enumerate() {
+                    // Newest sample first
+                    let index = (self.position + TAPS_PER_PHASE - 1 - tap) % TAPS_PER_PHASE;
+                    value += history[index] * coefficient;
+                }
+                sample_peak = sample_peak.max(value.abs());
+            }
+            *channel_peak = sample_peak;
+            frame_peak = frame_peak.max(sample_peak);
+        }
+        self.peak = self.peak.max(frame_peak);
+        frame_peak
+    }
+
+    /// Each channel's true-peak amplitude in the frame last pushed with
+    /// [`TruePeakMeter::process_frame`]
+    pub fn frame_peaks(&self) -> &[f32] {
+        &self.frame_peaks
+    }
+
+    /// Highest true-peak level since the last reset, in dBTP
+    pub fn peak_dbtp(&self) -> f32 {
+        20.0 * self.peak.log10()
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quarter sample-rate sine sampled 45 degrees off its peaks, so every sample sits 3.01dB
+    /// under the true peak, as in the Tech 3341 true-peak cases
+    fn quarter_rate_sine(peak_dbfs: f32, len: usize) -> Vec<f32> {
+        let amplitude = 10.0_f32.powf(peak_dbfs / 20.0);
+        (0..len)
+            .map(|index| {
+                let phase =
+                    std::f32::consts::FRAC_PI_2 * index as f32 + std::f32::consts::FRAC_PI_4;
+                amplitude * phase.sin()
+            })
+            .collect()
+    }
+
+    /// The peak is only in the right channel, the left is 20dB under it
+    #[test]
+    fn finds_inter_sample_peaks() {
+        let mut meter = TruePeakMeter::with_channels(2);
+        let signal = quarter_rate_sine(-3.0, 4_800);
+        let sample_peak = signal
+            .iter()
+            .fold(0.0_f32, |peak, sample| peak.max(sample.abs()));
+        for sample in signal {
+            meter.process_frame(&[sample * 0.1, sample]);
+        }
+        let [left, right] = [meter.frame_peaks()[0], meter.frame_peaks()[1]];
+        assert!((20.0 * (right / left).log10() - 20.0).abs() < 0.01);
+        assert!((20.0 * sample_peak.log10() + 6.01).abs() < 0.01);
+        // Tech 3341 allows +0.2/-0.4dB
+        let peak = meter.peak_dbtp();
+        assert!((-3.4..=-2.8).contains(&peak), "read {} dBTP", peak);
+    }
+
+    #[test]
+    fn dc_reads_its_level() {
+        let mut meter = TruePeakMeter::with_channels(2);
+        // The step up to DC rings over, so only measure once it has settled
+        for _ in 0..100 {
+            meter.process_frame(&[0.5, -0.25]);
+        }
+        meter.reset();
+        for _ in 0..100 {
+            meter.process_frame(&[0.5, -0.25]);
+        }
+        assert!((meter.peak_dbtp() - 20.0 * 0.5_f32.log10()).abs() < 0.01);
+    }
+
+    #[test]
+    fn reset_clears_the_peak() {
+        let mut meter = TruePeakMeter::with_channels(2);
+        meter.process_frame(&[0.0, 1.0]);
+        meter.reset();
+        meter.process_frame(&[0.25, 0.0]);
+        assert!(meter.peak_dbtp() < -11.0);
+    }
+}
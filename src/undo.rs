@@ -0,0 +1,132 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Undo and redo for settings. The delegate compares the settings before every event and
+//! command with the last ones it saw, so whatever changed them (controls, menus, MIDI) ends up
+//! in the history.
+
+use std::time::{Duration, Instant};
+
+use druid::{
+    AppDelegate, Command, Data, DelegateCtx, Env, Event, Handled, Selector, Target, WindowId,
+};
+
+pub const UNDO_SETTINGS: Selector = Selector::new("event-example.undo_settings");
+pub const REDO_SETTINGS: Selector = Selector::new("event-example.redo_settings");
+
+/// Changes closer together than this are one step, so a slider drag undoes in one go
+const MERGE_WINDOW: Duration = Duration::from_millis(500);
+/// Oldest steps are dropped past this
+const MAX_STEPS: usize = 100;
+
+pub struct SettingsHistory<T, S> {
+    capture: fn(&T) -> S,
+    apply: fn(&S, &mut T),
+    /// The settings as of the last event, `None` before the first one
+    current: Option<S>,
+    undo: Vec<S>,
+    redo: Vec<S>,
+    last_change: Option<Instant>,
+}
+
+impl<T, S: Clone + PartialEq> SettingsHistory<T, S> {
+    pub fn new(capture: fn(&T) -> S, apply: fn(&S, &mut T)) -> Self {
+        SettingsHistory {
+            capture,
+            apply,
+            current: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_change: None,
+        }
+    }
+
+    /// Record a step if the settings changed since the last call
+    fn observe(&mut self, data: &T) {
+        let settings = (self.capture)(data);
+        let Some(current) = self.current.replace(settings.clone()) else {
+            return;
+        };
+        if current == settings {
+            return;
+        }
+        let now = Instant::now();
+        let merge = self
+            .last_change
+            .is_some_and(|last| now.duration_since(last) < MERGE_WINDOW);
+        if !merge {
+            self.undo.push(current);
+            if self.undo.len() > MAX_STEPS {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_change = Some(now);
+    }
+
+    /// Move one step from `from` to `to`, applying it
+    fn step(&mut self, data: &mut T, redo: bool) {
+        let (from, to) = if redo {
+            (&mut self.redo, &mut self.undo)
+        } else {
+            (&mut self.undo, &mut self.redo)
+        };
+        if let Some(settings) = from.pop() {
+            if let Some(current) = self.current.take() {
+                to.push(current);
+            }
+            (self.apply)(&settings, data);
+            self.current = Some(settings);
+            self.last_change = None;
+        }
+    }
+}
+
+impl<T: Data, S: Clone + PartialEq + 'static> AppDelegate<T> for SettingsHistory<T, S> {
+    fn event(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _window_id: WindowId,
+        event: Event,
+        data: &mut T,
+        _env: &Env,
+    ) -> Option<Event> {
+        self.observe(data);
+        Some(event)
+    }
+
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut T,
+        _env: &Env,
+    ) -> Handled {
+        self.observe(data);
+        if cmd.is(UNDO_SETTINGS) || cmd.is(REDO_SETTINGS) {
+            self.step(data, cmd.is(REDO_SETTINGS));
+            return Handled::Yes;
+        }
+        Handled::No
+    }
+}
@@ -0,0 +1,107 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Display units for amplitudes, shared by every meter and axis through the druid [`Env`].
+
+use druid::{Data, Env, Key, LocalizedString};
+use serde::{Deserialize, Serialize};
+
+pub const AMPLITUDE_UNIT: Key<u64> = Key::new("event-example.amplitude_unit");
+pub const DB_FLOOR: Key<f64> = Key::new("event-example.db_floor");
+
+/// dB floors offered in the settings
+pub const DB_FLOORS: [f64; 2] = [-60.0, -90.0];
+
+#[derive(Clone, Copy, Data, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AmplitudeUnit {
+    Dbfs,
+    Linear,
+    Percent,
+}
+
+impl AmplitudeUnit {
+    pub fn label<T>(&self) -> LocalizedString<T> {
+        LocalizedString::new(match self {
+            AmplitudeUnit::Dbfs => "unit-dbfs",
+            AmplitudeUnit::Linear => "unit-linear",
+            AmplitudeUnit::Percent => "unit-percent",
+        })
+    }
+}
+
+/// The unit and dB floor amplitudes are displayed with
+#[derive(Clone, Copy, Debug)]
+pub struct AmplitudeScale {
+    pub unit: AmplitudeUnit,
+    pub floor_db: f64,
+}
+
+impl Default for AmplitudeScale {
+    fn default() -> Self {
+        AmplitudeScale {
+            unit: AmplitudeUnit::Dbfs,
+            floor_db: DB_FLOORS[0],
+        }
+    }
+}
+
+impl AmplitudeScale {
+    pub fn set_env(&self, env: &mut Env) {
+        env.set(AMPLITUDE_UNIT, self.unit as u64);
+        env.set(DB_FLOOR, self.floor_db);
+    }
+
+    /// The scale set by [`AmplitudeScale::set_env`], or the default for windows that don't set one
+    pub fn from_env(env: &Env) -> Self {
+        let default = AmplitudeScale::default();
+        let unit = match env.try_get(AMPLITUDE_UNIT) {
+            Ok(1) => AmplitudeUnit::Linear,
+            Ok(2) => AmplitudeUnit::Percent,
+            Ok(_) => AmplitudeUnit::Dbfs,
+            Err(_) => default.unit,
+        };
+        AmplitudeScale {
+            unit,
+            floor_db: env.try_get(DB_FLOOR).unwrap_or(default.floor_db),
+        }
+    }
+
+    /// Format a linear amplitude, full scale being 1
+    pub fn format(&self, amplitude: f32) -> String {
+        self.format_db(20.0 * amplitude.abs().log10())
+    }
+
+    /// Format a level given in dB relative to full scale. Levels under the floor read as silence.
+    pub fn format_db(&self, db: f32) -> String {
+        let silent = !db.is_finite() || (db as f64) < self.floor_db;
+        let amplitude = 10.0_f32.powf(db / 20.0);
+        match self.unit {
+            AmplitudeUnit::Dbfs if silent => "-inf dB".to_string(),
+            AmplitudeUnit::Dbfs => format!("{:.1} dB", db),
+            AmplitudeUnit::Linear if silent => "0".to_string(),
+            AmplitudeUnit::Linear => format!("{:.3}", amplitude),
+            AmplitudeUnit::Percent if silent => "0 %".to_string(),
+            AmplitudeUnit::Percent => format!("{:.1} %", amplitude * 100.0),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! A simple voice activity detector combining frame energy against an adaptive noise floor with
+//! the share of that energy inside the speech band.
+
+use std::ops::Range;
+
+use crate::buffer_analyser::HISTORY_LEN;
+
+const FRAME_MS: f32 = 20.0;
+/// Frames must be this much louder than the noise floor to count as speech
+const THRESHOLD_DB: f32 = 9.0;
+/// Anything quieter than this is silence regardless of the noise floor
+pub const SILENCE_DB: f32 = -60.0;
+/// Minimum share of the frame's energy between 300Hz and 3.4kHz
+const MIN_SPEECH_BAND_RATIO: f32 = 0.4;
+/// How fast the noise floor creeps back up when the signal is above it
+const FLOOR_RISE_DB_PER_FRAME: f32 = 0.05;
+/// Frames speech stays active after the last detection, so short gaps between words are bridged
+const HANGOVER_FRAMES: usize = 10;
+
+struct OnePole {
+    coefficient: f32,
+    state: f32,
+}
+
+impl OnePole {
+    fn new(cutoff: f32, sample_rate: f32) -> Self {
+        OnePole {
+            coefficient: (-2.0 * std::f32::consts::PI * cutoff / sample_rate).exp(),
+            state: 0.0,
+        }
+    }
+
+    fn low_pass(&mut self, input: f32) -> f32 {
+        self.state = input + self.coefficient * (self.state - input);
+        self.state
+    }
+
+    fn high_pass(&mut self, input: f32) -> f32 {
+        input - self.low_pass(input)
+    }
+}
+
+pub struct VoiceActivityDetector {
+    frame_len: usize,
+    high_pass: OnePole,
+    low_pass: OnePole,
+    frame_position: usize,
+    energy: f32,
+    band_energy: f32,
+    noise_floor_db: Option<f32>,
+    hangover: usize,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(sample_rate: f32) -> Self {
+        VoiceActivityDetector {
+            frame_len: (sample_rate * FRAME_MS / 1000.0) as usize,
+            high_pass: OnePole::new(300.0, sample_rate),
+            low_pass: OnePole::new(3400.0, sample_rate),
+            frame_position: 0,
+            energy: 0.0,
+            band_energy: 0.0,
+            noise_floor_db: None,
+            hangover: 0,
+        }
+    }
+
+    /// Push a sample, returning whether speech is active as of the last complete frame
+    pub fn process(&mut self, sample: f32) -> bool {
+        let band = self.low_pass.low_pass(self.high_pass.high_pass(sample));
+        self.energy += sample * sample;
+        self.band_energy += band * band;
+        self.frame_position += 1;
+
+        if self.frame_position == self.frame_len {
+            let energy_db = 10.0 * (self.energy / self.frame_len as f32 + 1e-12).log10();
+            let band_ratio = self.band_energy / (self.energy + 1e-12);
+
+            let noise_floor_db = self.noise_floor_db.get_or_insert(energy_db);
+            let speech = energy_db > SILENCE_DB
+                && energy_db > *noise_floor_db + THRESHOLD_DB
+                && band_ratio > MIN_SPEECH_BAND_RATIO;
+            *noise_floor_db = energy_db.min(*noise_floor_db + FLOOR_RISE_DB_PER_FRAME);
+
+            if speech {
+                self.hangover = HANGOVER_FRAMES;
+            } else {
+                self.hangover = self.hangover.saturating_sub(1);
+            }
+            self.frame_position = 0;
+            self.energy = 0.0;
+            self.band_energy = 0.0;
+        }
+
+        self.hangover > 0
+    }
+}
+
+/// Voice activity flags laid out like [`crate::buffer_analyser::SampleHistory`], one per sample
+pub struct ActivityHistory {
+    flags: Vec<bool>,
+    position: usize,
+}
+
+impl Default for ActivityHistory {
+    fn default() -> Self {
+        ActivityHistory {
+            flags: vec![false; HISTORY_LEN],
+            position: 0,
+        }
+    }
+}
+
+impl ActivityHistory {
+    pub fn push(&mut self, active: bool) {
+        let buffer_size = self.flags.len();
+        self.flags[self.position % buffer_size] = active;
+        self.position += 1;
+    }
+
+    /// The active stretches of the history, indexed oldest first like [`SampleHistory::latest`]
+    ///
+    /// [`SampleHistory::latest`]: crate::buffer_analyser::SampleHistory::latest
+    pub fn regions(&self) -> Vec<Range<usize>> {
+        let buffer_size = self.flags.len();
+        let oldest = self.position % buffer_size;
+        let flags = self.flags[oldest..].iter().chain(&self.flags[..oldest]);
+        let mut regions = Vec::new();
+        let mut start = None;
+        for (index, active) in flags.enumerate() {
+            match (start, active) {
+                (None, true) => start = Some(index),
+                (Some(region_start), false) => {
+                    regions.push(region_start..index);
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(region_start) = start {
+            regions.push(region_start..self.flags.len());
+        }
+        regions
+    }
+}
@@ -0,0 +1,261 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! "Record video..." in the live view: the waveform drawn offscreen at a fixed frame rate and
+//! piped to `ffmpeg` as raw RGBA frames, which encodes them to whatever the file extension says
+//! (MP4, GIF, WebM...). Needs `ffmpeg` on the `PATH`.
+//!
+//! `--stream-video` sends the same frames to a URL instead, as H.264 in MPEG-TS, so OBS (a Media
+//! Source) or VJ software can composite the view live without screen capture. `--ndi` publishes
+//! them as an NDI source, see [`crate::ndi`], and `--syphon` and `--spout` share them as a texture
+//! on macOS and Windows, see [`crate::syphon`] and [`crate::spout`].
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::audio_wave::WaveSnapshot;
+use crate::decimate::{self, MinMax};
+use crate::ndi::NdiSender;
+use crate::spout::SpoutSender;
+use crate::syphon::SyphonServer;
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 360;
+const FPS: u32 = 30;
+
+const BACKGROUND_RGBA: [u8; 4] = [0, 0, 0, 255];
+const CENTER_RGBA: [u8; 4] = [80, 80, 80, 255];
+/// The live waveform's default stroke, the video keeps it whatever color the window uses
+const WAVE_RGBA: [u8; 4] = [255, 0, 0, 255];
+
+pub struct VideoRecorder {
+    stop: Arc<AtomicBool>,
+    writer: JoinHandle<()>,
+}
+
+impl VideoRecorder {
+    /// Start encoding the newest `visible_samples` of each snapshot to `path`, a file or a
+    /// `udp://`, `srt://` or similar URL
+    pub fn start(path: &Path, snapshot: WaveSnapshot, visible_samples: usize) -> io::Result<Self> {
+        let mut ffmpeg = spawn_ffmpeg(path)?;
+        let stdin = ffmpeg.stdin.take().expect("Failed to open ffmpeg's stdin");
+        Ok(Self::spawn(
+            FrameSink::Ffmpeg(ffmpeg, stdin),
+            snapshot,
+            visible_samples,
+        ))
+    }
+
+    /// Publish the same frames as an NDI source called `name`
+    pub fn start_ndi(
+        name: &str,
+        snapshot: WaveSnapshot,
+        visible_samples: usize,
+    ) -> io::Result<Self> {
+        let sender = NdiSender::new(name)?;
+        Ok(Self::spawn(
+            FrameSink::Ndi(sender),
+            snapshot,
+            visible_samples,
+        ))
+    }
+
+    /// Publish the same frames as a Syphon server called `name`
+    pub fn start_syphon(
+        name: &str,
+        snapshot: WaveSnapshot,
+        visible_samples: usize,
+    ) -> io::Result<Self> {
+        let server = SyphonServer::new(name)?;
+        Ok(Self::spawn(
+            FrameSink::Syphon(server),
+            snapshot,
+            visible_samples,
+        ))
+    }
+
+    /// Publish the same frames as a Spout sender called `name`
+    pub fn start_spout(
+        name: &str,
+        snapshot: WaveSnapshot,
+        visible_samples: usize,
+    ) -> io::Result<Self> {
+        let sender = SpoutSender::new(name)?;
+        Ok(Self::spawn(
+            FrameSink::Spout(sender),
+            snapshot,
+            visible_samples,
+        ))
+    }
+
+    fn spawn(sink: FrameSink, snapshot: WaveSnapshot, visible_samples: usize) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let stop = stop.clone();
+            thread::spawn(move || write_frames(sink, snapshot, visible_samples, &stop))
+        };
+        VideoRecorder { stop, writer }
+    }
+
+    /// Finish the file, waiting for `ffmpeg` to exit
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if self.writer.join().is_err() {
+            log::error!("Video writer panicked");
+        }
+    }
+}
+
+/// Where the rendered frames go
+enum FrameSink {
+    Ffmpeg(Child, ChildStdin),
+    Ndi(NdiSender),
+    Syphon(SyphonServer),
+    Spout(SpoutSender),
+}
+
+impl FrameSink {
+    fn send(&mut self, frame: &[u8]) -> io::Result<()> {
+        match self {
+            FrameSink::Ffmpeg(_, stdin) => stdin.write_all(frame),
+            FrameSink::Ndi(sender) => sender.send(frame, WIDTH, HEIGHT, FPS),
+            FrameSink::Syphon(server) => server.send(frame, WIDTH, HEIGHT),
+            FrameSink::Spout(sender) => sender.send(frame, WIDTH, HEIGHT),
+        }
+    }
+
+    /// Close ffmpeg's input and wait for it to finish the file
+    fn finish(self) {
+        if let FrameSink::Ffmpeg(mut ffmpeg, stdin) = self {
+            drop(stdin);
+            match ffmpeg.wait() {
+                Ok(status) if !status.success() => log::error!("ffmpeg exited with {}", status),
+                Ok(_) => {}
+                Err(err) => log::error!("Failed to wait for ffmpeg: {}", err),
+            }
+        }
+    }
+}
+
+fn spawn_ffmpeg(path: &Path) -> io::Result<Child> {
+    let size = format!("{}x{}", WIDTH, HEIGHT);
+    let rate = FPS.to_string();
+    let mut command = Command::new("ffmpeg");
+    command
+        .args([
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &size, "-r", &rate, "-i", "-"]);
+    let is_gif = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+    let is_stream = path.to_str().is_some_and(|path| path.contains("://"));
+    if is_stream {
+        // Low latency over file size in a container that needs no seeking back to finish, with
+        // H.264 since the mpegts muxer's default MPEG-2 has no low latency tuning
+        command.args([
+            "-f",
+            "mpegts",
+            "-c:v",
+            "libx264",
+            "-preset",
+            "ultrafast",
+            "-tune",
+            "zerolatency",
+        ]);
+        command.args(["-pix_fmt", "yuv420p"]);
+    } else if is_gif {
+        // A palette made from the frames instead of the default web-safe one
+        command.args(["-vf", "split[a][b];[a]palettegen[p];[b][p]paletteuse"]);
+    } else {
+        // What most players expect in MP4 and WebM files
+        command.args(["-pix_fmt", "yuv420p"]);
+    }
+    command.arg(path).stdin(Stdio::piped()).spawn()
+}
+
+fn write_frames(
+    mut sink: FrameSink,
+    snapshot: WaveSnapshot,
+    visible_samples: usize,
+    stop: &AtomicBool,
+) {
+    let interval = Duration::from_secs(1) / FPS;
+    let mut frame = vec![0; WIDTH * HEIGHT * 4];
+    let mut buckets = Vec::new();
+    let mut next_frame = Instant::now();
+    let _background = snapshot.read_in_background();
+    while !stop.load(Ordering::Relaxed) {
+        let samples = snapshot.latest_audio();
+        let samples = samples.as_ref().map_or(&[][..], |audio| &audio.0[..]);
+        let visible = &samples[samples.len().saturating_sub(visible_samples)..];
+        decimate::decimate_into(visible, WIDTH, &mut buckets);
+        draw_frame(&buckets, WIDTH, HEIGHT, &mut frame);
+        if let Err(err) = sink.send(&frame) {
+            log::error!("Failed to write video frame: {}", err);
+            break;
+        }
+
+        next_frame += interval;
+        if let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+    sink.finish();
+}
+
+/// Draw the waveform into a `width x height` RGBA frame, one min/max span per column, stretched
+/// across the width when there are fewer buckets
+pub fn draw_frame(buckets: &[MinMax], width: usize, height: usize, frame: &mut [u8]) {
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&BACKGROUND_RGBA);
+    }
+    let mut set = |x: usize, y: usize, rgba: &[u8; 4]| {
+        let offset = (y * width + x) * 4;
+        frame[offset..offset + 4].copy_from_slice(rgba);
+    };
+    let row = |sample: f32| {
+        let y = (1.0 - sample.clamp(-1.0, 1.0)) * 0.5 * (height - 1) as f32;
+        y.round() as usize
+    };
+    for x in 0..width {
+        set(x, height / 2, &CENTER_RGBA);
+        if buckets.is_empty() {
+            continue;
+        }
+        let bucket = buckets[x * buckets.len() / width];
+        for y in row(bucket.max)..=row(bucket.min) {
+            set(x, y, &WAVE_RGBA);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Offscreen rasterization of the live waveform on a worker thread.
+//!
+//! The image is a ring of pixel columns keyed by stream position: column `k` holds the min/max of
+//! samples `k * samples_per_column..(k + 1) * samples_per_column` and lives in slot
+//! `k % num_columns`, so a new snapshot only needs the columns of its new samples drawn.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+
+use crate::audio_wave::{AudioData, WaveScale};
+use crate::decimate;
+use crate::profiling::profile_scope;
+
+/// What the raster is drawn for; changing any of it redraws every column
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RasterLayout {
+    pub samples_per_column: usize,
+    /// Slots in the ring, one more than the view spans to hold the partly scrolled-in column
+    pub num_columns: usize,
+    /// In device pixels, so a hairline stays one pixel on high-DPI screens
+    pub height: usize,
+    pub scale: WaveScale,
+    /// Of the drawn line in device pixels, the same as the vector waveform's stroke
+    pub stroke_width: f64,
+    /// Blend the partly covered pixels at the ends of each span, or snap them to whole pixels
+    pub antialias: bool,
+}
+
+pub struct RasterRequest {
+    pub audio: AudioData,
+    /// Stream position of `audio[0]`
+    pub first_sample: u64,
+    pub layout: RasterLayout,
+}
+
+/// The ring image shared with the UI thread
+pub struct RasterImage {
+    pub layout: RasterLayout,
+    /// Premultiplied RGBA rows of `num_columns` pixels
+    pub pixels: Vec<u8>,
+    /// Newest column drawn, exclusive. Slots past it still hold columns from a ring ago.
+    pub end_column: u64,
+    /// Incremented whenever a column is redrawn
+    pub revision: u64,
+}
+
+pub struct WaveRaster {
+    requests: Sender<RasterRequest>,
+    image: Arc<Mutex<Option<RasterImage>>>,
+}
+
+impl WaveRaster {
+    pub fn new() -> Self {
+        let (requests, requests_rx) = channel();
+        let image = Arc::new(Mutex::new(None));
+        let worker_image = image.clone();
+        thread::spawn(move || rasterize_requests(requests_rx, worker_image));
+        WaveRaster { requests, image }
+    }
+
+    pub fn request(&self, request: RasterRequest) {
+        // The worker only stops once this sender is dropped
+        let _ = self.requests.send(request);
+    }
+
+    pub fn image(&self) -> MutexGuard<'_, Option<RasterImage>> {
+        self.image.lock().expect("Failed to lock waveform raster")
+    }
+}
+
+impl Default for WaveRaster {
+    fn default() -> Self {
+        WaveRaster::new()
+    }
+}
+
+fn rasterize_requests(requests: Receiver<RasterRequest>, image: Arc<Mutex<Option<RasterImage>>>) {
+    // Stream column held by each slot
+    let mut slots: Vec<Option<u64>> = Vec::new();
+    while let Ok(request) = requests.recv() {
+        // Only the newest snapshot is worth drawing
+        let request = requests.try_iter().last().unwrap_or(request);
+        profile_scope!("rasterize");
+        let layout = request.layout;
+        let mut image = image.lock().expect("Failed to lock waveform raster");
+        if image.as_ref().is_none_or(|image| image.layout != layout) {
+            slots = vec![None; layout.num_columns];
+            *image = Some(RasterImage {
+                layout,
+                pixels: vec![0; layout.num_columns * layout.height * 4],
+                end_column: 0,
+                revision: 0,
+            });
+        }
+        let image = image.as_mut().expect("Failed to create waveform raster");
+
+        let samples = &request.audio.0;
+        let samples_per_column = layout.samples_per_column as u64;
+        let end_sample = request.first_sample + samples.len() as u64;
+        // Whole columns inside the snapshot, newest `num_columns` of them
+        let end_column = end_sample / samples_per_column;
+        let start_column = request
+            .first_sample
+            .div_ceil(samples_per_column)
+            .max(end_column.saturating_sub(layout.num_columns as u64));
+        let mut changed = false;
+        for column in start_column..end_column {
+            let slot = (column % layout.num_columns as u64) as usize;
+            if slots[slot] == Some(column) {
+                continue;
+            }
+            slots[slot] = Some(column);
+            // Start from the previous column's last sample so neighbouring columns join up
+            let start = (column * samples_per_column)
+                .saturating_sub(1)
+                .max(request.first_sample);
+            let end = (column + 1) * samples_per_column;
+            let range = decimate::min_max(
+                &samples[(start - request.first_sample) as usize
+                    ..(end - request.first_sample) as usize],
+            );
+            draw_column(image, slot, range);
+            changed = true;
+        }
+        if changed {
+            image.end_column = image.end_column.max(end_column);
+            image.revision += 1;
+        }
+    }
+}
+
+fn draw_column(image: &mut RasterImage, slot: usize, range: decimate::MinMax) {
+    let RasterLayout {
+        num_columns,
+        height,
+        scale,
+        stroke_width,
+        antialias,
+        ..
+    } = image.layout;
+    // Same orientation as the vector waveform
+    let (red, green, blue, _) = scale.color.as_rgba8();
+    let to_row = |sample: f32| (scale.position(sample) + 1.0) * height as f64 / 2.0;
+    let mut top = to_row(range.min) - stroke_width / 2.0;
+    let mut bottom = to_row(range.max) + stroke_width / 2.0;
+    if !antialias {
+        // Whole rows, as many as the span is tall, so a 1px line covers exactly one
+        let rows = (bottom - top).round().max(1.0);
+        top = top.round();
+        bottom = top + rows;
+    }
+    for row in 0..height {
+        let offset = (row * num_columns + slot) * 4;
+        // Fraction of the row the span covers, only ever partial at its two ends
+        let coverage = (bottom.min(row as f64 + 1.0) - top.max(row as f64)).clamp(0.0, 1.0);
+        let premultiply = |channel: u8| (channel as f64 * coverage).round() as u8;
+        let rgba = [
+            premultiply(red),
+            premultiply(green),
+            premultiply(blue),
+            premultiply(255),
+        ];
+        image.pixels[offset..offset + 4].copy_from_slice(&rgba);
+    }
+}
+
+/// Min/max spans of several signals added onto each other per color channel, so where they
+/// overlap reads as the sum of their colors
+#[derive(Default)]
+pub struct AdditiveSpans {
+    /// Unpremultiplied RGBA rows, transparent where no span is drawn
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+    buckets: Vec<decimate::MinMax>,
+}
+
+impl AdditiveSpans {
+    /// Start a new `width x height` frame
+    pub fn clear(&mut self, width: usize, height: usize) {
+        self.pixels.clear();
+        self.pixels.resize(width * height * 4, 0);
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Add `samples` spread over the width, `to_row` placing a sample on a pixel row
+    pub fn add(&mut self, samples: &[f32], rgb: [u8; 3], to_row: impl Fn(f32) -> f64) {
+        decimate::decimate_into(samples, self.width, &mut self.buckets);
+        if self.buckets.is_empty() || self.height == 0 {
+            return;
+        }
+        let last_row = self.height - 1;
+        let row = |sample: f32| (to_row(sample).round().max(0.0) as usize).min(last_row);
+        for x in 0..self.width {
+            let bucket = self.buckets[x * self.buckets.len() / self.width];
+            let (min_row, max_row) = (row(bucket.min), row(bucket.max));
+            for y in min_row.min(max_row)..=min_row.max(max_row) {
+                let offset = (y * self.width + x) * 4;
+                let pixel = &mut self.pixels[offset..offset + 4];
+                for (channel, value) in pixel[..3].iter_mut().zip(rgb) {
+                    *channel = channel.saturating_add(value);
+                }
+                pixel[3] = 255;
+            }
+        }
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+}
@@ -0,0 +1,161 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! `--features web` build for wasm32: the live waveform fed from the browser's microphone through
+//! Web Audio, drawn by druid's web backend into the page's `#canvas`, see index.html.
+//!
+//! Only the waveform is shown. The analysis threads, files and the native host don't exist here.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use druid::widget::prelude::*;
+use druid::widget::Controller;
+use druid::{AppLauncher, WidgetExt, WindowDesc};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{AnalyserNode, AudioContext, AudioContextState, MediaStream, MediaStreamConstraints};
+
+use crate::audio_wave::{AudioData, AudioWave, WaveData};
+
+/// Samples the analyser node keeps, the most Web Audio allows
+const FFT_SIZE: u32 = 32768;
+
+/// Set once the user allows microphone access
+type SharedAnalyser = Rc<RefCell<Option<AnalyserNode>>>;
+
+pub fn run() {
+    let analyser = SharedAnalyser::default();
+    if let Err(err) = request_microphone(analyser.clone()) {
+        log::error!("Failed to request microphone access: {:?}", err);
+    }
+
+    let wave = AudioWave::default().controller(WebController {
+        analyser,
+        buffer: vec![0.0; FFT_SIZE as usize],
+    });
+    AppLauncher::with_window(WindowDesc::new(wave).title("Audio viz"))
+        .launch(WaveData {
+            audio: AudioData::new(Vec::new()),
+            right: None,
+            visible_samples: FFT_SIZE as f64,
+            voice_activity: None,
+            scroll: None,
+            frozen: false,
+            beat_grid: None,
+            record_start: None,
+            input_latency: 0.0,
+            position: 0.0,
+        })
+        .expect("launch failed");
+}
+
+fn request_microphone(analyser: SharedAnalyser) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("no window")?;
+    let constraints = MediaStreamConstraints::new();
+    constraints.set_audio(&JsValue::TRUE);
+    let promise = window
+        .navigator()
+        .media_devices()?
+        .get_user_media_with_constraints(&constraints)?;
+
+    let on_stream = Closure::once(move |stream: JsValue| {
+        if let Err(err) = connect(stream.unchecked_into(), &analyser) {
+            log::error!("Failed to start Web Audio input: {:?}", err);
+        }
+    });
+    let on_error = Closure::once(|err: JsValue| log::error!("No microphone access: {:?}", err));
+    let _ = promise.then2(&on_stream, &on_error);
+    // Called from the browser after this returns
+    on_stream.forget();
+    on_error.forget();
+    Ok(())
+}
+
+fn connect(stream: MediaStream, analyser: &SharedAnalyser) -> Result<(), JsValue> {
+    let context = AudioContext::new()?;
+    let source = context.create_media_stream_source(&stream)?;
+    let node = context.create_analyser()?;
+    node.set_fft_size(FFT_SIZE);
+    source.connect_with_audio_node(&node)?;
+    resume_on_gesture(context)?;
+    *analyser.borrow_mut() = Some(node);
+    Ok(())
+}
+
+/// Browsers keep a context made outside a user gesture suspended, and the permission prompt's
+/// promise doesn't count as one. Try now, and again on the next click or key press.
+fn resume_on_gesture(context: AudioContext) -> Result<(), JsValue> {
+    let _ = context.resume()?;
+    let window = web_sys::window().ok_or("no window")?;
+    let on_gesture = Closure::<dyn Fn()>::new(move || {
+        if context.state() == AudioContextState::Suspended {
+            let _ = context.resume();
+        }
+    });
+    for event in ["pointerdown", "keydown"] {
+        window.add_event_listener_with_callback(event, on_gesture.as_ref().unchecked_ref())?;
+    }
+    // Stays registered for the life of the page
+    on_gesture.forget();
+    Ok(())
+}
+
+/// Copies the analyser's latest samples into the waveform on every animation frame
+struct WebController {
+    analyser: SharedAnalyser,
+    buffer: Vec<f32>,
+}
+
+impl<W: Widget<WaveData>> Controller<WaveData, W> for WebController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut WaveData,
+        env: &Env,
+    ) {
+        if let Event::AnimFrame(_) = event {
+            if let Some(analyser) = self.analyser.borrow().as_ref() {
+                analyser.get_float_time_domain_data(&mut self.buffer);
+                data.audio = AudioData::new(self.buffer.clone());
+            }
+            ctx.request_anim_frame();
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &WaveData,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            ctx.request_anim_frame();
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
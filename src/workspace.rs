@@ -0,0 +1,104 @@
+// Augmented Audio: Audio libraries and applications
+// Copyright (c) 2022 Pedro Tacla Yamada
+//
+// The MIT License (MIT)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//! Named workspaces: which views are open, how the window is split between them and the
+//! waveform's own settings, restored with one click.
+
+use serde::{Deserialize, Serialize};
+
+use crate::buffer_analyser::HISTORY_LEN;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub show_phase: bool,
+    #[serde(default)]
+    pub show_spectrum: bool,
+    #[serde(default)]
+    pub show_spectrogram: bool,
+    #[serde(default)]
+    pub show_denoise: bool,
+    pub show_envelope: bool,
+    #[serde(default)]
+    pub show_level_history: bool,
+    pub show_midi: bool,
+    /// Share of the window's width given to the meters, the side panel gets the rest
+    pub meters_ratio: f64,
+    /// Share of the meters' height given to the waveform
+    pub wave_ratio: f64,
+    pub visible_samples: f64,
+    pub wave_gain_db: f64,
+    pub db_waveform: bool,
+}
+
+impl Workspace {
+    /// Offered until some are saved
+    pub fn defaults() -> Vec<Workspace> {
+        vec![
+            Workspace {
+                name: "Mastering".to_string(),
+                show_phase: true,
+                show_spectrum: true,
+                show_spectrogram: false,
+                show_denoise: false,
+                show_envelope: false,
+                show_level_history: false,
+                show_midi: false,
+                meters_ratio: 0.75,
+                wave_ratio: 0.3,
+                visible_samples: HISTORY_LEN as f64,
+                wave_gain_db: 0.0,
+                db_waveform: true,
+            },
+            Workspace {
+                name: "Tuning".to_string(),
+                show_phase: false,
+                show_spectrum: false,
+                show_spectrogram: false,
+                show_denoise: false,
+                show_envelope: true,
+                show_level_history: false,
+                show_midi: true,
+                meters_ratio: 0.8,
+                wave_ratio: 0.5,
+                visible_samples: HISTORY_LEN as f64 / 20.0,
+                wave_gain_db: 6.0,
+                db_waveform: false,
+            },
+            Workspace {
+                name: "Teaching".to_string(),
+                show_phase: false,
+                show_spectrum: false,
+                show_spectrogram: false,
+                show_denoise: false,
+                show_envelope: true,
+                show_level_history: false,
+                show_midi: false,
+                meters_ratio: 0.9,
+                wave_ratio: 0.6,
+                visible_samples: HISTORY_LEN as f64 / 4.0,
+                wave_gain_db: 0.0,
+                db_waveform: false,
+            },
+        ]
+    }
+}